@@ -65,15 +65,22 @@ extern crate log;
 
 mod opt;
 
+use std::collections::HashMap;
+use std::io::Write;
 use std::path::PathBuf;
-use std::sync::{mpsc::channel, Arc};
+use std::sync::{mpsc::channel, Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 use structopt::StructOpt;
 use task_maker_cache::Cache;
-use task_maker_dag::CacheMode;
-use task_maker_exec::{executors::LocalExecutor, ExecutorClient};
+use task_maker_dag::{CacheMode, CriticalPath, VerifyMode, DEFAULT_PROGRESS_STEP};
+use task_maker_exec::executors::LocalExecutor;
+use task_maker_exec::{
+    AbortHandle, ExecutorClient, MemoryReservation, StallWatchdog, DEFAULT_MAX_CONCURRENT_TRANSFERS,
+};
 use task_maker_format::ui::UIMessage;
 use task_maker_format::{ioi, EvaluationConfig, EvaluationData, TaskFormat, UISender};
+use task_maker_lang::LanguageManager;
 use task_maker_store::*;
 
 fn main() {
@@ -88,6 +95,9 @@ fn main() {
         unimplemented!("This option is not implemented yet");
     }
 
+    // the languages of the extensions have to be set before detecting the language of any file
+    LanguageManager::set_overrides(opt.language.clone()).expect("Invalid language override");
+
     // setup the task
     let eval_config = opt.to_config();
     let task: Box<dyn TaskFormat> =
@@ -101,19 +111,117 @@ fn main() {
 
     // setup the configuration and the evaluation metadata
     let (mut eval, receiver) = EvaluationData::new();
+
+    // report the health of the task through the UI
+    for message in task.validate() {
+        eval.sender
+            .send(UIMessage::Warning {
+                message: message.to_string(),
+            })
+            .expect("Failed to send the validation messages to the UI");
+    }
     let config = eval.dag.config_mut();
     config
         .keep_sandboxes(opt.keep_sandboxes)
         .dry_run(opt.dry_run)
         .cache_mode(CacheMode::from(opt.no_cache))
-        .copy_exe(opt.copy_exe);
+        .verify_mode(VerifyMode::from(opt.verify.clone()))
+        .max_executions(opt.max_executions)
+        .max_cpu_time(opt.max_cpu_time)
+        .copy_exe(opt.copy_exe)
+        .parallel_compilation(opt.parallel_compilation)
+        .largest_inputs_first(opt.largest_inputs_first);
     if let Some(extra_time) = opt.extra_time {
         assert!(extra_time >= 0.0, "the extra time cannot be negative");
         config.extra_time(extra_time);
     }
+    if let Some(extra_memory) = opt.extra_memory {
+        config.extra_memory(extra_memory);
+    }
+    if let Some(max_total_duration) = opt.max_total_duration {
+        assert!(
+            max_total_duration > 0.0,
+            "the maximum total duration must be positive"
+        );
+        config.max_total_duration(Some(max_total_duration));
+    }
+    if let Some(safety_wall_time) = opt.safety_wall_time {
+        assert!(
+            safety_wall_time > 0.0,
+            "the safety wall time must be positive"
+        );
+        config.safety_wall_time(Some(safety_wall_time));
+    }
+    if let Some(compilation_time_limit) = opt.compilation_time_limit {
+        assert!(
+            compilation_time_limit > 0.0,
+            "the compilation time limit must be positive"
+        );
+        config.compilation_time_limit(Some(compilation_time_limit));
+    }
+    if let Some(compilation_memory_limit) = opt.compilation_memory_limit {
+        config.compilation_memory_limit(Some(compilation_memory_limit * 1024)); // MiB -> KiB
+    }
+
+    // build the DAG for the task
+    task.execute(&mut eval, &eval_config)
+        .expect("Failed to build the DAG");
+
+    // report the nondeterministic executions found by the verification of the cache
+    if opt.verify.is_some() {
+        let executions: Vec<_> = eval
+            .dag
+            .data
+            .executions
+            .values()
+            .map(|exec| (exec.uuid, exec.description.clone()))
+            .collect();
+        for (uuid, description) in executions {
+            let sender = eval.sender.clone();
+            eval.dag.on_execution_nondeterminism(&uuid, move |files| {
+                sender.send(UIMessage::Warning {
+                    message: format!(
+                        "{} is not deterministic: {} outputs differ from the cached ones",
+                        description,
+                        files.len()
+                    ),
+                })
+            });
+        }
+    }
+
+    // only report the files that the evaluation would write
+    if opt.list_outputs {
+        for path in eval.dag.planned_outputs() {
+            println!("{}", path.display());
+        }
+        return;
+    }
+
+    // collect the results of all the executions for computing the critical path
+    let executions = eval.dag.data.executions.clone();
+    let results = Arc::new(Mutex::new(HashMap::new()));
+    for uuid in executions.keys() {
+        let results = results.clone();
+        let uuid = *uuid;
+        eval.dag.on_execution_done(&uuid, move |result| {
+            results.lock().unwrap().insert(uuid, result);
+            Ok(())
+        });
+    }
+
+    // write the progress of the evaluation, separately from the UI
+    if let Some(path) = &opt.progress {
+        let mut file = std::fs::File::create(path).expect("Cannot create the progress file");
+        eval.dag
+            .on_progress(DEFAULT_PROGRESS_STEP, move |progress| {
+                writeln!(file, "{}", serde_json::to_string(&progress)?)?;
+                Ok(())
+            });
+    }
 
     // setup the ui thread
-    let mut ui = task.ui(opt.ui).expect("Invalid UI");
+    let mut ui = task.ui(opt.ui, &eval_config).expect("Invalid UI");
     let ui_thread = std::thread::Builder::new()
         .name("UI".to_owned())
         .spawn(move || {
@@ -133,16 +241,50 @@ fn main() {
             (cwd.path().to_owned(), Some(cwd))
         }
     };
-    let file_store =
-        Arc::new(FileStore::new(store_path.join("store")).expect("Cannot create the file store"));
-    let cache = Cache::new(store_path.join("cache")).expect("Cannot create the cache");
+    let file_store = match opt.max_store_size {
+        Some(size) => FileStore::new_with_limit(store_path.join("store"), size * 1024 * 1024),
+        None => FileStore::new(store_path.join("store")),
+    };
+    let file_store = Arc::new(file_store.expect("Cannot create the file store"));
+    let mut cache = Cache::new(store_path.join("cache")).expect("Cannot create the cache");
+    if opt.gc_store {
+        let referenced = cache
+            .referenced_files()
+            .expect("Cannot list the files of the cache");
+        let reclaimed = file_store
+            .gc(|key| referenced.contains(key))
+            .expect("Cannot clean up the file store");
+        info!(
+            "Removed {} unreferenced files from the store, reclaiming {} bytes",
+            reclaimed.entries, reclaimed.bytes
+        );
+    }
+    if opt.verify_store {
+        let corrupted = file_store.verify().expect("Cannot verify the file store");
+        if !corrupted.is_empty() {
+            warn!("{} files of the store are corrupted", corrupted.len());
+        }
+    }
     let num_cores = opt.num_cores.unwrap_or_else(num_cpus::get);
-    let sandbox_path = store_path.join("sandboxes");
-    let executor = LocalExecutor::new(file_store.clone(), num_cores, sandbox_path);
-
-    // build the DAG for the task
-    task.execute(&mut eval, &eval_config)
-        .expect("Failed to build the DAG");
+    let sandbox_path = opt
+        .sandbox_dir
+        .clone()
+        .unwrap_or_else(|| store_path.join("sandboxes"));
+    let mut executor = LocalExecutor::new(file_store.clone(), num_cores, sandbox_path);
+    if let Some(min_free_memory) = opt.min_free_memory {
+        match MemoryReservation::from_system(min_free_memory * 1024) {
+            Ok(reservation) => {
+                executor.memory_reservation(reservation);
+            }
+            Err(e) => warn!("Cannot reserve the free memory: {}", e),
+        }
+    }
+    if let Some(stall_timeout) = opt.stall_timeout {
+        assert!(stall_timeout > 0.0, "the stall timeout must be positive");
+        let watchdog = StallWatchdog::new(Duration::from_secs_f64(stall_timeout))
+            .dump_path(store_path.join("stall-diagnostics.txt"));
+        executor.stall_watchdog(watchdog);
+    }
 
     trace!("The DAG is: {:#?}", eval.dag);
 
@@ -156,14 +298,63 @@ fn main() {
         })
         .expect("Failed to spawn the executor thread");
 
+    // the first Ctrl-C stops the evaluation waiting for the running executions
+    let abort = AbortHandle::on_sigint().unwrap_or_else(|e| {
+        warn!("{}", e);
+        AbortHandle::new()
+    });
     let ui_sender = eval.sender.clone();
-    ExecutorClient::evaluate(eval.dag, tx, &rx, file_store, move |status| {
-        ui_sender.send(UIMessage::ServerStatus { status })
-    })
+    ExecutorClient::evaluate_with_abort(
+        eval.dag,
+        tx,
+        &rx,
+        file_store.clone(),
+        DEFAULT_MAX_CONCURRENT_TRANSFERS,
+        abort,
+        move |status| ui_sender.send(UIMessage::ServerStatus { status }),
+    )
     .expect("Client failed");
     task.sanity_check_post_hook(&mut eval.sender.lock().unwrap())
         .expect("Sanity checks failed");
 
+    // report the critical path of the evaluation
+    let critical_path = CriticalPath::compute(&executions, &results.lock().unwrap());
+    eval.sender
+        .send(UIMessage::CriticalPath {
+            length: critical_path.length,
+            total_time: critical_path.total_time,
+            executions: critical_path
+                .executions
+                .iter()
+                .map(|uuid| executions[uuid].description.clone())
+                .collect(),
+        })
+        .expect("Failed to send the critical path to the UI");
+    // report the time spent preparing the sandboxes, the cached executions did not use one
+    let sandboxed: Vec<_> = results
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|result| !result.was_cached)
+        .map(|result| (result.resources.setup_time, result.resources.wall_time))
+        .collect();
+    eval.sender
+        .send(UIMessage::SandboxOverhead {
+            setup_time: sandboxed.iter().map(|(setup, _)| setup).sum(),
+            wall_time: sandboxed.iter().map(|(_, wall)| wall).sum(),
+            executions: sandboxed.len(),
+        })
+        .expect("Failed to send the sandbox overhead to the UI");
+
+    // report the files evicted from the store during the evaluation to keep it below its limit
+    let evicted = file_store.evicted();
+    if evicted.entries > 0 {
+        info!(
+            "{} files ({} bytes) were evicted from the store during the evaluation",
+            evicted.entries, evicted.bytes
+        );
+    }
+
     // wait for the server and the ui to exit
     server.join().expect("Executor panicked");
     drop(eval.sender); // make the UI exit
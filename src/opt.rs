@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 use structopt::StructOpt;
-use task_maker_format::EvaluationConfig;
+use task_maker_format::{EvaluationConfig, EvaluationPhase};
 
 #[derive(StructOpt, Debug)]
 #[structopt(
@@ -12,12 +12,19 @@ pub struct Opt {
     #[structopt(short = "t", long = "task-dir", default_value = ".")]
     pub task_dir: PathBuf,
 
-    /// Which UI to use, available UIS are: print, raw, curses, json.
+    /// Which UI to use, available UIS are: print, silent, raw, curses, json.
     ///
     /// Note that the JSON api is not stable yet.
     #[structopt(long = "ui", default_value = "print")]
     pub ui: task_maker_format::ui::UIType,
 
+    /// Write the progress of the evaluation to this file, a JSON object per line
+    ///
+    /// Each object has the percentage of completed executions and the current phase, separately
+    /// from the UI.
+    #[structopt(long = "progress")]
+    pub progress: Option<PathBuf>,
+
     /// Keep all the sandbox directories
     #[structopt(long = "keep-sandboxes")]
     pub keep_sandboxes: bool,
@@ -34,6 +41,15 @@ pub struct Opt {
     #[allow(clippy::option_option)]
     pub no_cache: Option<Option<String>>,
 
+    /// Run again the cached executions with this comma separated list of tags, checking that their
+    /// outputs match the cached ones
+    ///
+    /// Providing an empty list will verify all the cached executions. The supported tags are the
+    /// same of --no-cache.
+    #[structopt(long = "verify")]
+    #[allow(clippy::option_option)]
+    pub verify: Option<Option<String>>,
+
     /// Do not run in parallel time critical executions on the same machine
     #[structopt(long = "exclusive")]
     pub exclusive: bool,
@@ -42,10 +58,62 @@ pub struct Opt {
     #[structopt(long = "extra-time")]
     pub extra_time: Option<f64>,
 
+    /// Give to the solution some extra memory, in KiB, before being killed
+    ///
+    /// The solutions using more memory than the memory limit are still reported as exceeding it.
+    #[structopt(long = "extra-memory")]
+    pub extra_memory: Option<u64>,
+
+    /// Kill the executions without a wall time limit after running for this number of seconds
+    ///
+    /// The killed executions are reported as internal errors.
+    #[structopt(long = "safety-wall-time")]
+    pub safety_wall_time: Option<f64>,
+
+    /// Run first the executions with the largest inputs
+    ///
+    /// This gives a quick feedback on the biggest testcases of each subtask.
+    #[structopt(long = "largest-inputs-first")]
+    pub largest_inputs_first: bool,
+
+    /// Stop running new executions after this number of them has been run
+    ///
+    /// The cached executions are not counted, the remaining executions are skipped.
+    #[structopt(long = "max-executions")]
+    pub max_executions: Option<usize>,
+
+    /// Stop running new executions after they used this amount of CPU time, in seconds
+    ///
+    /// The cached executions are not counted, the remaining executions are skipped.
+    #[structopt(long = "max-cpu-time")]
+    pub max_cpu_time: Option<f64>,
+
+    /// Stop the evaluation if it lasts more than this number of seconds, failing
+    ///
+    /// The running executions are killed and the remaining ones are skipped.
+    #[structopt(long = "max-total-duration")]
+    pub max_total_duration: Option<f64>,
+
     /// Copy the executables to the bin/ folder
     #[structopt(long = "copy-exe")]
     pub copy_exe: bool,
 
+    /// Compile the graders and the solutions in separate executions, linking them afterwards
+    #[structopt(long = "parallel-compilation")]
+    pub parallel_compilation: bool,
+
+    /// The wall time limit, in seconds, of the compilations
+    ///
+    /// It does not affect the time limit of the solutions.
+    #[structopt(long = "compilation-time-limit")]
+    pub compilation_time_limit: Option<f64>,
+
+    /// The memory limit, in MiB, of the compilations
+    ///
+    /// It does not affect the memory limit of the solutions.
+    #[structopt(long = "compilation-memory-limit")]
+    pub compilation_memory_limit: Option<u64>,
+
     /// Execute only the solutions whose names start with the filter
     ///
     /// Note that just the file name is checked (e.g. sol.cpp is the same as sol/sol.cpp). Without
@@ -62,6 +130,10 @@ pub struct Opt {
     #[structopt(long = "max-depth", default_value = "3")]
     pub max_depth: u32,
 
+    /// List the files that the evaluation would write to disk and exit, without running anything
+    #[structopt(long = "list-outputs")]
+    pub list_outputs: bool,
+
     /// Clear the task directory and exit
     #[structopt(long = "clean")]
     pub clean: bool,
@@ -70,13 +142,105 @@ pub struct Opt {
     #[structopt(long = "store-dir")]
     pub store_dir: Option<PathBuf>,
 
+    /// The maximum size, in MiB, of the files kept in the store directory
+    ///
+    /// When the store gets bigger the least recently used files are removed. By default the store
+    /// is not limited.
+    #[structopt(long = "max-store-size")]
+    pub max_store_size: Option<u64>,
+
+    /// Before the evaluation remove from the store directory the files not used by the cache
+    #[structopt(long = "gc-store")]
+    pub gc_store: bool,
+
+    /// Before the evaluation check the integrity of the files in the store directory
+    ///
+    /// The corrupted files are removed, so that they are computed again.
+    #[structopt(long = "verify-store")]
+    pub verify_store: bool,
+
+    /// Where to create the sandboxes, by default inside the store directory
+    ///
+    /// A fast filesystem (like a tmpfs) can be used, it must allow running the executables.
+    #[structopt(long = "sandbox-dir")]
+    pub sandbox_dir: Option<PathBuf>,
+
     /// The number of CPU cores to use
     #[structopt(long = "num-cores")]
     pub num_cores: Option<usize>,
 
+    /// Keep at least this many MiB of memory free, delaying the executions whose memory limit would
+    /// not fit in the rest
+    #[structopt(long = "min-free-memory")]
+    pub min_free_memory: Option<u64>,
+
+    /// Dump the state of the evaluation if no execution starts or completes for this number of
+    /// seconds
+    ///
+    /// The diagnostics are written to the log and to stall-diagnostics.txt inside the store
+    /// directory, for debugging the evaluations that hang.
+    #[structopt(long = "stall-timeout")]
+    pub stall_timeout: Option<f64>,
+
     /// Include the solutions in the booklet.
     #[structopt(long = "booklet-solutions")]
     pub booklet_solutions: bool,
+
+    /// Show the first bytes of the input and output files of each testcase, at most this many
+    #[structopt(long = "testcase-preview")]
+    pub testcase_preview: Option<usize>,
+
+    /// Which phases of the evaluation to run: full, generation (only generate the testcases) or
+    /// evaluation (only evaluate the solutions on the testcases already generated)
+    #[structopt(long = "phase", default_value = "full")]
+    pub phase: EvaluationPhase,
+
+    /// Run the custom checker twice on each output, warning if the two outcomes differ
+    #[structopt(long = "verify-checker")]
+    pub verify_checker: bool,
+
+    /// If the custom checker fails to compile compare the outputs with white diff instead of
+    /// skipping the checking, useful while writing the checker
+    #[structopt(long = "checker-fallback", conflicts_with = "verify_checker")]
+    pub checker_fallback: bool,
+
+    /// Check that the outputs of these correct solutions are accepted by the checker against the
+    /// output of the first one, warning on the testcases where they disagree
+    ///
+    /// Note that just the file name is checked (e.g. sol.cpp is the same as sol/sol.cpp).
+    #[structopt(long = "cross-check")]
+    pub cross_check: Vec<String>,
+
+    /// Fail before starting the evaluation if a solution would run more than this number of
+    /// executions
+    #[structopt(long = "max-solution-executions")]
+    pub max_solution_executions: Option<usize>,
+
+    /// Print at most this many testcase updates per second with the print UI, summarizing the
+    /// others
+    ///
+    /// All the updates are still used for the final results.
+    #[structopt(long = "ui-rate-limit")]
+    pub ui_rate_limit: Option<usize>,
+
+    /// Use this language for the files with an extension, instead of the detected one, in the form
+    /// ext=language
+    ///
+    /// For example `--language py=Python2` runs the .py files with Python 2. Other versions of the
+    /// languages are available: Python2, Python3, C++11 / gcc, C++11 / clang and C99 / gcc.
+    #[structopt(long = "language", parse(try_from_str = "parse_language_override"))]
+    pub language: Vec<(String, String)>,
+}
+
+/// Parse a language override in the form `ext=language`.
+fn parse_language_override(s: &str) -> Result<(String, String), String> {
+    let mut parts = s.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(ext), Some(language)) if !ext.is_empty() && !language.is_empty() => {
+            Ok((ext.to_string(), language.to_string()))
+        }
+        _ => Err(format!("Expecting ext=language, got {:?}", s)),
+    }
 }
 
 impl Opt {
@@ -86,6 +250,13 @@ impl Opt {
             solution_filter: self.filter.clone(),
             booklet_solutions: self.booklet_solutions,
             solution_paths: self.solution.clone(),
+            preview_size: self.testcase_preview,
+            phase: self.phase,
+            verify_checker: self.verify_checker,
+            checker_fallback: self.checker_fallback,
+            cross_check: self.cross_check.clone(),
+            max_solution_executions: self.max_solution_executions,
+            ui_rate_limit: self.ui_rate_limit,
         }
     }
 }
@@ -101,13 +101,23 @@ impl CacheEntry {
                 check_limit!($left.nofile, $right.nofile);
                 check_limit!($left.fsize, $right.fsize);
                 check_limit!($left.memlock, $right.memlock);
-                check_limit!($left.stack, $right.stack);
+                // a stack limit of 0 means unlimited, like a missing one
+                check_limit!(
+                    $left.stack.filter(|&stack| stack != 0),
+                    $right.stack.filter(|&stack| stack != 0)
+                );
+                check_limit!($left.stdio_size, $right.stdio_size);
+                check_limit!($left.tmpfs_size, $right.tmpfs_size);
+                check_limit!($left.seccomp, $right.seccomp);
                 if $left.read_only < $right.read_only {
                     return false;
                 }
                 if $left.mount_tmpfs > $right.mount_tmpfs {
                     return false;
                 }
+                if $left.allow_network > $right.allow_network {
+                    return false;
+                }
                 let left_readable_dirs: HashSet<PathBuf> =
                     $left.extra_readable_dirs.iter().cloned().collect();
                 let right_readable_dirs: HashSet<PathBuf> =
@@ -141,7 +151,8 @@ mod tests {
     use std::io::Write;
     use std::path::{Path, PathBuf};
     use task_maker_dag::{
-        Execution, ExecutionCommand, ExecutionResourcesUsage, ExecutionResult, ExecutionStatus,
+        Execution, ExecutionCommand, ExecutionProvenance, ExecutionResourcesUsage, ExecutionResult,
+        ExecutionStatus,
     };
     use task_maker_store::{FileStore, FileStoreHandle, FileStoreKey, ReadFileIterator};
 
@@ -162,11 +173,15 @@ mod tests {
                     status: ExecutionStatus::Success,
                     was_killed: false,
                     was_cached: false,
+                    truncated_output: false,
+                    provenance: ExecutionProvenance::Unknown,
                     resources: ExecutionResourcesUsage {
                         cpu_time: 0.0,
                         sys_time: 0.0,
                         wall_time: 0.0,
                         memory: 0,
+                        processes: None,
+                        setup_time: 0.0,
                     },
                 },
                 limits: Default::default(),
@@ -359,4 +374,45 @@ mod tests {
         exec2.limits.read_only = true;
         assert!(entry.is_compatible(&exec2));
     }
+
+    #[test]
+    fn test_compatible_success_network() {
+        let (mut entry, exec1) = empty_entry();
+        entry.result.status = ExecutionStatus::Success;
+        entry.limits.allow_network = true;
+        assert!(!entry.is_compatible(&exec1));
+
+        let mut exec2 = Execution::new("exec", ExecutionCommand::local("foo"));
+        exec2.limits.allow_network = true;
+        assert!(entry.is_compatible(&exec2));
+    }
+
+    #[test]
+    fn test_compatible_fail_network() {
+        let (mut entry, exec1) = empty_entry();
+        entry.result.status = ExecutionStatus::ReturnCode(1);
+        entry.limits.allow_network = false;
+        assert!(entry.is_compatible(&exec1));
+
+        let mut exec2 = Execution::new("exec", ExecutionCommand::local("foo"));
+        exec2.limits.allow_network = true;
+        assert!(!entry.is_compatible(&exec2));
+    }
+
+    #[test]
+    fn test_compatible_unlimited_stack() {
+        let (mut entry, mut exec1) = empty_entry();
+        entry.result.status = ExecutionStatus::Success;
+        entry.limits.stack = Some(0);
+        exec1.limits.stack = None;
+        assert!(entry.is_compatible(&exec1));
+        exec1.limits.stack = Some(8192);
+        assert!(!entry.is_compatible(&exec1));
+
+        entry.limits.stack = Some(8192);
+        exec1.limits.stack = Some(0);
+        assert!(entry.is_compatible(&exec1));
+        entry.result.status = ExecutionStatus::ReturnCode(1);
+        assert!(!entry.is_compatible(&exec1));
+    }
 }
@@ -21,6 +21,12 @@ pub struct CacheKey {
     pub inputs: Vec<(PathBuf, FileStoreKey, bool)>,
     /// The list of environment variables to set. Sorted by the variable name.
     pub env: Vec<(String, String)>,
+    /// The version of the toolchain run by the execution, if known.
+    #[serde(default)]
+    pub toolchain: Option<String>,
+    /// Whether the execution can access the network, its outputs may depend on it.
+    #[serde(default)]
+    pub allow_network: bool,
 }
 
 impl CacheKey {
@@ -45,6 +51,8 @@ impl CacheKey {
             stdin,
             inputs,
             env,
+            toolchain: execution.toolchain.clone(),
+            allow_network: execution.limits.allow_network,
         }
     }
 }
@@ -205,4 +213,36 @@ mod tests {
         assert_ne!(hash(&key1), hash(&key3));
         assert_ne!(hash(&key1), hash(&key4));
     }
+
+    #[test]
+    fn test_toolchain() {
+        let mut exec1 = Execution::new("exec1", ExecutionCommand::system("g++"));
+        exec1.toolchain("g++ 9.2.0");
+        let mut exec2 = Execution::new("exec2", ExecutionCommand::system("g++"));
+        exec2.toolchain("g++ 9.2.0");
+        let mut exec3 = Execution::new("exec3", ExecutionCommand::system("g++"));
+        exec3.toolchain("g++ 10.1.0");
+        let exec4 = Execution::new("exec4", ExecutionCommand::system("g++"));
+        let key1 = CacheKey::from_execution(&exec1, &HashMap::new());
+        let key2 = CacheKey::from_execution(&exec2, &HashMap::new());
+        let key3 = CacheKey::from_execution(&exec3, &HashMap::new());
+        let key4 = CacheKey::from_execution(&exec4, &HashMap::new());
+        assert_eq!(key1, key2);
+        assert_ne!(key1, key3);
+        assert_ne!(key1, key4);
+        assert_eq!(hash(&key1), hash(&key2));
+        assert_ne!(hash(&key1), hash(&key3));
+        assert_ne!(hash(&key1), hash(&key4));
+    }
+
+    #[test]
+    fn test_allow_network() {
+        let exec1 = Execution::new("exec1", ExecutionCommand::system("curl"));
+        let mut exec2 = Execution::new("exec2", ExecutionCommand::system("curl"));
+        exec2.limits_mut().allow_network(true);
+        let key1 = CacheKey::from_execution(&exec1, &HashMap::new());
+        let key2 = CacheKey::from_execution(&exec2, &HashMap::new());
+        assert_ne!(key1, key2);
+        assert_ne!(hash(&key1), hash(&key2));
+    }
 }
@@ -5,6 +5,10 @@
 //! inputs. For each cache key there may be more than one cache entry, allowing different execution
 //! limits to be used.
 //!
+//! The cache index is kept on disk, one file per cache key, addressed by the hash of the key. Only
+//! the keys used during the current session are loaded in memory, so the startup time and the
+//! memory usage do not depend on the size of the cache.
+//!
 //! The algorithm for extending a cache entry for a different limit is the following:
 //! - call `E1` the cached execution's result and `L1` its limits
 //! - call `E2` the execution to check and `L2` its limits
@@ -23,7 +27,7 @@
 //! use tempdir::TempDir;
 //! use task_maker_cache::{Cache, CacheResult};
 //! use std::collections::HashMap;
-//! use task_maker_dag::{Execution, ExecutionCommand, ExecutionResult, ExecutionStatus, ExecutionResourcesUsage, File};
+//! use task_maker_dag::{Execution, ExecutionCommand, ExecutionProvenance, ExecutionResult, ExecutionStatus, ExecutionResourcesUsage, File};
 //! use task_maker_store::{FileStore, FileStoreKey, ReadFileIterator};
 //!
 //! // make a new store and a new cache in a testing environment
@@ -47,10 +51,14 @@
 //!         cpu_time: 1.123,
 //!         sys_time: 0.2,
 //!         wall_time: 1.5,
-//!         memory: 12345
+//!         memory: 12345,
+//!         processes: None,
+//!         setup_time: 0.0,
 //!     },
 //!     was_killed: false,
 //!     was_cached: false,
+//!     truncated_output: false,
+//!     provenance: ExecutionProvenance::Unknown,
 //! };
 //!
 //! // make the FileUuid -> FileStoreHandle map
@@ -82,26 +90,31 @@ mod key;
 use entry::CacheEntry;
 use key::CacheKey;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use failure::Error;
+use failure::{bail, Error};
 use itertools::Itertools;
 
-use task_maker_dag::{Execution, ExecutionResult, ExecutionStatus, FileUuid};
-use task_maker_store::{FileStore, FileStoreHandle};
+use task_maker_dag::{Execution, ExecutionProvenance, ExecutionResult, ExecutionStatus, FileUuid};
+use task_maker_store::{FileStore, FileStoreHandle, FileStoreKey};
 
-/// The name of the file which holds the cache data.
+/// The name of the legacy file which holds all the cache data. If present it's migrated to the
+/// on-disk index.
 const CACHE_FILE: &str = "cache.json";
+/// The name of the directory with the on-disk index of the cache.
+const INDEX_DIR: &str = "index";
 
 /// Handle the cached executions, loading and storing them to disk.
 #[derive(Debug)]
 pub struct Cache {
-    /// All the cached entries.
+    /// The cached entries loaded from the disk during this session.
     entries: HashMap<CacheKey, Vec<CacheEntry>>,
-    /// The path to the cache file.
-    cache_file: PathBuf,
+    /// The keys whose entries have changed and have to be written back to disk.
+    dirty: HashSet<CacheKey>,
+    /// The path to the directory of the on-disk index.
+    index_dir: PathBuf,
 }
 
 /// The result of a cache query, can be either successful (`Hit`) or unsuccessful (`Miss`).
@@ -120,21 +133,26 @@ pub enum CacheResult {
 impl Cache {
     /// Make a new `Cache` stored in the specified cache directory. If the cache file is present
     /// it will be used and its content will be loaded, if valid, otherwise an error is returned.
+    ///
+    /// The index is not loaded in memory, the entries are read from disk only when needed.
     pub fn new<P: AsRef<Path>>(cache_dir: P) -> Result<Cache, Error> {
-        let path = cache_dir.as_ref().join(CACHE_FILE);
-        if path.exists() {
-            let file = std::fs::File::open(&path)?;
+        let mut cache = Cache {
+            entries: HashMap::new(),
+            dirty: HashSet::new(),
+            index_dir: cache_dir.as_ref().join(INDEX_DIR),
+        };
+        let legacy_path = cache_dir.as_ref().join(CACHE_FILE);
+        if legacy_path.exists() {
+            let file = std::fs::File::open(&legacy_path)?;
             let entries: Vec<(CacheKey, Vec<CacheEntry>)> = serde_json::from_reader(file)?;
-            Ok(Cache {
-                entries: entries.into_iter().collect(),
-                cache_file: path,
-            })
-        } else {
-            Ok(Cache {
-                entries: HashMap::new(),
-                cache_file: path,
-            })
+            for (key, entries) in entries {
+                cache.dirty.insert(key.clone());
+                cache.entries.insert(key, entries);
+            }
+            cache.flush()?;
+            std::fs::remove_file(&legacy_path)?;
         }
+        Ok(cache)
     }
 
     /// Insert a new entry inside the cache. They key is computed based on the execution's metadata
@@ -147,7 +165,8 @@ impl Cache {
         result: ExecutionResult,
     ) {
         let key = CacheKey::from_execution(execution, file_keys);
-        let set = self.entries.entry(key.clone()).or_default();
+        self.dirty.insert(key.clone());
+        let set = self.load_entries(&key);
         let stdout = execution
             .stdout
             .as_ref()
@@ -191,10 +210,7 @@ impl Cache {
         file_store: &FileStore,
     ) -> CacheResult {
         let key = CacheKey::from_execution(execution, file_keys);
-        if !self.entries.contains_key(&key) {
-            return CacheResult::Miss;
-        }
-        for entry in self.entries[&key].iter() {
+        for entry in self.load_entries(&key).iter() {
             match entry.outputs(file_store, execution) {
                 None => {
                     // TODO: remove the entry because it's not valid anymore
@@ -215,6 +231,8 @@ impl Cache {
                                 ),
                                 was_killed: entry.result.was_killed,
                                 was_cached: true,
+                                truncated_output: entry.result.truncated_output,
+                                provenance: ExecutionProvenance::Cache,
                                 resources: entry.result.resources.clone(),
                             },
                             outputs,
@@ -226,6 +244,52 @@ impl Cache {
         CacheResult::Miss
     }
 
+    /// The keys of all the files produced by the cached executions, the ones that should be kept
+    /// in the `FileStore` for the cache to be useful. The pending entries are written to disk
+    /// first.
+    pub fn referenced_files(&mut self) -> Result<HashSet<FileStoreKey>, Error> {
+        self.flush()?;
+        let mut files = HashSet::new();
+        if !self.index_dir.exists() {
+            return Ok(files);
+        }
+        // the index files are at depth 2 inside the index directory, for example aa/bb/aabbcc...
+        for first in std::fs::read_dir(&self.index_dir)? {
+            let first = first?.path();
+            if !first.is_dir() {
+                continue;
+            }
+            for second in std::fs::read_dir(first)? {
+                let second = second?.path();
+                if !second.is_dir() {
+                    continue;
+                }
+                for file in std::fs::read_dir(second)? {
+                    let path = file?.path();
+                    if path.extension().is_some() {
+                        // a temporary file of an interrupted flush
+                        continue;
+                    }
+                    let file = std::fs::File::open(&path)?;
+                    let (_, entries): (CacheKey, Vec<CacheEntry>) =
+                        match serde_json::from_reader(file) {
+                            Ok(data) => data,
+                            Err(e) => {
+                                warn!("Invalid cache index file {:?}: {:?}", path, e);
+                                continue;
+                            }
+                        };
+                    for entry in entries {
+                        files.extend(entry.stdout);
+                        files.extend(entry.stderr);
+                        files.extend(entry.outputs.into_values());
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+
     /// Checks whether a result is allowed in the cache.
     pub fn is_cacheable(result: &ExecutionResult) -> bool {
         if let ExecutionStatus::InternalError(_) = result.status {
@@ -234,33 +298,200 @@ impl Cache {
             true
         }
     }
+
+    /// Get the entries of a cache key, loading them from the on-disk index if they are not in
+    /// memory yet. If the index file is missing or invalid no entries are returned.
+    fn load_entries(&mut self, key: &CacheKey) -> &mut Vec<CacheEntry> {
+        if !self.entries.contains_key(key) {
+            let path = self.key_path(key);
+            let entries = if path.exists() {
+                match Cache::read_index_file(&path, key) {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        warn!("Invalid cache index file {:?}: {:?}", path, e);
+                        vec![]
+                    }
+                }
+            } else {
+                vec![]
+            };
+            self.entries.insert(key.clone(), entries);
+        }
+        self.entries.get_mut(key).expect("Entries not loaded")
+    }
+
+    /// Read the entries of a key from an index file, checking that the file refers to that key.
+    fn read_index_file(path: &Path, key: &CacheKey) -> Result<Vec<CacheEntry>, Error> {
+        let file = std::fs::File::open(path)?;
+        let (stored_key, entries): (CacheKey, Vec<CacheEntry>) = serde_json::from_reader(file)?;
+        if &stored_key != key {
+            bail!("Hash collision in the cache index");
+        }
+        Ok(entries)
+    }
+
+    /// The path of the index file of a cache key. The file is addressed by the hash of the
+    /// serialized key, for example `index/aa/bb/aabbccddeeff...`.
+    fn key_path(&self, key: &CacheKey) -> PathBuf {
+        let serialized = serde_json::to_vec(key).expect("Cannot serialize cache key");
+        let hash = FileStoreKey::from_content(&serialized).to_string();
+        self.index_dir
+            .join(&hash[0..2])
+            .join(&hash[2..4])
+            .join(hash)
+    }
+
+    /// Write to disk the entries that have been changed since the last flush. This is done also
+    /// when the cache is dropped, flushing earlier makes the entries survive a crash or a kill of
+    /// the process.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        for key in self.dirty.drain().collect_vec() {
+            let path = self.key_path(&key);
+            let dir = path.parent().expect("Invalid index file path");
+            std::fs::create_dir_all(dir)?;
+            let serialized = serde_json::to_string(&(&key, &self.entries[&key]))?;
+            // write to a temporary file and then move it, so the index file is never half-written
+            let tmp_path = path.with_extension("tmp");
+            std::fs::File::create(&tmp_path)?.write_all(serialized.as_bytes())?;
+            std::fs::rename(&tmp_path, &path)?;
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Cache {
     fn drop(&mut self) {
-        if let Err(e) =
-            std::fs::create_dir_all(self.cache_file.parent().expect("Invalid cache file"))
+        if let Err(e) = self.flush() {
+            error!("Cannot write cache index to disk! {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use task_maker_dag::{ExecutionCommand, ExecutionResourcesUsage};
+
+    fn result() -> ExecutionResult {
+        ExecutionResult {
+            status: ExecutionStatus::Success,
+            resources: ExecutionResourcesUsage {
+                cpu_time: 1.0,
+                sys_time: 0.0,
+                wall_time: 1.0,
+                memory: 1234,
+                processes: None,
+                setup_time: 0.0,
+            },
+            was_killed: false,
+            was_cached: false,
+            truncated_output: false,
+            provenance: ExecutionProvenance::Unknown,
+        }
+    }
+
+    #[test]
+    fn test_many_entries_lazy_load() {
+        let dir = tempdir::TempDir::new("tm-test").unwrap();
+        let store = FileStore::new(dir.path().join("store")).unwrap();
+        let execs = (0..1000)
+            .map(|i| {
+                let mut exec = Execution::new("exec", ExecutionCommand::system("true"));
+                exec.args(vec![i.to_string()]);
+                exec
+            })
+            .collect_vec();
         {
-            error!("Failed to create the directory of the cache file: {:?}", e);
-            return;
+            let mut cache = Cache::new(dir.path().join("cache")).unwrap();
+            for exec in execs.iter() {
+                cache.insert(exec, &HashMap::new(), result());
+            }
         }
-        let mut file = match std::fs::File::create(&self.cache_file) {
-            Ok(file) => file,
-            Err(e) => {
-                error!("Cannot save cache file to disk! {:?}", e);
-                return;
+        let mut cache = Cache::new(dir.path().join("cache")).unwrap();
+        assert!(cache.entries.is_empty());
+        for exec in execs.iter().step_by(100) {
+            match cache.get(exec, &HashMap::new(), &store) {
+                CacheResult::Hit { result, .. } => {
+                    assert!(result.was_cached);
+                    assert_eq!(result.provenance, ExecutionProvenance::Cache);
+                }
+                CacheResult::Miss => panic!("Expecting a hit"),
             }
+        }
+        assert_eq!(cache.entries.len(), 10);
+        let mut missing = Execution::new("exec", ExecutionCommand::system("false"));
+        missing.args(vec!["0"]);
+        match cache.get(&missing, &HashMap::new(), &store) {
+            CacheResult::Hit { .. } => panic!("Expecting a miss"),
+            CacheResult::Miss => {}
+        }
+    }
+
+    #[test]
+    fn test_flush() {
+        let dir = tempdir::TempDir::new("tm-test").unwrap();
+        let store = FileStore::new(dir.path().join("store")).unwrap();
+        let exec = Execution::new("exec", ExecutionCommand::system("true"));
+        let mut cache = Cache::new(dir.path().join("cache")).unwrap();
+        cache.insert(&exec, &HashMap::new(), result());
+        cache.flush().unwrap();
+        // the entry is on disk even if the first cache is never dropped
+        let mut other = Cache::new(dir.path().join("cache")).unwrap();
+        match other.get(&exec, &HashMap::new(), &store) {
+            CacheResult::Hit { .. } => {}
+            CacheResult::Miss => panic!("Expecting a hit"),
+        }
+    }
+
+    #[test]
+    fn test_referenced_files() {
+        let dir = tempdir::TempDir::new("tm-test").unwrap();
+        let store = FileStore::new(dir.path().join("store")).unwrap();
+        let mut cache = Cache::new(dir.path().join("cache")).unwrap();
+        assert!(cache.referenced_files().unwrap().is_empty());
+
+        let mut exec = Execution::new("exec", ExecutionCommand::system("true"));
+        let stdout = exec.stdout();
+        let output = exec.output("out");
+        let store_content = |content: &[u8]| {
+            store
+                .store(&FileStoreKey::from_content(content), vec![content.to_vec()])
+                .unwrap()
         };
-        let serialized = match serde_json::to_string(&self.entries.iter().collect_vec()) {
-            Ok(data) => data,
-            Err(e) => {
-                error!("Cannot serialize cache! {:?}", e);
-                return;
-            }
+        let mut file_keys = HashMap::new();
+        file_keys.insert(stdout.uuid, store_content(b"stdout"));
+        file_keys.insert(output.uuid, store_content(b"output"));
+        cache.insert(&exec, &file_keys, result());
+        // the files that are not in the index layout are ignored
+        std::fs::create_dir_all(&cache.index_dir).unwrap();
+        std::fs::write(cache.index_dir.join("README"), "not an index").unwrap();
+        let referenced = cache.referenced_files().unwrap();
+        assert_eq!(referenced.len(), 2);
+        assert!(referenced.contains(&FileStoreKey::from_content(b"stdout")));
+        assert!(referenced.contains(&FileStoreKey::from_content(b"output")));
+    }
+
+    #[test]
+    fn test_migrate_legacy_cache_file() {
+        let dir = tempdir::TempDir::new("tm-test").unwrap();
+        let store = FileStore::new(dir.path().join("store")).unwrap();
+        let exec = Execution::new("exec", ExecutionCommand::system("true"));
+        let key = CacheKey::from_execution(&exec, &HashMap::new());
+        let entry = CacheEntry {
+            result: result(),
+            limits: exec.limits.clone(),
+            stdout: None,
+            stderr: None,
+            outputs: HashMap::new(),
         };
-        match file.write_all(serialized.as_bytes()) {
-            Ok(_) => {}
-            Err(e) => error!("Cannot write cache file to disk! {:?}", e),
+        let legacy = serde_json::to_string(&vec![(key, vec![entry])]).unwrap();
+        std::fs::write(dir.path().join(CACHE_FILE), legacy).unwrap();
+
+        let mut cache = Cache::new(dir.path()).unwrap();
+        assert!(!dir.path().join(CACHE_FILE).exists());
+        match cache.get(&exec, &HashMap::new(), &store) {
+            CacheResult::Hit { .. } => {}
+            CacheResult::Miss => panic!("Expecting a hit"),
         }
     }
 }
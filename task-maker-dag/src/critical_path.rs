@@ -0,0 +1,249 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The longest chain of dependent executions of a DAG, weighted by their wall time. Its length is
+/// the minimum wall time of the evaluation, no matter how many workers are available.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CriticalPath {
+    /// The sum of the wall times of the executions in the critical path, in seconds.
+    pub length: f64,
+    /// The sum of the wall times of all the executions of the DAG, in seconds.
+    pub total_time: f64,
+    /// The executions in the critical path, in the order of execution.
+    pub executions: Vec<ExecutionUuid>,
+}
+
+impl CriticalPath {
+    /// Compute the critical path of the executions using the wall time of their results. The
+    /// executions without a result (for example the skipped ones) are considered instantaneous.
+    ///
+    /// ```
+    /// use task_maker_dag::{CriticalPath, Execution, ExecutionCommand};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut exec = Execution::new("generator", ExecutionCommand::local("gen"));
+    /// let input = exec.stdout();
+    /// let mut exec2 = Execution::new("solution", ExecutionCommand::local("sol"));
+    /// exec2.stdin(&input);
+    /// let (uuid, uuid2) = (exec.uuid, exec2.uuid);
+    /// let executions = vec![(uuid, exec), (uuid2, exec2)].into_iter().collect();
+    ///
+    /// let critical_path = CriticalPath::compute(&executions, &HashMap::new());
+    /// assert_eq!(critical_path.length, 0.0);
+    /// assert_eq!(critical_path.executions, vec![uuid, uuid2]);
+    /// ```
+    pub fn compute(
+        executions: &HashMap<ExecutionUuid, Execution>,
+        results: &HashMap<ExecutionUuid, ExecutionResult>,
+    ) -> CriticalPath {
+        let duration = |exec: &ExecutionUuid| {
+            results
+                .get(exec)
+                .map(|res| res.resources.wall_time)
+                .unwrap_or(0.0)
+        };
+        let producers: HashMap<FileUuid, ExecutionUuid> = executions
+            .values()
+            .flat_map(|exec| exec.outputs().into_iter().map(move |out| (out, exec.uuid)))
+            .collect();
+        // for each execution the executions that produce one of its inputs
+        let mut predecessors: HashMap<ExecutionUuid, Vec<ExecutionUuid>> = HashMap::new();
+        let mut successors: HashMap<ExecutionUuid, Vec<ExecutionUuid>> = HashMap::new();
+        for exec in executions.values() {
+            let mut preds: Vec<_> = exec
+                .dependencies()
+                .iter()
+                .filter_map(|file| producers.get(file).cloned())
+                .collect();
+            preds.sort();
+            preds.dedup();
+            for pred in &preds {
+                successors.entry(*pred).or_default().push(exec.uuid);
+            }
+            predecessors.insert(exec.uuid, preds);
+        }
+        for succs in successors.values_mut() {
+            succs.sort();
+        }
+
+        // visit the executions in topological order, keeping the longest chain ending in each
+        let mut missing: HashMap<ExecutionUuid, usize> = predecessors
+            .iter()
+            .map(|(exec, preds)| (*exec, preds.len()))
+            .collect();
+        let mut ready: Vec<_> = missing
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(exec, _)| *exec)
+            .collect();
+        ready.sort();
+        let mut queue: VecDeque<_> = ready.into();
+        let mut finish: HashMap<ExecutionUuid, f64> = HashMap::new();
+        let mut parent: HashMap<ExecutionUuid, ExecutionUuid> = HashMap::new();
+        let mut last: Option<(ExecutionUuid, f64)> = None;
+        while let Some(exec) = queue.pop_front() {
+            let longest_pred = predecessors[&exec]
+                .iter()
+                .filter_map(|pred| finish.get(pred).map(|time| (*pred, *time)))
+                .fold(
+                    None,
+                    |best: Option<(ExecutionUuid, f64)>, (pred, time)| match best {
+                        Some((_, best_time)) if best_time >= time => best,
+                        _ => Some((pred, time)),
+                    },
+                );
+            let start = match longest_pred {
+                Some((pred, time)) => {
+                    parent.insert(exec, pred);
+                    time
+                }
+                None => 0.0,
+            };
+            let end = start + duration(&exec);
+            finish.insert(exec, end);
+            // on ties prefer the later executions, making the path as long as possible
+            if last.is_none_or(|(_, time)| end >= time) {
+                last = Some((exec, end));
+            }
+            for succ in successors.get(&exec).into_iter().flatten() {
+                let count = missing.get_mut(succ).expect("Unknown successor");
+                *count -= 1;
+                if *count == 0 {
+                    queue.push_back(*succ);
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut length = 0.0;
+        if let Some((last, time)) = last {
+            length = time;
+            let mut current = Some(last);
+            while let Some(exec) = current {
+                path.push(exec);
+                current = parent.get(&exec).cloned();
+            }
+            path.reverse();
+        }
+        CriticalPath {
+            length,
+            total_time: executions.keys().map(duration).sum(),
+            executions: path,
+        }
+    }
+
+    /// The maximum speedup that adding more workers can give: the ratio between the total time of
+    /// the executions and the length of the critical path. `None` if the critical path is empty.
+    pub fn max_parallelism(&self) -> Option<f64> {
+        if self.length > 0.0 {
+            Some(self.total_time / self.length)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(wall_time: f64) -> ExecutionResult {
+        ExecutionResult {
+            status: ExecutionStatus::Success,
+            was_killed: false,
+            was_cached: false,
+            truncated_output: false,
+            provenance: ExecutionProvenance::Unknown,
+            resources: ExecutionResourcesUsage {
+                cpu_time: wall_time,
+                sys_time: 0.0,
+                wall_time,
+                memory: 0,
+                processes: None,
+                setup_time: 0.0,
+            },
+        }
+    }
+
+    fn add(
+        executions: &mut HashMap<ExecutionUuid, Execution>,
+        results: &mut HashMap<ExecutionUuid, ExecutionResult>,
+        exec: Execution,
+        wall_time: f64,
+    ) -> ExecutionUuid {
+        let uuid = exec.uuid;
+        executions.insert(uuid, exec);
+        results.insert(uuid, result(wall_time));
+        uuid
+    }
+
+    #[test]
+    fn test_critical_path_linear() {
+        let mut executions = HashMap::new();
+        let mut results = HashMap::new();
+        let mut exec1 = Execution::new("exec1", ExecutionCommand::local("foo"));
+        let out1 = exec1.stdout();
+        let mut exec2 = Execution::new("exec2", ExecutionCommand::local("foo"));
+        exec2.stdin(&out1);
+        let out2 = exec2.output("out");
+        let mut exec3 = Execution::new("exec3", ExecutionCommand::local("foo"));
+        exec3.input(&out2, "in", false);
+        let exec1 = add(&mut executions, &mut results, exec1, 1.0);
+        let exec2 = add(&mut executions, &mut results, exec2, 2.0);
+        let exec3 = add(&mut executions, &mut results, exec3, 3.0);
+
+        let critical_path = CriticalPath::compute(&executions, &results);
+        assert_abs_diff_eq!(critical_path.length, 6.0);
+        assert_abs_diff_eq!(critical_path.total_time, 6.0);
+        assert_eq!(critical_path.executions, vec![exec1, exec2, exec3]);
+        assert_abs_diff_eq!(critical_path.max_parallelism().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_critical_path_parallel() {
+        let mut executions = HashMap::new();
+        let mut results = HashMap::new();
+        let mut gen = Execution::new("gen", ExecutionCommand::local("foo"));
+        let input = gen.stdout();
+        let mut fast = Execution::new("fast", ExecutionCommand::local("foo"));
+        fast.stdin(&input);
+        let mut slow = Execution::new("slow", ExecutionCommand::local("foo"));
+        slow.stdin(&input);
+        let other = Execution::new("other", ExecutionCommand::local("foo"));
+        let gen = add(&mut executions, &mut results, gen, 1.0);
+        add(&mut executions, &mut results, fast, 2.0);
+        let slow = add(&mut executions, &mut results, slow, 4.0);
+        add(&mut executions, &mut results, other, 3.0);
+
+        let critical_path = CriticalPath::compute(&executions, &results);
+        assert_abs_diff_eq!(critical_path.length, 5.0);
+        assert_abs_diff_eq!(critical_path.total_time, 10.0);
+        assert_eq!(critical_path.executions, vec![gen, slow]);
+        assert_abs_diff_eq!(critical_path.max_parallelism().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_critical_path_missing_results() {
+        let mut executions = HashMap::new();
+        let mut results = HashMap::new();
+        let mut gen = Execution::new("gen", ExecutionCommand::local("foo"));
+        let input = gen.stdout();
+        let mut skipped = Execution::new("skipped", ExecutionCommand::local("foo"));
+        skipped.stdin(&input);
+        let gen = add(&mut executions, &mut results, gen, 1.5);
+        let skipped_uuid = skipped.uuid;
+        executions.insert(skipped_uuid, skipped);
+
+        let critical_path = CriticalPath::compute(&executions, &results);
+        assert_abs_diff_eq!(critical_path.length, 1.5);
+        assert_eq!(critical_path.executions[0], gen);
+
+        let empty = CriticalPath::compute(&HashMap::new(), &HashMap::new());
+        assert_abs_diff_eq!(empty.length, 0.0);
+        assert!(empty.executions.is_empty());
+        assert!(empty.max_parallelism().is_none());
+    }
+}
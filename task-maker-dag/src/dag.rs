@@ -10,6 +10,13 @@ use task_maker_store::*;
 use crate::file::*;
 use crate::*;
 
+/// The default wall time limit, in seconds, of the executions without an explicit one.
+pub const DEFAULT_SAFETY_WALL_TIME: f64 = 600.0;
+/// The default wall time limit, in seconds, of the compilations.
+pub const DEFAULT_COMPILATION_TIME_LIMIT: f64 = 10.0;
+/// The default memory limit, in KiB, of the compilations.
+pub const DEFAULT_COMPILATION_MEMORY_LIMIT: u64 = 2 * 1024 * 1024;
+
 /// The setting of the cache level.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CacheMode {
@@ -21,6 +28,18 @@ pub enum CacheMode {
     Except(HashSet<ExecutionTag>),
 }
 
+/// The setting of the verification of the cached executions. The executions to verify are run
+/// even if they are in the cache, and their outputs are compared with the cached ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum VerifyMode {
+    /// Do not verify any execution.
+    Nothing,
+    /// Verify all the cached executions.
+    Everything,
+    /// Verify only the executions with these tags.
+    Only(HashSet<ExecutionTag>),
+}
+
 /// Configuration setting of an `ExecutionDAG`, some of the values set here will be inherited in the
 /// configuration of the executions added.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,10 +52,39 @@ pub struct ExecutionDAGConfig {
     pub dry_run: bool,
     /// The cache mode for this DAG.
     pub cache_mode: CacheMode,
+    /// Which cached executions should be run again to check that they are deterministic.
+    pub verify_mode: VerifyMode,
+    /// The maximum number of executions to dispatch to the workers, the cached ones are not
+    /// counted. After the budget is exhausted the remaining executions are skipped.
+    pub max_executions: Option<usize>,
+    /// The maximum total CPU time (user + sys), in seconds, the dispatched executions can use.
+    /// After the budget is exhausted the remaining executions are skipped.
+    pub max_cpu_time: Option<f64>,
+    /// The maximum wall time, in seconds, of the whole evaluation. After it the evaluation is
+    /// stopped: the running executions are killed, the remaining ones are skipped and the
+    /// evaluation fails.
+    pub max_total_duration: Option<f64>,
     /// Extra time to give to the sandbox before killing the process, in seconds.
     pub extra_time: f64,
+    /// Extra memory, in KiB, to give to the sandbox before killing the process. The executions
+    /// using more memory than their limit are still reported as exceeding it.
+    pub extra_memory: u64,
     /// Whether to copy the executables of the compilation inside their default destinations.
     pub copy_exe: bool,
+    /// Whether to compile the units of the programs in separate executions, linking them together
+    /// afterwards, when the language supports it.
+    pub parallel_compilation: bool,
+    /// The wall time limit, in seconds, of the executions without an explicit one. The executions
+    /// exceeding it are killed and reported as internal errors.
+    pub safety_wall_time: Option<f64>,
+    /// Dispatch first the ready executions with the largest inputs, for having a quick feedback on
+    /// the biggest testcases of each subtask.
+    pub largest_inputs_first: bool,
+    /// The wall time limit, in seconds, of the compilations. The limits of the compilations are
+    /// independent of the ones of the executions of the programs.
+    pub compilation_time_limit: Option<f64>,
+    /// The memory limit, in KiB, of the compilations.
+    pub compilation_memory_limit: Option<u64>,
 }
 
 /// A wrapper around a `File` provided by the client, this means that the client knows the
@@ -129,6 +177,9 @@ impl ExecutionDAG {
     /// Add an execution to the DAG.
     pub fn add_execution(&mut self, mut execution: Execution) {
         execution.config = self.data.config.clone();
+        for concurrent in execution.concurrent.iter_mut() {
+            concurrent.config = self.data.config.clone();
+        }
         self.data.executions.insert(execution.uuid, execution);
     }
 
@@ -170,6 +221,19 @@ impl ExecutionDAG {
         }
     }
 
+    /// The list of the paths that will be written by the `write_file_to` callbacks when the DAG
+    /// is evaluated, sorted. Some of them may not be written if the generation of the file fails.
+    pub fn planned_outputs(&self) -> Vec<PathBuf> {
+        let mut outputs: Vec<PathBuf> = self
+            .file_callbacks
+            .values()
+            .filter_map(|callbacks| callbacks.write_to.as_ref())
+            .map(|write_to| write_to.dest.clone())
+            .collect();
+        outputs.sort();
+        outputs
+    }
+
     /// Call `callback` with the first `limit` bytes of the file when it's ready. The file must be
     /// present in the DAG before the evaluation starts.
     ///
@@ -206,12 +270,32 @@ impl ExecutionDAG {
     pub fn on_execution_skip<F>(&mut self, execution: &ExecutionUuid, callback: F)
     where
         F: (FnOnce() -> Result<(), Error>) + 'static,
+    {
+        self.on_execution_skip_reason(execution, move |_| callback());
+    }
+
+    /// Add a callback that will be called when the execution is skipped, with the reason of the
+    /// skip.
+    pub fn on_execution_skip_reason<F>(&mut self, execution: &ExecutionUuid, callback: F)
+    where
+        F: (FnOnce(SkipReason) -> Result<(), Error>) + 'static,
     {
         self.execution_callback(execution)
             .on_skip
             .push(BoxFnOnce::from(callback));
     }
 
+    /// Add a callback that will be called when the execution, run again for verifying the cache,
+    /// produced outputs different from the cached ones.
+    pub fn on_execution_nondeterminism<F>(&mut self, execution: &ExecutionUuid, callback: F)
+    where
+        F: (FnOnce(Vec<FileUuid>) -> Result<(), Error>) + 'static,
+    {
+        self.execution_callback(execution)
+            .on_nondeterminism
+            .push(BoxFnOnce::from(callback));
+    }
+
     /// Get a mutable reference to the config of this DAG.
     pub fn config_mut(&mut self) -> &mut ExecutionDAGConfig {
         &mut self.data.config
@@ -235,8 +319,18 @@ impl ExecutionDAGConfig {
             keep_sandboxes: false,
             dry_run: false,
             cache_mode: CacheMode::Everything,
+            verify_mode: VerifyMode::Nothing,
+            max_executions: None,
+            max_cpu_time: None,
+            max_total_duration: None,
             extra_time: 0.5,
+            extra_memory: 0,
             copy_exe: false,
+            parallel_compilation: false,
+            safety_wall_time: Some(DEFAULT_SAFETY_WALL_TIME),
+            largest_inputs_first: false,
+            compilation_time_limit: Some(DEFAULT_COMPILATION_TIME_LIMIT),
+            compilation_memory_limit: Some(DEFAULT_COMPILATION_MEMORY_LIMIT),
         }
     }
 
@@ -258,6 +352,33 @@ impl ExecutionDAGConfig {
         self
     }
 
+    /// Set which cached executions of this DAG should be verified.
+    pub fn verify_mode(&mut self, verify_mode: VerifyMode) -> &mut Self {
+        self.verify_mode = verify_mode;
+        self
+    }
+
+    /// Set the maximum number of executions to dispatch to the workers.
+    pub fn max_executions(&mut self, max_executions: Option<usize>) -> &mut Self {
+        self.max_executions = max_executions;
+        self
+    }
+
+    /// Set the maximum total CPU time, in seconds, the dispatched executions can use.
+    pub fn max_cpu_time(&mut self, max_cpu_time: Option<f64>) -> &mut Self {
+        self.max_cpu_time = max_cpu_time;
+        self
+    }
+
+    /// Set the maximum wall time, in seconds, of the whole evaluation.
+    pub fn max_total_duration(&mut self, max_total_duration: Option<f64>) -> &mut Self {
+        if let Some(max_total_duration) = max_total_duration {
+            assert!(max_total_duration > 0.0);
+        }
+        self.max_total_duration = max_total_duration;
+        self
+    }
+
     /// Set the extra time to give to the executions before being killed by the sandbox.
     pub fn extra_time(&mut self, extra_time: f64) -> &mut Self {
         assert!(extra_time >= 0.0);
@@ -265,11 +386,55 @@ impl ExecutionDAGConfig {
         self
     }
 
+    /// Set the extra memory, in KiB, to give to the executions before being killed by the
+    /// sandbox.
+    pub fn extra_memory(&mut self, extra_memory: u64) -> &mut Self {
+        self.extra_memory = extra_memory;
+        self
+    }
+
     /// Set whether to copy the executables of the compilation inside their default destinations.
     pub fn copy_exe(&mut self, copy_exe: bool) -> &mut Self {
         self.copy_exe = copy_exe;
         self
     }
+
+    /// Set whether to compile the units of the programs in separate executions.
+    pub fn parallel_compilation(&mut self, parallel_compilation: bool) -> &mut Self {
+        self.parallel_compilation = parallel_compilation;
+        self
+    }
+
+    /// Set the wall time limit, in seconds, of the executions without an explicit one. `None`
+    /// lets those executions run forever.
+    pub fn safety_wall_time(&mut self, safety_wall_time: Option<f64>) -> &mut Self {
+        if let Some(safety_wall_time) = safety_wall_time {
+            assert!(safety_wall_time > 0.0);
+        }
+        self.safety_wall_time = safety_wall_time;
+        self
+    }
+
+    /// Set whether to dispatch first the ready executions with the largest inputs.
+    pub fn largest_inputs_first(&mut self, largest_inputs_first: bool) -> &mut Self {
+        self.largest_inputs_first = largest_inputs_first;
+        self
+    }
+
+    /// Set the wall time limit, in seconds, of the compilations. `None` lets them run forever.
+    pub fn compilation_time_limit(&mut self, compilation_time_limit: Option<f64>) -> &mut Self {
+        if let Some(compilation_time_limit) = compilation_time_limit {
+            assert!(compilation_time_limit > 0.0);
+        }
+        self.compilation_time_limit = compilation_time_limit;
+        self
+    }
+
+    /// Set the memory limit, in KiB, of the compilations. `None` removes the limit.
+    pub fn compilation_memory_limit(&mut self, compilation_memory_limit: Option<u64>) -> &mut Self {
+        self.compilation_memory_limit = compilation_memory_limit;
+        self
+    }
 }
 
 impl Default for ExecutionDAGConfig {
@@ -296,6 +461,16 @@ impl From<Option<Option<String>>> for CacheMode {
     }
 }
 
+impl From<Option<Option<String>>> for VerifyMode {
+    fn from(conf: Option<Option<String>>) -> Self {
+        match conf {
+            None => VerifyMode::Nothing,
+            Some(None) => VerifyMode::Everything,
+            Some(Some(list)) => VerifyMode::Only(list.split(',').map(ExecutionTag::from).collect()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -351,6 +526,17 @@ mod tests {
         assert_eq!(&42.0, &dag.data.executions[&exec.uuid].config.extra_time);
     }
 
+    #[test]
+    fn test_add_execution_concurrent() {
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().extra_time(42.0);
+        let mut exec = Execution::new("exec", ExecutionCommand::local("foo"));
+        exec.add_concurrent(Execution::new("concurrent", ExecutionCommand::local("bar")));
+        dag.add_execution(exec.clone());
+        let concurrent = &dag.data.executions[&exec.uuid].concurrent[0];
+        assert_eq!(&42.0, &concurrent.config.extra_time);
+    }
+
     #[test]
     fn test_write_file_to() {
         let mut dag = ExecutionDAG::new();
@@ -362,6 +548,21 @@ mod tests {
         assert_eq!(false, write_to.executable);
     }
 
+    #[test]
+    fn test_planned_outputs() {
+        let mut dag = ExecutionDAG::new();
+        let file1 = File::new("file1");
+        let file2 = File::new("file2");
+        let file3 = File::new("file3");
+        dag.write_file_to(file1, "output/b", false);
+        dag.write_file_to_allow_fail(file2, "output/a", true);
+        dag.get_file_content(file3, 42, |_| Ok(()));
+        assert_eq!(
+            dag.planned_outputs(),
+            vec![PathBuf::from("output/a"), PathBuf::from("output/b")]
+        );
+    }
+
     #[test]
     fn test_write_file_to_executable() {
         let mut dag = ExecutionDAG::new();
@@ -428,6 +629,17 @@ mod tests {
         assert_eq!(1, dag.execution_callbacks[&exec.uuid].on_skip.len());
     }
 
+    #[test]
+    fn test_on_execution_nondeterminism() {
+        let mut dag = ExecutionDAG::new();
+        let exec = Execution::new("exec", ExecutionCommand::local("foo"));
+        dag.on_execution_nondeterminism(&exec.uuid, |_| Ok(()));
+        assert_eq!(
+            1,
+            dag.execution_callbacks[&exec.uuid].on_nondeterminism.len()
+        );
+    }
+
     #[test]
     fn test_config_mut() {
         let mut dag = ExecutionDAG::new();
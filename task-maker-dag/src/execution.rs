@@ -23,8 +23,14 @@ pub type OnStartCallback = BoxFnOnce<'static, (WorkerUuid,), Result<(), Error>>;
 /// Type of the callback called when an [`Execution`](struct.Execution.html) ends.
 pub type OnDoneCallback = BoxFnOnce<'static, (ExecutionResult,), Result<(), Error>>;
 
-/// Type of the callback called when an [`Execution`](struct.Execution.html) is skipped.
-pub type OnSkipCallback = BoxFnOnce<'static, (), Result<(), Error>>;
+/// Type of the callback called when an [`Execution`](struct.Execution.html) is skipped, with the
+/// reason of the skip.
+pub type OnSkipCallback = BoxFnOnce<'static, (SkipReason,), Result<(), Error>>;
+
+/// Type of the callback called when an [`Execution`](struct.Execution.html), run again for
+/// verifying the cache, produced outputs different from the cached ones. The argument is the list
+/// of the mismatching files.
+pub type OnNondeterminismCallback = BoxFnOnce<'static, (Vec<FileUuid>,), Result<(), Error>>;
 
 /// A tag on an `Execution`. Can be used to classify the executions into groups and refer to them,
 /// for example for splitting the cache scopes.
@@ -73,6 +79,8 @@ pub struct ExecutionCallbacks {
     pub on_done: Vec<OnDoneCallback>,
     /// The callbacks called when the execution has been skipped.
     pub on_skip: Vec<OnSkipCallback>,
+    /// The callbacks called when the verification of the cache found a mismatch in the outputs.
+    pub on_nondeterminism: Vec<OnNondeterminismCallback>,
 }
 
 /// An [`Execution`](struct.Execution.html) is a process that will be executed by a worker inside a
@@ -133,6 +141,24 @@ pub struct Execution {
     pub inputs: HashMap<PathBuf, ExecutionInput>,
     /// List of the output files that should be capture from the sandbox.
     pub outputs: HashMap<PathBuf, File>,
+    /// The fifos to create inside the sandbox of this execution, where the process can open them
+    /// by name. They can be connected to the standard input and output of the concurrent
+    /// executions.
+    #[serde(default)]
+    pub fifos: Vec<PathBuf>,
+    /// The fifo, among the ones of the group of concurrent executions, to use as standard input
+    /// instead of a file.
+    #[serde(default)]
+    pub stdin_fifo: Option<PathBuf>,
+    /// The fifo, among the ones of the group of concurrent executions, to use as standard output
+    /// instead of a file.
+    #[serde(default)]
+    pub stdout_fifo: Option<PathBuf>,
+    /// The executions to run at the same time as this one, on the same worker, for example for
+    /// letting them communicate using the fifos of this execution. Their files are part of the
+    /// dependencies and of the outputs of this execution.
+    #[serde(default)]
+    pub concurrent: Vec<Execution>,
 
     /// Environment variables to set.
     pub env: HashMap<String, String>,
@@ -143,11 +169,62 @@ pub struct Execution {
     /// The configuration of the underlying DAG. Will be overwritten by
     /// `ExecutionDAG.add_execution`.
     pub(crate) config: ExecutionDAGConfig,
+    /// The extra time to give to this execution, overriding the one of the configuration of the
+    /// DAG.
+    pub extra_time: Option<f64>,
+    /// The extra memory, in KiB, to give to this execution, overriding the one of the
+    /// configuration of the DAG.
+    pub extra_memory: Option<u64>,
+    /// Whether to run this execution even if some of its inputs come from failed executions. The
+    /// execution is still skipped if one of its inputs has not been produced at all.
+    pub allow_failed_inputs: bool,
+    /// The names of the files, inside the sandbox directory, used for the standard input, output
+    /// and error of the process.
+    pub stdio_names: ExecutionStdioNames,
+    /// The priority of this execution: among the ready executions the ones with the highest
+    /// priority are dispatched first.
+    pub priority: i32,
+    /// The version of the toolchain (e.g. the compiler) run by this execution, if known. It's part
+    /// of the cache key, so that the results of a toolchain are not reused after it's upgraded.
+    pub toolchain: Option<String>,
 
     /// The tag associated with this execution.
     pub tag: Option<ExecutionTag>,
 }
 
+/// The names of the files used by the sandbox for the standard input, output and error of an
+/// [`Execution`](struct.Execution.html). The files are stored in the sandbox directory, next to
+/// the `box` directory where the process runs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExecutionStdioNames {
+    /// The name of the file with the standard input.
+    pub stdin: String,
+    /// The name of the file with the standard output.
+    pub stdout: String,
+    /// The name of the file with the standard error.
+    pub stderr: String,
+}
+
+/// The seccomp profile the sandbox applies to an execution. The profiles are ordered from the most
+/// restrictive to the least restrictive.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SeccompProfile {
+    /// Disallow the dangerous syscalls, including the network ones. Suitable for the solutions.
+    Strict,
+    /// Allow most of the syscalls, for the programs that need them (e.g. the compilers).
+    Permissive,
+}
+
+impl SeccompProfile {
+    /// The name of the profile, as passed to the sandbox.
+    pub fn name(self) -> &'static str {
+        match self {
+            SeccompProfile::Strict => "strict",
+            SeccompProfile::Permissive => "permissive",
+        }
+    }
+}
+
 /// Limits on an [`Execution`](struct.Execution.html). On some worker platforms some of the fields
 /// may not be supported or may be less accurate.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -162,6 +239,12 @@ pub struct ExecutionLimits {
     /// Limit on the number of KiB the process can use in any moment. This can be page-aligned by
     /// the sandbox.
     pub memory: Option<u64>,
+    /// The argument that tells the program its own memory limit, with `{}` replaced by the limit
+    /// in KiB (e.g. `-Xmx{}k` for the JVM). When set, the memory limit is passed to the program as
+    /// its first argument instead of being enforced by the sandbox, which would limit the address
+    /// space the program can reserve. The peak memory usage is still checked against the limit.
+    #[serde(default)]
+    pub memory_arg: Option<String>,
     /// Limit on the number of threads/processes the process can spawn.
     pub nproc: Option<u32>,
     /// Limit on the number of file descriptors the process can keep open.
@@ -170,14 +253,33 @@ pub struct ExecutionLimits {
     pub fsize: Option<u64>,
     /// RLIMIT_MEMLOCK
     pub memlock: Option<u64>,
-    /// Limit on the stack size for the process. 0 means unlimited.
+    /// Limit on the stack size for the process, in KiB. 0 means unlimited.
     pub stack: Option<u64>,
+    /// Maximum size (in bytes) of the standard output and of the standard error of the process,
+    /// the exceeding part is discarded.
+    #[serde(default)]
+    pub stdio_size: Option<u64>,
     /// Whether the process in the sandbox is not allowed to create new files inside the sandbox.
     pub read_only: bool,
     /// Whether the process in the sandbox can use `/dev/null` and `/tmp`.
     pub mount_tmpfs: bool,
+    /// Maximum size (in bytes) of the `/tmp` mounted in the sandbox, only if `mount_tmpfs` is
+    /// set. `None` lets the sandbox use its default size.
+    #[serde(default)]
+    pub tmpfs_size: Option<u64>,
     /// Extra directory that can be read inside the sandbox.
     pub extra_readable_dirs: Vec<PathBuf>,
+    /// Whether the sandbox refuses to start if some of the extra readable directories do not
+    /// exist. If `false` the missing directories are silently ignored.
+    #[serde(default = "default_strict_readable_dirs")]
+    pub strict_readable_dirs: bool,
+    /// The seccomp profile to apply to the process, if `None` the sandbox does not filter the
+    /// syscalls.
+    pub seccomp: Option<SeccompProfile>,
+    /// Whether the process in the sandbox can use the network. By default the process is fully
+    /// isolated, not even the loopback interface is available.
+    #[serde(default)]
+    pub allow_network: bool,
 }
 
 /// Status of a completed [`Execution`](struct.Execution.html).
@@ -201,6 +303,17 @@ pub enum ExecutionStatus {
     InternalError(String),
 }
 
+/// The reason why an [`Execution`](struct.Execution.html) has been skipped.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SkipReason {
+    /// One of its inputs has not been produced, since the execution producing it failed.
+    FailedDependency,
+    /// The budget of the DAG has been exhausted.
+    OverBudget,
+    /// The evaluation has been aborted before the execution could start.
+    Aborted,
+}
+
 /// Resources used during the execution, note that on some platform these values may not be
 /// accurate.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -213,6 +326,14 @@ pub struct ExecutionResourcesUsage {
     pub wall_time: f64,
     /// Number of KiB used _at most_ by the process.
     pub memory: u64,
+    /// Number of processes and threads spawned by the execution, including the first one. It's
+    /// `None` if the sandbox cannot count them.
+    #[serde(default)]
+    pub processes: Option<u32>,
+    /// Number of seconds spent preparing the sandbox before starting the process, mostly copying
+    /// its input files. Not included in the `wall_time`.
+    #[serde(default)]
+    pub setup_time: f64,
 }
 
 /// The result of an [`Execution`](struct.Execution.html).
@@ -224,10 +345,65 @@ pub struct ExecutionResult {
     pub was_killed: bool,
     /// Whether the execution result come from the cache.
     pub was_cached: bool,
+    /// Whether the standard output or the standard error of the process have been truncated
+    /// because they exceeded the limit on their size.
+    #[serde(default)]
+    pub truncated_output: bool,
+    /// Who produced this result.
+    #[serde(default)]
+    pub provenance: ExecutionProvenance,
     /// Resources used by the execution.
     pub resources: ExecutionResourcesUsage,
 }
 
+/// Who produced the result of an execution.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum ExecutionProvenance {
+    /// It is not known who produced the result.
+    #[default]
+    Unknown,
+    /// The result comes from the cache, the execution has not been run again.
+    Cache,
+    /// The execution has been run by a worker.
+    Worker {
+        /// The identifier of the worker.
+        uuid: WorkerUuid,
+        /// The name of the worker.
+        name: String,
+    },
+}
+
+impl std::fmt::Display for ExecutionProvenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExecutionProvenance::Unknown => write!(f, "unknown"),
+            ExecutionProvenance::Cache => write!(f, "cache"),
+            ExecutionProvenance::Worker { name, .. } => write!(f, "{}", name),
+        }
+    }
+}
+
+impl ExecutionStdioNames {
+    /// Make a new set of names for the standard input, output and error files.
+    pub fn new<S1: Into<String>, S2: Into<String>, S3: Into<String>>(
+        stdin: S1,
+        stdout: S2,
+        stderr: S3,
+    ) -> ExecutionStdioNames {
+        ExecutionStdioNames {
+            stdin: stdin.into(),
+            stdout: stdout.into(),
+            stderr: stderr.into(),
+        }
+    }
+}
+
+impl Default for ExecutionStdioNames {
+    fn default() -> Self {
+        ExecutionStdioNames::new("stdin", "stdout", "stderr")
+    }
+}
+
 impl ExecutionLimits {
     /// Make an empty limits where all the limits are disabled. You may want to
     /// use `default()` instead of this
@@ -237,14 +413,20 @@ impl ExecutionLimits {
             sys_time: None,
             wall_time: None,
             memory: None,
+            memory_arg: None,
             nproc: None,
             nofile: None,
             fsize: None,
             memlock: None,
             stack: None,
+            stdio_size: None,
             read_only: false,
             mount_tmpfs: true,
+            tmpfs_size: None,
             extra_readable_dirs: Vec::new(),
+            strict_readable_dirs: true,
+            seccomp: None,
+            allow_network: false,
         }
     }
 
@@ -272,6 +454,21 @@ impl ExecutionLimits {
         self
     }
 
+    /// Pass the memory limit to the program with the specified argument instead of enforcing it
+    /// with the sandbox, `{}` is replaced by the limit in KiB.
+    ///
+    /// ```
+    /// use task_maker_dag::ExecutionLimits;
+    ///
+    /// let mut limits = ExecutionLimits::default();
+    /// limits.memory(1024).memory_arg("-Xmx{}k");
+    /// assert_eq!(limits.memory_arg, Some("-Xmx{}k".to_string()));
+    /// ```
+    pub fn memory_arg<S: Into<String>>(&mut self, arg: S) -> &mut Self {
+        self.memory_arg = Some(arg.into());
+        self
+    }
+
     /// Set the maximum number of processes/threads.
     pub fn nproc(&mut self, limit: u32) -> &mut Self {
         self.nproc = Some(limit);
@@ -296,12 +493,19 @@ impl ExecutionLimits {
         self
     }
 
-    /// Set the stack limit.
+    /// Set the stack limit, in KiB. 0 means unlimited.
     pub fn stack(&mut self, limit: u64) -> &mut Self {
         self.stack = Some(limit);
         self
     }
 
+    /// Set the maximum size (in bytes) of the standard output and of the standard error of the
+    /// process.
+    pub fn stdio_size(&mut self, limit: u64) -> &mut Self {
+        self.stdio_size = Some(limit);
+        self
+    }
+
     /// Set whether the process in the sandbox is not allowed to create new files inside the
     /// sandbox.
     pub fn read_only(&mut self, read_only: bool) -> &mut Self {
@@ -315,11 +519,36 @@ impl ExecutionLimits {
         self
     }
 
+    /// Set the maximum size (in bytes) of the `/tmp` mounted in the sandbox.
+    pub fn tmpfs_size(&mut self, limit: u64) -> &mut Self {
+        self.tmpfs_size = Some(limit);
+        self
+    }
+
     /// Add a directory to the list of additional readable directories in the sandbox.
     pub fn add_extra_readable_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
         self.extra_readable_dirs.push(dir.into());
         self
     }
+
+    /// Set whether the sandbox refuses to start if some of the extra readable directories do not
+    /// exist, instead of ignoring them.
+    pub fn strict_readable_dirs(&mut self, strict_readable_dirs: bool) -> &mut Self {
+        self.strict_readable_dirs = strict_readable_dirs;
+        self
+    }
+
+    /// Set whether the process in the sandbox can use the network.
+    pub fn allow_network(&mut self, allow_network: bool) -> &mut Self {
+        self.allow_network = allow_network;
+        self
+    }
+
+    /// Set the seccomp profile to apply to the process.
+    pub fn seccomp(&mut self, profile: SeccompProfile) -> &mut Self {
+        self.seccomp = Some(profile);
+        self
+    }
 }
 
 impl std::default::Default for ExecutionLimits {
@@ -331,18 +560,30 @@ impl std::default::Default for ExecutionLimits {
             sys_time: None,
             wall_time: None,
             memory: None,
+            memory_arg: None,
             nproc: Some(1),
             nofile: None,
             fsize: Some(1024u64.pow(3)),
             memlock: None,
             stack: Some(0),
+            stdio_size: Some(1024u64.pow(3)),
             read_only: true,
             mount_tmpfs: false,
+            tmpfs_size: None,
             extra_readable_dirs: Vec::new(),
+            strict_readable_dirs: true,
+            seccomp: None,
+            allow_network: false,
         }
     }
 }
 
+/// The default value for the `strict_readable_dirs` field of the limits, for the ones serialized
+/// before it was introduced.
+fn default_strict_readable_dirs() -> bool {
+    true
+}
+
 impl ExecutionCommand {
     /// Make a new `ExecutionCommand::System`.
     pub fn system<P: Into<PathBuf>>(path: P) -> ExecutionCommand {
@@ -376,18 +617,29 @@ impl Execution {
             stderr: None,
             inputs: HashMap::new(),
             outputs: HashMap::new(),
+            fifos: vec![],
+            stdin_fifo: None,
+            stdout_fifo: None,
+            concurrent: vec![],
 
             env: HashMap::new(),
 
             limits: ExecutionLimits::default(),
 
             config: ExecutionDAGConfig::new(),
+            extra_time: None,
+            extra_memory: None,
+            allow_failed_inputs: false,
+            stdio_names: ExecutionStdioNames::default(),
+            priority: 0,
+            toolchain: None,
 
             tag: None,
         }
     }
 
-    /// List of all the [File](struct.File.html) dependencies of the execution, including `stdin`.
+    /// List of all the [File](struct.File.html) dependencies of the execution, including `stdin`
+    /// and the dependencies of the concurrent executions.
     ///
     /// ```
     /// use task_maker_dag::{Execution, ExecutionCommand, File};
@@ -406,11 +658,14 @@ impl Execution {
         for input in self.inputs.values() {
             deps.push(input.file);
         }
+        for exec in &self.concurrent {
+            deps.extend(exec.dependencies());
+        }
         deps
     }
 
-    /// List of all the [File](struct.File.html) produced by the execution, including `stdout` and
-    /// `stderr`.
+    /// List of all the [File](struct.File.html) produced by the execution, including `stdout`,
+    /// `stderr` and the outputs of the concurrent executions.
     ///
     /// ```
     /// use task_maker_dag::{Execution, ExecutionCommand};
@@ -431,6 +686,9 @@ impl Execution {
         for output in self.outputs.values() {
             outs.push(output.uuid);
         }
+        for exec in &self.concurrent {
+            outs.extend(exec.outputs());
+        }
         outs
     }
 
@@ -488,6 +746,9 @@ impl Execution {
     /// the evaluation starts in order to track the file. Calling this method more than once will
     /// return the same value.
     ///
+    /// Like the standard output, the returned file can be used as an input of other executions,
+    /// for example as their standard input.
+    ///
     /// ```
     /// use task_maker_dag::{Execution, ExecutionCommand};
     ///
@@ -585,6 +846,218 @@ impl Execution {
         &self.config
     }
 
+    /// Override the extra time of the DAG configuration for this `Execution`. The extra time is
+    /// added to the cpu and wall time limits before killing the process.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand};
+    ///
+    /// let mut exec = Execution::new("generator of prime numbers", ExecutionCommand::local("foo"));
+    /// assert_eq!(exec.effective_extra_time(), exec.config().extra_time);
+    /// exec.extra_time(2.0);
+    /// assert_eq!(exec.effective_extra_time(), 2.0);
+    /// ```
+    pub fn extra_time(&mut self, extra_time: f64) -> &mut Self {
+        assert!(extra_time >= 0.0);
+        self.extra_time = Some(extra_time);
+        self
+    }
+
+    /// The extra time given to this `Execution`: the one set with
+    /// [`extra_time`](#method.extra_time) if any, otherwise the one of the DAG configuration.
+    pub fn effective_extra_time(&self) -> f64 {
+        self.extra_time.unwrap_or(self.config.extra_time)
+    }
+
+    /// The wall time limit the sandbox enforces on this `Execution`, without the extra time: its
+    /// own limit if any, otherwise the safety wall time of the DAG configuration.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand, DEFAULT_SAFETY_WALL_TIME};
+    ///
+    /// let mut exec = Execution::new("generator of prime numbers", ExecutionCommand::local("foo"));
+    /// assert_eq!(exec.effective_wall_time(), Some(DEFAULT_SAFETY_WALL_TIME));
+    /// exec.limits_mut().wall_time(2.0);
+    /// assert_eq!(exec.effective_wall_time(), Some(2.0));
+    /// ```
+    pub fn effective_wall_time(&self) -> Option<f64> {
+        self.limits.wall_time.or(self.config.safety_wall_time)
+    }
+
+    /// Override the extra memory of the DAG configuration for this `Execution`. The extra memory,
+    /// in KiB, is added to the memory limit before killing the process, but the execution is still
+    /// reported as exceeding the memory limit if it uses more than the limit.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand};
+    ///
+    /// let mut exec = Execution::new("generator of prime numbers", ExecutionCommand::local("foo"));
+    /// assert_eq!(exec.effective_extra_memory(), exec.config().extra_memory);
+    /// exec.extra_memory(1024);
+    /// assert_eq!(exec.effective_extra_memory(), 1024);
+    /// ```
+    pub fn extra_memory(&mut self, extra_memory: u64) -> &mut Self {
+        self.extra_memory = Some(extra_memory);
+        self
+    }
+
+    /// The extra memory given to this `Execution`: the one set with
+    /// [`extra_memory`](#method.extra_memory) if any, otherwise the one of the DAG configuration.
+    pub fn effective_extra_memory(&self) -> u64 {
+        self.extra_memory.unwrap_or(self.config.extra_memory)
+    }
+
+    /// Run this `Execution` even if some of its inputs are produced by failed executions, for
+    /// example for checking the partial output of a solution that timed out.
+    pub fn allow_failed_inputs(&mut self, allow: bool) -> &mut Self {
+        self.allow_failed_inputs = allow;
+        self
+    }
+
+    /// Change the names of the files used by the sandbox for the standard input, output and error
+    /// of this `Execution`. The names must be distinct plain file names, different from `box`.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand, ExecutionStdioNames};
+    ///
+    /// let mut exec = Execution::new("generator of prime numbers", ExecutionCommand::local("foo"));
+    /// assert_eq!(exec.stdio_names.stdout, "stdout");
+    /// exec.stdio_names(ExecutionStdioNames::new("input.txt", "output.txt", "error.txt"));
+    /// assert_eq!(exec.stdio_names.stdout, "output.txt");
+    /// ```
+    pub fn stdio_names(&mut self, names: ExecutionStdioNames) -> &mut Self {
+        for name in &[&names.stdin, &names.stdout, &names.stderr] {
+            assert!(
+                !name.is_empty()
+                    && !name.contains('/')
+                    && *name != "."
+                    && *name != ".."
+                    && *name != "box",
+                "Invalid stdio file name: {:?}",
+                name
+            );
+        }
+        assert!(
+            names.stdin != names.stdout
+                && names.stdin != names.stderr
+                && names.stdout != names.stderr,
+            "The stdio file names must be distinct"
+        );
+        self.stdio_names = names;
+        self
+    }
+
+    /// Set the priority of this `Execution`, by default it's zero. The ready executions with higher
+    /// priority are dispatched to the workers before the others, for example for getting a quick
+    /// feedback on some important ones.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand};
+    ///
+    /// let mut exec = Execution::new("official solution", ExecutionCommand::local("sol"));
+    /// assert_eq!(exec.priority, 0);
+    /// exec.priority(10);
+    /// assert_eq!(exec.priority, 10);
+    /// ```
+    pub fn priority(&mut self, priority: i32) -> &mut Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set the version of the toolchain run by this `Execution`. The executions with a different
+    /// toolchain version never share the cache entries.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand};
+    ///
+    /// let mut exec = Execution::new("compilation", ExecutionCommand::system("g++"));
+    /// assert_eq!(exec.toolchain, None);
+    /// exec.toolchain("g++ (GCC) 9.2.0");
+    /// assert_eq!(exec.toolchain, Some("g++ (GCC) 9.2.0".to_string()));
+    /// ```
+    pub fn toolchain<S: Into<String>>(&mut self, version: S) -> &mut Self {
+        self.toolchain = Some(version.into());
+        self
+    }
+
+    /// Create a fifo named `path` inside the sandbox of this `Execution`. The process can open it
+    /// by name, and the concurrent executions can use it as their standard input or output.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand};
+    /// use std::path::PathBuf;
+    ///
+    /// let mut exec = Execution::new("manager", ExecutionCommand::local("manager"));
+    /// exec.fifo("sol_to_man").fifo("man_to_sol");
+    /// assert_eq!(exec.fifos, vec![PathBuf::from("sol_to_man"), PathBuf::from("man_to_sol")]);
+    /// ```
+    pub fn fifo<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.fifos.push(path.into());
+        self
+    }
+
+    /// Use a fifo of the group of concurrent executions as the standard input, instead of a file.
+    /// Calling this method will remove the standard input file, if any.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand};
+    /// use std::path::PathBuf;
+    ///
+    /// let mut exec = Execution::new("solution", ExecutionCommand::local("sol"));
+    /// exec.stdin_fifo("man_to_sol");
+    /// assert_eq!(exec.stdin_fifo, Some(PathBuf::from("man_to_sol")));
+    /// ```
+    pub fn stdin_fifo<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.stdin = None;
+        self.stdin_fifo = Some(path.into());
+        self
+    }
+
+    /// Use a fifo of the group of concurrent executions as the standard output, instead of a file.
+    /// Calling this method will stop tracking the standard output file, if any.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand};
+    /// use std::path::PathBuf;
+    ///
+    /// let mut exec = Execution::new("solution", ExecutionCommand::local("sol"));
+    /// exec.stdout_fifo("sol_to_man");
+    /// assert_eq!(exec.stdout_fifo, Some(PathBuf::from("sol_to_man")));
+    /// assert_eq!(exec.stdout, None);
+    /// ```
+    pub fn stdout_fifo<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.stdout = None;
+        self.stdout_fifo = Some(path.into());
+        self
+    }
+
+    /// Run `execution` at the same time as this one, on the same worker. The fifos of this
+    /// execution are created before starting any of the processes, and as soon as one of them
+    /// exits the others see the end of the fifos they are connected to. The concurrent executions
+    /// cannot have concurrent executions on their own.
+    ///
+    /// The group is never cached, but each execution has its own result and the outputs of each
+    /// of them are marked as failed only if that execution fails.
+    ///
+    /// ```
+    /// use task_maker_dag::{Execution, ExecutionCommand};
+    ///
+    /// let mut manager = Execution::new("manager", ExecutionCommand::local("manager"));
+    /// manager.fifo("sol_to_man").fifo("man_to_sol");
+    /// let mut solution = Execution::new("solution", ExecutionCommand::local("sol"));
+    /// solution.stdin_fifo("man_to_sol").stdout_fifo("sol_to_man");
+    /// manager.add_concurrent(solution);
+    /// assert_eq!(manager.concurrent.len(), 1);
+    /// ```
+    pub fn add_concurrent(&mut self, execution: Execution) -> &mut Self {
+        assert!(
+            execution.concurrent.is_empty(),
+            "The concurrent executions cannot be nested"
+        );
+        self.concurrent.push(execution);
+        self
+    }
+
     /// Set the tag of this `Execution`.
     pub fn tag(&mut self, tag: ExecutionTag) -> &mut Self {
         self.tag = Some(tag);
@@ -615,7 +1088,17 @@ impl Execution {
             if resources.wall_time > wall_time_limit {
                 return ExecutionStatus::WallTimeLimitExceeded;
             }
+        } else if let Some(safety_wall_time) = self.config.safety_wall_time {
+            // the execution was not expected to run for this long, it probably hanged
+            if resources.wall_time > safety_wall_time {
+                return ExecutionStatus::InternalError(format!(
+                    "The execution has been killed after running for more than {}s",
+                    safety_wall_time
+                ));
+            }
         }
+        // the extra memory is only given to the sandbox, the execution using more than the limit
+        // exceeds it even if it was not killed
         if let Some(memory_limit) = self.limits.memory {
             if resources.memory > memory_limit {
                 return ExecutionStatus::MemoryLimitExceeded;
@@ -638,6 +1121,7 @@ impl std::fmt::Debug for ExecutionCallbacks {
             .field("on_start", &self.on_start.len())
             .field("on_done", &self.on_done.len())
             .field("on_skip", &self.on_skip.len())
+            .field("on_nondeterminism", &self.on_nondeterminism.len())
             .finish()?;
         Ok(())
     }
@@ -649,6 +1133,7 @@ impl std::default::Default for ExecutionCallbacks {
             on_start: Vec::new(),
             on_done: Vec::new(),
             on_skip: Vec::new(),
+            on_nondeterminism: Vec::new(),
         }
     }
 }
@@ -675,6 +1160,8 @@ mod tests {
                 sys_time: 0.0,
                 wall_time: 0.0,
                 memory: 0,
+                processes: None,
+                setup_time: 0.0,
             },
         );
         assert_eq!(ExecutionStatus::Success, status);
@@ -692,6 +1179,8 @@ mod tests {
                 sys_time: 0.0,
                 wall_time: 0.0,
                 memory: 0,
+                processes: None,
+                setup_time: 0.0,
             },
         );
         assert_eq!(ExecutionStatus::TimeLimitExceeded, status);
@@ -709,6 +1198,8 @@ mod tests {
                 sys_time: 1.1,
                 wall_time: 0.0,
                 memory: 0,
+                processes: None,
+                setup_time: 0.0,
             },
         );
         assert_eq!(ExecutionStatus::SysTimeLimitExceeded, status);
@@ -726,11 +1217,35 @@ mod tests {
                 sys_time: 0.0,
                 wall_time: 1.1,
                 memory: 0,
+                processes: None,
+                setup_time: 0.0,
             },
         );
         assert_eq!(ExecutionStatus::WallTimeLimitExceeded, status);
     }
 
+    #[test]
+    fn test_status_safety_wall_time() {
+        let mut exec = Execution::new("foo", ExecutionCommand::local("foo"));
+        exec.config.safety_wall_time(Some(1.0));
+        let status = exec.status(
+            0,
+            None,
+            &ExecutionResourcesUsage {
+                cpu_time: 0.0,
+                sys_time: 0.0,
+                wall_time: 1.1,
+                memory: 0,
+                processes: None,
+                setup_time: 0.0,
+            },
+        );
+        match status {
+            ExecutionStatus::InternalError(_) => {}
+            status => panic!("Unexpected status {:?}", status),
+        }
+    }
+
     #[test]
     fn test_status_memory() {
         let mut exec = Execution::new("foo", ExecutionCommand::local("foo"));
@@ -743,6 +1258,28 @@ mod tests {
                 sys_time: 0.0,
                 wall_time: 0.0,
                 memory: 1235,
+                processes: None,
+                setup_time: 0.0,
+            },
+        );
+        assert_eq!(ExecutionStatus::MemoryLimitExceeded, status);
+    }
+
+    #[test]
+    fn test_status_memory_extra_memory() {
+        let mut exec = Execution::new("foo", ExecutionCommand::local("foo"));
+        exec.limits_mut().memory(1234);
+        exec.extra_memory(100);
+        let status = exec.status(
+            0,
+            None,
+            &ExecutionResourcesUsage {
+                cpu_time: 0.0,
+                sys_time: 0.0,
+                wall_time: 0.0,
+                memory: 1300,
+                processes: None,
+                setup_time: 0.0,
             },
         );
         assert_eq!(ExecutionStatus::MemoryLimitExceeded, status);
@@ -759,6 +1296,8 @@ mod tests {
                 sys_time: 0.0,
                 wall_time: 0.0,
                 memory: 0,
+                processes: None,
+                setup_time: 0.0,
             },
         );
         assert_eq!(ExecutionStatus::Signal(11, strsignal(11)), status);
@@ -775,6 +1314,8 @@ mod tests {
                 sys_time: 0.0,
                 wall_time: 0.0,
                 memory: 0,
+                processes: None,
+                setup_time: 0.0,
             },
         );
         assert_eq!(ExecutionStatus::ReturnCode(1), status);
@@ -43,11 +43,15 @@
 #[macro_use]
 extern crate approx;
 
+mod critical_path;
 mod dag;
 mod execution;
 mod file;
+mod progress;
 mod signals;
 
+pub use critical_path::*;
 pub use dag::*;
 pub use execution::*;
 pub use file::*;
+pub use progress::*;
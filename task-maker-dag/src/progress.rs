@@ -0,0 +1,115 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use failure::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The default minimum increment, in percentage points, between two progress updates.
+pub const DEFAULT_PROGRESS_STEP: f64 = 1.0;
+
+/// A lightweight update on the progress of the evaluation of a DAG, meant for the tools that wrap
+/// task-maker and do not want to parse the full UI messages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgressUpdate {
+    /// The percentage of the executions of the DAG that are completed or skipped, from 0 to 100.
+    pub percent: f64,
+    /// The phase of the evaluation the last completed execution belongs to: the name of its tag,
+    /// `None` for the executions without a tag.
+    pub phase: Option<String>,
+    /// Whether all the executions of the phase are completed or skipped.
+    pub phase_done: bool,
+}
+
+/// The state of the progress of the evaluation, shared by the callbacks of the executions.
+struct ProgressState {
+    /// The number of executions in the DAG.
+    total: usize,
+    /// The number of executions completed or skipped.
+    completed: usize,
+    /// The number of executions of each phase not completed yet.
+    remaining: HashMap<Option<String>, usize>,
+    /// The percentage of the last update sent.
+    last_percent: Option<f64>,
+    /// The minimum increment between two updates, in percentage points.
+    step: f64,
+    /// The function to call with the updates.
+    callback: Box<dyn FnMut(ProgressUpdate) -> Result<(), Error>>,
+}
+
+impl ProgressState {
+    /// Mark an execution of that phase as completed, sending an update if the phase is done or
+    /// the progress advanced enough since the last update.
+    fn completed(&mut self, phase: Option<String>) -> Result<(), Error> {
+        self.completed += 1;
+        let remaining = self
+            .remaining
+            .get_mut(&phase)
+            .expect("Execution of an unknown phase");
+        *remaining -= 1;
+        let phase_done = *remaining == 0;
+        let percent = 100.0 * self.completed as f64 / self.total as f64;
+        let advanced = match self.last_percent {
+            Some(last) => percent >= last + self.step,
+            None => true,
+        };
+        if phase_done || advanced || self.completed == self.total {
+            self.last_percent = Some(percent);
+            (self.callback)(ProgressUpdate {
+                percent,
+                phase,
+                phase_done,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl ExecutionDAG {
+    /// Call `callback` with the progress of the evaluation: when all the executions with the same
+    /// tag are completed (or skipped) and every time the completed percentage advances by at least
+    /// `step` percentage points. The last update has always `percent` equal to 100.
+    ///
+    /// This must be called after all the executions have been added to the DAG.
+    ///
+    /// ```
+    /// use task_maker_dag::{ExecutionDAG, Execution, ExecutionCommand};
+    ///
+    /// let mut dag = ExecutionDAG::new();
+    /// dag.add_execution(Execution::new("Get the date", ExecutionCommand::system("date")));
+    /// dag.on_progress(5.0, |progress| Ok(println!("{:.1}% done", progress.percent)));
+    /// ```
+    pub fn on_progress<F>(&mut self, step: f64, callback: F)
+    where
+        F: (FnMut(ProgressUpdate) -> Result<(), Error>) + 'static,
+    {
+        assert!(step >= 0.0);
+        let mut remaining: HashMap<Option<String>, usize> = HashMap::new();
+        let executions: Vec<_> = self
+            .data
+            .executions
+            .values()
+            .map(|exec| (exec.uuid, exec.tag.as_ref().map(|tag| tag.name.clone())))
+            .collect();
+        for (_, phase) in &executions {
+            *remaining.entry(phase.clone()).or_default() += 1;
+        }
+        let state = Rc::new(RefCell::new(ProgressState {
+            total: executions.len(),
+            completed: 0,
+            remaining,
+            last_percent: None,
+            step,
+            callback: Box::new(callback),
+        }));
+        for (uuid, phase) in executions {
+            let done = state.clone();
+            let done_phase = phase.clone();
+            self.on_execution_done(&uuid, move |_| done.borrow_mut().completed(done_phase));
+            let skipped = state.clone();
+            self.on_execution_skip(&uuid, move || skipped.borrow_mut().completed(phase));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use failure::{bail, Error};
+
+/// Whether a SIGINT has been received since the handler has been installed.
+static SIGINT_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// An handle to gracefully stop an evaluation: when it's aborted the client asks the server to
+/// stop, the executions not started yet are skipped and the running ones are waited, so that their
+/// sandboxes are cleaned up.
+///
+/// ```
+/// use task_maker_exec::AbortHandle;
+///
+/// let handle = AbortHandle::new();
+/// let other = handle.clone();
+/// assert!(!other.is_aborted());
+/// handle.abort();
+/// assert!(other.is_aborted());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AbortHandle {
+    /// Whether `abort` has been called on this handle or on one of its clones.
+    aborted: Arc<AtomicBool>,
+    /// Whether this handle is aborted also by a SIGINT.
+    on_sigint: bool,
+}
+
+impl AbortHandle {
+    /// Make a new handle, not aborted.
+    pub fn new() -> AbortHandle {
+        AbortHandle::default()
+    }
+
+    /// Install an handler for SIGINT and make a new handle that is aborted when the process
+    /// receives it. A second SIGINT terminates the process immediately.
+    ///
+    /// The handler is process-wide and replaces the default behaviour of Ctrl-C, for this reason
+    /// it's never installed implicitly.
+    pub fn on_sigint() -> Result<AbortHandle, Error> {
+        let handler = sigint_handler as extern "C" fn(libc::c_int);
+        if unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) } == libc::SIG_ERR {
+            bail!(
+                "Cannot install the SIGINT handler: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(AbortHandle {
+            aborted: Arc::new(AtomicBool::new(false)),
+            on_sigint: true,
+        })
+    }
+
+    /// Abort the evaluation.
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the evaluation has been aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::SeqCst)
+            || (self.on_sigint && SIGINT_RECEIVED.load(Ordering::SeqCst))
+    }
+}
+
+/// The SIGINT handler, it only marks the signal as received since it has to be async-signal-safe.
+extern "C" fn sigint_handler(_: libc::c_int) {
+    if SIGINT_RECEIVED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(130) };
+    }
+}
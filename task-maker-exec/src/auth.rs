@@ -0,0 +1,101 @@
+use blake2::crypto_mac::Mac;
+use blake2::{Blake2b, Digest};
+use uuid::Uuid;
+
+/// A secret shared by an executor and the workers and clients allowed to connect to it.
+///
+/// When the connection starts the executor sends a random nonce, and the other side has to answer
+/// with the MAC of the nonce computed with the token, so that the token never travels on the
+/// network.
+///
+/// ```
+/// use task_maker_exec::AuthToken;
+///
+/// let token = AuthToken::new("secret");
+/// let nonce = AuthToken::nonce();
+/// let response = token.sign(&nonce);
+/// assert!(token.verify(&nonce, &response));
+/// assert!(!AuthToken::new("guess").verify(&nonce, &response));
+/// ```
+#[derive(Clone)]
+pub struct AuthToken {
+    /// The key of the MAC, derived from the secret.
+    key: Vec<u8>,
+}
+
+impl AuthToken {
+    /// Make a new token from a secret of any length.
+    pub fn new<S: AsRef<[u8]>>(secret: S) -> AuthToken {
+        AuthToken {
+            key: Blake2b::digest(secret.as_ref()).to_vec(),
+        }
+    }
+
+    /// Generate a new random nonce for a connection.
+    pub fn nonce() -> Vec<u8> {
+        Uuid::new_v4().as_bytes().to_vec()
+    }
+
+    /// The response to send back for the nonce.
+    pub fn sign(&self, nonce: &[u8]) -> Vec<u8> {
+        Mac::result(self.mac(nonce)).code().to_vec()
+    }
+
+    /// Check whether the response is the correct one for the nonce, in constant time.
+    pub fn verify(&self, nonce: &[u8], response: &[u8]) -> bool {
+        self.mac(nonce).verify(response).is_ok()
+    }
+
+    /// The MAC keyed with this token, fed with the nonce.
+    fn mac(&self, nonce: &[u8]) -> Blake2b {
+        let mut mac = Blake2b::new_varkey(&self.key).expect("Invalid key length");
+        Mac::input(&mut mac, nonce);
+        mac
+    }
+}
+
+impl std::fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // never print the secret
+        write!(f, "AuthToken(..)")
+    }
+}
+
+/// Check the response to an authentication challenge. Without a token every response is accepted,
+/// otherwise the response must be present and match the nonce. The reason of the rejection is
+/// returned on failure.
+pub(crate) fn check_auth_response(
+    token: Option<&AuthToken>,
+    nonce: &[u8],
+    response: Option<&[u8]>,
+) -> Result<(), String> {
+    match (token, response) {
+        (None, _) => Ok(()),
+        (Some(_), None) => Err("authentication token required".into()),
+        (Some(token), Some(response)) => {
+            if token.verify(nonce, response) {
+                Ok(())
+            } else {
+                Err("wrong authentication token".into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_auth_response() {
+        let token = AuthToken::new("secret");
+        let nonce = AuthToken::nonce();
+        let response = token.sign(&nonce);
+        assert!(check_auth_response(None, &nonce, None).is_ok());
+        assert!(check_auth_response(Some(&token), &nonce, Some(&response)).is_ok());
+        assert!(check_auth_response(Some(&token), &nonce, None).is_err());
+        // the response of another nonce cannot be replayed
+        let other = AuthToken::nonce();
+        assert!(check_auth_response(Some(&token), &other, Some(&response)).is_err());
+    }
+}
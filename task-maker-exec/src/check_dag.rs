@@ -135,9 +135,14 @@ pub fn check_dag(dag: &ExecutionDAGData, callbacks: &ExecutionDAGWatchSet) -> Re
             });
         }
     }
-    // check the execution callbacks
+    // check the execution callbacks, the concurrent executions can have callbacks too
+    let concurrent: HashSet<ExecutionUuid> = dag
+        .executions
+        .values()
+        .flat_map(|exec| exec.concurrent.iter().map(|concurrent| concurrent.uuid))
+        .collect();
     for exec in callbacks.executions.iter() {
-        if !num_dependencies.contains_key(&exec) {
+        if !num_dependencies.contains_key(&exec) && !concurrent.contains(&exec) {
             return Err(DAGError::MissingExecution { uuid: *exec });
         }
     }
@@ -181,6 +186,20 @@ mod tests {
         assert!(check_dag(&dag.data, &watch).is_err());
     }
 
+    #[test]
+    fn test_concurrent_execution_callback() {
+        let mut dag = ExecutionDAG::new();
+        let mut exec = Execution::new("exec", ExecutionCommand::local("foo"));
+        let concurrent = Execution::new("concurrent", ExecutionCommand::local("bar"));
+        let watch = ExecutionDAGWatchSet {
+            executions: [concurrent.uuid].iter().cloned().collect(),
+            files: Default::default(),
+        };
+        exec.add_concurrent(concurrent);
+        dag.add_execution(exec);
+        assert!(check_dag(&dag.data, &watch).is_ok());
+    }
+
     #[test]
     fn test_cycle_self() {
         let mut dag = ExecutionDAG::new();
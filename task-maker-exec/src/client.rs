@@ -1,16 +1,21 @@
 use crate::proto::*;
 use crate::*;
-use failure::{format_err, Error};
+use failure::{bail, format_err, Error};
 use std::collections::HashMap;
 use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, SystemTime};
-use task_maker_dag::{FileCallbacks, FileUuid, ProvidedFile, WriteToCallback};
+use std::time::{Duration, Instant, SystemTime};
+use task_maker_dag::{ExecutionStatus, FileCallbacks, FileUuid, ProvidedFile, WriteToCallback};
 use task_maker_store::*;
 
 /// Interval between each Status message is sent asking for server status updates.
 const STATUS_POLL_INTERVAL_MS: u64 = 1000;
+/// Interval between each check of whether the evaluation has been aborted.
+const ABORT_POLL_INTERVAL_MS: u64 = 100;
+/// Time the server has to kill the evaluation after its deadline, after that the client stops
+/// waiting for it.
+const DEADLINE_GRACE_MS: u64 = 5000;
 
 /// This is a client of the `Executor`, the client is who sends a DAG for an evaluation, provides
 /// some files and receives the callbacks from the server. When the server notifies a callback
@@ -21,11 +26,13 @@ impl ExecutorClient {
     /// Begin the evaluation sending the DAG to the server, sending the files as needed and storing
     /// the files from the server.
     ///
-    /// This method is blocking until the server ends the computation.
+    /// This method is blocking until the server ends the computation. If the configuration of the
+    /// DAG has a `max_total_duration` and the evaluation lasts longer, the server is asked to kill
+    /// it and an error is returned, without waiting for the server if it does not stop in time.
     ///
     /// * `eval` - The EvaluationData to evaluate.
-    /// * `sender` - A channel that sends messages to the server.
-    /// * `receiver` - A channel that receives messages from the server.
+    /// * `sender` - A transport that sends messages to the server.
+    /// * `receiver` - A transport that receives messages from the server.
     ///
     /// ```
     /// use task_maker_dag::ExecutionDAG;
@@ -58,23 +65,150 @@ impl ExecutorClient {
     ///
     /// server.join().expect("Server paniced");
     /// ```
-    pub fn evaluate<F>(
+    pub fn evaluate<F, S>(
+        dag: ExecutionDAG,
+        sender: S,
+        receiver: &dyn TransportReceiver,
+        file_store: Arc<FileStore>,
+        status_callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(ExecutorStatus<SystemTime>) -> Result<(), Error>,
+        S: TransportSender + Clone + 'static,
+    {
+        ExecutorClient::evaluate_with_transfer_limit(
+            dag,
+            sender,
+            receiver,
+            file_store,
+            DEFAULT_MAX_CONCURRENT_TRANSFERS,
+            status_callback,
+        )
+    }
+
+    /// Same as [`evaluate`](#method.evaluate) but with at most `max_transfers` files being sent
+    /// to the server at the same time. Each file in flight is read in memory before being sent,
+    /// so this bounds the memory used by the transfers.
+    pub fn evaluate_with_transfer_limit<F, S>(
+        dag: ExecutionDAG,
+        sender: S,
+        receiver: &dyn TransportReceiver,
+        file_store: Arc<FileStore>,
+        max_transfers: usize,
+        status_callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(ExecutorStatus<SystemTime>) -> Result<(), Error>,
+        S: TransportSender + Clone + 'static,
+    {
+        ExecutorClient::evaluate_with_abort(
+            dag,
+            sender,
+            receiver,
+            file_store,
+            max_transfers,
+            AbortHandle::new(),
+            status_callback,
+        )
+    }
+
+    /// Same as [`evaluate_with_transfer_limit`](#method.evaluate_with_transfer_limit) but when
+    /// `abort` is aborted the server is asked to stop the evaluation: the executions not started
+    /// yet are skipped and the callbacks are called as usual.
+    pub fn evaluate_with_abort<F, S>(
+        dag: ExecutionDAG,
+        sender: S,
+        receiver: &dyn TransportReceiver,
+        file_store: Arc<FileStore>,
+        max_transfers: usize,
+        abort: AbortHandle,
+        status_callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(ExecutorStatus<SystemTime>) -> Result<(), Error>,
+        S: TransportSender + Clone + 'static,
+    {
+        ExecutorClient::evaluate_with_auth(
+            dag,
+            sender,
+            receiver,
+            file_store,
+            max_transfers,
+            abort,
+            None,
+            status_callback,
+        )
+    }
+
+    /// Same as [`evaluate_with_abort`](#method.evaluate_with_abort) but authenticating to the
+    /// server with `auth_token`, needed if the server has been configured with one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn evaluate_with_auth<F, S>(
+        dag: ExecutionDAG,
+        sender: S,
+        receiver: &dyn TransportReceiver,
+        file_store: Arc<FileStore>,
+        max_transfers: usize,
+        abort: AbortHandle,
+        auth_token: Option<AuthToken>,
+        status_callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(ExecutorStatus<SystemTime>) -> Result<(), Error>,
+        S: TransportSender + Clone + 'static,
+    {
+        ExecutorClient::evaluate_with_compression(
+            dag,
+            sender,
+            receiver,
+            file_store,
+            max_transfers,
+            abort,
+            auth_token,
+            false,
+            status_callback,
+        )
+    }
+
+    /// Same as [`evaluate_with_auth`](#method.evaluate_with_auth) but, if `compression` is true,
+    /// the files are compressed while being transferred in both directions. This is worth only
+    /// when the server is reached through the network.
+    #[allow(clippy::too_many_arguments)]
+    pub fn evaluate_with_compression<F, S>(
         mut dag: ExecutionDAG,
-        sender: ChannelSender,
-        receiver: &ChannelReceiver,
+        sender: S,
+        receiver: &dyn TransportReceiver,
         file_store: Arc<FileStore>,
+        max_transfers: usize,
+        abort: AbortHandle,
+        auth_token: Option<AuthToken>,
+        compression: bool,
         mut status_callback: F,
     ) -> Result<(), Error>
     where
         F: FnMut(ExecutorStatus<SystemTime>) -> Result<(), Error>,
+        S: TransportSender + Clone + 'static,
     {
         trace!("ExecutorClient started");
+        match deserialize_from::<ExecutorServerMessage>(receiver)? {
+            ExecutorServerMessage::Challenge(nonce) => {
+                let response = auth_token.map(|token| token.sign(&nonce));
+                serialize_into(
+                    &ExecutorClientMessage::Authenticate(response, compression),
+                    &sender,
+                )?;
+            }
+            message => bail!("Expecting a Challenge from the server, got {:?}", message),
+        }
         // list all the files/executions that want callbacks
         let dag_callbacks = ExecutionDAGWatchSet {
             executions: dag.execution_callbacks.keys().cloned().collect(),
             files: dag.file_callbacks.keys().cloned().collect(),
         };
         let provided_files = dag.data.provided_files.clone();
+        let max_total_duration = dag.data.config.max_total_duration;
+        let deadline =
+            max_total_duration.map(|secs| Instant::now() + Duration::from_secs_f64(secs));
         for (uuid, file) in provided_files.iter() {
             match file {
                 ProvidedFile::LocalFile { local_path, .. } => {
@@ -99,60 +233,93 @@ impl ExecutorClient {
             &sender,
         )?;
         // setup the status poller that will send to the server a Status message every
-        // STATUS_POLL_INTERVAL_MS milliseconds.
+        // STATUS_POLL_INTERVAL_MS milliseconds, a Stop message as soon as the evaluation is aborted
+        // and a Kill message when its deadline has passed.
         let done = Arc::new(AtomicBool::new(false));
         let done_thread = done.clone();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let timed_out_thread = timed_out.clone();
         let file_mode = Arc::new(Mutex::new(()));
         let file_mode_thread = file_mode.clone();
         let sender_thread = sender.clone();
         let status_poller = thread::Builder::new()
             .name("Client status poller".into())
             .spawn(move || {
+                let mut stopped = false;
+                let mut last_status: Option<Instant> = None;
                 while !done_thread.load(Ordering::Relaxed) {
-                    {
+                    if !stopped && abort.is_aborted() {
+                        warn!("Evaluation aborted, asking the server to stop");
+                        let _lock = file_mode_thread.lock().unwrap();
+                        let _ = serialize_into(&ExecutorClientMessage::Stop, &sender_thread);
+                        stopped = true;
+                    }
+                    let deadline_passed = deadline.is_some_and(|d| Instant::now() >= d);
+                    if deadline_passed && !timed_out_thread.load(Ordering::Relaxed) {
+                        warn!("Evaluation took too long, asking the server to kill it");
+                        let _lock = file_mode_thread.lock().unwrap();
+                        let _ = serialize_into(&ExecutorClientMessage::Kill, &sender_thread);
+                        timed_out_thread.store(true, Ordering::Relaxed);
+                        stopped = true;
+                    }
+                    let poll_interval = Duration::from_millis(STATUS_POLL_INTERVAL_MS);
+                    if last_status.is_none_or(|last| last.elapsed() >= poll_interval) {
                         // make sure to not interfere with the file sending protocol.
                         let _lock = file_mode_thread.lock().unwrap();
                         // this may fail if the server is gone
                         let _ = serialize_into(&ExecutorClientMessage::Status, &sender_thread);
+                        last_status = Some(Instant::now());
                     }
-                    thread::sleep(Duration::from_millis(STATUS_POLL_INTERVAL_MS));
+                    thread::sleep(Duration::from_millis(ABORT_POLL_INTERVAL_MS));
                 }
             })
             .expect("Failed to start client status poller thread");
+        let (transfers_tx, transfers) = spawn_transfer_pool(
+            TransferLimiter::new(max_transfers),
+            sender.clone(),
+            file_mode.clone(),
+            compression,
+        )?;
         let mut missing_files = None;
+        let mut server_error = None;
+        let give_up = deadline.map(|d| d + Duration::from_millis(DEADLINE_GRACE_MS));
+        let mut abandoned = false;
         while missing_files.unwrap_or(1) > 0 {
-            match deserialize_from::<ExecutorServerMessage>(&receiver) {
+            let message = match give_up {
+                Some(give_up) => {
+                    let now = Instant::now();
+                    if now >= give_up {
+                        warn!("The server did not stop the evaluation in time, giving up");
+                        abandoned = true;
+                        break;
+                    }
+                    match deserialize_from_timeout::<ExecutorServerMessage>(receiver, give_up - now)
+                    {
+                        Ok(Some(message)) => Ok(message),
+                        Ok(None) => continue,
+                        Err(e) => Err(e),
+                    }
+                }
+                None => deserialize_from::<ExecutorServerMessage>(receiver),
+            };
+            match message {
                 Ok(ExecutorServerMessage::AskFile(uuid)) => {
                     info!("Server is asking for {}", uuid);
-                    // prevent the status poller for sending messages while sending the file
-                    let _lock = file_mode
-                        .lock()
-                        .map_err(|e| format_err!("Failed to lock: {:?}", e))?;
-                    match &provided_files[&uuid] {
-                        ProvidedFile::LocalFile {
-                            local_path, key, ..
-                        } => {
-                            serialize_into(
-                                &ExecutorClientMessage::ProvideFile(uuid, key.clone()),
-                                &sender,
-                            )?;
-                            ChannelFileSender::send(&local_path, &sender)?;
-                        }
-                        ProvidedFile::Content { content, key, .. } => {
-                            serialize_into(
-                                &ExecutorClientMessage::ProvideFile(uuid, key.clone()),
-                                &sender,
-                            )?;
-                            ChannelFileSender::send_data(content.clone(), &sender)?;
-                        }
+                    let file = provided_files
+                        .get(&uuid)
+                        .cloned()
+                        .ok_or_else(|| format_err!("Server asked for unknown file {}", uuid))?;
+                    if transfers_tx.send((uuid, file)).is_err() {
+                        // all the transfer threads failed, their error is reported below
+                        break;
                     }
                 }
-                Ok(ExecutorServerMessage::ProvideFile(uuid, success)) => {
+                Ok(ExecutorServerMessage::ProvideFile(uuid, success, compressed)) => {
                     info!("Server sent the file {}, success: {}", uuid, success);
                     if let Some(missing) = missing_files {
                         missing_files = Some(missing - 1);
                     }
-                    let iterator = ChannelFileIterator::new(&receiver);
+                    let iterator = ChannelFileIterator::with_compression(receiver, compressed);
                     process_provided_file(&mut dag.file_callbacks, uuid, success, iterator)?;
                 }
                 Ok(ExecutorServerMessage::NotifyStart(uuid, worker)) => {
@@ -163,25 +330,47 @@ impl ExecutorClient {
                         }
                     }
                 }
-                Ok(ExecutorServerMessage::NotifyDone(uuid, result)) => {
+                Ok(ExecutorServerMessage::NotifyDone(uuid, result, outputs)) => {
                     info!("Execution {} completed with {:?}", uuid, result);
+                    let success = result.status == ExecutionStatus::Success;
+                    for (file, content) in outputs {
+                        process_provided_file(
+                            &mut dag.file_callbacks,
+                            file,
+                            success,
+                            vec![content],
+                        )?;
+                    }
                     if let Some(callbacks) = dag.execution_callbacks.get_mut(&uuid) {
                         for callback in callbacks.on_done.drain(..) {
-                            callback.call(result.clone())?;
+                            callback.call((*result).clone())?;
                         }
                     }
                 }
-                Ok(ExecutorServerMessage::NotifySkip(uuid)) => {
-                    info!("Execution {} skipped", uuid);
+                Ok(ExecutorServerMessage::NotifySkip(uuid, reason)) => {
+                    info!("Execution {} skipped: {:?}", uuid, reason);
                     if let Some(callbacks) = dag.execution_callbacks.get_mut(&uuid) {
                         for callback in callbacks.on_skip.drain(..) {
-                            callback.call()?;
+                            callback.call(reason)?;
+                        }
+                    }
+                }
+                Ok(ExecutorServerMessage::NotifyNondeterminism(uuid, files)) => {
+                    warn!("Execution {} is not deterministic: {:?}", uuid, files);
+                    if let Some(callbacks) = dag.execution_callbacks.get_mut(&uuid) {
+                        for callback in callbacks.on_nondeterminism.drain(..) {
+                            callback.call(files.clone())?;
                         }
                     }
                 }
+                Ok(ExecutorServerMessage::Challenge(_)) => {
+                    warn!("Server sent a second challenge");
+                }
                 Ok(ExecutorServerMessage::Error(error)) => {
                     error!("Error occurred: {}", error);
-                    // TODO abort
+                    // the server has given up with the evaluation, the error is returned after the
+                    // helper threads are stopped
+                    server_error = Some(error);
                     break;
                 }
                 Ok(ExecutorServerMessage::Status(status)) => {
@@ -200,6 +389,9 @@ impl ExecutorClient {
                             .collect(),
                         ready_execs: status.ready_execs,
                         waiting_execs: status.waiting_execs,
+                        memory_in_use: status.memory_in_use,
+                        memory_available: status.memory_available,
+                        peak_running_execs: status.peak_running_execs,
                     })?;
                 }
                 Ok(ExecutorServerMessage::Done(result)) => {
@@ -239,10 +431,115 @@ impl ExecutorClient {
         status_poller
             .join()
             .map_err(|e| format_err!("Failed to join status poller: {:?}", e))?;
+        drop(transfers_tx);
+        for transfer in transfers {
+            transfer
+                .join()
+                .map_err(|e| format_err!("Failed to join transfer thread: {:?}", e))??;
+        }
+        if let Some(error) = server_error {
+            bail!("The server failed to evaluate the DAG: {}", error);
+        }
+        if abandoned || timed_out.load(Ordering::Relaxed) {
+            bail!(
+                "The evaluation has been stopped after exceeding the maximum duration of {}s",
+                max_total_duration.unwrap_or_default()
+            );
+        }
         Ok(())
     }
 }
 
+/// The files to send to the server, with the uuid they are asked with.
+type TransferQueue = Sender<(FileUuid, ProvidedFile)>;
+/// A thread sending the files to the server, see `spawn_transfer_pool`.
+type TransferThread = thread::JoinHandle<Result<(), Error>>;
+
+/// Spawn the threads that send to the server the files it asks for, as many as the transfers the
+/// limiter allows in flight. The files are sent through the returned queue; after it's dropped the
+/// threads exit and they can be joined.
+fn spawn_transfer_pool<S>(
+    limiter: TransferLimiter,
+    sender: S,
+    file_mode: Arc<Mutex<()>>,
+    compression: bool,
+) -> Result<(TransferQueue, Vec<TransferThread>), Error>
+where
+    S: TransportSender + Clone + 'static,
+{
+    let (queue_tx, queue_rx) = channel::<(FileUuid, ProvidedFile)>();
+    let queue_rx = Arc::new(Mutex::new(queue_rx));
+    let mut transfers = Vec::new();
+    for i in 0..limiter.limit() {
+        let queue_rx = queue_rx.clone();
+        let sender = sender.clone();
+        let file_mode = file_mode.clone();
+        let limiter = limiter.clone();
+        let transfer = thread::Builder::new()
+            .name(format!("Client transfer {}", i))
+            .spawn(move || -> Result<(), Error> {
+                loop {
+                    // the lock is released as soon as a file is taken from the queue
+                    let next = queue_rx.lock().unwrap().recv();
+                    let (uuid, file) = match next {
+                        Ok(next) => next,
+                        Err(_) => return Ok(()),
+                    };
+                    send_provided_file(uuid, file, &sender, &file_mode, &limiter, compression)?;
+                }
+            })
+            .map_err(|e| format_err!("Failed to start transfer thread: {:?}", e))?;
+        transfers.push(transfer);
+    }
+    Ok((queue_tx, transfers))
+}
+
+/// Send a file provided by the client to the server, waiting for a free slot in the limiter. The
+/// file is streamed in chunks, holding the `file_mode` lock so that the files in flight are never
+/// interleaved on the transport.
+fn send_provided_file(
+    uuid: FileUuid,
+    file: ProvidedFile,
+    sender: &dyn TransportSender,
+    file_mode: &Mutex<()>,
+    limiter: &TransferLimiter,
+    compression: bool,
+) -> Result<(), Error> {
+    let _permit = limiter.acquire();
+    // prevent the status poller and the other transfers from sending messages while sending the
+    // file
+    let _lock = file_mode
+        .lock()
+        .map_err(|e| format_err!("Failed to lock: {:?}", e))?;
+    match file {
+        ProvidedFile::LocalFile {
+            local_path, key, ..
+        } => {
+            serialize_into(
+                &ExecutorClientMessage::ProvideFile(uuid, key, compression),
+                sender,
+            )?;
+            if compression {
+                ChannelFileSender::send_compressed(&local_path, sender)?;
+            } else {
+                ChannelFileSender::send(&local_path, sender)?;
+            }
+        }
+        ProvidedFile::Content { content, key, .. } => {
+            serialize_into(
+                &ExecutorClientMessage::ProvideFile(uuid, key, compression),
+                sender,
+            )?;
+            if compression {
+                ChannelFileSender::send_data_compressed(&content, sender)?;
+            } else {
+                ChannelFileSender::send_data(content, sender)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Process a file provided either by the client or by the server, calling the callback and writing
 /// it to the `write_to` path. This will consume the iterator even if the callback is not present.
 fn process_provided_file<I: IntoIterator<Item = Vec<u8>>>(
@@ -302,3 +599,112 @@ fn process_provided_file<I: IntoIterator<Item = Vec<u8>>>(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use task_maker_dag::File;
+    use tempdir::TempDir;
+
+    /// Read from `receiver` a file sent with `send_provided_file`.
+    fn receive_provided_file(receiver: &ChannelReceiver) -> (FileUuid, Vec<Vec<u8>>) {
+        let uuid = match deserialize_from::<ExecutorClientMessage>(receiver).unwrap() {
+            ExecutorClientMessage::ProvideFile(uuid, _, false) => uuid,
+            message => panic!("Expecting ProvideFile, got {:?}", message),
+        };
+        (uuid, ChannelFileIterator::new(receiver).collect())
+    }
+
+    #[test]
+    fn test_send_provided_file_in_chunks() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("file");
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+        let file = File::new("file");
+        let uuid = file.uuid;
+        let provided = ProvidedFile::LocalFile {
+            file,
+            key: FileStoreKey::from_content(&content),
+            local_path: path,
+        };
+        let (sender, receiver) = channel();
+        let limiter = TransferLimiter::new(1);
+        send_provided_file(uuid, provided, &sender, &Mutex::new(()), &limiter, false).unwrap();
+
+        let (received, chunks) = receive_provided_file(&receiver);
+        assert_eq!(received, uuid);
+        // the file is not sent in a single message
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() < content.len() / 2));
+        assert_eq!(chunks.concat(), content);
+    }
+
+    #[test]
+    fn test_transfer_pool() {
+        let (sender, receiver) = channel();
+        let (queue, transfers) = spawn_transfer_pool(
+            TransferLimiter::new(2),
+            sender,
+            Arc::new(Mutex::new(())),
+            false,
+        )
+        .unwrap();
+        // the threads are bounded by the limit, not by the number of files
+        assert_eq!(transfers.len(), 2);
+        let mut expected = HashMap::new();
+        for i in 0..10 {
+            let content = vec![i as u8; 20_000];
+            let file = File::new("file");
+            expected.insert(file.uuid, content.clone());
+            let provided = ProvidedFile::Content {
+                key: FileStoreKey::from_content(&content),
+                file: file.clone(),
+                content,
+            };
+            queue.send((file.uuid, provided)).unwrap();
+        }
+        drop(queue);
+        for transfer in transfers {
+            transfer.join().unwrap().unwrap();
+        }
+
+        // the files are never interleaved
+        for _ in 0..expected.len() {
+            let (uuid, chunks) = receive_provided_file(&receiver);
+            assert_eq!(chunks.concat(), expected[&uuid]);
+        }
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_deadline_unresponsive_server() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path()).unwrap());
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().max_total_duration(Some(0.1));
+        let (tx, rx_remote) = channel();
+        let (tx_remote, rx) = channel();
+        // a server that never completes the evaluation, nor kills it
+        let server = thread::spawn(move || {
+            serialize_into(&ExecutorServerMessage::Challenge(vec![]), &tx_remote).unwrap();
+            let mut kill_asked = false;
+            while let Ok(message) = deserialize_from::<ExecutorClientMessage>(&rx_remote) {
+                if let ExecutorClientMessage::Kill = message {
+                    kill_asked = true;
+                }
+            }
+            kill_asked
+        });
+
+        let start = Instant::now();
+        let err = ExecutorClient::evaluate(dag, tx, &rx, file_store, |_| Ok(())).unwrap_err();
+        assert!(
+            err.to_string().contains("maximum duration"),
+            "Unexpected error: {}",
+            err
+        );
+        assert!(start.elapsed() < Duration::from_millis(DEADLINE_GRACE_MS + 2000));
+        assert!(server.join().unwrap());
+    }
+}
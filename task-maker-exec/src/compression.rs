@@ -0,0 +1,95 @@
+//! Compression of the chunks of the files transferred between the client, the server and the
+//! workers, using zlib.
+//!
+//! Each chunk is compressed independently and prefixed by its uncompressed size, so that it can be
+//! decompressed without knowing the chunks that came before it.
+
+use failure::{bail, format_err, Error};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+/// The zlib compression level: a good trade off between speed and size.
+const COMPRESSION_LEVEL: u32 = 6;
+/// The size, in bytes, of the header with the uncompressed size of the chunk.
+const HEADER_SIZE: usize = 8;
+/// The maximum uncompressed size, in bytes, of a chunk. The chunks of the files are way smaller,
+/// bigger sizes are rejected before decompressing, so that a bogus chunk does not exhaust the
+/// memory.
+pub const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Compress a chunk of data, the result can be decompressed with
+/// [`decompress`](fn.decompress.html) if the chunk is not bigger than `MAX_CHUNK_SIZE`.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(HEADER_SIZE + data.len() / 2);
+    res.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    let mut encoder = ZlibEncoder::new(res, Compression::new(COMPRESSION_LEVEL));
+    // writing to a vector cannot fail
+    encoder.write_all(data).expect("zlib failed to compress");
+    encoder.finish().expect("zlib failed to compress")
+}
+
+/// Decompress a chunk of data compressed with [`compress`](fn.compress.html). An error is returned
+/// if the data is corrupted or if the chunk is bigger than `MAX_CHUNK_SIZE`.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < HEADER_SIZE {
+        bail!("Compressed chunk too short: {} bytes", data.len());
+    }
+    let size = u64::from_le_bytes(data[..HEADER_SIZE].try_into().unwrap());
+    if size > MAX_CHUNK_SIZE as u64 {
+        bail!("Compressed chunk too big: {} bytes", size);
+    }
+    let mut res = Vec::with_capacity(size as usize);
+    // read one more byte than expected for detecting the chunks bigger than declared
+    ZlibDecoder::new(&data[HEADER_SIZE..])
+        .take(size + 1)
+        .read_to_end(&mut res)
+        .map_err(|e| format_err!("Corrupted compressed chunk: {}", e))?;
+    if res.len() as u64 != size {
+        bail!(
+            "Corrupted compressed chunk: expecting {} bytes, got {}",
+            size,
+            res.len()
+        );
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..MAX_CHUNK_SIZE as u32).map(|i| (i % 13) as u8).collect();
+        let compressed = compress(&data);
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(decompress(&compress(&[])).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let mut compressed = compress(b"hello hello hello world");
+        let last = compressed.len() - 1;
+        compressed[last] ^= 0xff;
+        assert!(decompress(&compressed).is_err());
+        assert!(decompress(&compressed[..4]).is_err());
+    }
+
+    #[test]
+    fn test_too_big() {
+        let compressed = compress(&vec![0; MAX_CHUNK_SIZE + 1]);
+        assert!(decompress(&compressed).is_err());
+        // the declared size is checked against the actual one
+        let mut compressed = compress(&[0; 100]);
+        compressed[..HEADER_SIZE].copy_from_slice(&10u64.to_le_bytes());
+        assert!(decompress(&compressed).is_err());
+    }
+}
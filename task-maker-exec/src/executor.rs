@@ -1,7 +1,7 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use failure::{format_err, Error};
+use failure::{bail, format_err, Error};
 use serde::{Deserialize, Serialize};
 use task_maker_dag::*;
 use task_maker_store::*;
@@ -54,6 +54,14 @@ pub struct ExecutorStatus<T> {
     pub ready_execs: usize,
     /// Number of executions waiting for dependencies.
     pub waiting_execs: usize,
+    /// The sum of the memory limits, in KiB, of the running executions.
+    pub memory_in_use: u64,
+    /// The memory, in KiB, the running executions can use without breaching the memory
+    /// reservation, if any.
+    pub memory_available: Option<u64>,
+    /// The maximum number of executions that ran at the same time since the start of the
+    /// evaluation.
+    pub peak_running_execs: usize,
 }
 
 /// The `Executor` is the main component of the server, this will receive the DAG to evaluate and
@@ -67,6 +75,14 @@ pub(crate) struct Executor {
     /// The receiving part of the `Scheduler`. Will be consumed when the `Scheduler` is
     /// instantiated.
     scheduler_rx: Option<Receiver<SchedulerInMessage>>,
+    /// The memory to keep free on the machine of the workers, if any.
+    pub(crate) memory_reservation: Option<MemoryReservation>,
+    /// How many times a file received corrupted is asked again to the client.
+    pub(crate) transfer_retries: usize,
+    /// The token the client has to authenticate with, if any.
+    pub(crate) auth_token: Option<AuthToken>,
+    /// The watchdog of the stalled evaluations, if any.
+    pub(crate) stall_watchdog: Option<StallWatchdog>,
 }
 
 impl Executor {
@@ -78,20 +94,45 @@ impl Executor {
             file_store,
             scheduler_tx: sched_tx,
             scheduler_rx: Some(sched_rx),
+            memory_reservation: None,
+            transfer_retries: DEFAULT_TRANSFER_RETRIES,
+            auth_token: None,
+            stall_watchdog: None,
         }
     }
 
     /// Starts the `Executor` for a client, this will block and will manage the communication with
-    /// the client.
+    /// the client. The client is rejected with an error if it fails the authentication.
     ///
-    /// * `sender` - A channel that sends messages to the client.
-    /// * `receiver` - A channel that receives messages from the client.
-    pub fn evaluate(
-        mut self,
-        client_tx: ChannelSender,
-        client_rx: ChannelReceiver,
-        cache: Cache,
-    ) -> Result<(), Error> {
+    /// * `client_tx` - A transport that sends messages to the client.
+    /// * `client_rx` - A transport that receives messages from the client.
+    pub fn evaluate<S, R>(mut self, client_tx: S, client_rx: R, cache: Cache) -> Result<(), Error>
+    where
+        S: TransportSender + Clone + 'static,
+        R: TransportReceiver,
+    {
+        let nonce = AuthToken::nonce();
+        let challenge = ExecutorServerMessage::Challenge(nonce.clone());
+        // whether the client wants to receive the files compressed
+        let mut compression = false;
+        let rejection = match serialize_into(&challenge, &client_tx)
+            .and_then(|_| deserialize_from::<ExecutorClientMessage>(&client_rx))
+        {
+            Ok(ExecutorClientMessage::Authenticate(response, compress)) => {
+                compression = compress;
+                check_auth_response(self.auth_token.as_ref(), &nonce, response.as_deref()).err()
+            }
+            Ok(message) => Some(format!("expecting Authenticate, got {:?}", message)),
+            // the client left before the handshake
+            Err(_) => return Ok(()),
+        };
+        if let Some(reason) = rejection {
+            warn!("Rejecting the client: {}", reason);
+            let message = format!("Client rejected: {}", reason);
+            serialize_into(&ExecutorServerMessage::Error(message.clone()), &client_tx)?;
+            bail!("{}", message);
+        }
+
         let (sched_binder_tx, sched_binder_rx) = channel();
         let sched_binder_client = client_tx.clone();
         let produced_handles = Arc::new(Mutex::new(Vec::new()));
@@ -108,7 +149,15 @@ impl Executor {
             })
             .expect("Failed to spawn scheduler binder thread");
 
-        let scheduler = Scheduler::new(cache, self.file_store.clone(), sched_binder_tx);
+        let mut scheduler = Scheduler::new(
+            cache,
+            self.file_store.clone(),
+            sched_binder_tx,
+            self.memory_reservation,
+        );
+        if let Some(watchdog) = self.stall_watchdog.take() {
+            scheduler.stall_watchdog(watchdog);
+        }
         let sched_rx = self
             .scheduler_rx
             .take()
@@ -120,6 +169,8 @@ impl Executor {
             })
             .expect("Failed to spawn the scheduler");
 
+        // the number of times each file has been received corrupted
+        let mut corrupted_transfers: HashMap<FileUuid, usize> = HashMap::new();
         loop {
             let message = deserialize_from::<ExecutorClientMessage>(&client_rx);
             match message {
@@ -155,11 +206,25 @@ impl Executor {
                             })?;
                     }
                 }
-                Ok(ExecutorClientMessage::ProvideFile(uuid, key)) => {
+                Ok(ExecutorClientMessage::ProvideFile(uuid, key, compressed)) => {
                     info!("Client provided file {}", uuid);
-                    let handle = self
-                        .file_store
-                        .store(&key, ChannelFileIterator::new(&client_rx))?;
+                    let iterator = ChannelFileIterator::with_compression(&client_rx, compressed);
+                    let handle = match self.file_store.store_verified(&key, iterator)? {
+                        Some(handle) => handle,
+                        None => {
+                            let attempts = corrupted_transfers.entry(uuid).or_default();
+                            *attempts += 1;
+                            if *attempts > self.transfer_retries {
+                                let message =
+                                    format!("File {} received corrupted {} times", uuid, attempts);
+                                serialize_into(&ExecutorServerMessage::Error(message), &client_tx)?;
+                                break;
+                            }
+                            warn!("File {} received corrupted, asking it again", uuid);
+                            serialize_into(&ExecutorServerMessage::AskFile(uuid), &client_tx)?;
+                            continue;
+                        }
+                    };
                     self.scheduler_tx
                         .send(SchedulerInMessage::FileReady { uuid, handle })
                         .map_err(|e| format_err!("Failed to send message to scheduler: {:?}", e))?;
@@ -168,10 +233,14 @@ impl Executor {
                     info!("Client asking file {:?}", key);
                     if let Some(handle) = self.file_store.get(&key) {
                         serialize_into(
-                            &ExecutorServerMessage::ProvideFile(uuid, success),
+                            &ExecutorServerMessage::ProvideFile(uuid, success, compression),
                             &client_tx,
                         )?;
-                        ChannelFileSender::send(handle.path(), &client_tx)?;
+                        if compression {
+                            ChannelFileSender::send_compressed(handle.path(), &client_tx)?;
+                        } else {
+                            ChannelFileSender::send(handle.path(), &client_tx)?;
+                        }
                     } else {
                         serialize_into(
                             &ExecutorServerMessage::Error(format!("Unknown file {:?}", key)),
@@ -186,7 +255,18 @@ impl Executor {
                 }
                 Ok(ExecutorClientMessage::Stop) => {
                     info!("Client asking to stop");
-                    unimplemented!();
+                    self.scheduler_tx
+                        .send(SchedulerInMessage::Stop)
+                        .map_err(|e| format_err!("Failed to send message to scheduler: {:?}", e))?;
+                }
+                Ok(ExecutorClientMessage::Kill) => {
+                    info!("Client asking to stop killing the running executions");
+                    self.scheduler_tx
+                        .send(SchedulerInMessage::Kill)
+                        .map_err(|e| format_err!("Failed to send message to scheduler: {:?}", e))?;
+                }
+                Ok(ExecutorClientMessage::Authenticate(_, _)) => {
+                    warn!("Client authenticating twice");
                 }
                 Err(_) => {
                     // the receiver has been dropped
@@ -206,9 +286,9 @@ impl Executor {
     /// eventually blocking reading files.
     ///
     /// This function will block until the `Scheduler` drops its sender.
-    fn scheduler_thread(
+    fn scheduler_thread<S: TransportSender>(
         receiver: Receiver<SchedulerOutMessage>,
-        client_tx: ChannelSender,
+        client_tx: S,
         produced_files: Arc<Mutex<Vec<(FileUuid, FileStoreHandle, bool)>>>,
     ) -> Result<(), Error> {
         loop {
@@ -220,11 +300,20 @@ impl Executor {
                         &client_tx,
                     )?;
                 }
-                Ok(SchedulerOutMessage::ExecutionSkipped(exec)) => {
-                    serialize_into(&ExecutorServerMessage::NotifySkip(exec), &client_tx)?;
+                Ok(SchedulerOutMessage::ExecutionSkipped(exec, reason)) => {
+                    serialize_into(&ExecutorServerMessage::NotifySkip(exec, reason), &client_tx)?;
+                }
+                Ok(SchedulerOutMessage::ExecutionNondeterministic(exec, files)) => {
+                    serialize_into(
+                        &ExecutorServerMessage::NotifyNondeterminism(exec, files),
+                        &client_tx,
+                    )?;
                 }
-                Ok(SchedulerOutMessage::ExecutionDone(exec, result)) => {
-                    serialize_into(&ExecutorServerMessage::NotifyDone(exec, result), &client_tx)?;
+                Ok(SchedulerOutMessage::ExecutionDone(exec, result, outputs)) => {
+                    serialize_into(
+                        &ExecutorServerMessage::NotifyDone(exec, result, outputs),
+                        &client_tx,
+                    )?;
                 }
                 Ok(SchedulerOutMessage::FileReady(uuid, handle, success)) => {
                     produced_files.lock().unwrap().push((uuid, handle, success));
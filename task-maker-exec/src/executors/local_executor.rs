@@ -1,5 +1,7 @@
+use crate::proto::DEFAULT_TRANSFER_RETRIES;
 use crate::*;
-use failure::{format_err, Error};
+use failure::{bail, format_err, Error};
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::thread;
 use task_maker_cache::Cache;
@@ -13,12 +15,19 @@ pub struct LocalExecutor {
     sandbox_path: PathBuf,
     /// The number of local workers to spawn.
     pub num_workers: usize,
+    /// How many times the workers ask again a file received corrupted.
+    transfer_retries: usize,
+    /// The implementation of the sandbox used by the workers.
+    sandbox_backend: SandboxBackend,
+    /// The CPU core each worker is pinned to, if any.
+    affinity: Option<Vec<usize>>,
 }
 
 impl LocalExecutor {
     /// Make a new [`LocalExecutor`](struct.LocalExecutor.html) based on a
     /// [`FileStore`](../../task_maker_store/struct.FileStore.html) and ready to spawn that number
-    /// of workers.
+    /// of workers. The sandbox backend is selected from the environment, see
+    /// [`SandboxBackend::from_env`](../enum.SandboxBackend.html#method.from_env).
     pub fn new<P: Into<PathBuf>>(
         file_store: Arc<FileStore>,
         num_workers: usize,
@@ -29,38 +38,116 @@ impl LocalExecutor {
             num_workers,
             file_store,
             sandbox_path: sandbox_path.into(),
+            transfer_retries: DEFAULT_TRANSFER_RETRIES,
+            sandbox_backend: SandboxBackend::from_env(),
+            affinity: None,
         }
     }
 
+    /// Make a new [`LocalExecutor`](struct.LocalExecutor.html) like
+    /// [`new`](#method.new), spawning a worker for each of the specified CPU cores. Each worker,
+    /// and the sandboxes it starts, is pinned to its own core, so that the processes are not
+    /// migrated by the OS scheduler and the measured times are more stable.
+    ///
+    /// The list of cores must not be empty and must not contain duplicates, since each worker needs
+    /// a dedicated core.
+    pub fn with_affinity<P: Into<PathBuf>>(
+        file_store: Arc<FileStore>,
+        cores: Vec<usize>,
+        sandbox_path: P,
+    ) -> Result<LocalExecutor, Error> {
+        if cores.is_empty() {
+            bail!("At least a CPU core is required");
+        }
+        let mut seen = HashSet::new();
+        if let Some(core) = cores.iter().find(|core| !seen.insert(**core)) {
+            bail!("The CPU core {} is listed more than once", core);
+        }
+        let mut executor = LocalExecutor::new(file_store, cores.len(), sandbox_path);
+        executor.affinity = Some(cores);
+        Ok(executor)
+    }
+
+    /// Keep some memory free on this machine, starting an execution only if its memory limit fits
+    /// in the memory left by the running ones.
+    pub fn memory_reservation(&mut self, reservation: MemoryReservation) -> &mut Self {
+        self.executor.memory_reservation = Some(reservation);
+        self
+    }
+
+    /// Dump the state of the scheduler when the evaluation makes no progress for a while, see
+    /// [`StallWatchdog`](../struct.StallWatchdog.html).
+    pub fn stall_watchdog(&mut self, watchdog: StallWatchdog) -> &mut Self {
+        self.executor.stall_watchdog = Some(watchdog);
+        self
+    }
+
+    /// Set how many times a file whose content does not match its key is transferred again, before
+    /// failing the evaluation. The default is
+    /// [`DEFAULT_TRANSFER_RETRIES`](../proto/constant.DEFAULT_TRANSFER_RETRIES.html).
+    pub fn transfer_retries(&mut self, retries: usize) -> &mut Self {
+        self.transfer_retries = retries;
+        self.executor.transfer_retries = retries;
+        self
+    }
+
+    /// Set the implementation of the sandbox the workers use for running the executions.
+    pub fn sandbox_backend(&mut self, backend: SandboxBackend) -> &mut Self {
+        self.sandbox_backend = backend;
+        self
+    }
+
     /// Starts the Executor spawning the workers on new threads and blocking on the `Executor`
     /// thread.
     ///
-    /// * `sender` - Channel that sends messages to the client.
-    /// * `receiver` - Channel that receives messages from the client.
+    /// * `sender` - Transport that sends messages to the client.
+    /// * `receiver` - Transport that receives messages from the client.
     /// * `cache` - The cache the executor has to use.
-    pub fn evaluate(
-        self,
-        sender: ChannelSender,
-        receiver: ChannelReceiver,
-        cache: Cache,
-    ) -> Result<(), Error> {
+    pub fn evaluate<S, R>(self, sender: S, receiver: R, cache: Cache) -> Result<(), Error>
+    where
+        S: TransportSender + Clone + 'static,
+        R: TransportReceiver,
+    {
         info!("Spawning {} workers", self.num_workers);
+        check_sandbox_path(&self.sandbox_path)?;
+        if let Some(cores) = &self.affinity {
+            let available = available_cores()?;
+            for core in cores {
+                if !available.contains(core) {
+                    bail!(
+                        "Cannot pin a worker to the CPU core {}: not available",
+                        core
+                    );
+                }
+            }
+        }
 
-        let mut worker_manager =
-            WorkerManager::new(self.file_store.clone(), self.executor.scheduler_tx.clone());
+        // the local workers don't need to authenticate
+        let mut worker_manager = WorkerManager::new(
+            self.file_store.clone(),
+            self.executor.scheduler_tx.clone(),
+            None,
+        );
 
         let mut workers = vec![];
         for i in 0..self.num_workers {
-            let (worker, conn) = Worker::new(
+            let (mut worker, conn) = Worker::new(
                 &format!("Local worker {}", i),
                 self.file_store.clone(),
                 self.sandbox_path.clone(),
             );
+            worker.transfer_retries = self.transfer_retries;
+            worker.sandbox_backend = self.sandbox_backend.clone();
+            let core = self.affinity.as_ref().map(|cores| cores[i]);
             workers.push(worker_manager.add(conn));
             workers.push(
                 thread::Builder::new()
                     .name(format!("Worker {}", worker))
                     .spawn(move || {
+                        // the threads of the sandboxes, and their processes, inherit the affinity
+                        if let Some(core) = core {
+                            pin_to_core(core).expect("Cannot pin the worker");
+                        }
                         worker.work().expect("Worker failed");
                     })?,
             );
@@ -75,3 +162,82 @@ impl LocalExecutor {
         Ok(())
     }
 }
+
+/// The CPU cores the current thread is allowed to run on.
+fn available_cores() -> Result<Vec<usize>, Error> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            bail!(
+                "Cannot get the CPU affinity: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok((0..8 * std::mem::size_of::<libc::cpu_set_t>())
+            .filter(|core| libc::CPU_ISSET(*core, &set))
+            .collect())
+    }
+}
+
+/// Pin the current thread to a CPU core. The threads and the processes spawned afterwards by this
+/// thread are pinned to the same core.
+fn pin_to_core(core: usize) -> Result<(), Error> {
+    if core >= 8 * std::mem::size_of::<libc::cpu_set_t>() {
+        bail!("Invalid CPU core {}", core);
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            bail!(
+                "Cannot pin the thread to the CPU core {}: {}",
+                core,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_pin_to_core() {
+        let core = available_cores().unwrap()[0];
+        let pinned = thread::spawn(move || {
+            pin_to_core(core).unwrap();
+            // the threads spawned by a pinned thread inherit its affinity
+            thread::spawn(available_cores).join().unwrap().unwrap()
+        });
+        assert_eq!(pinned.join().unwrap(), vec![core]);
+    }
+
+    #[test]
+    fn test_with_affinity_unavailable_core() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let executor =
+            LocalExecutor::with_affinity(file_store, vec![100_000], tmpdir.path()).unwrap();
+        assert_eq!(executor.num_workers, 1);
+        let (tx, _rx_remote) = channel();
+        let (_tx_remote, rx) = channel();
+        let err = executor.evaluate(tx, rx, cache).unwrap_err();
+        assert!(err.to_string().contains("100000"), "{}", err);
+    }
+
+    #[test]
+    fn test_with_affinity_invalid_cores() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        assert!(LocalExecutor::with_affinity(file_store.clone(), vec![], tmpdir.path()).is_err());
+        let err = LocalExecutor::with_affinity(file_store, vec![0, 1, 0], tmpdir.path())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("core 0"), "{}", err);
+    }
+}
@@ -2,7 +2,8 @@
 //!
 //! An executor is something that implements the communication protocol for evaluating DAGs.
 //! For example the `LocalExecutor` is an implementation of a thread-based executor that will listen
-//! on the client channel and will spawn a list of local workers.
+//! on the client channel and will spawn a list of local workers, while the `RemoteExecutor` accepts
+//! the workers connecting through TCP from other machines.
 //!
 //! # Example
 //!
@@ -33,5 +34,7 @@
 //! ```
 
 mod local_executor;
+mod remote_executor;
 
 pub use local_executor::*;
+pub use remote_executor::*;
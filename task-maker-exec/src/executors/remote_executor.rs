@@ -0,0 +1,428 @@
+use crate::proto::DEFAULT_TRANSFER_RETRIES;
+use crate::*;
+use failure::{format_err, Error};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use task_maker_cache::Cache;
+use uuid::Uuid;
+
+/// How often the listener of a [`RemoteExecutor`](struct.RemoteExecutor.html) checks whether the
+/// evaluation is over.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An Executor whose workers are on other machines. The workers connect to the TCP address the
+/// executor is bound to, using a [`RemoteWorker`](struct.RemoteWorker.html), and can join at any
+/// time during the evaluation.
+///
+/// The client talks to the executor through a [`Transport`](../trait.Transport.html) as usual,
+/// either a channel or a TCP connection split with [`tcp_transport`](../fn.tcp_transport.html).
+///
+/// Since anyone able to connect could run arbitrary code on the workers, an
+/// [`AuthToken`](../struct.AuthToken.html) should be set with
+/// [`auth_token`](#method.auth_token): the workers and the client are then accepted only if they
+/// know it.
+pub struct RemoteExecutor {
+    executor: Executor,
+    /// A reference to the [`FileStore`](../../task_maker_store/struct.FileStore.html).
+    file_store: Arc<FileStore>,
+    /// The listener accepting the connections of the workers.
+    listener: TcpListener,
+}
+
+/// A worker that runs on this machine the executions of a remote
+/// [`RemoteExecutor`](struct.RemoteExecutor.html).
+pub struct RemoteWorker {
+    /// The name of the worker.
+    name: String,
+    /// A reference to the local [`FileStore`](../../task_maker_store/struct.FileStore.html).
+    file_store: Arc<FileStore>,
+    /// Where to store the sandboxes of the worker.
+    sandbox_path: PathBuf,
+    /// How many times the worker asks again a file received corrupted.
+    transfer_retries: usize,
+    /// The implementation of the sandbox used by the worker.
+    sandbox_backend: SandboxBackend,
+    /// The token to authenticate with to the executor, if any.
+    auth_token: Option<AuthToken>,
+    /// Whether the files are transferred compressed.
+    compression: bool,
+}
+
+impl RemoteExecutor {
+    /// Make a new [`RemoteExecutor`](struct.RemoteExecutor.html) based on a
+    /// [`FileStore`](../../task_maker_store/struct.FileStore.html), accepting the workers on the
+    /// specified address.
+    pub fn bind<A: ToSocketAddrs>(
+        file_store: Arc<FileStore>,
+        addr: A,
+    ) -> Result<RemoteExecutor, Error> {
+        Ok(RemoteExecutor {
+            executor: Executor::new(file_store.clone()),
+            file_store,
+            listener: TcpListener::bind(addr)?,
+        })
+    }
+
+    /// The address the workers should connect to.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Set how many times a file whose content does not match its key is transferred again, before
+    /// failing the evaluation. The default is
+    /// [`DEFAULT_TRANSFER_RETRIES`](../proto/constant.DEFAULT_TRANSFER_RETRIES.html).
+    pub fn transfer_retries(&mut self, retries: usize) -> &mut Self {
+        self.executor.transfer_retries = retries;
+        self
+    }
+
+    /// Accept only the workers and the client that authenticate with this token.
+    pub fn auth_token(&mut self, token: AuthToken) -> &mut Self {
+        self.executor.auth_token = Some(token);
+        self
+    }
+
+    /// Dump the state of the scheduler when the evaluation makes no progress for a while, see
+    /// [`StallWatchdog`](../struct.StallWatchdog.html).
+    pub fn stall_watchdog(&mut self, watchdog: StallWatchdog) -> &mut Self {
+        self.executor.stall_watchdog = Some(watchdog);
+        self
+    }
+
+    /// Starts the Executor accepting the workers on a new thread and blocking on the `Executor`
+    /// thread. At the end of the evaluation the connected workers are asked to exit.
+    ///
+    /// * `sender` - Transport that sends messages to the client.
+    /// * `receiver` - Transport that receives messages from the client.
+    /// * `cache` - The cache the executor has to use.
+    pub fn evaluate<S, R>(self, sender: S, receiver: R, cache: Cache) -> Result<(), Error>
+    where
+        S: TransportSender + Clone + 'static,
+        R: TransportReceiver,
+    {
+        info!("Accepting the workers on {}", self.listener.local_addr()?);
+        let worker_manager = Arc::new(Mutex::new(WorkerManager::new(
+            self.file_store.clone(),
+            self.executor.scheduler_tx.clone(),
+            self.executor.auth_token.clone(),
+        )));
+        let done = Arc::new(AtomicBool::new(false));
+        // the listener is polled, so that the thread can exit at the end of the evaluation
+        self.listener.set_nonblocking(true)?;
+        let listener = self.listener;
+        let manager = worker_manager.clone();
+        let thread_done = done.clone();
+        let acceptor = thread::Builder::new()
+            .name("Remote workers acceptor".into())
+            .spawn(move || -> Result<(), Error> {
+                while !thread_done.load(Ordering::SeqCst) {
+                    match listener.accept() {
+                        Ok((stream, addr)) => {
+                            if let Err(e) = accept_worker(stream, addr, &manager) {
+                                warn!("Cannot accept the worker at {}: {}", addr, e);
+                            }
+                        }
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                            thread::sleep(ACCEPT_POLL_INTERVAL);
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Ok(())
+            })?;
+        let result = self.executor.evaluate(sender, receiver, cache);
+        done.store(true, Ordering::SeqCst);
+        acceptor
+            .join()
+            .map_err(|e| format_err!("Acceptor panicked: {:?}", e))??;
+        worker_manager.lock().unwrap().stop()?;
+        result
+    }
+}
+
+/// Add the worker that connected from `addr` to the manager. The messages are moved between the
+/// connection and the channels of the manager by a pair of threads.
+fn accept_worker(
+    stream: TcpStream,
+    addr: SocketAddr,
+    manager: &Mutex<WorkerManager>,
+) -> Result<(), Error> {
+    stream.set_nonblocking(false)?;
+    let (socket_tx, socket_rx) = tcp_transport(stream)?;
+    let (tx, rx_worker) = channel();
+    let (tx_worker, rx) = channel();
+    let name = format!("Remote worker {}", addr);
+    info!("{} connected", name);
+    let shutdown = socket_tx.clone();
+    spawn_forwarder(
+        format!("Receiver of {}", name),
+        socket_rx,
+        tx_worker,
+        move || shutdown.shutdown(),
+    )?;
+    let shutdown = socket_tx.clone();
+    spawn_forwarder(
+        format!("Sender of {}", name),
+        rx_worker,
+        socket_tx,
+        move || shutdown.shutdown(),
+    )?;
+    manager.lock().unwrap().add(WorkerConn {
+        uuid: Uuid::new_v4(),
+        name,
+        sender: tx,
+        receiver: rx,
+    });
+    Ok(())
+}
+
+impl RemoteWorker {
+    /// Make a new [`RemoteWorker`](struct.RemoteWorker.html) based on a local
+    /// [`FileStore`](../../task_maker_store/struct.FileStore.html). The sandbox backend is selected
+    /// from the environment, see
+    /// [`SandboxBackend::from_env`](../enum.SandboxBackend.html#method.from_env).
+    pub fn new<S: Into<String>, P: Into<PathBuf>>(
+        name: S,
+        file_store: Arc<FileStore>,
+        sandbox_path: P,
+    ) -> RemoteWorker {
+        RemoteWorker {
+            name: name.into(),
+            file_store,
+            sandbox_path: sandbox_path.into(),
+            transfer_retries: DEFAULT_TRANSFER_RETRIES,
+            sandbox_backend: SandboxBackend::from_env(),
+            auth_token: None,
+            compression: false,
+        }
+    }
+
+    /// Set how many times a file whose content does not match its key is asked again to the
+    /// executor.
+    pub fn transfer_retries(&mut self, retries: usize) -> &mut Self {
+        self.transfer_retries = retries;
+        self
+    }
+
+    /// Set the implementation of the sandbox the worker uses for running the executions.
+    pub fn sandbox_backend(&mut self, backend: SandboxBackend) -> &mut Self {
+        self.sandbox_backend = backend;
+        self
+    }
+
+    /// Set the token to authenticate with, needed if the executor has been configured with one.
+    pub fn auth_token(&mut self, token: AuthToken) -> &mut Self {
+        self.auth_token = Some(token);
+        self
+    }
+
+    /// Set whether the files are compressed while being transferred to and from the executor. This
+    /// is worth only when the executor is reached through a slow network.
+    pub fn compression(&mut self, compression: bool) -> &mut Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Connect to the [`RemoteExecutor`](struct.RemoteExecutor.html) at that address and run the
+    /// executions it sends, blocking until the executor asks the worker to exit or the connection
+    /// is closed.
+    pub fn work<A: ToSocketAddrs>(self, addr: A) -> Result<(), Error> {
+        check_sandbox_path(&self.sandbox_path)?;
+        let (socket_tx, socket_rx) = tcp_transport(TcpStream::connect(addr)?)?;
+        let (mut worker, conn) = Worker::new(&self.name, self.file_store, self.sandbox_path);
+        worker.transfer_retries = self.transfer_retries;
+        worker.sandbox_backend = self.sandbox_backend;
+        worker.auth_token = self.auth_token;
+        worker.compression = self.compression;
+        // the transfers interrupted by a drop of the connection are resumed on the next run
+        worker.resume_transfers = true;
+        info!("Worker {} connected to the executor", worker);
+        let shutdown = socket_tx.clone();
+        let receiver = spawn_forwarder(
+            format!("Receiver of {}", self.name),
+            socket_rx,
+            conn.sender,
+            move || shutdown.shutdown(),
+        )?;
+        let shutdown = socket_tx.clone();
+        let sender = spawn_forwarder(
+            format!("Sender of {}", self.name),
+            conn.receiver,
+            socket_tx,
+            move || shutdown.shutdown(),
+        )?;
+        let result = worker.work();
+        // the worker has dropped its channels, closing the connection
+        sender
+            .join()
+            .map_err(|e| format_err!("Sender panicked: {:?}", e))?;
+        receiver
+            .join()
+            .map_err(|e| format_err!("Receiver panicked: {:?}", e))?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use task_maker_dag::{Execution, ExecutionCommand, ExecutionDAG, File};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_remote_evaluation() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let store = |name: &str| Arc::new(FileStore::new(tmpdir.path().join(name)).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let token = AuthToken::new("secret");
+        let mut executor = RemoteExecutor::bind(store("server"), "127.0.0.1:0").unwrap();
+        executor.auth_token(token.clone());
+        let workers_addr = executor.local_addr().unwrap();
+        // the client talks to the executor through TCP as well
+        let clients = TcpListener::bind("127.0.0.1:0").unwrap();
+        let clients_addr = clients.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = clients.accept().unwrap();
+            let (sender, receiver) = tcp_transport(stream).unwrap();
+            executor.evaluate(sender, receiver, cache).unwrap();
+        });
+        let mut worker =
+            RemoteWorker::new("Test worker", store("worker"), tmpdir.path().join("boxes"));
+        worker.auth_token(token.clone());
+        let worker = thread::spawn(move || worker.work(workers_addr).unwrap());
+
+        let mut dag = ExecutionDAG::new();
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Upper", ExecutionCommand::system("tr"));
+        exec.args(vec!["a-z", "A-Z"]).stdin(&input);
+        let output = exec.stdout();
+        dag.provide_content(input, b"hello".to_vec());
+        dag.add_execution(exec);
+        let content = Arc::new(Mutex::new(None));
+        let content2 = content.clone();
+        dag.get_file_content(output, 1024, move |data| {
+            *content2.lock().unwrap() = Some(data);
+            Ok(())
+        });
+        let (sender, receiver) = tcp_transport(TcpStream::connect(clients_addr).unwrap()).unwrap();
+        ExecutorClient::evaluate_with_auth(
+            dag,
+            sender,
+            &receiver,
+            store("client"),
+            DEFAULT_MAX_CONCURRENT_TRANSFERS,
+            AbortHandle::new(),
+            Some(token),
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        server.join().unwrap();
+        worker.join().unwrap();
+        assert_eq!(*content.lock().unwrap(), Some(b"HELLO".to_vec()));
+    }
+
+    #[test]
+    fn test_remote_evaluation_with_compression() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let store = |name: &str| Arc::new(FileStore::new(tmpdir.path().join(name)).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let executor = RemoteExecutor::bind(store("server"), "127.0.0.1:0").unwrap();
+        let workers_addr = executor.local_addr().unwrap();
+        let clients = TcpListener::bind("127.0.0.1:0").unwrap();
+        let clients_addr = clients.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = clients.accept().unwrap();
+            let (sender, receiver) = tcp_transport(stream).unwrap();
+            executor.evaluate(sender, receiver, cache).unwrap();
+        });
+        let mut worker =
+            RemoteWorker::new("Test worker", store("worker"), tmpdir.path().join("boxes"));
+        worker.compression(true);
+        let worker = thread::spawn(move || worker.work(workers_addr).unwrap());
+
+        // both the input and the output are bigger than INLINE_OUTPUT_LIMIT, so they are sent
+        // with the file transfer protocol, also between the server and the worker
+        let text = "hello world\n".repeat(10_000);
+        let mut dag = ExecutionDAG::new();
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Upper", ExecutionCommand::system("tr"));
+        exec.args(vec!["a-z", "A-Z"]).stdin(&input);
+        let output = exec.stdout();
+        dag.provide_content(input, text.as_bytes().to_vec());
+        dag.add_execution(exec);
+        let content = Arc::new(Mutex::new(None));
+        let content2 = content.clone();
+        dag.get_file_content(output, 1 << 20, move |data| {
+            *content2.lock().unwrap() = Some(data);
+            Ok(())
+        });
+        let (sender, receiver) = tcp_transport(TcpStream::connect(clients_addr).unwrap()).unwrap();
+        ExecutorClient::evaluate_with_compression(
+            dag,
+            sender,
+            &receiver,
+            store("client"),
+            DEFAULT_MAX_CONCURRENT_TRANSFERS,
+            AbortHandle::new(),
+            None,
+            true,
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        server.join().unwrap();
+        worker.join().unwrap();
+        let expected = text.to_uppercase().into_bytes();
+        assert_eq!(*content.lock().unwrap(), Some(expected));
+    }
+
+    #[test]
+    fn test_remote_authentication() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let store = |name: &str| Arc::new(FileStore::new(tmpdir.path().join(name)).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let mut executor = RemoteExecutor::bind(store("server"), "127.0.0.1:0").unwrap();
+        executor.auth_token(AuthToken::new("secret"));
+        let workers_addr = executor.local_addr().unwrap();
+        let (tx, rx_remote) = channel();
+        let (tx_remote, rx) = channel();
+        let server = thread::spawn(move || executor.evaluate(tx_remote, rx_remote, cache));
+
+        // a worker without the token is rejected
+        let worker = RemoteWorker::new("Intruder", store("worker1"), tmpdir.path().join("boxes1"));
+        let err = worker.work(workers_addr).unwrap_err().to_string();
+        assert!(err.contains("token required"), "{}", err);
+        // and so is a worker with the wrong one
+        let mut worker =
+            RemoteWorker::new("Intruder", store("worker2"), tmpdir.path().join("boxes2"));
+        worker.auth_token(AuthToken::new("guess"));
+        let err = worker.work(workers_addr).unwrap_err().to_string();
+        assert!(err.contains("wrong authentication token"), "{}", err);
+
+        // the client with the wrong token cannot evaluate the DAG
+        let mut dag = ExecutionDAG::new();
+        let exec = Execution::new("True", ExecutionCommand::system("true"));
+        dag.on_execution_done(&exec.uuid, |_| panic!("The execution should not run"));
+        dag.add_execution(exec);
+        let err = ExecutorClient::evaluate_with_auth(
+            dag,
+            tx,
+            &rx,
+            store("client"),
+            DEFAULT_MAX_CONCURRENT_TRANSFERS,
+            AbortHandle::new(),
+            Some(AuthToken::new("guess")),
+            |_| Ok(()),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("wrong authentication token"), "{}", err);
+        let err = server.join().unwrap().unwrap_err().to_string();
+        assert!(err.contains("wrong authentication token"), "{}", err);
+    }
+}
@@ -6,7 +6,10 @@
 //!
 //! A [`FileStore`](../task_maker_store/struct.FileStore.html) is used to store the files of the DAG
 //! and [`std::sync::mpsc::channel`](https://doc.rust-lang.org/std/sync/mpsc/fn.channel.html) is
-//! used for the internal communication.
+//! used for the internal communication. The communication with the client goes through a
+//! [`Transport`](trait.Transport.html), which for local evaluations is a channel as well. The
+//! workers on other machines connect to a
+//! [`RemoteExecutor`](executors/struct.RemoteExecutor.html) through TCP.
 //!
 //! A simple `Scheduler` is used to dispatch the jobs when all their dependencies are ready. When an
 //! execution is not successful (i.e. does not return with zero) all the depending jobs are
@@ -21,30 +24,45 @@
 extern crate log;
 
 use bincode;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use task_maker_dag::ExecutionDAG;
+use std::time::Duration;
+use task_maker_dag::{ExecutionDAG, ExecutionResult, ExecutionUuid, FileUuid};
 use task_maker_store::FileStore;
 
+pub use abort::*;
+pub use auth::*;
 pub(crate) use check_dag::*;
 pub use client::*;
 pub use executor::*;
-use failure::Error;
+use failure::{format_err, Error};
+pub use memory_reservation::*;
 pub use sandbox::*;
 pub(crate) use scheduler::*;
+pub use stall_watchdog::*;
 use task_maker_cache::Cache;
+pub use transfer_limiter::*;
+pub use transport::*;
 pub(crate) use worker::*;
 pub(crate) use worker_manager::*;
 
+mod abort;
+mod auth;
 mod check_dag;
 mod client;
+mod compression;
 mod executor;
 pub mod executors;
+mod memory_reservation;
 pub mod proto;
 mod sandbox;
 mod scheduler;
+mod stall_watchdog;
+mod transfer_limiter;
+mod transport;
 mod worker;
 mod worker_manager;
 
@@ -54,15 +72,15 @@ pub type ChannelSender = Sender<Vec<u8>>;
 pub type ChannelReceiver = Receiver<Vec<u8>>;
 
 /// Serialize a message into the sender serializing it.
-pub fn serialize_into<T>(what: &T, sender: &ChannelSender) -> Result<(), Error>
+pub fn serialize_into<T>(what: &T, sender: &dyn TransportSender) -> Result<(), Error>
 where
     T: serde::Serialize,
 {
-    sender.send(bincode::serialize(what)?).map_err(|e| e.into())
+    sender.send(bincode::serialize(what)?)
 }
 
 /// Deserialize a message from the channel and return it.
-pub fn deserialize_from<T>(reader: &ChannelReceiver) -> Result<T, Error>
+pub fn deserialize_from<T>(reader: &dyn TransportReceiver) -> Result<T, Error>
 where
     for<'de> T: serde::Deserialize<'de>,
 {
@@ -70,6 +88,42 @@ where
     bincode::deserialize(&data).map_err(|e| e.into())
 }
 
+/// Deserialize a message from the channel like [`deserialize_from`](fn.deserialize_from.html),
+/// waiting at most `timeout` for it. Returns `None` if no message has arrived in time.
+pub fn deserialize_from_timeout<T>(
+    reader: &dyn TransportReceiver,
+    timeout: Duration,
+) -> Result<Option<T>, Error>
+where
+    for<'de> T: serde::Deserialize<'de>,
+{
+    match reader.recv_timeout(timeout)? {
+        Some(data) => Ok(Some(bincode::deserialize(&data)?)),
+        None => Ok(None),
+    }
+}
+
+/// The outcome of an execution evaluated by
+/// [`eval_dag_locally_with_results`](fn.eval_dag_locally_with_results.html).
+#[derive(Debug, Clone)]
+pub enum ExecutionOutcome {
+    /// The execution has completed, possibly unsuccessfully, with this result.
+    Done(ExecutionResult),
+    /// The execution has been skipped.
+    Skipped,
+}
+
+/// The results of a DAG evaluated by
+/// [`eval_dag_locally_with_results`](fn.eval_dag_locally_with_results.html).
+#[derive(Debug, Clone, Default)]
+pub struct LocalEvaluationResults {
+    /// The outcome of each execution of the DAG.
+    pub executions: HashMap<ExecutionUuid, ExecutionOutcome>,
+    /// The content of the output files of the executions, truncated to the requested limit. The
+    /// files whose execution has been skipped are missing.
+    pub files: HashMap<FileUuid, Vec<u8>>,
+}
+
 /// Evaluate a DAG locally spawning a new [`LocalExecutor`](executors/struct.LocalExecutor.html)
 /// with the specified number of workers.
 pub fn eval_dag_locally<P: Into<PathBuf>, P2: Into<PathBuf>>(
@@ -78,23 +132,136 @@ pub fn eval_dag_locally<P: Into<PathBuf>, P2: Into<PathBuf>>(
     num_cores: usize,
     sandbox_path: P2,
 ) {
+    run_dag_locally(
+        dag,
+        store_dir.into(),
+        num_cores,
+        sandbox_path.into(),
+        AbortHandle::new(),
+    )
+    .expect("Local evaluation failed");
+}
+
+/// Evaluate a DAG locally like [`eval_dag_locally`](fn.eval_dag_locally.html), stopping the
+/// evaluation when the handle is aborted. The executions not started yet are skipped, the running
+/// ones are waited.
+///
+/// Use [`AbortHandle::on_sigint`](struct.AbortHandle.html#method.on_sigint) for stopping the
+/// evaluation with Ctrl-C.
+pub fn eval_dag_locally_with_abort<P: Into<PathBuf>, P2: Into<PathBuf>>(
+    dag: ExecutionDAG,
+    store_dir: P,
+    num_cores: usize,
+    sandbox_path: P2,
+    abort: AbortHandle,
+) -> Result<(), Error> {
+    run_dag_locally(dag, store_dir.into(), num_cores, sandbox_path.into(), abort)
+}
+
+/// Evaluate a DAG locally like [`eval_dag_locally`](fn.eval_dag_locally.html), collecting the
+/// outcome of all the executions and the first `file_limit` bytes of all their output files.
+///
+/// The callbacks already registered in the DAG are still called.
+pub fn eval_dag_locally_with_results<P: Into<PathBuf>, P2: Into<PathBuf>>(
+    mut dag: ExecutionDAG,
+    store_dir: P,
+    num_cores: usize,
+    sandbox_path: P2,
+    file_limit: usize,
+) -> Result<LocalEvaluationResults, Error> {
+    let results = Arc::new(Mutex::new(LocalEvaluationResults::default()));
+    let executions: Vec<_> = dag
+        .data
+        .executions
+        .values()
+        .map(|exec| (exec.uuid, exec.outputs()))
+        .collect();
+    for (exec, outputs) in executions {
+        let done = results.clone();
+        dag.on_execution_done(&exec, move |result| {
+            let mut results = done.lock().unwrap();
+            results
+                .executions
+                .insert(exec, ExecutionOutcome::Done(result));
+            Ok(())
+        });
+        let skipped = results.clone();
+        dag.on_execution_skip(&exec, move || {
+            let mut results = skipped.lock().unwrap();
+            results.executions.insert(exec, ExecutionOutcome::Skipped);
+            Ok(())
+        });
+        for file in outputs {
+            let files = results.clone();
+            let previous = dag
+                .file_callbacks
+                .get_mut(&file)
+                .and_then(|callbacks| callbacks.get_content.take());
+            let limit = match &previous {
+                Some((prev_limit, _)) => file_limit.max(*prev_limit),
+                None => file_limit,
+            };
+            let callback = move |content: Vec<u8>| {
+                let res = match previous {
+                    Some((prev_limit, callback)) => {
+                        callback.call(content[..content.len().min(prev_limit)].to_vec())
+                    }
+                    None => Ok(()),
+                };
+                let content = content[..content.len().min(file_limit)].to_vec();
+                files.lock().unwrap().files.insert(file, content);
+                res
+            };
+            dag.get_file_content(file, limit, callback);
+        }
+    }
+    run_dag_locally(
+        dag,
+        store_dir.into(),
+        num_cores,
+        sandbox_path.into(),
+        AbortHandle::new(),
+    )?;
+    let results = results.lock().unwrap().clone();
+    Ok(results)
+}
+
+/// Evaluate the DAG with a local executor, returning the error of the executor or, if it
+/// succeeded, the one of the client.
+fn run_dag_locally(
+    dag: ExecutionDAG,
+    store_dir: PathBuf,
+    num_cores: usize,
+    sandbox_path: PathBuf,
+    abort: AbortHandle,
+) -> Result<(), Error> {
     let (tx, rx_remote) = channel();
     let (tx_remote, rx) = channel();
-    let store_dir = store_dir.into();
-    let sandbox_path = sandbox_path.into();
-    let file_store = Arc::new(FileStore::new(&store_dir).expect("Cannot create the file store"));
+    let file_store = Arc::new(FileStore::new(&store_dir)?);
     let server_file_store = file_store.clone();
-    let server = thread::Builder::new()
-        .name("Local executor".into())
-        .spawn(move || {
-            let cache = Cache::new(store_dir).expect("Cannot create the cache");
+    let server = thread::Builder::new().name("Local executor".into()).spawn(
+        move || -> Result<(), Error> {
+            let cache = Cache::new(store_dir)?;
             let executor =
                 executors::LocalExecutor::new(server_file_store, num_cores, sandbox_path);
-            executor.evaluate(tx_remote, rx_remote, cache).unwrap();
-        })
-        .expect("Failed to spawn local executor thread");
-    ExecutorClient::evaluate(dag, tx, &rx, file_store, |_| Ok(())).expect("Client failed");
-    server.join().expect("Server panicked");
+            executor.evaluate(tx_remote, rx_remote, cache)
+        },
+    )?;
+    let client = ExecutorClient::evaluate_with_abort(
+        dag,
+        tx,
+        &rx,
+        file_store,
+        DEFAULT_MAX_CONCURRENT_TRANSFERS,
+        abort,
+        |_| Ok(()),
+    );
+    let server = server
+        .join()
+        .map_err(|_| format_err!("The local executor panicked"))?;
+    // if the executor failed the client only sees the connection closed
+    server?;
+    client
 }
 
 #[cfg(test)]
@@ -206,4 +373,509 @@ mod tests {
         assert!(!cwd.path().join("stdout2").exists());
         assert!(!cwd.path().join("output3").exists());
     }
+
+    #[test]
+    fn test_eval_dag_locally_with_results() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Succeeding", ExecutionCommand::system("cat"));
+        exec.stdin(&input);
+        let stdout = exec.stdout();
+        let mut exec2 = Execution::new("Failing", ExecutionCommand::system("false"));
+        exec2.stdin(&stdout);
+        let stdout2 = exec2.stdout();
+        let mut exec3 = Execution::new("Skipped", ExecutionCommand::system("true"));
+        exec3.stdin(&stdout2);
+        let (exec_uuid, exec2_uuid, exec3_uuid) = (exec.uuid, exec2.uuid, exec3.uuid);
+
+        let content = Arc::new(Mutex::new(None));
+        let content2 = content.clone();
+        dag.provide_content(input, b"hello".to_vec());
+        dag.get_file_content(&stdout, 2, move |content| {
+            *content2.lock().unwrap() = Some(content);
+            Ok(())
+        });
+        dag.add_execution(exec);
+        dag.add_execution(exec2);
+        dag.add_execution(exec3);
+
+        let results = eval_dag_locally_with_results(dag, cwd.path(), 2, cwd.path(), 1024).unwrap();
+
+        assert_eq!(results.executions.len(), 3);
+        match &results.executions[&exec_uuid] {
+            ExecutionOutcome::Done(result) => assert_eq!(result.status, ExecutionStatus::Success),
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+        match &results.executions[&exec2_uuid] {
+            ExecutionOutcome::Done(result) => {
+                assert_eq!(result.status, ExecutionStatus::ReturnCode(1))
+            }
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+        match &results.executions[&exec3_uuid] {
+            ExecutionOutcome::Skipped => {}
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+        assert_eq!(results.files[&stdout.uuid], b"hello".to_vec());
+        // the callbacks of the DAG are still called, with their limit
+        assert_eq!(*content.lock().unwrap(), Some(b"he".to_vec()));
+    }
+
+    #[test]
+    fn test_stderr_as_stdin() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Logger", ExecutionCommand::system("sh"));
+        exec.args(vec!["-c", "echo out; cat >&2"]);
+        exec.stdin(&input);
+        let stdout = exec.stdout();
+        let stderr = exec.stderr();
+        let mut exec2 = Execution::new("Filter", ExecutionCommand::system("tr"));
+        exec2.args(vec!["a-z", "A-Z"]);
+        exec2.stdin(&stderr);
+        let stdout2 = exec2.stdout();
+
+        dag.provide_content(input, b"hello".to_vec());
+        dag.add_execution(exec);
+        dag.add_execution(exec2);
+
+        let results = eval_dag_locally_with_results(dag, cwd.path(), 2, cwd.path(), 1024).unwrap();
+
+        assert_eq!(results.files[&stdout.uuid], b"out\n".to_vec());
+        assert_eq!(results.files[&stderr.uuid], b"hello".to_vec());
+        assert_eq!(results.files[&stdout2.uuid], b"HELLO".to_vec());
+    }
+
+    #[test]
+    fn test_stderr_truncated() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Spammer", ExecutionCommand::system("sh"));
+        exec.args(vec![
+            "-c",
+            "read w; i=0; while [ $i -lt 2000 ]; do echo $w >&2; i=$((i+1)); done; echo done",
+        ]);
+        exec.stdin(&input);
+        exec.limits_mut().stdio_size(1024);
+        let stdout = exec.stdout();
+        let stderr = exec.stderr();
+        let uuid = exec.uuid;
+        dag.provide_content(input, b"spam\n".to_vec());
+        dag.add_execution(exec);
+
+        let results =
+            eval_dag_locally_with_results(dag, cwd.path(), 2, cwd.path(), 1024 * 1024).unwrap();
+
+        match &results.executions[&uuid] {
+            ExecutionOutcome::Done(result) => {
+                assert_eq!(result.status, ExecutionStatus::Success);
+                assert!(result.truncated_output);
+            }
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+        assert_eq!(results.files[&stdout.uuid], b"done\n".to_vec());
+        let stderr = &results.files[&stderr.uuid];
+        assert_eq!(stderr.len(), 1024);
+        assert_eq!(&stderr[..10], b"spam\nspam\n");
+    }
+
+    #[test]
+    fn test_result_provenance() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let run = || {
+            let mut dag = ExecutionDAG::new();
+            let input = File::new("Input file");
+            let mut exec = Execution::new("Cat", ExecutionCommand::system("cat"));
+            exec.stdin(&input);
+            let uuid = exec.uuid;
+            dag.provide_content(input, b"hello".to_vec());
+            dag.add_execution(exec);
+            let mut results =
+                eval_dag_locally_with_results(dag, cwd.path(), 2, cwd.path(), 1024).unwrap();
+            match results.executions.remove(&uuid) {
+                Some(ExecutionOutcome::Done(result)) => result,
+                outcome => panic!("Unexpected outcome {:?}", outcome),
+            }
+        };
+
+        let fresh = run();
+        assert!(!fresh.was_cached);
+        match &fresh.provenance {
+            ExecutionProvenance::Worker { name, .. } => {
+                assert!(name.starts_with("Local worker"), "Unexpected name {}", name)
+            }
+            provenance => panic!("Unexpected provenance {:?}", provenance),
+        }
+        let cached = run();
+        assert!(cached.was_cached);
+        assert_eq!(cached.provenance, ExecutionProvenance::Cache);
+        assert_eq!(cached.provenance.to_string(), "cache");
+    }
+
+    #[test]
+    fn test_sandbox_setup_time() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Cat", ExecutionCommand::system("cat"));
+        exec.stdin(&input);
+        let uuid = exec.uuid;
+        dag.provide_content(input, vec![b'x'; 1 << 20]);
+        dag.add_execution(exec);
+        let mut results =
+            eval_dag_locally_with_results(dag, cwd.path(), 2, cwd.path(), 1024).unwrap();
+        let result = match results.executions.remove(&uuid) {
+            Some(ExecutionOutcome::Done(result)) => result,
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        };
+        assert_eq!(result.status, ExecutionStatus::Success);
+        // the sandbox directory is created and the input copied before starting the process
+        assert!(result.resources.setup_time > 0.0);
+        assert!(result.resources.setup_time.is_finite());
+    }
+
+    #[test]
+    fn test_safety_wall_time() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().extra_time(0.0).safety_wall_time(Some(0.5));
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Hanging", ExecutionCommand::system("sleep"));
+        exec.args(vec!["100"]);
+        exec.stdin(&input);
+        let uuid = exec.uuid;
+        dag.provide_content(input, b"hello".to_vec());
+        dag.add_execution(exec);
+        let mut results =
+            eval_dag_locally_with_results(dag, cwd.path(), 2, cwd.path(), 1024).unwrap();
+        let result = match results.executions.remove(&uuid) {
+            Some(ExecutionOutcome::Done(result)) => result,
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        };
+        match result.status {
+            ExecutionStatus::InternalError(_) => {}
+            status => panic!("Unexpected status {:?}", status),
+        }
+        assert!(result.was_killed);
+        assert!(result.resources.wall_time < 10.0);
+    }
+
+    #[test]
+    fn test_separate_sandbox_path() {
+        let store = TempDir::new("tm-test").unwrap();
+        let sandboxes = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().keep_sandboxes(true);
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Cat", ExecutionCommand::system("cat"));
+        exec.stdin(&input);
+        let output = exec.stdout();
+        dag.provide_content(input, b"hello".to_vec());
+        dag.add_execution(exec);
+        let results =
+            eval_dag_locally_with_results(dag, store.path(), 2, sandboxes.path(), 1024).unwrap();
+        assert_eq!(results.files[&output.uuid], b"hello".to_vec());
+        // the sandbox has been kept in its own directory, not in the store
+        let kept: Vec<_> = std::fs::read_dir(sandboxes.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].starts_with("box"), "{:?}", kept);
+        for entry in std::fs::read_dir(store.path()).unwrap() {
+            let name = entry.unwrap().file_name();
+            assert!(!name.to_string_lossy().starts_with("box"), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_unusable_sandbox_path() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let sandboxes = cwd.path().join("sandboxes");
+        std::fs::write(&sandboxes, "not a directory").unwrap();
+        let mut dag = ExecutionDAG::new();
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Cat", ExecutionCommand::system("cat"));
+        exec.stdin(&input);
+        dag.provide_content(input, b"hello".to_vec());
+        dag.add_execution(exec);
+        let err = eval_dag_locally_with_results(dag, cwd.path().join("store"), 2, &sandboxes, 1024)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("The sandbox directory"), "{}", err);
+    }
+
+    #[test]
+    fn test_progress() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+        let input = File::new("Input file");
+        let mut gen = Execution::new("Generator", ExecutionCommand::system("cat"));
+        gen.stdin(&input).tag("generation".into());
+        let generated = gen.stdout();
+        dag.add_execution(gen);
+        for i in 0..4 {
+            let mut sol = Execution::new(format!("Solution {}", i), ExecutionCommand::system("wc"));
+            sol.args(vec!["-c"])
+                .stdin(&generated)
+                .tag("evaluation".into());
+            dag.add_execution(sol);
+        }
+        dag.provide_content(input, b"hello".to_vec());
+        let updates = Arc::new(Mutex::new(vec![]));
+        let updates2 = updates.clone();
+        dag.on_progress(30.0, move |progress| {
+            updates2.lock().unwrap().push(progress);
+            Ok(())
+        });
+        eval_dag_locally(dag, cwd.path(), 2, cwd.path());
+
+        let updates = updates.lock().unwrap();
+        // the generation is done at 20%, then 60%, 100%
+        let percents: Vec<_> = updates.iter().map(|p| p.percent).collect();
+        assert_eq!(percents, vec![20.0, 60.0, 100.0]);
+        assert_eq!(updates[0].phase, Some("generation".to_string()));
+        assert!(updates[0].phase_done);
+        assert_eq!(updates[2].phase, Some("evaluation".to_string()));
+        assert!(updates[2].phase_done);
+    }
+
+    #[test]
+    fn test_eval_dag_locally_with_abort() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let sandboxes = cwd.path().join("sandboxes");
+        let mut dag = ExecutionDAG::new();
+
+        let input = File::new("Input file");
+        let mut exec = Execution::new("First", ExecutionCommand::system("cat"));
+        exec.stdin(&input);
+        let stdout = exec.stdout();
+        let mut exec2 = Execution::new("Second", ExecutionCommand::system("cat"));
+        exec2.stdin(&stdout);
+        let stdout2 = exec2.stdout();
+        let mut exec3 = Execution::new("Third", ExecutionCommand::system("cat"));
+        exec3.stdin(&stdout2);
+
+        let exec3_skipped = Arc::new(AtomicBool::new(false));
+        let exec3_skipped2 = exec3_skipped.clone();
+        dag.provide_content(input, b"hello".to_vec());
+        dag.add_execution(exec);
+        dag.add_execution(exec2);
+        dag.on_execution_done(&exec3.uuid, |_res| panic!("exec3 has not been skipped"));
+        dag.on_execution_skip(&exec3.uuid, move || {
+            exec3_skipped.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+        dag.add_execution(exec3);
+
+        let abort = AbortHandle::new();
+        abort.abort();
+        eval_dag_locally_with_abort(dag, cwd.path(), 2, &sandboxes, abort).unwrap();
+
+        assert!(exec3_skipped2.load(Ordering::Relaxed));
+        // the sandboxes of the executions that have been started are not leaked
+        if sandboxes.exists() {
+            let kept: Vec<_> = std::fs::read_dir(&sandboxes).unwrap().collect();
+            assert!(kept.is_empty(), "Kept sandboxes: {:?}", kept);
+        }
+    }
+
+    #[test]
+    fn test_max_total_duration() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let sandboxes = cwd.path().join("sandboxes");
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().max_total_duration(Some(0.5));
+
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Stuck", ExecutionCommand::system("sleep"));
+        exec.args(vec!["10".to_string()]).stdin(&input);
+        let stdout = exec.stdout();
+        let mut exec2 = Execution::new("Dependent", ExecutionCommand::system("cat"));
+        exec2.stdin(&stdout);
+
+        let exec2_skipped = Arc::new(AtomicBool::new(false));
+        let exec2_skipped2 = exec2_skipped.clone();
+        dag.provide_content(input, b"hello".to_vec());
+        dag.add_execution(exec);
+        dag.on_execution_done(&exec2.uuid, |_res| panic!("exec2 has not been skipped"));
+        dag.on_execution_skip_reason(&exec2.uuid, move |reason| {
+            assert_eq!(reason, SkipReason::Aborted);
+            exec2_skipped.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+        dag.add_execution(exec2);
+
+        let start = std::time::Instant::now();
+        let res = eval_dag_locally_with_abort(dag, cwd.path(), 2, &sandboxes, AbortHandle::new());
+        let err = res.unwrap_err().to_string();
+        assert!(
+            err.contains("maximum duration"),
+            "Unexpected error: {}",
+            err
+        );
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        assert!(exec2_skipped2.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_verify_deterministic() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let make_dag = |verify_mode| {
+            let mut dag = ExecutionDAG::new();
+            dag.config_mut().verify_mode(verify_mode);
+            let input = File::new("Input file");
+            let mut exec = Execution::new("Deterministic", ExecutionCommand::system("cat"));
+            exec.stdin(&input);
+            exec.stdout();
+            dag.provide_content(input, b"hello".to_vec());
+            let started = Arc::new(AtomicBool::new(false));
+            let started2 = started.clone();
+            dag.on_execution_start(&exec.uuid, move |_w| {
+                started2.store(true, Ordering::Relaxed);
+                Ok(())
+            });
+            dag.on_execution_nondeterminism(&exec.uuid, |files| {
+                panic!("Nondeterminism reported on {:?}", files)
+            });
+            dag.add_execution(exec);
+            (dag, started)
+        };
+
+        // populate the cache
+        let (dag, started) = make_dag(VerifyMode::Nothing);
+        eval_dag_locally(dag, cwd.path(), 2, cwd.path());
+        assert!(started.load(Ordering::Relaxed));
+        // a cache hit does not start the execution
+        let (dag, started) = make_dag(VerifyMode::Nothing);
+        eval_dag_locally(dag, cwd.path(), 2, cwd.path());
+        assert!(!started.load(Ordering::Relaxed));
+        // the verification runs it again and finds no mismatch
+        let (dag, started) = make_dag(VerifyMode::Everything);
+        eval_dag_locally(dag, cwd.path(), 2, cwd.path());
+        assert!(started.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_execution_budget() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().max_executions(Some(1));
+
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Within budget", ExecutionCommand::system("cat"));
+        exec.stdin(&input);
+        let stdout = exec.stdout();
+        let mut exec2 = Execution::new("Over budget", ExecutionCommand::system("wc"));
+        exec2.stdin(&stdout);
+
+        let exec_done = Arc::new(AtomicBool::new(false));
+        let exec_done2 = exec_done.clone();
+        let exec2_skipped = Arc::new(AtomicBool::new(false));
+        let exec2_skipped2 = exec2_skipped.clone();
+        dag.provide_content(input, b"hello".to_vec());
+        dag.on_execution_done(&exec.uuid, move |_res| {
+            exec_done.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+        dag.on_execution_skip(&exec.uuid, || panic!("exec has been skipped"));
+        dag.add_execution(exec);
+        dag.on_execution_done(&exec2.uuid, |_res| panic!("exec2 has not been skipped"));
+        dag.on_execution_skip_reason(&exec2.uuid, move |reason| {
+            assert_eq!(reason, SkipReason::OverBudget);
+            exec2_skipped.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+        dag.add_execution(exec2);
+
+        eval_dag_locally(dag, cwd.path(), 2, cwd.path());
+
+        assert!(exec_done2.load(Ordering::Relaxed));
+        assert!(exec2_skipped2.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_allow_failed_inputs() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let mut dag = ExecutionDAG::new();
+
+        let input = File::new("Input file");
+        let mut exec = Execution::new("Failing", ExecutionCommand::system("false"));
+        exec.stdin(&input);
+        let stdout = exec.stdout();
+        let mut exec2 = Execution::new("Allowing failures", ExecutionCommand::system("true"));
+        exec2.stdin(&stdout).allow_failed_inputs(true);
+        let mut exec3 = Execution::new("Not allowing failures", ExecutionCommand::system("true"));
+        exec3.stdin(&stdout);
+
+        let exec2_done = Arc::new(AtomicBool::new(false));
+        let exec2_done2 = exec2_done.clone();
+        let exec3_skipped = Arc::new(AtomicBool::new(false));
+        let exec3_skipped2 = exec3_skipped.clone();
+        dag.provide_content(input, b"hello".to_vec());
+        dag.add_execution(exec);
+        dag.on_execution_done(&exec2.uuid, move |_res| {
+            exec2_done.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+        dag.on_execution_skip(&exec2.uuid, || panic!("exec2 has been skipped"));
+        dag.add_execution(exec2);
+        dag.on_execution_done(&exec3.uuid, |_res| panic!("exec3 has not been skipped"));
+        dag.on_execution_skip(&exec3.uuid, move || {
+            exec3_skipped.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+        dag.add_execution(exec3);
+
+        eval_dag_locally(dag, cwd.path(), 2, cwd.path());
+
+        assert!(exec2_done2.load(Ordering::Relaxed));
+        assert!(exec3_skipped2.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_store_eviction() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new_with_limit(cwd.path(), 3000).unwrap());
+        // the files in use are never evicted, only the ones of the previous evaluations are
+        let evaluate = |contents: &[u8]| {
+            let mut dag = ExecutionDAG::new();
+            for content in contents {
+                let input = File::new("Input file");
+                let mut exec = Execution::new("Copy", ExecutionCommand::system("cat"));
+                exec.stdin(&input);
+                let stdout = exec.stdout();
+                dag.provide_content(input, vec![*content; 1000]);
+                let path = cwd.path().join(format!("output{}", *content as char));
+                dag.write_file_to(&stdout, &path, false);
+                dag.add_execution(exec);
+            }
+            let (tx, rx_remote) = channel();
+            let (tx_remote, rx) = channel();
+            let server_file_store = file_store.clone();
+            let store_dir = cwd.path().to_owned();
+            let server = thread::spawn(move || {
+                let cache = Cache::new(&store_dir).unwrap();
+                let executor = executors::LocalExecutor::new(server_file_store, 2, store_dir);
+                executor.evaluate(tx_remote, rx_remote, cache).unwrap();
+            });
+            ExecutorClient::evaluate(dag, tx, &rx, file_store.clone(), |_| Ok(())).unwrap();
+            server.join().unwrap();
+        };
+
+        evaluate(b"ab");
+        assert_eq!(file_store.evicted().entries, 0);
+        evaluate(b"cd");
+        assert!(file_store.evicted().entries > 0);
+        assert!(file_store.evicted().bytes > 0);
+        for name in &["a", "b", "c", "d"] {
+            assert!(cwd.path().join(format!("output{}", name)).exists());
+        }
+    }
 }
@@ -0,0 +1,77 @@
+use failure::{format_err, Error};
+
+/// The file with the memory information of the system.
+const MEMINFO_PATH: &str = "/proc/meminfo";
+
+/// A minimum amount of memory to keep free on the machine of the workers, so that the sandboxes
+/// never push the system into the hands of the OOM-killer. The memory demand of an execution is
+/// estimated from its memory limit, the executions without a memory limit are assumed not to need
+/// any memory.
+///
+/// All the sizes are in KiB, like the memory limits of the executions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryReservation {
+    /// The total memory of the machine.
+    pub total: u64,
+    /// The memory that must be left free.
+    pub min_free: u64,
+}
+
+impl MemoryReservation {
+    /// Make a new reservation of `min_free` KiB on a machine with `total` KiB of memory.
+    pub fn new(total: u64, min_free: u64) -> MemoryReservation {
+        MemoryReservation { total, min_free }
+    }
+
+    /// Make a new reservation of `min_free` KiB on the current machine, reading its total memory
+    /// from `/proc/meminfo`.
+    pub fn from_system(min_free: u64) -> Result<MemoryReservation, Error> {
+        let meminfo = std::fs::read_to_string(MEMINFO_PATH)?;
+        let total = parse_mem_total(&meminfo)
+            .ok_or_else(|| format_err!("Cannot find the total memory in {}", MEMINFO_PATH))?;
+        Ok(MemoryReservation::new(total, min_free))
+    }
+
+    /// The memory the sandboxes can use without breaching the reservation.
+    pub fn available(&self) -> u64 {
+        self.total.saturating_sub(self.min_free)
+    }
+
+    /// Whether a new execution that needs `demand` KiB can start while the running ones need
+    /// `in_use` KiB. When nothing is running the execution is always admitted, otherwise an
+    /// execution bigger than the available memory would never start.
+    pub fn admits(&self, in_use: u64, demand: u64) -> bool {
+        in_use == 0 || in_use + demand <= self.available()
+    }
+}
+
+/// Extract the value of `MemTotal`, in KiB, from the content of `/proc/meminfo`.
+fn parse_mem_total(meminfo: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find(|line| line.starts_with("MemTotal:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|value| value.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mem_total() {
+        let meminfo = "MemTotal:       16318480 kB\nMemFree:         1234567 kB\n";
+        assert_eq!(parse_mem_total(meminfo), Some(16318480));
+        assert_eq!(parse_mem_total("MemFree: 123 kB\n"), None);
+    }
+
+    #[test]
+    fn test_memory_reservation_admits() {
+        let reservation = MemoryReservation::new(1000, 200);
+        assert_eq!(reservation.available(), 800);
+        assert!(reservation.admits(0, 5000));
+        assert!(reservation.admits(300, 500));
+        assert!(!reservation.admits(300, 501));
+        assert_eq!(MemoryReservation::new(100, 200).available(), 0);
+    }
+}
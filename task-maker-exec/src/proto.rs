@@ -24,6 +24,17 @@
 //! - `Worker` — [`WorkerClientMessage`](enum.WorkerClientMessage.html) → `Server`
 //! - `Worker` ← [`WorkerServerMessage`](enum.WorkerServerMessage.html) — `Server`
 //!
+//! As soon as a worker connects it sends a [`Hello`](enum.WorkerClientMessage.html#variant.Hello)
+//! message with its [`PROTOCOL_VERSION`](constant.PROTOCOL_VERSION.html). If it doesn't match the
+//! one of the server the worker is [`Rejected`](enum.WorkerServerMessage.html#variant.Rejected)
+//! and not added to the pool.
+//!
+//! Before accepting a DAG or a worker the server authenticates the other side: it sends a random
+//! nonce with a `Challenge` message (to the clients as soon as they connect, to the workers after
+//! their `Hello`) and the answer must be an `Authenticate` message with the MAC of the nonce
+//! computed with the shared [`AuthToken`](../struct.AuthToken.html). If the server has no token
+//! every answer is accepted, otherwise the connections with a missing or wrong MAC are rejected.
+//!
 //! When an actor needs a file a particular series of messages is sent. Let's assume `A` wants a
 //! file from `B`:
 //! - `A` sends a `AskFile` to `B`
@@ -31,15 +42,49 @@
 //! - `B` sends [`FileProtocol::Data`](enum.FileProtocol.html#variant.Data) zero or more times
 //! - `B` sends [`FileProtocol::End`](enum.FileProtocol.html#variant.End) which triggers a protocol
 //!   switch, back into normal mode
+//!
+//! The files can be compressed with zlib during the transfer, which saves bandwidth when the client
+//! or the workers talk to the server through the network but only wastes time with the in-process
+//! channels of the local evaluations. Hence compression is opt-in: in its `Authenticate` message
+//! the client (or the worker) tells the server whether it wants to receive the files compressed,
+//! and it compresses the files it sends with the same setting. Each `ProvideFile` message
+//! carries a flag telling whether the `Data` chunks that follow are compressed, so the receiver
+//! always knows how to read them.
+//!
+//! If the connection drops in the middle of a transfer the data already received is not lost: the
+//! receiver keeps it with a [`PartialFileReceiver`](struct.PartialFileReceiver.html), indexed by the
+//! key of the file. After reconnecting, instead of asking again the whole file, the worker sends a
+//! [`ResumeFile`](enum.WorkerClientMessage.html#variant.ResumeFile) with the number of bytes it
+//! already has, and the server answers with `ProvideFile` followed by only the remaining data.
 
 use crate::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use task_maker_dag::*;
 use task_maker_store::*;
 
+/// The version of the protocol, workers with a different version cannot talk to the server since
+/// the messages may be misinterpreted.
+///
+/// The version of the crate is not bumped as often as the messages change, so it's followed by a
+/// revision of the protocol: bump it every time the serialized format of a message changes.
+pub const PROTOCOL_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+proto9");
+
+/// The default number of times a file received with a content not matching its key is asked
+/// again, before giving up.
+pub const DEFAULT_TRANSFER_RETRIES: usize = 3;
+
+/// The maximum size, in bytes, of an output to be sent to the client inline with the completion of
+/// its execution. The bigger outputs are sent at the end of the evaluation.
+pub const INLINE_OUTPUT_LIMIT: u64 = 4 * 1024;
+
+/// The size, in bytes, of the chunks in which the data sent with
+/// [`ChannelFileSender::send_data`](struct.ChannelFileSender.html#method.send_data) is split.
+const DATA_CHUNK_SIZE: usize = 8 * 1024;
+
 /// Messages that the client sends to the server.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ExecutorClientMessage {
@@ -51,17 +96,24 @@ pub enum ExecutorClientMessage {
         callbacks: ExecutionDAGWatchSet,
     },
     /// The client is providing a file. After this message there is a protocol switch for the file
-    /// transmission.
-    ProvideFile(FileUuid, FileStoreKey),
+    /// transmission. The last entry is true if the chunks of the file are compressed.
+    ProvideFile(FileUuid, FileStoreKey, bool),
     /// The client is asking the server to send a file. After this message there is a protocol
     /// switch for the file transmission.
     AskFile(FileUuid, FileStoreKey, bool),
-    /// The client is asking to stop the evaluation. All the running executions will be killed and
-    /// no more execution will be run. All the callbacks will be called as usual.
+    /// The client is asking to stop the evaluation. No more execution will be run, the running
+    /// ones are waited. All the callbacks will be called as usual.
     Stop,
+    /// The client is asking to stop the evaluation like with `Stop`, but the running executions
+    /// are killed too and reported as internal errors.
+    Kill,
     /// The client is asking for the server status. After this message the client should expect a
     /// [`Status`](enum.ExecutorServerMessage.html#variant.Status) message back.
     Status,
+    /// The answer to the [`Challenge`](enum.ExecutorServerMessage.html#variant.Challenge) of the
+    /// server: the MAC of the nonce, if the client has a token, and whether the client wants the
+    /// files sent by the server to be compressed. This must be the first message of the client.
+    Authenticate(Option<Vec<u8>>, bool),
 }
 
 /// Messages that the server sends to the client.
@@ -72,37 +124,64 @@ pub enum ExecutorServerMessage {
     AskFile(FileUuid),
     /// The server is sending a file. After this message there is a protocol switch for the file
     /// transmission protocol. The second entry is true if the generation of the file was
-    /// successful.
-    ProvideFile(FileUuid, bool),
+    /// successful, the third one is true if the chunks of the file are compressed.
+    ProvideFile(FileUuid, bool, bool),
     /// The execution has started on a worker.
     NotifyStart(ExecutionUuid, WorkerUuid),
-    /// The execution has completed with that result.
-    NotifyDone(ExecutionUuid, ExecutionResult),
-    /// The execution has been skipped.
-    NotifySkip(ExecutionUuid),
+    /// The execution has completed with that result. The content of its outputs the client is
+    /// interested in and not bigger than `INLINE_OUTPUT_LIMIT` is sent along.
+    NotifyDone(
+        ExecutionUuid,
+        Box<ExecutionResult>,
+        HashMap<FileUuid, Vec<u8>>,
+    ),
+    /// The execution has been skipped for that reason.
+    NotifySkip(ExecutionUuid, SkipReason),
+    /// The execution, run again for verifying the cache, produced these files with a content
+    /// different from the cached one.
+    NotifyNondeterminism(ExecutionUuid, Vec<FileUuid>),
     /// There was an error during the evaluation.
     Error(String),
     /// The server status as asked by the client.
     Status(ExecutorStatus<Duration>),
     /// The evaluation of the DAG is complete, this message will close the connection.
     Done(Vec<(FileUuid, FileStoreKey, bool)>),
+    /// The first message sent to the client, with the nonce it has to authenticate with.
+    Challenge(Vec<u8>),
 }
 
 /// Messages sent by the workers to the server.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum WorkerClientMessage {
+    /// The first message sent by the worker, with the version of its protocol. This must remain
+    /// the first variant, so that it's decoded correctly even by servers with a different
+    /// version.
+    Hello(String),
     /// The worker is ready for some job. The worker will wait for a
     /// [`Work`](enum.WorkerServerMessage.html#variant.Work) message.
     GetWork,
-    /// The worker completed the job with this result producing those files. The actual files will
-    /// be sent immediately after using `ProvideFile` messages.
-    WorkerDone(ExecutionResult, HashMap<FileUuid, FileStoreKey>),
+    /// The worker completed the job with this result, and these results of the concurrent
+    /// executions in the same order, producing those files. The actual files will be sent
+    /// immediately after using `ProvideFile` messages.
+    WorkerDone(
+        ExecutionResult,
+        Vec<ExecutionResult>,
+        HashMap<FileUuid, FileStoreKey>,
+    ),
     /// The worker is sending a file to the server. After this message there is a protocol switch
-    /// for the file transmission.
-    ProvideFile(FileUuid, FileStoreKey),
+    /// for the file transmission. The last entry is true if the chunks of the file are compressed.
+    ProvideFile(FileUuid, FileStoreKey, bool),
     /// The worker needs a file from the server. The server should send back that file in order to
     /// run the execution on the worker.
     AskFile(FileStoreKey),
+    /// The worker has received only the first bytes of the file before the connection dropped.
+    /// The server should answer like for `AskFile`, but sending the content of the file starting
+    /// from the specified offset.
+    ResumeFile(FileStoreKey, u64),
+    /// The answer to the [`Challenge`](enum.WorkerServerMessage.html#variant.Challenge) of the
+    /// server: the MAC of the nonce, if the worker has a token, and whether the worker wants the
+    /// files sent by the server to be compressed.
+    Authenticate(Option<Vec<u8>>, bool),
 }
 
 /// Messages sent by the server to the worker.
@@ -111,10 +190,19 @@ pub enum WorkerServerMessage {
     /// The job the worker should do. Boxed to reduce the enum size.
     Work(Box<WorkerJob>),
     /// The file the workers as asked. After this message there is a protocol switch for the file
-    /// transmission.
-    ProvideFile(FileStoreKey),
+    /// transmission. The second entry is true if the chunks of the file are compressed.
+    ProvideFile(FileStoreKey, bool),
+    /// Ask the worker to kill the job it's doing, which will be reported as an internal error. The
+    /// worker will ask for more work as usual.
+    KillJob,
     /// Ask the worker to exit.
     Exit,
+    /// The worker is not accepted by the server, the reason is attached. The connection will be
+    /// closed.
+    Rejected(String),
+    /// The answer to the `Hello` of the worker, with the nonce it has to authenticate with. The
+    /// worker will ask for work only after the authentication.
+    Challenge(Vec<u8>),
 }
 
 /// Messages sent during the FileProtocol operation, during the transfer of a file.
@@ -132,13 +220,27 @@ pub enum FileProtocol {
 /// An iterator over the byte chunks sent during the FileProtocol mode in a channel.
 pub struct ChannelFileIterator<'a> {
     /// Reference to the channel from where to read
-    reader: &'a ChannelReceiver,
+    reader: &'a dyn TransportReceiver,
+    /// Whether the chunks are compressed and have to be decompressed.
+    compressed: bool,
 }
 
 impl<'a> ChannelFileIterator<'a> {
     /// Create a new iterator over a receiver channel.
-    pub fn new(reader: &'a ChannelReceiver) -> ChannelFileIterator<'a> {
-        ChannelFileIterator { reader }
+    pub fn new(reader: &'a dyn TransportReceiver) -> ChannelFileIterator<'a> {
+        ChannelFileIterator {
+            reader,
+            compressed: false,
+        }
+    }
+
+    /// Create a new iterator over a receiver channel, decompressing the chunks if they have been
+    /// sent compressed.
+    pub fn with_compression(
+        reader: &'a dyn TransportReceiver,
+        compressed: bool,
+    ) -> ChannelFileIterator<'a> {
+        ChannelFileIterator { reader, compressed }
     }
 }
 
@@ -147,6 +249,15 @@ impl<'a> Iterator for ChannelFileIterator<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         // errors cannot be handled in this iterator yet
         match deserialize_from::<FileProtocol>(self.reader).expect("deserialize error") {
+            FileProtocol::Data(d) if self.compressed => match compression::decompress(&d) {
+                Ok(d) => Some(d),
+                Err(e) => {
+                    // the chunk is passed on as is, the content of the file won't match its key
+                    // and the transfer is treated like any other corrupted one
+                    warn!("Failed to decompress a chunk: {}", e);
+                    Some(d)
+                }
+            },
             FileProtocol::Data(d) => Some(d),
             FileProtocol::End => None,
         }
@@ -158,7 +269,7 @@ pub struct ChannelFileSender;
 
 impl ChannelFileSender {
     /// Send a local file to a channel using [`FileProtocol`](enum.FileProtocol.html).
-    pub fn send<P: AsRef<Path>>(path: P, sender: &ChannelSender) -> Result<(), Error> {
+    pub fn send<P: AsRef<Path>>(path: P, sender: &dyn TransportSender) -> Result<(), Error> {
         for buf in ReadFileIterator::new(path.as_ref())? {
             serialize_into(&FileProtocol::Data(buf), sender)?;
         }
@@ -166,12 +277,128 @@ impl ChannelFileSender {
         Ok(())
     }
 
+    /// Send a local file to a channel using [`FileProtocol`](enum.FileProtocol.html), skipping its
+    /// first `offset` bytes, which the receiver already has.
+    pub fn send_from<P: AsRef<Path>>(
+        path: P,
+        offset: u64,
+        sender: &dyn TransportSender,
+    ) -> Result<(), Error> {
+        for buf in ReadFileIterator::with_offset(path.as_ref(), offset)? {
+            serialize_into(&FileProtocol::Data(buf), sender)?;
+        }
+        serialize_into(&FileProtocol::End, sender)?;
+        Ok(())
+    }
+
     /// Send a file's data to a channel using [`FileProtocol`](enum.FileProtocol.html).
-    pub fn send_data(data: Vec<u8>, sender: &ChannelSender) -> Result<(), Error> {
-        serialize_into(&FileProtocol::Data(data), sender)?;
+    pub fn send_data(data: Vec<u8>, sender: &dyn TransportSender) -> Result<(), Error> {
+        if data.len() <= DATA_CHUNK_SIZE {
+            serialize_into(&FileProtocol::Data(data), sender)?;
+        } else {
+            for chunk in data.chunks(DATA_CHUNK_SIZE) {
+                serialize_into(&FileProtocol::Data(chunk.to_vec()), sender)?;
+            }
+        }
         serialize_into(&FileProtocol::End, sender)?;
         Ok(())
     }
+
+    /// Send a file's data to a channel like [`send_data`](#method.send_data), compressing its
+    /// chunks.
+    pub fn send_data_compressed(data: &[u8], sender: &dyn TransportSender) -> Result<(), Error> {
+        for chunk in data.chunks(DATA_CHUNK_SIZE) {
+            serialize_into(&FileProtocol::Data(compression::compress(chunk)), sender)?;
+        }
+        serialize_into(&FileProtocol::End, sender)?;
+        Ok(())
+    }
+
+    /// Send a local file to a channel like [`send`](#method.send), compressing its chunks.
+    pub fn send_compressed<P: AsRef<Path>>(
+        path: P,
+        sender: &dyn TransportSender,
+    ) -> Result<(), Error> {
+        ChannelFileSender::send_compressed_from(path, 0, sender)
+    }
+
+    /// Send a local file to a channel like [`send_from`](#method.send_from), compressing its
+    /// chunks.
+    pub fn send_compressed_from<P: AsRef<Path>>(
+        path: P,
+        offset: u64,
+        sender: &dyn TransportSender,
+    ) -> Result<(), Error> {
+        for buf in ReadFileIterator::with_offset(path.as_ref(), offset)? {
+            serialize_into(&FileProtocol::Data(compression::compress(&buf)), sender)?;
+        }
+        serialize_into(&FileProtocol::End, sender)?;
+        Ok(())
+    }
+}
+
+/// Receiver of files sent with [`FileProtocol`](enum.FileProtocol.html) that keeps the data of the
+/// interrupted transfers on disk, so that they can be resumed from where they stopped.
+pub struct PartialFileReceiver {
+    /// The directory where the partially received files are stored.
+    dir: PathBuf,
+}
+
+impl PartialFileReceiver {
+    /// Make a new receiver storing the partial files inside the specified directory.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<PartialFileReceiver, Error> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(PartialFileReceiver { dir })
+    }
+
+    /// The number of bytes of the file already received, i.e. the offset from which its transfer
+    /// should be resumed.
+    pub fn offset(&self, key: &FileStoreKey) -> u64 {
+        std::fs::metadata(self.path(key))
+            .map(|m| m.len())
+            .unwrap_or(0)
+    }
+
+    /// Receive the data of the file from the channel, appending it to the data already received
+    /// and decompressing the chunks if `compressed` is true. When the transfer completes the path
+    /// of the full file is returned: it should be moved away, or [`discard`](#method.discard)ed
+    /// after use. If the transfer is interrupted an error is returned and the data received so far
+    /// is kept.
+    pub fn receive(
+        &self,
+        key: &FileStoreKey,
+        reader: &dyn TransportReceiver,
+        compressed: bool,
+    ) -> Result<PathBuf, Error> {
+        let path = self.path(key);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        while let FileProtocol::Data(data) = deserialize_from::<FileProtocol>(reader)? {
+            if compressed {
+                file.write_all(&compression::decompress(&data)?)?;
+            } else {
+                file.write_all(&data)?;
+            }
+        }
+        Ok(path)
+    }
+
+    /// Forget the data received for the file.
+    pub fn discard(&self, key: &FileStoreKey) -> Result<(), Error> {
+        let path = self.path(key);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// The path where the data of the file is stored.
+    fn path(&self, key: &FileStoreKey) -> PathBuf {
+        self.dir.join(format!("{}.partial", key.to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -198,4 +425,65 @@ mod tests {
         let data: Vec<u8> = receiver.flat_map(|d| d.into_iter()).collect();
         assert_eq!(String::from_utf8(data).unwrap(), "hello world");
     }
+
+    #[test]
+    fn test_send_compressed_file() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let content = "hello world ".repeat(10_000);
+        std::fs::write(tmpdir.path().join("file.txt"), &content).unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        ChannelFileSender::send_compressed(tmpdir.path().join("file.txt"), &sender).unwrap();
+        let sent: usize = receiver.try_iter().map(|m| m.len()).sum();
+        assert!(sent < content.len() / 10);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let iterator = ChannelFileIterator::with_compression(&receiver, true);
+        ChannelFileSender::send_compressed(tmpdir.path().join("file.txt"), &sender).unwrap();
+        let data: Vec<u8> = iterator.flat_map(|d| d.into_iter()).collect();
+        assert_eq!(String::from_utf8(data).unwrap(), content);
+    }
+
+    #[test]
+    fn test_send_file_from_offset() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        std::fs::write(tmpdir.path().join("file.txt"), "hello world").unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let receiver = ChannelFileIterator::new(&receiver);
+        ChannelFileSender::send_from(tmpdir.path().join("file.txt"), 6, &sender).unwrap();
+        let data: Vec<u8> = receiver.flat_map(|d| d.into_iter()).collect();
+        assert_eq!(String::from_utf8(data).unwrap(), "world");
+    }
+
+    #[test]
+    fn test_resume_interrupted_transfer() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let path = tmpdir.path().join("file.bin");
+        std::fs::write(&path, &content).unwrap();
+        let key = FileStoreKey::from_content(&content);
+        let partial = PartialFileReceiver::new(tmpdir.path().join("partial")).unwrap();
+        assert_eq!(partial.offset(&key), 0);
+
+        // the connection drops after the first chunks
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for chunk in ReadFileIterator::new(&path).unwrap().take(3) {
+            serialize_into(&FileProtocol::Data(chunk), &sender).unwrap();
+        }
+        drop(sender);
+        assert!(partial.receive(&key, &receiver, false).is_err());
+        let offset = partial.offset(&key);
+        assert!(offset > 0 && offset < content.len() as u64);
+
+        // after reconnecting only the rest of the file is sent
+        let (sender, receiver) = std::sync::mpsc::channel();
+        ChannelFileSender::send_compressed_from(&path, offset, &sender).unwrap();
+        let received = partial.receive(&key, &receiver, true).unwrap();
+        assert_eq!(std::fs::read(&received).unwrap(), content);
+        assert_eq!(FileStoreKey::from_file(&received).unwrap(), key);
+
+        partial.discard(&key).unwrap();
+        assert_eq!(partial.offset(&key), 0);
+    }
 }
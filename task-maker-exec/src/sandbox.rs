@@ -1,11 +1,13 @@
-use failure::Error;
+use failure::{bail, format_err, Error};
 use itertools::Itertools;
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::ffi::OsString;
+use std::ffi::{CString, OsString};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use task_maker_dag::*;
 use task_maker_store::*;
@@ -23,6 +25,59 @@ const READABLE_DIRS: &[&str] = &[
     "/var/lib/dpkg/alternatives/",
 ];
 
+/// The environment variable that selects the sandbox backend, see
+/// [`SandboxBackend::from_env`](enum.SandboxBackend.html#method.from_env).
+pub const SANDBOX_BACKEND_ENV: &str = "TM_SANDBOX";
+
+/// The implementation of the sandbox used for running the executions. All the backends take the
+/// command line arguments of `tmbox` and print its JSON outcome.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum SandboxBackend {
+    /// The `tmbox` built with task-maker, or the one in the `PATH` if it has not been built.
+    #[default]
+    Tmbox,
+    /// A program compatible with `tmbox` at the specified path, for example a non-hermetic
+    /// backend for the development.
+    Custom(PathBuf),
+}
+
+impl SandboxBackend {
+    /// The backend selected by the `TM_SANDBOX` environment variable: `tmbox` (or an empty value)
+    /// selects `tmbox`, any other value is the path of a custom backend. If the variable is not set
+    /// `tmbox` is used.
+    pub fn from_env() -> SandboxBackend {
+        match std::env::var_os(SANDBOX_BACKEND_ENV) {
+            Some(value) => SandboxBackend::from(PathBuf::from(value)),
+            None => SandboxBackend::Tmbox,
+        }
+    }
+
+    /// The path of the program to run.
+    fn program(&self) -> PathBuf {
+        match self {
+            SandboxBackend::Tmbox => {
+                let tmbox_path = Path::new(env!("OUT_DIR")).join("bin").join("tmbox");
+                if tmbox_path.exists() {
+                    tmbox_path
+                } else {
+                    "tmbox".into()
+                }
+            }
+            SandboxBackend::Custom(path) => path.clone(),
+        }
+    }
+}
+
+impl From<PathBuf> for SandboxBackend {
+    fn from(path: PathBuf) -> Self {
+        if path.as_os_str().is_empty() || path.as_os_str() == "tmbox" {
+            SandboxBackend::Tmbox
+        } else {
+            SandboxBackend::Custom(path)
+        }
+    }
+}
+
 /// Result of the execution of the sandbox.
 #[derive(Debug)]
 pub enum SandboxResult {
@@ -36,6 +91,9 @@ pub enum SandboxResult {
         resources: ExecutionResourcesUsage,
         /// Whether the sandbox killed the process.
         was_killed: bool,
+        /// Whether the standard output or the standard error have been truncated because they
+        /// exceeded the limit on their size.
+        truncated_output: bool,
     },
     /// The sandbox failed to execute the process, an error message is reported. Note that this
     /// represents a sandbox error, not the process failure.
@@ -43,6 +101,112 @@ pub enum SandboxResult {
         /// The error reported by the sandbox.
         error: String,
     },
+    /// The sandbox has been killed with [`kill`](struct.Sandbox.html#method.kill) before the
+    /// process exited.
+    Killed,
+}
+
+/// The symbolic name of a signal, like `SIGSEGV`, or `None` if the signal is not known.
+///
+/// ```
+/// use task_maker_exec::signal_name;
+///
+/// assert_eq!(signal_name(11), Some("SIGSEGV"));
+/// assert_eq!(signal_name(1000), None);
+/// ```
+pub fn signal_name(signal: u32) -> Option<&'static str> {
+    let name = match signal as libc::c_int {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGCHLD => "SIGCHLD",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGSTOP => "SIGSTOP",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        libc::SIGURG => "SIGURG",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGVTALRM => "SIGVTALRM",
+        libc::SIGPROF => "SIGPROF",
+        libc::SIGWINCH => "SIGWINCH",
+        libc::SIGIO => "SIGIO",
+        libc::SIGSYS => "SIGSYS",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Check that the sandboxes can be created inside `path`, creating it if missing. The directory
+/// must be writable, support the Unix permissions and allow running the executables, since the
+/// sandbox directories are set up with the most restrictive permissions possible.
+///
+/// The sandboxes can live on a different filesystem than the store, for example on a tmpfs.
+pub fn check_sandbox_path(path: &Path) -> Result<(), Error> {
+    let unusable = |reason: String| {
+        format_err!(
+            "The sandbox directory {} is not usable: {}",
+            path.display(),
+            reason
+        )
+    };
+    std::fs::create_dir_all(path).map_err(|e| unusable(format!("cannot create it: {}", e)))?;
+    let check_dir = TempDir::new_in(path, "check")
+        .map_err(|e| unusable(format!("cannot write inside it: {}", e)))?;
+    let file = check_dir.path().join("exe");
+    std::fs::write(&file, "")
+        .and_then(|_| {
+            let mut permissions = std::fs::metadata(&file)?.permissions();
+            permissions.set_mode(0o500);
+            std::fs::set_permissions(&file, permissions)
+        })
+        .map_err(|e| unusable(format!("cannot set the file permissions: {}", e)))?;
+    let mode = std::fs::metadata(&file)?.permissions().mode() & 0o777;
+    if mode != 0o500 {
+        return Err(unusable(format!(
+            "the file permissions are not supported (0o500 became 0o{:o})",
+            mode
+        )));
+    }
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(unusable(format!(
+            "cannot get the filesystem information: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    if stat.f_flag & libc::ST_NOEXEC != 0 {
+        return Err(unusable(
+            "the filesystem is mounted noexec, the executables cannot be run".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Append to the error message of a failed sandbox the diagnostic it printed to its standard error,
+/// if any.
+fn with_diagnostic(error: String, stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let stderr = stderr.trim();
+    if stderr.is_empty() {
+        error
+    } else {
+        format!("{}\nSandbox stderr: {}", error, stderr)
+    }
 }
 
 /// Internals of the sandbox.
@@ -53,6 +217,10 @@ struct SandboxData {
     boxdir: Option<TempDir>,
     /// Whether to keep the sandbox after exit.
     keep_sandbox: bool,
+    /// The PID of the sandbox process, if it's running.
+    pid: Option<u32>,
+    /// Whether the sandbox has been killed, it won't be started if it's not already running.
+    killed: bool,
 }
 
 /// Wrapper around the sandbox. Cloning this struct will keep the reference of the same sandbox,
@@ -66,6 +234,10 @@ pub struct Sandbox {
     data: Arc<Mutex<SandboxData>>,
     /// Execution to run.
     execution: Execution,
+    /// The implementation of the sandbox to use.
+    backend: SandboxBackend,
+    /// The directory with the fifos used as standard input and output, if any.
+    fifo_dir: Option<PathBuf>,
 }
 
 /// The outcome from `tmbox`. If the sandbox fails to run only `error` and `message` are set,
@@ -90,6 +262,8 @@ struct TMBoxResult {
     signal: Option<u32>,
     /// Whether the sandbox killed the process.
     killed_by_sandbox: Option<bool>,
+    /// Number of processes and threads spawned, if the sandbox is able to count them.
+    processes: Option<u32>,
 }
 
 impl Sandbox {
@@ -107,44 +281,140 @@ impl Sandbox {
             data: Arc::new(Mutex::new(SandboxData {
                 boxdir: Some(boxdir),
                 keep_sandbox: false,
+                pid: None,
+                killed: false,
             })),
             execution: execution.clone(),
+            backend: SandboxBackend::default(),
+            fifo_dir: None,
         })
     }
 
+    /// Set the implementation of the sandbox to use for running the execution.
+    pub fn backend(&mut self, backend: SandboxBackend) -> &mut Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Set the directory with the fifos the execution uses as standard input and output. It's
+    /// usually the directory of the sandbox that created them.
+    pub fn fifo_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.fifo_dir = Some(dir.into());
+        self
+    }
+
     /// Starts the sandbox and blocks the thread until the sandbox exits.
     pub fn run(&self) -> Result<SandboxResult, Error> {
         let boxdir = self.data.lock().unwrap().path().to_owned();
         trace!("Running sandbox at {:?}", boxdir);
-        let tmbox_path = Path::new(env!("OUT_DIR")).join("bin").join("tmbox");
-        let tmbox_path = if tmbox_path.exists() {
-            tmbox_path
-        } else {
-            "tmbox".into()
-        };
-        let mut sandbox = Command::new(tmbox_path);
+        let program = self.backend.program();
+        let mut sandbox = Command::new(&program);
         let command = match self.build_command(&boxdir) {
             Ok(cmd) => cmd,
             Err(e) => return Ok(SandboxResult::Failed { error: e }),
         };
-        sandbox.args(command);
-        trace!("Sandbox command: {:?}", sandbox);
-        let res = sandbox.output()?;
+        debug!(
+            "Sandbox command: {} {}",
+            program.display(),
+            redacted_command(&command)
+        );
+        sandbox
+            .args(command)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // in its own process group, so that its children can be killed along with it
+            .process_group(0);
+        let child = {
+            let mut data = self.data.lock().unwrap();
+            if data.killed {
+                return Ok(SandboxResult::Killed);
+            }
+            let child = sandbox.spawn()?;
+            data.pid = Some(child.id());
+            child
+        };
+        let res = child.wait_with_output();
+        {
+            let mut data = self.data.lock().unwrap();
+            data.pid = None;
+            if data.killed {
+                return Ok(SandboxResult::Killed);
+            }
+        }
+        let res = res?;
         trace!("Sandbox output: {:?}", res);
-        let outcome = serde_json::from_str::<TMBoxResult>(std::str::from_utf8(&res.stdout)?)?;
+        let outcome = match serde_json::from_slice::<TMBoxResult>(&res.stdout) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                let error = format!(
+                    "The sandbox exited ({}) without a valid outcome: {}\nSandbox stdout: {}",
+                    res.status,
+                    e,
+                    String::from_utf8_lossy(&res.stdout).trim()
+                );
+                return Ok(SandboxResult::Failed {
+                    error: with_diagnostic(error, &res.stderr),
+                });
+            }
+        };
+        let mut result = Sandbox::parse_outcome(outcome);
+        match &mut result {
+            SandboxResult::Success {
+                truncated_output, ..
+            } => {
+                *truncated_output = self.truncate_stdio()?;
+            }
+            SandboxResult::Failed { error } => {
+                *error = with_diagnostic(std::mem::take(error), &res.stderr);
+            }
+            SandboxResult::Killed => {}
+        }
+        Ok(result)
+    }
+
+    /// Truncate the standard output and the standard error of the process to the limit on their
+    /// size, returning whether at least one of them was truncated.
+    fn truncate_stdio(&self) -> Result<bool, Error> {
+        let limit = match self.execution.limits.stdio_size {
+            Some(limit) => limit,
+            None => return Ok(false),
+        };
+        let mut paths = vec![];
+        if self.execution.stdout.is_some() {
+            paths.push(self.stdout_path());
+        }
+        if self.execution.stderr.is_some() {
+            paths.push(self.stderr_path());
+        }
+        let mut truncated = false;
+        for path in paths {
+            if std::fs::metadata(&path)?.len() > limit {
+                warn!("Truncating {:?} to {} bytes", path, limit);
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(limit)?;
+                truncated = true;
+            }
+        }
+        Ok(truncated)
+    }
+
+    /// Convert the outcome reported by tmbox into the result of the sandbox.
+    fn parse_outcome(outcome: TMBoxResult) -> SandboxResult {
         if outcome.error {
-            Ok(SandboxResult::Failed {
+            SandboxResult::Failed {
                 error: outcome
                     .message
                     .unwrap_or_else(|| "No output from sandbox".into()),
-            })
+            }
         } else {
             let signal = if outcome.signal.unwrap() == 0 {
                 None
             } else {
                 Some(outcome.signal.unwrap())
             };
-            Ok(SandboxResult::Success {
+            SandboxResult::Success {
                 exit_status: outcome.status_code.unwrap(),
                 signal,
                 resources: ExecutionResourcesUsage {
@@ -152,57 +422,94 @@ impl Sandbox {
                     sys_time: outcome.sys_time.unwrap(),
                     wall_time: outcome.wall_time.unwrap(),
                     memory: outcome.memory_usage.unwrap(),
+                    processes: outcome.processes,
+                    setup_time: 0.0,
                 },
                 was_killed: outcome.killed_by_sandbox.unwrap(),
-            })
+                truncated_output: false,
+            }
         }
     }
 
-    /// Tell the sandbox process to kill the underlying process, this will make `run` terminate more
-    /// quickly.
+    /// Kill the sandbox process and all the processes of its group with `SIGKILL`, making `run`
+    /// return `SandboxResult::Killed`. If the sandbox is not running yet it won't be started.
     pub fn kill(&self) {
-        info!(
-            "Sandbox at {:?} got killed",
-            self.data.lock().unwrap().path()
-        );
-        unimplemented!();
+        let mut data = self.data.lock().unwrap();
+        info!("Sandbox at {:?} got killed", data.path());
+        data.killed = true;
+        if let Some(pid) = data.pid {
+            if unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+                warn!(
+                    "Cannot kill the sandbox process {}: {}",
+                    pid,
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
     }
 
     /// Make the sandbox persistent, the sandbox directory won't be deleted after the execution.
     pub fn keep(&mut self) {
-        let mut data = self.data.lock().unwrap();
-        let path = data
-            .boxdir
-            .as_ref()
-            .expect("Box dir has gone?!?")
-            .path()
-            .to_owned();
+        let path = {
+            let mut data = self.data.lock().unwrap();
+            data.keep_sandbox = true;
+            data.path().to_owned()
+        };
         debug!("Keeping sandbox at {:?}", path);
-        data.keep_sandbox = true;
-        let serialized =
-            serde_json::to_string_pretty(&self.execution).expect("Cannot serialize execution");
-        std::fs::write(path.join("info.json"), serialized)
-            .expect("Cannot write execution info inside sandbox");
+        self.dump_reproducer(&path)
+            .expect("Cannot write the reproduction files inside sandbox");
+    }
+
+    /// Write inside `dir` the files for reproducing the execution: `info.json` with the serialized
+    /// `Execution` and `command.txt` with the command line of `tmbox`. The sandbox is not made
+    /// persistent: unless [`keep`](#method.keep) is called its directory, used by the command, is
+    /// deleted after the execution.
+    pub fn dump_reproducer(&self, dir: &Path) -> Result<(), Error> {
+        let path = self.data.lock().unwrap().path().to_owned();
+        std::fs::create_dir_all(dir)?;
+        let serialized = serde_json::to_string_pretty(&self.execution)?;
+        std::fs::write(dir.join("info.json"), serialized)?;
         if let Ok(command) = self.build_command(&path) {
             let command = command.into_iter().map(|s| format!("{:?}", s)).join(" ");
-            std::fs::write(path.join("command.txt"), format!("tmbox {}\n", command))
-                .expect("Cannot write command info inside sandbox");
+            std::fs::write(dir.join("command.txt"), format!("tmbox {}\n", command))?;
         }
+        Ok(())
     }
 
     /// Path of the file where the standard output is written to.
     pub fn stdout_path(&self) -> PathBuf {
-        self.data.lock().unwrap().path().join("stdout")
+        self.data
+            .lock()
+            .unwrap()
+            .path()
+            .join(&self.execution.stdio_names.stdout)
     }
 
     /// Path of the file where the standard error is written to.
     pub fn stderr_path(&self) -> PathBuf {
-        self.data.lock().unwrap().path().join("stderr")
+        self.data
+            .lock()
+            .unwrap()
+            .path()
+            .join(&self.execution.stdio_names.stderr)
+    }
+
+    /// Path of the directory where the process runs.
+    pub fn box_path(&self) -> PathBuf {
+        self.data.lock().unwrap().path().join("box")
     }
 
     /// Path of the file where that output file is written to.
     pub fn output_path(&self, output: &Path) -> PathBuf {
-        self.data.lock().unwrap().path().join("box").join(output)
+        self.box_path().join(output)
+    }
+
+    /// The path of a fifo used as standard input or output.
+    fn fifo_path(&self, fifo: &Path) -> Result<PathBuf, String> {
+        match &self.fifo_dir {
+            Some(dir) => Ok(dir.join(fifo)),
+            None => Err(format!("The directory of the fifo {:?} is unknown", fifo)),
+        }
     }
 
     /// Build the command line arguments of `tmbox`.
@@ -213,23 +520,29 @@ impl Sandbox {
         args.push("--json".into());
         args.push("--env".into());
         args.push("PATH".into());
-        if self.execution.stdin.is_some() {
+        if let Some(fifo) = &self.execution.stdin_fifo {
             args.push("--stdin".into());
-            args.push(boxdir.join("stdin").into());
+            args.push(self.fifo_path(fifo)?.into());
+        } else if self.execution.stdin.is_some() {
+            args.push("--stdin".into());
+            args.push(boxdir.join(&self.execution.stdio_names.stdin).into());
         } else {
             args.push("--stdin".into());
             args.push("/dev/null".into());
         }
-        if self.execution.stdout.is_some() {
+        if let Some(fifo) = &self.execution.stdout_fifo {
             args.push("--stdout".into());
-            args.push(boxdir.join("stdout").into());
+            args.push(self.fifo_path(fifo)?.into());
+        } else if self.execution.stdout.is_some() {
+            args.push("--stdout".into());
+            args.push(boxdir.join(&self.execution.stdio_names.stdout).into());
         } else {
             args.push("--stdout".into());
             args.push("/dev/null".into());
         }
         if self.execution.stderr.is_some() {
             args.push("--stderr".into());
-            args.push(boxdir.join("stderr").into());
+            args.push(boxdir.join(&self.execution.stdio_names.stderr).into());
         } else {
             args.push("--stderr".into());
             args.push("/dev/null".into());
@@ -249,18 +562,38 @@ impl Sandbox {
             (None, None) => None,
         };
         if let Some(cpu) = cpu_limit {
-            let cpu = cpu + self.execution.config().extra_time;
+            let cpu = cpu + self.execution.effective_extra_time();
             args.push("--time".into());
             args.push(cpu.to_string().into());
         }
-        if let Some(wall) = self.execution.limits.wall_time {
-            let wall = wall + self.execution.config().extra_time;
+        if let Some(wall) = self.execution.effective_wall_time() {
+            let wall = wall + self.execution.effective_extra_time();
             args.push("--wall".into());
             args.push(wall.to_string().into());
         }
-        if let Some(mem) = self.execution.limits.memory {
-            args.push("--memory".into());
-            args.push(mem.to_string().into());
+        // the memory limit is given to the program itself if it knows how to respect it
+        let memory = self
+            .execution
+            .limits
+            .memory
+            .map(|mem| mem + self.execution.effective_extra_memory());
+        let memory_arg = match (memory, &self.execution.limits.memory_arg) {
+            (Some(mem), Some(arg)) => Some(arg.replace("{}", &mem.to_string())),
+            (Some(mem), None) => {
+                args.push("--memory".into());
+                args.push(mem.to_string().into());
+                None
+            }
+            (None, _) => None,
+        };
+        // 0 means unlimited for tmbox too
+        if let Some(stack) = self.execution.limits.stack {
+            args.push("--stack".into());
+            args.push(stack.to_string().into());
+        }
+        if let Some(nofile) = self.execution.limits.nofile {
+            args.push("--nofile".into());
+            args.push(nofile.to_string().into());
         }
         if let Some(1) = self.execution.limits.nproc {
             // default is not multi process
@@ -274,6 +607,24 @@ impl Sandbox {
                 args.push(dir.into());
             }
         }
+        // the missing directories are ignored only if the limits are not strict, otherwise the
+        // sandbox fails without starting the process
+        if self.execution.limits.strict_readable_dirs {
+            let missing: Vec<_> = self
+                .execution
+                .limits
+                .extra_readable_dirs
+                .iter()
+                .filter(|dir| !dir.is_dir())
+                .map(|dir| dir.to_string_lossy())
+                .collect();
+            if !missing.is_empty() {
+                return Err(format!(
+                    "The readable directories do not exist: {}",
+                    missing.join(", ")
+                ));
+            }
+        }
         for dir in &self.execution.limits.extra_readable_dirs {
             if dir.is_dir() {
                 args.push("--readable-dir".into());
@@ -282,6 +633,17 @@ impl Sandbox {
         }
         if self.execution.limits.mount_tmpfs {
             args.push("--mount-tmpfs".into());
+            if let Some(size) = self.execution.limits.tmpfs_size {
+                args.push("--mount-tmpfs-size".into());
+                args.push(size.to_string().into());
+            }
+        }
+        if self.execution.limits.allow_network {
+            args.push("--allow-network".into());
+        }
+        if let Some(profile) = self.execution.limits.seccomp {
+            args.push("--seccomp".into());
+            args.push(profile.name().into());
         }
         args.push("--".into());
         match &self.execution.command {
@@ -294,6 +656,9 @@ impl Sandbox {
             }
             ExecutionCommand::Local(cmd) => args.push(cmd.into()),
         };
+        if let Some(memory_arg) = memory_arg {
+            args.push(memory_arg.into());
+        }
         for arg in self.execution.args.iter() {
             args.push(arg.into());
         }
@@ -314,16 +679,16 @@ impl Sandbox {
         std::fs::create_dir_all(box_dir.as_ref().join("box"))?;
         if let Some(stdin) = execution.stdin {
             Sandbox::write_sandbox_file(
-                &box_dir.as_ref().join("stdin"),
+                &box_dir.as_ref().join(&execution.stdio_names.stdin),
                 dep_keys.get(&stdin).expect("stdin not provided").path(),
                 false,
             )?;
         }
         if execution.stdout.is_some() {
-            Sandbox::touch_file(&box_dir.as_ref().join("stdout"), 0o600)?;
+            Sandbox::touch_file(&box_dir.as_ref().join(&execution.stdio_names.stdout), 0o600)?;
         }
         if execution.stderr.is_some() {
-            Sandbox::touch_file(&box_dir.as_ref().join("stderr"), 0o600)?;
+            Sandbox::touch_file(&box_dir.as_ref().join(&execution.stdio_names.stderr), 0o600)?;
         }
         for (path, input) in execution.inputs.iter() {
             Sandbox::write_sandbox_file(
@@ -335,6 +700,9 @@ impl Sandbox {
         for path in execution.outputs.keys() {
             Sandbox::touch_file(&box_dir.as_ref().join("box").join(&path), 0o600)?;
         }
+        for path in execution.fifos.iter() {
+            Sandbox::make_fifo(&box_dir.as_ref().join("box").join(&path))?;
+        }
         // remove the write bit on the box folder
         if execution.limits.read_only {
             Sandbox::set_permissions(&box_dir.as_ref().join("box"), 0o500)?;
@@ -370,6 +738,21 @@ impl Sandbox {
         Ok(())
     }
 
+    /// Create a fifo inside the sandbox. Its permissions do not depend on the `read_only` limit
+    /// since writing to a fifo does not change the content of the sandbox.
+    fn make_fifo(dest: &Path) -> Result<(), Error> {
+        std::fs::create_dir_all(dest.parent().expect("Invalid fifo path"))?;
+        let c_path = CString::new(dest.as_os_str().as_bytes())?;
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            bail!(
+                "Cannot create the fifo {}: {}",
+                dest.display(),
+                std::io::Error::last_os_error()
+            );
+        }
+        Sandbox::set_permissions(dest, 0o600)
+    }
+
     fn set_permissions(dest: &Path, perm: u32) -> Result<(), Error> {
         let mut permissions = std::fs::metadata(&dest)?.permissions();
         permissions.set_mode(perm);
@@ -398,13 +781,37 @@ impl Drop for SandboxData {
     }
 }
 
+/// Format the arguments of `tmbox` for the logs, replacing the values of the environment variables
+/// with a placeholder since they may contain secrets. The names of the variables are kept.
+fn redacted_command(args: &[OsString]) -> String {
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut after_env = false;
+    for arg in args {
+        let arg = arg.to_string_lossy();
+        match arg.find('=') {
+            Some(pos) if after_env => {
+                redacted.push(format!("{:?}", format!("{}=<redacted>", &arg[..pos])))
+            }
+            _ => redacted.push(format!("{:?}", arg)),
+        }
+        after_env = arg == "--env";
+    }
+    redacted.join(" ")
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Sandbox;
+    use crate::sandbox::{check_sandbox_path, redacted_command, TMBoxResult};
+    use crate::{Sandbox, SandboxBackend, SandboxResult};
     use itertools::Itertools;
     use std::collections::HashMap;
-    use std::path::Path;
-    use task_maker_dag::{Execution, ExecutionCommand};
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+    use task_maker_dag::{
+        Execution, ExecutionCommand, ExecutionStatus, ExecutionStdioNames, File, SeccompProfile,
+    };
+    use task_maker_store::{FileStore, FileStoreKey};
 
     fn assert_contains(source: &[String], check: &[&str]) {
         for i in 0..source.len() {
@@ -439,6 +846,274 @@ mod tests {
         assert!(!outfile.parent().unwrap().parent().unwrap().exists()); // the sandbox dir
     }
 
+    #[test]
+    fn test_custom_backend() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let backend = tmpdir.path().join("fake-tmbox");
+        std::fs::write(
+            &backend,
+            "#!/bin/sh\necho '{\"error\":false,\"cpu_time\":0.5,\"sys_time\":0.0,\
+             \"wall_time\":1.0,\"memory_usage\":100,\"status_code\":42,\"signal\":0,\
+             \"killed_by_sandbox\":false}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&backend, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let exec = Execution::new("test", ExecutionCommand::system("true"));
+        let mut sandbox =
+            Sandbox::new(&tmpdir.path().join("sandboxes"), &exec, &HashMap::new()).unwrap();
+        sandbox.backend(SandboxBackend::Custom(backend));
+        match sandbox.run().unwrap() {
+            SandboxResult::Success {
+                exit_status,
+                resources,
+                ..
+            } => {
+                assert_eq!(exit_status, 42);
+                assert_eq!(resources.memory, 100);
+            }
+            SandboxResult::Failed { error } => panic!("Sandbox failed: {}", error),
+            SandboxResult::Killed => panic!("Sandbox killed"),
+        }
+    }
+
+    #[test]
+    fn test_strict_readable_dirs() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let missing = tmpdir.path().join("missing");
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.limits_mut()
+            .add_extra_readable_dir(tmpdir.path())
+            .add_extra_readable_dir(&missing);
+        // the sandbox is created, but it fails without running the process
+        let sandbox = Sandbox::new(&tmpdir.path().join("boxes"), &exec, &HashMap::new()).unwrap();
+        let err = match sandbox.run().unwrap() {
+            SandboxResult::Failed { error } => error,
+            _ => panic!("The sandbox started with a missing readable dir"),
+        };
+        // only the missing directory is listed
+        assert_eq!(
+            err,
+            format!(
+                "The readable directories do not exist: {}",
+                missing.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_best_effort_readable_dirs() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let missing = tmpdir.path().join("missing");
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.limits_mut()
+            .add_extra_readable_dir(tmpdir.path())
+            .add_extra_readable_dir(&missing)
+            .strict_readable_dirs(false);
+        let sandbox = Sandbox::new(&tmpdir.path().join("boxes"), &exec, &HashMap::new()).unwrap();
+        let args = sandbox
+            .build_command(tmpdir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        assert_contains(&args, &["--readable-dir", &tmpdir.path().to_string_lossy()]);
+        assert!(!args.contains(&missing.to_string_lossy().to_string()));
+    }
+
+    #[test]
+    fn test_check_sandbox_path() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("sandboxes");
+        check_sandbox_path(&path).unwrap();
+        assert!(path.is_dir());
+        // the directory used for the check is removed
+        assert_eq!(std::fs::read_dir(&path).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_check_sandbox_path_not_a_dir() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("file");
+        std::fs::write(&path, "").unwrap();
+        let err = check_sandbox_path(&path).unwrap_err().to_string();
+        assert!(err.contains("is not usable"), "{}", err);
+        assert!(err.contains(&*path.to_string_lossy()), "{}", err);
+    }
+
+    #[test]
+    fn test_dump_reproducer() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.args(vec!["bar"]);
+        let sandbox =
+            Sandbox::new(&tmpdir.path().join("sandboxes"), &exec, &HashMap::new()).unwrap();
+        let boxdir = sandbox.data.lock().unwrap().path().to_owned();
+        let dest = tmpdir.path().join("reproducer");
+        sandbox.dump_reproducer(&dest).unwrap();
+
+        let command = std::fs::read_to_string(dest.join("command.txt")).unwrap();
+        assert!(command.starts_with("tmbox "));
+        assert!(command.contains(&format!("{:?}", boxdir.join("box"))));
+        assert!(command.trim_end().ends_with(r#""--" "foo" "bar""#));
+        let info: Execution =
+            serde_json::from_str(&std::fs::read_to_string(dest.join("info.json")).unwrap())
+                .unwrap();
+        assert_eq!(info.uuid, exec.uuid);
+        // the sandbox is not kept
+        assert!(!boxdir.join("command.txt").exists());
+        drop(sandbox);
+        assert!(!boxdir.exists());
+        assert!(dest.join("command.txt").exists());
+    }
+
+    /// Run an execution in a sandbox using a backend with the specified shell script.
+    fn run_with_backend(script: &str) -> SandboxResult {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let backend = tmpdir.path().join("fake-tmbox");
+        std::fs::write(&backend, script).unwrap();
+        std::fs::set_permissions(&backend, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let exec = Execution::new("test", ExecutionCommand::system("true"));
+        let mut sandbox =
+            Sandbox::new(&tmpdir.path().join("sandboxes"), &exec, &HashMap::new()).unwrap();
+        sandbox.backend(SandboxBackend::Custom(backend));
+        sandbox.run().unwrap()
+    }
+
+    #[test]
+    fn test_failure_diagnostic() {
+        let result = run_with_backend(
+            "#!/bin/sh\necho 'cannot mount /proc: permission denied' >&2\n\
+             echo '{\"error\":true,\"message\":\"Failed to setup the sandbox\"}'\n",
+        );
+        match result {
+            SandboxResult::Failed { error } => assert_eq!(
+                error,
+                "Failed to setup the sandbox\nSandbox stderr: cannot mount /proc: permission denied"
+            ),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_failure_invalid_outcome() {
+        let result = run_with_backend("#!/bin/sh\necho 'segfault in tmbox' >&2\nexit 139\n");
+        match result {
+            SandboxResult::Failed { error } => {
+                assert!(error.starts_with("The sandbox exited"), "{}", error);
+                assert!(error.contains("139"), "{}", error);
+                assert!(
+                    error.ends_with("Sandbox stderr: segfault in tmbox"),
+                    "{}",
+                    error
+                );
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_truncated_stdio() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let backend = tmpdir.path().join("fake-tmbox");
+        // the process writes 20 bytes on stdout and 5 on stderr
+        std::fs::write(
+            &backend,
+            "#!/bin/sh\nwhile [ \"$1\" != --stdout ]; do shift; done\n\
+             printf 0123456789abcdefghij > \"$2\"\nprintf 01234 > \"$4\"\n\
+             echo '{\"error\":false,\"cpu_time\":0.0,\"sys_time\":0.0,\"wall_time\":0.0,\
+             \"memory_usage\":0,\"status_code\":0,\"signal\":0,\"killed_by_sandbox\":false}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&backend, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::system("true"));
+        exec.stdout();
+        exec.stderr();
+        exec.limits_mut().stdio_size(10);
+        let mut sandbox =
+            Sandbox::new(&tmpdir.path().join("sandboxes"), &exec, &HashMap::new()).unwrap();
+        sandbox.backend(SandboxBackend::Custom(backend));
+        match sandbox.run().unwrap() {
+            SandboxResult::Success {
+                truncated_output, ..
+            } => assert!(truncated_output),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(std::fs::read(sandbox.stdout_path()).unwrap(), b"0123456789");
+        assert_eq!(std::fs::read(sandbox.stderr_path()).unwrap(), b"01234");
+    }
+
+    #[test]
+    fn test_kill() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let backend = tmpdir.path().join("slow-tmbox");
+        let child_pid = tmpdir.path().join("child.pid");
+        std::fs::write(
+            &backend,
+            format!(
+                "#!/bin/sh\nsleep 100 &\necho $! > {:?}\nexec sleep 100\n",
+                child_pid
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&backend, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let exec = Execution::new("test", ExecutionCommand::system("true"));
+        let mut sandbox =
+            Sandbox::new(&tmpdir.path().join("sandboxes"), &exec, &HashMap::new()).unwrap();
+        sandbox.backend(SandboxBackend::Custom(backend));
+        let start = Instant::now();
+        let thread_sandbox = sandbox.clone();
+        let runner = std::thread::spawn(move || thread_sandbox.run().unwrap());
+        // wait for the sandbox to have spawned its child
+        let read_child_pid = || std::fs::read_to_string(&child_pid).unwrap_or_default();
+        while !read_child_pid().ends_with('\n') {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        sandbox.kill();
+        match runner.join().unwrap() {
+            SandboxResult::Killed => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(start.elapsed() < Duration::from_secs(50));
+        assert!(sandbox.data.lock().unwrap().pid.is_none());
+        // the children of the sandbox are killed too
+        let child_pid = read_child_pid();
+        let child_stat = PathBuf::from("/proc").join(child_pid.trim()).join("stat");
+        let child_alive = || match std::fs::read_to_string(&child_stat) {
+            // a zombie is dead, just not reaped yet
+            Ok(stat) => !stat.contains(") Z "),
+            Err(_) => false,
+        };
+        while child_alive() && start.elapsed() < Duration::from_secs(10) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!child_alive(), "The child of the sandbox is still alive");
+    }
+
+    #[test]
+    fn test_kill_before_run() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let exec = Execution::new("test", ExecutionCommand::system("true"));
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        sandbox.kill();
+        match sandbox.run().unwrap() {
+            SandboxResult::Killed => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_backend_from_path() {
+        assert_eq!(
+            SandboxBackend::from(PathBuf::from("tmbox")),
+            SandboxBackend::Tmbox
+        );
+        assert_eq!(SandboxBackend::from(PathBuf::new()), SandboxBackend::Tmbox);
+        assert_eq!(
+            SandboxBackend::from(PathBuf::from("/usr/bin/isolate")),
+            SandboxBackend::Custom("/usr/bin/isolate".into())
+        );
+    }
+
     #[test]
     fn test_command_args() {
         let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
@@ -449,9 +1124,13 @@ mod tests {
             .cpu_time(2.6)
             .wall_time(10.0)
             .mount_tmpfs(true)
+            .tmpfs_size(256 * 1024 * 1024)
             .add_extra_readable_dir("/home")
             .nproc(2)
-            .memory(1234);
+            .nofile(64)
+            .stack(8192)
+            .memory(1234)
+            .seccomp(SeccompProfile::Strict);
         exec.env("foo", "bar");
         let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
         let args = sandbox
@@ -471,11 +1150,272 @@ mod tests {
         assert_contains(&args, &["--memory", "1234"]);
         assert_contains(&args, &["--readable-dir", "/home"]);
         assert_contains(&args, &["--mount-tmpfs"]);
+        assert_contains(&args, &["--mount-tmpfs-size", "268435456"]);
         assert_contains(&args, &["--multiprocess"]);
+        assert_contains(&args, &["--nofile", "64"]);
+        assert_contains(&args, &["--stack", "8192"]);
+        assert_contains(&args, &["--seccomp", "strict"]);
         assert_contains(&args, &["--env", "foo=bar"]);
         assert_contains(&args, &["--stdin", "/dev/null"]);
         assert_contains(&args, &["--stdout", "/dev/null"]);
         assert_contains(&args, &["--stderr", "/dev/null"]);
         assert_contains(&args, &["--", "foo", "bar", "baz"]);
     }
+
+    #[test]
+    fn test_command_args_extra_time_override() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.limits_mut().cpu_time(1.0).wall_time(10.0);
+        exec.extra_time(2.5);
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox
+            .build_command(tmpdir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        assert_contains(&args, &["--time", "3.5"]);
+        assert_contains(&args, &["--wall", "12.5"]);
+    }
+
+    #[test]
+    fn test_command_args_unlimited_stack() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let exec = Execution::new("test", ExecutionCommand::local("foo"));
+        assert_eq!(exec.limits.stack, Some(0));
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox
+            .build_command(tmpdir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        assert_contains(&args, &["--stack", "0"]);
+    }
+
+    #[test]
+    fn test_command_args_tmpfs_size_without_tmpfs() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.limits_mut().mount_tmpfs(false).tmpfs_size(1024);
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox.build_command(tmpdir.path()).unwrap();
+        assert!(!args.contains(&"--mount-tmpfs".into()));
+        assert!(!args.contains(&"--mount-tmpfs-size".into()));
+    }
+
+    #[test]
+    fn test_command_args_network() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox.build_command(tmpdir.path()).unwrap();
+        assert!(!args.contains(&"--allow-network".into()));
+
+        exec.limits_mut().allow_network(true);
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox
+            .build_command(tmpdir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        assert_contains(&args, &["--allow-network"]);
+    }
+
+    #[test]
+    fn test_command_args_seccomp() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox.build_command(tmpdir.path()).unwrap();
+        assert!(!args.contains(&"--seccomp".into()));
+
+        exec.limits_mut().seccomp(SeccompProfile::Permissive);
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox
+            .build_command(tmpdir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        assert_contains(&args, &["--seccomp", "permissive"]);
+    }
+
+    #[test]
+    fn test_command_args_extra_memory() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.limits_mut().memory(1000);
+        exec.extra_memory(500);
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox
+            .build_command(tmpdir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        assert_contains(&args, &["--memory", "1500"]);
+    }
+
+    #[test]
+    fn test_command_args_memory_arg() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.args(vec!["bar"]);
+        exec.limits_mut().memory(1000).memory_arg("-Xmx{}k");
+        exec.extra_memory(500);
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let args = sandbox
+            .build_command(tmpdir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        assert!(!args.contains(&"--memory".to_string()));
+        assert_contains(&args, &["--", "foo", "-Xmx1500k", "bar"]);
+    }
+
+    #[test]
+    fn test_command_args_fifos() {
+        use std::os::unix::fs::FileTypeExt;
+
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut manager = Execution::new("manager", ExecutionCommand::local("manager"));
+        manager.fifo("fifos/to_sol").fifo("fifos/to_man");
+        let manager = Sandbox::new(tmpdir.path(), &manager, &HashMap::new()).unwrap();
+        let fifo = manager.output_path(Path::new("fifos/to_sol"));
+        assert!(std::fs::metadata(&fifo).unwrap().file_type().is_fifo());
+
+        let mut exec = Execution::new("solution", ExecutionCommand::local("sol"));
+        exec.stdin_fifo("fifos/to_sol").stdout_fifo("fifos/to_man");
+        let mut sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        assert!(sandbox.build_command(tmpdir.path()).is_err());
+        sandbox.fifo_dir(manager.box_path());
+        let args = sandbox
+            .build_command(tmpdir.path())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        let path = |name: &str| manager.output_path(Path::new(name)).display().to_string();
+        assert_contains(&args, &["--stdin", &path("fifos/to_sol")]);
+        assert_contains(&args, &["--stdout", &path("fifos/to_man")]);
+    }
+
+    #[test]
+    fn test_custom_stdio_names() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let store = FileStore::new(tmpdir.path().join("store")).unwrap();
+        let content = b"hello".to_vec();
+        let handle = store
+            .store(&FileStoreKey::from_content(&content), vec![content])
+            .unwrap();
+        let stdin = File::new("stdin");
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.stdin(&stdin);
+        exec.stdout();
+        exec.stderr();
+        exec.stdio_names(ExecutionStdioNames::new("in.txt", "out.txt", "err.txt"));
+        let mut dep_keys = HashMap::new();
+        dep_keys.insert(stdin.uuid, handle);
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &dep_keys).unwrap();
+        let boxdir = sandbox.stdout_path().parent().unwrap().to_owned();
+        assert_eq!(sandbox.stdout_path(), boxdir.join("out.txt"));
+        assert_eq!(sandbox.stderr_path(), boxdir.join("err.txt"));
+        assert_eq!(std::fs::read(boxdir.join("in.txt")).unwrap(), b"hello");
+        assert!(boxdir.join("out.txt").exists());
+        assert!(boxdir.join("err.txt").exists());
+        for name in &["stdin", "stdout", "stderr"] {
+            assert!(!boxdir.join(name).exists());
+        }
+        let args = sandbox
+            .build_command(&boxdir)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_string_lossy().to_string())
+            .collect_vec();
+        let path = |name: &str| boxdir.join(name).to_string_lossy().to_string();
+        assert_contains(&args, &["--stdin", &path("in.txt")]);
+        assert_contains(&args, &["--stdout", &path("out.txt")]);
+        assert_contains(&args, &["--stderr", &path("err.txt")]);
+    }
+
+    #[test]
+    fn test_redacted_command() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.args(vec!["API_TOKEN=not-an-env"]);
+        exec.env("API_TOKEN", "s3cr3t-t0k3n");
+        let sandbox = Sandbox::new(tmpdir.path(), &exec, &HashMap::new()).unwrap();
+        let command = sandbox.build_command(tmpdir.path()).unwrap();
+        let log = redacted_command(&command);
+        assert!(!log.contains("s3cr3t-t0k3n"), "{}", log);
+        assert!(
+            log.contains("\"--env\" \"API_TOKEN=<redacted>\""),
+            "{}",
+            log
+        );
+        // the variables passed through and the other arguments are kept
+        assert!(log.contains("\"--env\" \"PATH\""), "{}", log);
+        assert!(log.contains("\"API_TOKEN=not-an-env\""), "{}", log);
+    }
+
+    /// Parse the output of tmbox for a process exited successfully.
+    fn parse_success(json: &str) -> SandboxResult {
+        let outcome: TMBoxResult = serde_json::from_str(json).unwrap();
+        Sandbox::parse_outcome(outcome)
+    }
+
+    #[test]
+    fn test_parse_outcome_processes() {
+        let json = r#"{"error": false, "cpu_time": 0.1, "sys_time": 0.0, "wall_time": 0.2,
+            "memory_usage": 1024, "status_code": 0, "signal": 0, "killed_by_sandbox": false,
+            "processes": 3}"#;
+        match parse_success(json) {
+            SandboxResult::Success { resources, .. } => {
+                assert_eq!(resources.processes, Some(3));
+                assert_eq!(resources.memory, 1024);
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_parse_outcome_no_processes() {
+        let json = r#"{"error": false, "cpu_time": 0.1, "sys_time": 0.0, "wall_time": 0.2,
+            "memory_usage": 1024, "status_code": 0, "signal": 0, "killed_by_sandbox": false}"#;
+        match parse_success(json) {
+            SandboxResult::Success { resources, .. } => assert_eq!(resources.processes, None),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_parse_outcome_extra_memory() {
+        let mut exec = Execution::new("test", ExecutionCommand::local("foo"));
+        exec.limits_mut().memory(1000);
+        exec.extra_memory(500);
+        // the process used more than the limit but less than the limit plus the extra memory,
+        // the sandbox let it finish
+        let json = r#"{"error": false, "cpu_time": 0.1, "sys_time": 0.0, "wall_time": 0.2,
+            "memory_usage": 1200, "status_code": 0, "signal": 0, "killed_by_sandbox": false}"#;
+        match parse_success(json) {
+            SandboxResult::Success {
+                exit_status,
+                signal,
+                resources,
+                was_killed,
+                ..
+            } => {
+                assert!(!was_killed);
+                assert_eq!(
+                    exec.status(exit_status, signal, &resources),
+                    ExecutionStatus::MemoryLimitExceeded
+                );
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
 }
@@ -1,17 +1,18 @@
-use crate::proto::WorkerServerMessage;
+use crate::proto::{WorkerServerMessage, INLINE_OUTPUT_LIMIT};
 use crate::{
     serialize_into, ChannelSender, ExecutionDAGWatchSet, ExecutorStatus, ExecutorWorkerStatus,
-    WorkerJob,
+    MemoryReservation, StallWatchdog, WorkerJob,
 };
 use failure::{format_err, Error};
 use std::collections::{BinaryHeap, HashMap, HashSet};
-use std::sync::mpsc::{Receiver, Sender};
+use std::fmt::Write;
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError, Sender};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use task_maker_cache::{Cache, CacheResult};
 use task_maker_dag::{
-    CacheMode, Execution, ExecutionDAGData, ExecutionResult, ExecutionStatus, ExecutionUuid,
-    FileUuid, WorkerUuid,
+    CacheMode, Execution, ExecutionDAGData, ExecutionProvenance, ExecutionResult, ExecutionStatus,
+    ExecutionUuid, FileUuid, SkipReason, VerifyMode, WorkerUuid,
 };
 use task_maker_store::{FileStore, FileStoreHandle, FileStoreKey};
 
@@ -23,8 +24,9 @@ pub struct Scheduler {
     /// The set of callbacks the client is interested in.
     callbacks: Option<ExecutionDAGWatchSet>,
 
-    /// The priority queue of the ready tasks, waiting for the workers.
-    ready_execs: BinaryHeap<ExecutionUuid>,
+    /// The priority queue of the ready tasks, waiting for the workers, each with its priority and
+    /// the size of its inputs.
+    ready_execs: BinaryHeap<(i32, u64, ExecutionUuid)>,
     /// The list of tasks waiting for some dependencies, each with the list of missing files, when a
     /// task is ready it's removed from the map.
     missing_deps: HashMap<ExecutionUuid, HashSet<FileUuid>>,
@@ -35,9 +37,33 @@ pub struct Scheduler {
     /// The list of known [`FileStoreHandle`](../task_maker_store/struct.FileStoreHandle.html)s
     /// for the current DAG. Storing them here prevents the `FileStore` from flushing them away.
     file_handles: HashMap<FileUuid, FileStoreHandle>,
+    /// The files already sent to the client inline with the completion of their execution, they
+    /// are not sent again at the end of the evaluation.
+    inlined_files: HashSet<FileUuid>,
 
     /// The cache of the executions.
     cache: Cache,
+    /// The executions that are cached but are being run again for verifying the cache, with the
+    /// keys of the outputs stored in the cache.
+    verifying: HashMap<ExecutionUuid, HashMap<FileUuid, FileStoreKey>>,
+    /// The number of executions dispatched to the workers, used for enforcing the budget.
+    dispatched_execs: usize,
+    /// The total CPU time used by the completed executions, used for enforcing the budget.
+    used_cpu_time: f64,
+    /// Whether the client asked to stop the evaluation.
+    stopped: bool,
+    /// The number of executions the workers are running.
+    running_execs: usize,
+    /// The maximum number of executions that ran at the same time.
+    peak_running_execs: usize,
+    /// The memory to keep free on the machine of the workers, if any.
+    memory_reservation: Option<MemoryReservation>,
+    /// The watchdog of the stalled evaluations, if any.
+    stall_watchdog: Option<StallWatchdog>,
+    /// The last time an execution started or completed.
+    last_progress: Instant,
+    /// Whether the current stall has already been reported.
+    stall_reported: bool,
     /// A reference to the server's [`FileStore`](../task_maker_store/struct.FileStore.html).
     file_store: Arc<FileStore>,
     /// The list of the workers that are either ready for some work or already working on a job.
@@ -82,6 +108,8 @@ pub enum SchedulerInMessage {
         worker: WorkerUuid,
         /// The result of the execution.
         result: ExecutionResult,
+        /// The results of the concurrent executions, in the same order.
+        concurrent_results: Vec<ExecutionResult>,
         /// The outputs that the worker produced.
         outputs: HashMap<FileUuid, FileStoreHandle>,
     },
@@ -101,6 +129,13 @@ pub enum SchedulerInMessage {
     },
     /// The executor is asking for the status of the scheduler.
     Status,
+    /// The client is asking to stop the evaluation: no more executions will be started and the
+    /// ones not started yet are skipped, as soon as they would become ready, with the `Aborted`
+    /// reason.
+    Stop,
+    /// The client is asking to stop the evaluation like with `Stop`, killing also the running
+    /// executions.
+    Kill,
     /// The executor is asking to exit.
     Exit,
 }
@@ -110,10 +145,18 @@ pub enum SchedulerInMessage {
 pub enum SchedulerOutMessage {
     /// An execution has started on a specific worker.
     ExecutionStarted(ExecutionUuid, WorkerUuid),
-    /// An execution has been completed.
-    ExecutionDone(ExecutionUuid, ExecutionResult),
-    /// An execution has been skipped.
-    ExecutionSkipped(ExecutionUuid),
+    /// An execution has been completed, with the content of its small outputs the client is
+    /// interested in.
+    ExecutionDone(
+        ExecutionUuid,
+        Box<ExecutionResult>,
+        HashMap<FileUuid, Vec<u8>>,
+    ),
+    /// An execution has been skipped for that reason.
+    ExecutionSkipped(ExecutionUuid, SkipReason),
+    /// An execution, run again for verifying the cache, produced these files with a content
+    /// different from the cached one.
+    ExecutionNondeterministic(ExecutionUuid, Vec<FileUuid>),
     /// A file is ready in the store. The `bool` is `true` if it comes from a successful execution.
     FileReady(FileUuid, FileStoreHandle, bool),
     /// The status of the scheduler.
@@ -121,11 +164,14 @@ pub enum SchedulerOutMessage {
 }
 
 impl Scheduler {
-    /// Make a new scheduler bound to the specified executor.
+    /// Make a new scheduler bound to the specified executor. If a memory reservation is specified
+    /// the executions are started only if their memory limit fits in the memory left by the
+    /// running ones.
     pub fn new(
         cache: Cache,
         file_store: Arc<FileStore>,
         executor: Sender<SchedulerOutMessage>,
+        memory_reservation: Option<MemoryReservation>,
     ) -> Scheduler {
         Scheduler {
             dag: None,
@@ -133,7 +179,18 @@ impl Scheduler {
             ready_execs: BinaryHeap::new(),
             missing_deps: HashMap::new(),
             file_handles: HashMap::new(),
+            inlined_files: HashSet::new(),
             cache,
+            verifying: HashMap::new(),
+            dispatched_execs: 0,
+            used_cpu_time: 0.0,
+            stopped: false,
+            running_execs: 0,
+            peak_running_execs: 0,
+            memory_reservation,
+            stall_watchdog: None,
+            last_progress: Instant::now(),
+            stall_reported: false,
             file_store,
             connected_workers: HashMap::new(),
             executor,
@@ -141,14 +198,36 @@ impl Scheduler {
         }
     }
 
+    /// Dump the state of the scheduler when the evaluation makes no progress for a while.
+    pub fn stall_watchdog(&mut self, watchdog: StallWatchdog) -> &mut Self {
+        self.stall_watchdog = Some(watchdog);
+        self
+    }
+
     /// Consume the `Scheduler` starting the scheduling process and returning after the evaluation
     /// has been completed.
     pub fn work(mut self, recv: Receiver<SchedulerInMessage>) -> Result<(), Error> {
         while self.dag.is_none() || !self.is_done() {
-            let message = recv.recv();
+            self.check_stall()?;
+            let message = match &self.stall_watchdog {
+                Some(watchdog) => {
+                    // wake up when the evaluation would be considered stalled
+                    let timeout = watchdog
+                        .interval
+                        .checked_sub(self.last_progress.elapsed())
+                        .unwrap_or(watchdog.interval);
+                    match recv.recv_timeout(timeout) {
+                        Ok(message) => Ok(message),
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => Err(RecvError),
+                    }
+                }
+                None => recv.recv(),
+            };
             match message {
                 Ok(SchedulerInMessage::DAG { dag, callbacks }) => {
                     info!("Scheduler received a new DAG");
+                    self.progress();
                     let mut input_of: HashMap<FileUuid, HashSet<ExecutionUuid>> = HashMap::new();
                     for exec in dag.executions.values() {
                         let missing_dep = self.missing_deps.entry(exec.uuid).or_default();
@@ -170,6 +249,7 @@ impl Scheduler {
                 Ok(SchedulerInMessage::WorkerResult {
                     worker,
                     result,
+                    concurrent_results,
                     outputs,
                 }) => {
                     let worker = match self.connected_workers.remove(&worker) {
@@ -180,7 +260,10 @@ impl Scheduler {
                         }
                     };
                     let execution_uuid = match worker.current_job {
-                        Some((uuid, _)) => uuid,
+                        Some((uuid, _)) => {
+                            self.running_execs -= 1;
+                            uuid
+                        }
                         None => {
                             warn!(
                                 "Worker {} ({}) completed a job that wasn't doing",
@@ -196,7 +279,20 @@ impl Scheduler {
                         .executions[&execution_uuid]
                         .clone();
                     info!("Worker {:?} completed execution {}", worker, execution.uuid);
-                    self.exec_completed(&execution, result, outputs)?;
+                    let provenance = ExecutionProvenance::Worker {
+                        uuid: worker.uuid,
+                        name: worker.name.clone(),
+                    };
+                    let mut results = vec![];
+                    for result in std::iter::once(result).chain(concurrent_results) {
+                        self.used_cpu_time += result.resources.cpu_time + result.resources.sys_time;
+                        results.push(ExecutionResult {
+                            provenance: provenance.clone(),
+                            ..result
+                        });
+                    }
+                    let result = results.remove(0);
+                    self.exec_completed(&execution, result, results, outputs)?;
                     self.assign_jobs()?;
                 }
                 Ok(SchedulerInMessage::WorkerConnected { uuid, name, sender }) => {
@@ -216,32 +312,39 @@ impl Scheduler {
                     info!("Worker {} disconnected", uuid);
                     if let Some(worker) = self.connected_workers.remove(&uuid) {
                         if let Some((job, _)) = worker.current_job {
-                            self.ready_execs.push(job);
+                            warn!(
+                                "Worker {} ({}) left while running {}, requeuing it",
+                                worker.name, worker.uuid, job
+                            );
+                            self.running_execs -= 1;
+                            // the lost run does not count for the budget
+                            self.dispatched_execs -= 1;
+                            self.exec_ready(job)?;
+                            self.assign_jobs()?;
                         }
                     }
                 }
                 Ok(SchedulerInMessage::Status) => {
-                    let dag = self
-                        .dag
-                        .as_ref()
-                        .ok_or_else(|| format_err!("DAG is gone"))?;
-                    let status = ExecutorStatus {
-                        connected_workers: self
-                            .connected_workers
-                            .values()
-                            .map(|worker| ExecutorWorkerStatus {
-                                uuid: worker.uuid,
-                                name: worker.name.clone(),
-                                current_job: worker.current_job.as_ref().map(|(exec, start)| {
-                                    (dag.executions[&exec].description.clone(), start.elapsed())
-                                }),
-                            })
-                            .collect(),
-                        ready_execs: self.ready_execs.len(),
-                        waiting_execs: self.missing_deps.len(),
-                    };
+                    let status = self.status()?;
                     self.executor.send(SchedulerOutMessage::Status(status))?;
                 }
+                Ok(SchedulerInMessage::Stop) => {
+                    info!("Scheduler asked to stop the evaluation");
+                    self.stopped = true;
+                    self.assign_jobs()?;
+                }
+                Ok(SchedulerInMessage::Kill) => {
+                    info!("Scheduler asked to stop the evaluation killing the running executions");
+                    self.stopped = true;
+                    self.assign_jobs()?;
+                    for worker in self.connected_workers.values() {
+                        if let Some((job, _)) = &worker.current_job {
+                            debug!("Killing {} on worker {}", job, worker.uuid);
+                            // the worker may be gone meanwhile
+                            let _ = serialize_into(&WorkerServerMessage::KillJob, &worker.sender);
+                        }
+                    }
+                }
                 Ok(SchedulerInMessage::Exit) => {
                     break;
                 }
@@ -251,10 +354,128 @@ impl Scheduler {
                 }
             }
         }
+        // send the final status, with the statistics of the whole evaluation
+        if self.dag.is_some() {
+            let status = self.status()?;
+            self.executor.send(SchedulerOutMessage::Status(status))?;
+        }
         debug!("Scheduler exited");
         Ok(())
     }
 
+    /// The current status of the scheduler.
+    fn status(&self) -> Result<ExecutorStatus<Duration>, Error> {
+        let dag = self
+            .dag
+            .as_ref()
+            .ok_or_else(|| format_err!("DAG is gone"))?;
+        Ok(ExecutorStatus {
+            connected_workers: self
+                .connected_workers
+                .values()
+                .map(|worker| ExecutorWorkerStatus {
+                    uuid: worker.uuid,
+                    name: worker.name.clone(),
+                    current_job: worker.current_job.as_ref().map(|(exec, start)| {
+                        (dag.executions[&exec].description.clone(), start.elapsed())
+                    }),
+                })
+                .collect(),
+            ready_execs: self.ready_execs.len(),
+            waiting_execs: self.missing_deps.len(),
+            memory_in_use: self.memory_in_use(),
+            memory_available: self
+                .memory_reservation
+                .as_ref()
+                .map(MemoryReservation::available),
+            peak_running_execs: self.peak_running_execs,
+        })
+    }
+
+    /// Some execution has started or completed, the evaluation is not stalled.
+    fn progress(&mut self) {
+        self.last_progress = Instant::now();
+        self.stall_reported = false;
+    }
+
+    /// If the evaluation made no progress for the interval of the watchdog, dump the diagnostics
+    /// to the log and to the file of the watchdog. Each stall is reported only once.
+    fn check_stall(&mut self) -> Result<(), Error> {
+        let watchdog = match &self.stall_watchdog {
+            Some(watchdog) => watchdog,
+            None => return Ok(()),
+        };
+        if self.dag.is_none()
+            || self.stall_reported
+            || self.last_progress.elapsed() < watchdog.interval
+        {
+            return Ok(());
+        }
+        let diagnostics = self.diagnostics()?;
+        warn!("{}", diagnostics);
+        if let Some(path) = &watchdog.dump_path {
+            if let Err(e) = std::fs::write(path, &diagnostics) {
+                warn!("Cannot write the diagnostics to {}: {}", path.display(), e);
+            }
+        }
+        self.stall_reported = true;
+        Ok(())
+    }
+
+    /// A human readable dump of the state of the scheduler: the ready executions, the ones waiting
+    /// for some dependencies, with the executions producing them, and the state of the workers.
+    fn diagnostics(&self) -> Result<String, Error> {
+        let dag = self
+            .dag
+            .as_ref()
+            .ok_or_else(|| format_err!("DAG is gone"))?;
+        let describe = |exec: &ExecutionUuid| match dag.executions.get(exec) {
+            Some(execution) => format!("{} ({})", execution.description, exec),
+            None => exec.to_string(),
+        };
+        let producer_of = |file: &FileUuid| {
+            dag.executions
+                .values()
+                .find(|exec| exec.outputs().contains(file))
+                .map(|exec| describe(&exec.uuid))
+                .unwrap_or_else(|| "the client".to_string())
+        };
+        let mut out = String::new();
+        writeln!(
+            out,
+            "The evaluation made no progress in the last {:.1}s",
+            self.last_progress.elapsed().as_secs_f64()
+        )?;
+        let mut ready: Vec<_> = self.ready_execs.iter().collect();
+        ready.sort_by(|a, b| b.cmp(a));
+        writeln!(out, "Ready executions ({}):", ready.len())?;
+        for (_, _, exec) in ready {
+            writeln!(out, "  {}", describe(exec))?;
+        }
+        writeln!(out, "Waiting executions ({}):", self.missing_deps.len())?;
+        for (exec, missing) in &self.missing_deps {
+            writeln!(out, "  {}, missing:", describe(exec))?;
+            for file in missing {
+                writeln!(out, "    {} from {}", file, producer_of(file))?;
+            }
+        }
+        writeln!(out, "Workers ({}):", self.connected_workers.len())?;
+        for worker in self.connected_workers.values() {
+            match &worker.current_job {
+                Some((exec, start)) => writeln!(
+                    out,
+                    "  {} ({}): running {} for {:.1}s",
+                    worker.name,
+                    worker.uuid,
+                    describe(exec),
+                    start.elapsed().as_secs_f64()
+                )?,
+                None => writeln!(out, "  {} ({}): idle", worker.name, worker.uuid)?,
+            }
+        }
+        Ok(out)
+    }
+
     /// Whether the evaluation of the DAG has been completed.
     fn is_done(&self) -> bool {
         if !self.ready_execs.is_empty() {
@@ -271,38 +492,69 @@ impl Scheduler {
         true
     }
 
-    /// Mark a file as failed, skipping all the executions that depends on it (even transitively).
-    /// This will also send the file to the client, if needed.
-    fn file_failed(&mut self, file: FileUuid) -> Result<(), Error> {
+    /// Mark a file as failed, skipping with that reason all the executions that depends on it (even
+    /// transitively). The executions that allow failed inputs are not skipped if the file has been
+    /// produced. This will also send the file to the client, if needed.
+    fn file_failed(&mut self, file: FileUuid, reason: SkipReason) -> Result<(), Error> {
         self.send_file(file, false)?;
         if !self.input_of.contains_key(&file) {
             return Ok(());
         }
+        let mut new_ready = false;
         for exec in self.input_of[&file].clone() {
             // do not skip the same execution twice
-            if self.missing_deps.contains_key(&exec) {
-                self.missing_deps.remove(&exec);
-            } else {
+            if !self.missing_deps.contains_key(&exec) {
                 continue;
             }
-            if self
-                .callbacks
-                .as_ref()
-                .ok_or_else(|| format_err!("Callbacks are gone"))?
-                .executions
-                .contains(&exec)
-            {
-                self.executor
-                    .send(SchedulerOutMessage::ExecutionSkipped(exec))?;
-            }
-            let exec = &self
+            let allow_failed = self
                 .dag
                 .as_ref()
                 .ok_or_else(|| format_err!("DAG is gone"))?
-                .executions[&exec];
-            for output in exec.outputs() {
-                self.file_failed(output)?;
+                .executions[&exec]
+                .allow_failed_inputs;
+            if allow_failed && self.file_handles.contains_key(&file) {
+                let missing = self.missing_deps.get_mut(&exec).unwrap();
+                missing.remove(&file);
+                if missing.is_empty() {
+                    self.missing_deps.remove(&exec);
+                    self.exec_ready(exec)?;
+                    new_ready = true;
+                }
+                continue;
             }
+            self.missing_deps.remove(&exec);
+            self.skip_execution(exec, reason)?;
+        }
+        if new_ready {
+            self.schedule_cached()?;
+            self.assign_jobs()?;
+        }
+        Ok(())
+    }
+
+    /// Skip an execution, together with its concurrent executions, notifying the client and
+    /// marking all its outputs as failed. The executions depending on it are skipped for the same
+    /// reason.
+    fn skip_execution(&mut self, exec: ExecutionUuid, reason: SkipReason) -> Result<(), Error> {
+        self.progress();
+        let exec = &self
+            .dag
+            .as_ref()
+            .ok_or_else(|| format_err!("DAG is gone"))?
+            .executions[&exec];
+        let watched = &self
+            .callbacks
+            .as_ref()
+            .ok_or_else(|| format_err!("Callbacks are gone"))?
+            .executions;
+        for uuid in std::iter::once(exec.uuid).chain(exec.concurrent.iter().map(|e| e.uuid)) {
+            if watched.contains(&uuid) {
+                self.executor
+                    .send(SchedulerOutMessage::ExecutionSkipped(uuid, reason))?;
+            }
+        }
+        for output in exec.outputs() {
+            self.file_failed(output, reason)?;
         }
         Ok(())
     }
@@ -314,12 +566,12 @@ impl Scheduler {
         if !self.input_of.contains_key(&file) {
             return Ok(());
         }
-        for exec in &self.input_of[&file] {
-            if self.missing_deps.contains_key(exec) {
-                self.missing_deps.get_mut(exec).unwrap().remove(&file);
-                if self.missing_deps[exec].is_empty() {
-                    self.missing_deps.remove(exec);
-                    self.ready_execs.push(*exec);
+        for exec in self.input_of[&file].clone() {
+            if let Some(missing) = self.missing_deps.get_mut(&exec) {
+                missing.remove(&file);
+                if missing.is_empty() {
+                    self.missing_deps.remove(&exec);
+                    self.exec_ready(exec)?;
                 }
             }
         }
@@ -328,6 +580,54 @@ impl Scheduler {
         Ok(())
     }
 
+    /// All the inputs of an execution are ready: add it to the ready ones, or skip it if the
+    /// evaluation has been stopped meanwhile.
+    fn exec_ready(&mut self, exec: ExecutionUuid) -> Result<(), Error> {
+        if self.stopped {
+            warn!("Execution {} skipped: evaluation stopped", exec);
+            self.skip_execution(exec, SkipReason::Aborted)
+        } else {
+            self.push_ready(exec);
+            Ok(())
+        }
+    }
+
+    /// Add an execution to the ready ones. The executions are sorted by their priority; among the
+    /// ones with the same priority, if the DAG asks for the largest inputs first, by the total size
+    /// of their inputs, and then by uuid.
+    fn push_ready(&mut self, exec: ExecutionUuid) {
+        let (largest_inputs_first, priority) = self
+            .dag
+            .as_ref()
+            .map(|dag| {
+                let priority = dag.executions.get(&exec).map(|e| e.priority).unwrap_or(0);
+                (dag.config.largest_inputs_first, priority)
+            })
+            .unwrap_or((false, 0));
+        let size = if largest_inputs_first {
+            self.inputs_size(&exec)
+        } else {
+            0
+        };
+        self.ready_execs.push((priority, size, exec));
+    }
+
+    /// The total size, in bytes, of the inputs of an execution. The inputs not in the store yet
+    /// are not counted.
+    fn inputs_size(&self, exec: &ExecutionUuid) -> u64 {
+        let execution = match self.dag.as_ref().and_then(|dag| dag.executions.get(exec)) {
+            Some(execution) => execution,
+            None => return 0,
+        };
+        execution
+            .dependencies()
+            .iter()
+            .filter_map(|file| self.file_handles.get(file))
+            .filter_map(|handle| std::fs::metadata(handle.path()).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
     /// Send a file to the client if its uuid is included in the callbacks.
     fn send_file(&self, file: FileUuid, status: bool) -> Result<(), Error> {
         if !self
@@ -339,7 +639,7 @@ impl Scheduler {
         {
             return Ok(());
         }
-        if !self.file_handles.contains_key(&file) {
+        if !self.file_handles.contains_key(&file) || self.inlined_files.contains(&file) {
             return Ok(());
         }
         self.executor.send(SchedulerOutMessage::FileReady(
@@ -353,43 +653,158 @@ impl Scheduler {
     /// Mark an execution as completed, sending the notification to the client and marking all the
     /// produced files as done. Add the execution to the cache and schedule all the new executions
     /// that become ready.
+    ///
+    /// The outputs of the execution and of each of its concurrent executions are marked as done
+    /// according to the result of the execution that produced them.
     fn exec_completed(
         &mut self,
         execution: &Execution,
         result: ExecutionResult,
+        concurrent_results: Vec<ExecutionResult>,
         outputs: HashMap<FileUuid, FileStoreHandle>,
     ) -> Result<(), Error> {
-        if self
+        self.progress();
+        let mut inline_outputs = self.inline_outputs(&outputs)?;
+        let watched = self
             .callbacks
             .as_ref()
             .ok_or_else(|| format_err!("Callbacks are gone"))?
             .executions
-            .contains(&execution.uuid)
-        {
+            .clone();
+        // split the outputs by the execution that produced them
+        let mut produced = vec![];
+        let concurrent_outputs: HashSet<FileUuid> = execution
+            .concurrent
+            .iter()
+            .flat_map(|concurrent| concurrent.outputs())
+            .collect();
+        let own_outputs: Vec<FileUuid> = execution
+            .outputs()
+            .into_iter()
+            .filter(|output| !concurrent_outputs.contains(output))
+            .collect();
+        produced.push((result.status == ExecutionStatus::Success, own_outputs));
+        // the concurrent executions are notified first, so that the client knows their results
+        // when the outputs of the main execution arrive. Each of them carries its own outputs,
+        // which are reported with its own result
+        for (concurrent, result) in execution.concurrent.iter().zip(concurrent_results.iter()) {
+            let outputs = concurrent.outputs();
+            let inline: HashMap<FileUuid, Vec<u8>> = outputs
+                .iter()
+                .filter_map(|uuid| inline_outputs.remove_entry(uuid))
+                .collect();
+            if !inline.is_empty() || watched.contains(&concurrent.uuid) {
+                self.executor.send(SchedulerOutMessage::ExecutionDone(
+                    concurrent.uuid,
+                    Box::new(result.clone()),
+                    inline,
+                ))?;
+            }
+            produced.push((result.status == ExecutionStatus::Success, outputs));
+        }
+        if !inline_outputs.is_empty() || watched.contains(&execution.uuid) {
             self.executor.send(SchedulerOutMessage::ExecutionDone(
                 execution.uuid,
-                result.clone(),
+                Box::new(result.clone()),
+                inline_outputs,
             ))?;
         }
+        if let Some(expected) = self.verifying.remove(&execution.uuid) {
+            self.verify_outputs(execution, expected, &outputs)?;
+        }
         for (uuid, handle) in outputs.iter() {
             self.file_handles.insert(*uuid, handle.clone());
         }
-        let successful = ExecutionStatus::Success == result.status;
-        self.cache_execution(&execution, outputs, result);
-        if successful {
-            for output in execution.outputs() {
-                self.file_success(output)?;
-            }
+        // the concurrent executions depend on each other, they cannot be reused separately
+        if execution.concurrent.is_empty() {
+            self.cache_execution(&execution, outputs, result);
+        }
+        // the executions still running when the evaluation is stopped are not waited for by
+        // their dependents, which are skipped as aborted
+        let reason = if self.stopped {
+            SkipReason::Aborted
         } else {
-            for output in execution.outputs() {
-                self.file_failed(output)?;
+            SkipReason::FailedDependency
+        };
+        for (successful, outputs) in produced {
+            for output in outputs {
+                if successful {
+                    self.file_success(output)?;
+                } else {
+                    self.file_failed(output, reason)?;
+                }
             }
         }
         self.schedule_cached()?;
         Ok(())
     }
 
-    /// Store an execution in the cache.
+    /// Read the content of the outputs the client is interested in that are small enough to be sent
+    /// inline with the completion of the execution, instead of at the end of the evaluation.
+    fn inline_outputs(
+        &mut self,
+        outputs: &HashMap<FileUuid, FileStoreHandle>,
+    ) -> Result<HashMap<FileUuid, Vec<u8>>, Error> {
+        let files = &self
+            .callbacks
+            .as_ref()
+            .ok_or_else(|| format_err!("Callbacks are gone"))?
+            .files;
+        let mut inline_outputs = HashMap::new();
+        for (uuid, handle) in outputs {
+            if !files.contains(uuid) || self.inlined_files.contains(uuid) {
+                continue;
+            }
+            if std::fs::metadata(handle.path())?.len() > INLINE_OUTPUT_LIMIT {
+                continue;
+            }
+            inline_outputs.insert(*uuid, std::fs::read(handle.path())?);
+        }
+        self.inlined_files.extend(inline_outputs.keys());
+        Ok(inline_outputs)
+    }
+
+    /// Compare the outputs of an execution run for verifying the cache with the cached ones,
+    /// notifying the client if some of them differ.
+    fn verify_outputs(
+        &self,
+        execution: &Execution,
+        expected: HashMap<FileUuid, FileStoreKey>,
+        outputs: &HashMap<FileUuid, FileStoreHandle>,
+    ) -> Result<(), Error> {
+        let mismatches: Vec<FileUuid> = expected
+            .into_iter()
+            .filter(|(uuid, key)| outputs.get(uuid).map(|h| h.key()) != Some(key))
+            .map(|(uuid, _)| uuid)
+            .collect();
+        if mismatches.is_empty() {
+            debug!("Execution {} is deterministic", execution.uuid);
+            return Ok(());
+        }
+        warn!(
+            "Execution {} ({}) is not deterministic: {} outputs differ from the cache",
+            execution.uuid,
+            execution.description,
+            mismatches.len()
+        );
+        if self
+            .callbacks
+            .as_ref()
+            .ok_or_else(|| format_err!("Callbacks are gone"))?
+            .executions
+            .contains(&execution.uuid)
+        {
+            self.executor
+                .send(SchedulerOutMessage::ExecutionNondeterministic(
+                    execution.uuid,
+                    mismatches,
+                ))?;
+        }
+        Ok(())
+    }
+
+    /// Store an execution in the cache. The new entries are written to disk immediately, so that
+    /// if the evaluation is interrupted the executions already completed are not run again.
     fn cache_execution(
         &mut self,
         execution: &Execution,
@@ -404,18 +819,25 @@ impl Scheduler {
         for output in execution.outputs() {
             file_keys.insert(output, outputs[&output].key().clone());
         }
+        let was_cached = result.was_cached;
         self.cache.insert(execution, &self.file_handles, result);
+        if !was_cached {
+            if let Err(e) = self.cache.flush() {
+                warn!("Cannot write the cache to disk: {:?}", e);
+            }
+        }
     }
 
     /// Look at all the ready executions and mark as completed all the ones that are inside the
     /// cache.
     fn schedule_cached(&mut self) -> Result<(), Error> {
-        let cache_mode = &self
+        let config = &self
             .dag
             .as_ref()
             .ok_or_else(|| format_err!("DAG is gone"))?
-            .config
-            .cache_mode;
+            .config;
+        let cache_mode = &config.cache_mode;
+        let verify_mode = &config.verify_mode;
         // disable the cache
         if let CacheMode::Nothing = cache_mode {
             return Ok(());
@@ -423,42 +845,57 @@ impl Scheduler {
 
         let mut not_cached = BinaryHeap::new();
         let mut cached = Vec::new();
+        let mut verifying = Vec::new();
 
-        for exec in self.ready_execs.iter() {
+        for (priority, size, exec) in self.ready_execs.iter() {
             let exec = self
                 .dag
                 .as_ref()
                 .ok_or_else(|| format_err!("DAG is gone"))?
                 .executions[exec]
                 .clone();
-            if !self.is_cacheable(&exec, &cache_mode) {
-                not_cached.push(exec.uuid);
+            if !self.is_cacheable(&exec, &cache_mode) || self.verifying.contains_key(&exec.uuid) {
+                not_cached.push((*priority, *size, exec.uuid));
                 continue;
             }
             let result = self
                 .cache
                 .get(&exec, &self.file_handles, self.file_store.as_ref());
             match result {
+                CacheResult::Hit { outputs, .. } if self.should_verify(&exec, verify_mode) => {
+                    info!("Execution {} is a cache hit, verifying it", exec.uuid);
+                    let expected = outputs
+                        .iter()
+                        .map(|(uuid, handle)| (*uuid, handle.key().clone()))
+                        .collect();
+                    verifying.push((exec.uuid, expected));
+                    not_cached.push((*priority, *size, exec.uuid));
+                }
                 CacheResult::Hit { result, outputs } => {
                     info!("Execution {} is a cache hit!", exec.uuid);
                     cached.push((exec, result, outputs));
                 }
                 CacheResult::Miss => {
-                    not_cached.push(exec.uuid);
+                    not_cached.push((*priority, *size, exec.uuid));
                 }
             }
         }
 
         self.ready_execs = not_cached;
+        self.verifying.extend(verifying);
         for (exec, result, outputs) in cached.into_iter() {
-            self.exec_completed(&exec, result, outputs)?;
+            self.exec_completed(&exec, result, vec![], outputs)?;
         }
 
         Ok(())
     }
 
-    /// Whether an execution is eligible to be fetch from the cache.
+    /// Whether an execution is eligible to be fetch from the cache. The executions with concurrent
+    /// executions are never cached.
     fn is_cacheable(&self, execution: &Execution, cache_mode: &CacheMode) -> bool {
+        if !execution.concurrent.is_empty() {
+            return false;
+        }
         if let (CacheMode::Except(set), Some(tag)) = (cache_mode, execution.tag.as_ref()) {
             if set.contains(tag) {
                 return false;
@@ -467,19 +904,115 @@ impl Scheduler {
         true
     }
 
-    /// Give to each free worker a job from the ready executions.
+    /// Whether a cached execution should be run again for verifying its determinism.
+    fn should_verify(&self, execution: &Execution, verify_mode: &VerifyMode) -> bool {
+        match (verify_mode, execution.tag.as_ref()) {
+            (VerifyMode::Nothing, _) => false,
+            (VerifyMode::Everything, _) => true,
+            (VerifyMode::Only(set), Some(tag)) => set.contains(tag),
+            (VerifyMode::Only(_), None) => false,
+        }
+    }
+
+    /// Whether the budget of the DAG has been exhausted, or the evaluation has been stopped, and no
+    /// more executions should be dispatched.
+    fn is_over_budget(&self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        let config = match self.dag.as_ref() {
+            Some(dag) => &dag.config,
+            None => return false,
+        };
+        if let Some(max_executions) = config.max_executions {
+            if self.dispatched_execs >= max_executions {
+                return true;
+            }
+        }
+        if let Some(max_cpu_time) = config.max_cpu_time {
+            if self.used_cpu_time >= max_cpu_time {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Skip all the ready executions because the budget has been exhausted or the evaluation has
+    /// been stopped.
+    fn skip_over_budget(&mut self) -> Result<(), Error> {
+        let reason = if self.stopped {
+            SkipReason::Aborted
+        } else {
+            SkipReason::OverBudget
+        };
+        for (_, _, exec) in std::mem::take(&mut self.ready_execs) {
+            warn!("Execution {} skipped: {:?}", exec, reason);
+            self.skip_execution(exec, reason)?;
+        }
+        Ok(())
+    }
+
+    /// The sum of the memory limits, in KiB, of the executions the workers are running.
+    fn memory_in_use(&self) -> u64 {
+        let dag = match self.dag.as_ref() {
+            Some(dag) => dag,
+            None => return 0,
+        };
+        self.connected_workers
+            .values()
+            .filter_map(|worker| worker.current_job.as_ref())
+            .map(|(exec, _)| memory_demand(dag, exec))
+            .sum()
+    }
+
+    /// Give to each free worker a job from the ready executions. If the budget is exhausted the
+    /// ready executions are skipped instead.
+    ///
+    /// With a memory reservation, when the next ready execution does not fit in the memory left by
+    /// the running ones no other job is assigned until some memory is freed, so that big
+    /// executions are not starved by the small ones.
     fn assign_jobs(&mut self) -> Result<(), Error> {
+        if self.is_over_budget() {
+            return self.skip_over_budget();
+        }
+        let max_executions = self.dag.as_ref().and_then(|dag| dag.config.max_executions);
+        let mut memory_in_use = self.memory_in_use();
         // borrow connected_workers as mut, file_handles as not mut
         let file_handles = &self.file_handles;
         for (worker_uuid, worker) in self.connected_workers.iter_mut() {
             if worker.current_job.is_some() {
                 continue;
             }
-            let exec = match self.ready_execs.pop() {
-                Some(exec) => exec,
+            if let Some(max_executions) = max_executions {
+                if self.dispatched_execs >= max_executions {
+                    break;
+                }
+            }
+            let exec = match self.ready_execs.peek() {
+                Some((_, _, exec)) => *exec,
                 None => break,
             };
+            if let (Some(reservation), Some(dag)) = (&self.memory_reservation, &self.dag) {
+                let demand = memory_demand(dag, &exec);
+                if !reservation.admits(memory_in_use, demand) {
+                    debug!(
+                        "Execution {} needs {} KiB but {} KiB of {} are in use, waiting",
+                        exec,
+                        demand,
+                        memory_in_use,
+                        reservation.available()
+                    );
+                    break;
+                }
+                memory_in_use += demand;
+            }
+            self.ready_execs.pop();
             worker.current_job = Some((exec, Instant::now()));
+            self.last_progress = Instant::now();
+            self.stall_reported = false;
+            self.dispatched_execs += 1;
+            self.running_execs += 1;
+            self.peak_running_execs = self.peak_running_execs.max(self.running_execs);
             let execution = self
                 .dag
                 .as_ref()
@@ -500,22 +1033,879 @@ impl Scheduler {
                     )
                 })
                 .collect();
+            let started: Vec<ExecutionUuid> = std::iter::once(exec)
+                .chain(execution.concurrent.iter().map(|e| e.uuid))
+                .collect();
             let job = WorkerJob {
                 execution,
                 dep_keys,
             };
             serialize_into(&WorkerServerMessage::Work(Box::new(job)), &worker.sender)?;
-            if self
+            let watched = &self
                 .callbacks
                 .as_ref()
                 .ok_or_else(|| format_err!("Callbacks are gone"))?
-                .executions
-                .contains(&exec)
-            {
-                self.executor
-                    .send(SchedulerOutMessage::ExecutionStarted(exec, *worker_uuid))?;
+                .executions;
+            for uuid in started {
+                if watched.contains(&uuid) {
+                    self.executor
+                        .send(SchedulerOutMessage::ExecutionStarted(uuid, *worker_uuid))?;
+                }
             }
         }
+        if self.is_over_budget() {
+            self.skip_over_budget()?;
+        }
         Ok(())
     }
 }
+
+/// The memory, in KiB, an execution is expected to need: its memory limit, or zero if it's not
+/// limited, plus the ones of its concurrent executions.
+fn memory_demand(dag: &ExecutionDAGData, exec: &ExecutionUuid) -> u64 {
+    dag.executions
+        .get(exec)
+        .map(|execution| {
+            std::iter::once(execution)
+                .chain(execution.concurrent.iter())
+                .filter_map(|execution| execution.limits.memory)
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use task_maker_dag::{ExecutionCommand, ExecutionDAG, ExecutionResourcesUsage, File};
+    use tempdir::TempDir;
+    use uuid::Uuid;
+
+    /// A fake worker: its uuid and the channel where the scheduler sends the jobs.
+    type FakeWorker = (WorkerUuid, Receiver<Vec<u8>>);
+
+    /// Make the uuid of the execution with the specified priority.
+    fn priority_uuid(priority: u8) -> ExecutionUuid {
+        let mut bytes = [0; 16];
+        bytes[15] = priority;
+        Uuid::from_bytes(bytes)
+    }
+
+    /// Make an execution with the specified memory limit. The executions with higher priority are
+    /// scheduled first.
+    fn make_execution(priority: u8, memory: u64, stdin: &File) -> Execution {
+        let mut exec = Execution::new(
+            format!("Execution {}", priority),
+            ExecutionCommand::system("true"),
+        );
+        exec.uuid = priority_uuid(priority);
+        // different arguments, so that the executions are not cached
+        exec.args(vec![priority.to_string()]);
+        exec.stdin(stdin);
+        exec.limits_mut().memory(memory);
+        exec
+    }
+
+    /// Wait for a job to be sent to one of the workers, returning the worker and the execution.
+    fn next_job(workers: &[FakeWorker]) -> (WorkerUuid, ExecutionUuid) {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(10) {
+            for (uuid, receiver) in workers {
+                if let Ok(data) = receiver.try_recv() {
+                    match bincode::deserialize(&data).unwrap() {
+                        WorkerServerMessage::Work(job) => return (*uuid, job.execution.uuid),
+                        message => panic!("Unexpected message {:?}", message),
+                    }
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("No job has been assigned");
+    }
+
+    /// Check that no job is sent to the workers for a while.
+    fn assert_no_job(workers: &[FakeWorker]) {
+        thread::sleep(Duration::from_millis(200));
+        for (_, receiver) in workers {
+            assert!(receiver.try_recv().is_err(), "Unexpected job assigned");
+        }
+    }
+
+    /// Tell the scheduler that the worker completed its job successfully.
+    fn complete_job(scheduler: &Sender<SchedulerInMessage>, worker: WorkerUuid) {
+        complete_job_with_outputs(scheduler, worker, HashMap::new());
+    }
+
+    /// Tell the scheduler that the worker completed its job successfully, producing those outputs.
+    fn complete_job_with_outputs(
+        scheduler: &Sender<SchedulerInMessage>,
+        worker: WorkerUuid,
+        outputs: HashMap<FileUuid, FileStoreHandle>,
+    ) {
+        complete_job_with_status(scheduler, worker, ExecutionStatus::Success, outputs);
+    }
+
+    /// Tell the scheduler that the worker completed its job with that status, producing those
+    /// outputs.
+    fn complete_job_with_status(
+        scheduler: &Sender<SchedulerInMessage>,
+        worker: WorkerUuid,
+        status: ExecutionStatus,
+        outputs: HashMap<FileUuid, FileStoreHandle>,
+    ) {
+        scheduler
+            .send(SchedulerInMessage::WorkerResult {
+                worker,
+                result: fake_result(status),
+                concurrent_results: vec![],
+                outputs,
+            })
+            .unwrap();
+    }
+
+    /// The result of an execution with that status that used no resources.
+    fn fake_result(status: ExecutionStatus) -> ExecutionResult {
+        ExecutionResult {
+            status,
+            was_killed: false,
+            was_cached: false,
+            truncated_output: false,
+            provenance: ExecutionProvenance::Unknown,
+            resources: ExecutionResourcesUsage {
+                cpu_time: 0.0,
+                sys_time: 0.0,
+                wall_time: 0.0,
+                memory: 0,
+                processes: None,
+                setup_time: 0.0,
+            },
+        }
+    }
+
+    /// Connect a new fake worker to the scheduler.
+    fn connect_worker(scheduler: &Sender<SchedulerInMessage>, uuid: WorkerUuid) -> FakeWorker {
+        let (sender, receiver) = channel();
+        scheduler
+            .send(SchedulerInMessage::WorkerConnected {
+                uuid,
+                name: format!("Worker {}", uuid),
+                sender,
+            })
+            .unwrap();
+        (uuid, receiver)
+    }
+
+    #[test]
+    fn test_peak_running_execs() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        let stdin = File::new("stdin");
+        let mut dag = ExecutionDAG::new();
+        for priority in 0..10 {
+            dag.add_execution(make_execution(priority, 100, &stdin));
+        }
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks: ExecutionDAGWatchSet::default(),
+        })
+        .unwrap();
+        let mut workers: Vec<_> = (0..3)
+            .map(|_| connect_worker(&tx, Uuid::new_v4()))
+            .collect();
+        let content = b"hello".to_vec();
+        let handle = file_store
+            .store(&FileStoreKey::from_content(&content), vec![content])
+            .unwrap();
+        tx.send(SchedulerInMessage::FileReady {
+            uuid: stdin.uuid,
+            handle,
+        })
+        .unwrap();
+
+        for _ in 0..10 {
+            let (worker, _) = next_job(&workers);
+            complete_job(&tx, worker);
+            // the worker asks for more work after completing its job
+            let index = workers
+                .iter()
+                .position(|(uuid, _)| *uuid == worker)
+                .unwrap();
+            workers[index] = connect_worker(&tx, worker);
+        }
+        scheduler.join().unwrap();
+        let status = executor_rx
+            .try_iter()
+            .filter_map(|message| match message {
+                SchedulerOutMessage::Status(status) => Some(status),
+                _ => None,
+            })
+            .last()
+            .expect("The final status has not been sent");
+        assert_eq!(status.peak_running_execs, 3);
+        assert_eq!(status.ready_execs, 0);
+    }
+
+    #[test]
+    fn test_memory_reservation_delays_big_execution() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, _executor_rx) = channel();
+        let reservation = MemoryReservation::new(1200, 200);
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, Some(reservation));
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        let stdin = File::new("stdin");
+        let mut dag = ExecutionDAG::new();
+        dag.add_execution(make_execution(3, 500, &stdin));
+        dag.add_execution(make_execution(2, 500, &stdin));
+        dag.add_execution(make_execution(1, 800, &stdin));
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks: ExecutionDAGWatchSet::default(),
+        })
+        .unwrap();
+        let mut workers = vec![];
+        for i in 0..3 {
+            let (sender, receiver) = channel();
+            let uuid = Uuid::new_v4();
+            tx.send(SchedulerInMessage::WorkerConnected {
+                uuid,
+                name: format!("Worker {}", i),
+                sender,
+            })
+            .unwrap();
+            workers.push((uuid, receiver));
+        }
+        let content = b"hello".to_vec();
+        let handle = file_store
+            .store(&FileStoreKey::from_content(&content), vec![content])
+            .unwrap();
+        tx.send(SchedulerInMessage::FileReady {
+            uuid: stdin.uuid,
+            handle,
+        })
+        .unwrap();
+
+        // the two small executions fill the available memory
+        let (worker_a, exec_a) = next_job(&workers);
+        let (worker_b, exec_b) = next_job(&workers);
+        let mut started = vec![exec_a, exec_b];
+        started.sort();
+        assert_eq!(started, vec![priority_uuid(2), priority_uuid(3)]);
+        assert_no_job(&workers);
+        // 500 KiB are still in use, the big execution cannot start yet
+        complete_job(&tx, worker_a);
+        assert_no_job(&workers);
+        complete_job(&tx, worker_b);
+        let (worker_c, exec_c) = next_job(&workers);
+        assert_eq!(exec_c, priority_uuid(1));
+        complete_job(&tx, worker_c);
+        scheduler.join().unwrap();
+    }
+
+    #[test]
+    fn test_largest_inputs_first() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, _executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        // the executions with the highest uuid have the smallest inputs
+        let sizes = vec![(1, 1000), (2, 100), (3, 10)];
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().largest_inputs_first(true);
+        let mut inputs = vec![];
+        for (priority, size) in sizes {
+            let stdin = File::new("stdin");
+            dag.add_execution(make_execution(priority, 0, &stdin));
+            inputs.push((stdin, vec![b'x'; size]));
+        }
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks: ExecutionDAGWatchSet::default(),
+        })
+        .unwrap();
+        for (stdin, content) in inputs {
+            let handle = file_store
+                .store(&FileStoreKey::from_content(&content), vec![content])
+                .unwrap();
+            tx.send(SchedulerInMessage::FileReady {
+                uuid: stdin.uuid,
+                handle,
+            })
+            .unwrap();
+        }
+
+        let worker = Uuid::new_v4();
+        let mut started = vec![];
+        for _ in 0..3 {
+            let workers = vec![connect_worker(&tx, worker)];
+            let (worker, exec) = next_job(&workers);
+            started.push(exec);
+            complete_job(&tx, worker);
+        }
+        scheduler.join().unwrap();
+        assert_eq!(
+            started,
+            vec![priority_uuid(1), priority_uuid(2), priority_uuid(3)]
+        );
+    }
+
+    #[test]
+    fn test_execution_priority() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, _executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        // the executions with the highest uuid have the lowest priority, even with larger inputs
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().largest_inputs_first(true);
+        let mut inputs = vec![];
+        for (uuid, priority, size) in [(1, 10, 10), (2, 0, 100), (3, -5, 1000)] {
+            let stdin = File::new("stdin");
+            let mut exec = make_execution(uuid, 0, &stdin);
+            exec.priority(priority);
+            dag.add_execution(exec);
+            inputs.push((stdin, vec![b'x'; size]));
+        }
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks: ExecutionDAGWatchSet::default(),
+        })
+        .unwrap();
+        for (stdin, content) in inputs {
+            let handle = file_store
+                .store(&FileStoreKey::from_content(&content), vec![content])
+                .unwrap();
+            tx.send(SchedulerInMessage::FileReady {
+                uuid: stdin.uuid,
+                handle,
+            })
+            .unwrap();
+        }
+
+        let worker = Uuid::new_v4();
+        let mut started = vec![];
+        for _ in 0..3 {
+            let workers = vec![connect_worker(&tx, worker)];
+            let (worker, exec) = next_job(&workers);
+            started.push(exec);
+            complete_job(&tx, worker);
+        }
+        scheduler.join().unwrap();
+        assert_eq!(
+            started,
+            vec![priority_uuid(1), priority_uuid(2), priority_uuid(3)]
+        );
+    }
+
+    #[test]
+    fn test_small_outputs_inline() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        let stdin = File::new("stdin");
+        let mut dag = ExecutionDAG::new();
+        let mut exec = make_execution(1, 0, &stdin);
+        let small = exec.stdout();
+        let large = exec.stderr();
+        let exec_uuid = exec.uuid;
+        dag.add_execution(exec);
+        let mut callbacks = ExecutionDAGWatchSet::default();
+        callbacks.files.insert(small.uuid);
+        callbacks.files.insert(large.uuid);
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks,
+        })
+        .unwrap();
+        let workers = vec![connect_worker(&tx, Uuid::new_v4())];
+        let store = |content: Vec<u8>| {
+            file_store
+                .store(&FileStoreKey::from_content(&content), vec![content])
+                .unwrap()
+        };
+        tx.send(SchedulerInMessage::FileReady {
+            uuid: stdin.uuid,
+            handle: store(b"hello".to_vec()),
+        })
+        .unwrap();
+
+        let (worker, _) = next_job(&workers);
+        let small_content = b"small".to_vec();
+        let large_content = vec![b'x'; INLINE_OUTPUT_LIMIT as usize + 1];
+        let mut outputs = HashMap::new();
+        outputs.insert(small.uuid, store(small_content.clone()));
+        outputs.insert(large.uuid, store(large_content));
+        complete_job_with_outputs(&tx, worker, outputs);
+        scheduler.join().unwrap();
+
+        let mut inline = None;
+        let mut ready = vec![];
+        for message in executor_rx.try_iter() {
+            match message {
+                SchedulerOutMessage::ExecutionDone(uuid, _, outputs) => {
+                    assert_eq!(uuid, exec_uuid);
+                    inline = Some(outputs);
+                }
+                SchedulerOutMessage::FileReady(uuid, _, _) => ready.push(uuid),
+                _ => {}
+            }
+        }
+        let inline = inline.expect("The execution completion has not been sent");
+        assert_eq!(inline.len(), 1);
+        assert_eq!(inline[&small.uuid], small_content);
+        assert_eq!(ready, vec![large.uuid]);
+    }
+
+    #[test]
+    fn test_stop_skips_dependents_as_aborted() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        let stdin = File::new("stdin");
+        let mut dag = ExecutionDAG::new();
+        let mut completed = make_execution(1, 0, &stdin);
+        let completed_output = completed.stdout();
+        let mut killed = make_execution(2, 0, &stdin);
+        let killed_output = killed.stdout();
+        let mut dependent = make_execution(3, 0, &completed_output);
+        let dependent_output = dependent.stdout();
+        let transitive = make_execution(4, 0, &dependent_output);
+        let dependent2 = make_execution(5, 0, &killed_output);
+        let mut callbacks = ExecutionDAGWatchSet::default();
+        for exec in &[&dependent, &transitive, &dependent2] {
+            callbacks.executions.insert(exec.uuid);
+        }
+        let (completed_uuid, dependents) = (
+            completed.uuid,
+            vec![dependent.uuid, transitive.uuid, dependent2.uuid],
+        );
+        for exec in [completed, killed, dependent, transitive, dependent2] {
+            dag.add_execution(exec);
+        }
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks,
+        })
+        .unwrap();
+        let workers = vec![
+            connect_worker(&tx, Uuid::new_v4()),
+            connect_worker(&tx, Uuid::new_v4()),
+        ];
+        let store = |content: Vec<u8>| {
+            file_store
+                .store(&FileStoreKey::from_content(&content), vec![content])
+                .unwrap()
+        };
+        tx.send(SchedulerInMessage::FileReady {
+            uuid: stdin.uuid,
+            handle: store(b"hello".to_vec()),
+        })
+        .unwrap();
+        let jobs = vec![next_job(&workers), next_job(&workers)];
+
+        // the evaluation is aborted while the upstream executions are running: one completes, the
+        // other is killed
+        tx.send(SchedulerInMessage::Stop).unwrap();
+        for (worker, exec) in jobs {
+            if exec == completed_uuid {
+                let mut outputs = HashMap::new();
+                outputs.insert(completed_output.uuid, store(b"world".to_vec()));
+                complete_job_with_outputs(&tx, worker, outputs);
+            } else {
+                let mut outputs = HashMap::new();
+                outputs.insert(killed_output.uuid, store(vec![]));
+                let status = ExecutionStatus::InternalError("The sandbox has been killed".into());
+                complete_job_with_status(&tx, worker, status, outputs);
+            }
+        }
+        scheduler.join().unwrap();
+        assert_no_job(&workers);
+
+        let skipped: HashMap<_, _> = executor_rx
+            .try_iter()
+            .filter_map(|message| match message {
+                SchedulerOutMessage::ExecutionSkipped(exec, reason) => Some((exec, reason)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(skipped.len(), dependents.len());
+        for exec in dependents {
+            assert_eq!(skipped[&exec], SkipReason::Aborted);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_executions_results() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        let stdin = File::new("stdin");
+        let mut dag = ExecutionDAG::new();
+        let mut manager = make_execution(1, 100, &stdin);
+        let manager_output = manager.stdout();
+        let mut solution = make_execution(2, 200, &stdin);
+        let solution_output = solution.stderr();
+        let solution_uuid = solution.uuid;
+        manager.add_concurrent(solution);
+        let checker = make_execution(3, 0, &manager_output);
+        let dependent = make_execution(4, 0, &solution_output);
+        let mut callbacks = ExecutionDAGWatchSet::default();
+        for exec in &[solution_uuid, checker.uuid, dependent.uuid] {
+            callbacks.executions.insert(*exec);
+        }
+        callbacks.files.insert(manager_output.uuid);
+        callbacks.files.insert(solution_output.uuid);
+        let (manager_uuid, checker_uuid, dependent_uuid) =
+            (manager.uuid, checker.uuid, dependent.uuid);
+        for exec in [manager, checker, dependent] {
+            dag.add_execution(exec);
+        }
+        assert_eq!(memory_demand(&dag.data, &manager_uuid), 300);
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks,
+        })
+        .unwrap();
+        let store = |content: Vec<u8>| {
+            file_store
+                .store(&FileStoreKey::from_content(&content), vec![content])
+                .unwrap()
+        };
+        tx.send(SchedulerInMessage::FileReady {
+            uuid: stdin.uuid,
+            handle: store(b"hello".to_vec()),
+        })
+        .unwrap();
+
+        // the manager succeeds while the solution fails: only the outputs of the solution fail
+        let worker = Uuid::new_v4();
+        let workers = vec![connect_worker(&tx, worker)];
+        let (worker, exec) = next_job(&workers);
+        assert_eq!(exec, manager_uuid);
+        let mut outputs = HashMap::new();
+        outputs.insert(manager_output.uuid, store(b"world".to_vec()));
+        outputs.insert(solution_output.uuid, store(vec![]));
+        tx.send(SchedulerInMessage::WorkerResult {
+            worker,
+            result: fake_result(ExecutionStatus::Success),
+            concurrent_results: vec![fake_result(ExecutionStatus::ReturnCode(1))],
+            outputs,
+        })
+        .unwrap();
+        let workers = vec![connect_worker(&tx, worker)];
+        let (worker, exec) = next_job(&workers);
+        assert_eq!(exec, checker_uuid);
+        complete_job(&tx, worker);
+        scheduler.join().unwrap();
+        assert_no_job(&workers);
+
+        let messages: Vec<_> = executor_rx.try_iter().collect();
+        assert!(messages.iter().any(|message| match message {
+            SchedulerOutMessage::ExecutionStarted(uuid, _) => *uuid == solution_uuid,
+            _ => false,
+        }));
+        // each execution carries its own outputs, with its own result
+        assert!(messages.iter().any(|message| match message {
+            SchedulerOutMessage::ExecutionDone(uuid, result, outputs) =>
+                *uuid == solution_uuid
+                    && result.status == ExecutionStatus::ReturnCode(1)
+                    && outputs.len() == 1
+                    && outputs[&solution_output.uuid].is_empty(),
+            _ => false,
+        }));
+        assert!(messages.iter().any(|message| match message {
+            SchedulerOutMessage::ExecutionDone(uuid, result, outputs) =>
+                *uuid == manager_uuid
+                    && result.status == ExecutionStatus::Success
+                    && outputs.len() == 1
+                    && outputs[&manager_output.uuid] == b"world",
+            _ => false,
+        }));
+        assert!(messages.iter().any(|message| match message {
+            SchedulerOutMessage::ExecutionSkipped(uuid, reason) =>
+                *uuid == dependent_uuid && *reason == SkipReason::FailedDependency,
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn test_kill_running_jobs() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        let stdin = File::new("stdin");
+        let mut dag = ExecutionDAG::new();
+        let mut exec = make_execution(1, 0, &stdin);
+        let output = exec.stdout();
+        let dependent = make_execution(2, 0, &output);
+        let dependent_uuid = dependent.uuid;
+        dag.add_execution(exec);
+        dag.add_execution(dependent);
+        let mut callbacks = ExecutionDAGWatchSet::default();
+        callbacks.executions.insert(dependent_uuid);
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks,
+        })
+        .unwrap();
+        let workers = vec![
+            connect_worker(&tx, Uuid::new_v4()),
+            connect_worker(&tx, Uuid::new_v4()),
+        ];
+        let store = |content: Vec<u8>| {
+            file_store
+                .store(&FileStoreKey::from_content(&content), vec![content])
+                .unwrap()
+        };
+        tx.send(SchedulerInMessage::FileReady {
+            uuid: stdin.uuid,
+            handle: store(b"hello".to_vec()),
+        })
+        .unwrap();
+        let (worker, _) = next_job(&workers);
+
+        // only the busy worker is asked to kill its job. The messages are processed in order, so
+        // when the status is reported the workers have already been asked.
+        tx.send(SchedulerInMessage::Kill).unwrap();
+        tx.send(SchedulerInMessage::Status).unwrap();
+        let mut messages = vec![];
+        loop {
+            match executor_rx.recv_timeout(Duration::from_secs(10)).unwrap() {
+                SchedulerOutMessage::Status(_) => break,
+                message => messages.push(message),
+            }
+        }
+        for (uuid, receiver) in &workers {
+            let message = receiver
+                .try_recv()
+                .map(|data| bincode::deserialize::<WorkerServerMessage>(&data).unwrap());
+            if *uuid == worker {
+                assert!(matches!(message, Ok(WorkerServerMessage::KillJob)));
+            } else {
+                assert!(message.is_err(), "Unexpected message {:?}", message);
+            }
+        }
+        let mut outputs = HashMap::new();
+        outputs.insert(output.uuid, store(vec![]));
+        let status = ExecutionStatus::InternalError("The sandbox has been killed".into());
+        complete_job_with_status(&tx, worker, status, outputs);
+        scheduler.join().unwrap();
+
+        let skipped: Vec<_> = messages
+            .into_iter()
+            .chain(executor_rx.try_iter())
+            .filter_map(|message| match message {
+                SchedulerOutMessage::ExecutionSkipped(exec, reason) => Some((exec, reason)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(skipped, vec![(dependent_uuid, SkipReason::Aborted)]);
+    }
+
+    #[test]
+    fn test_stall_watchdog_dump() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, _executor_rx) = channel();
+        let dump_path = tmpdir.path().join("stall.txt");
+        let mut scheduler = Scheduler::new(cache, file_store, executor_tx, None);
+        scheduler
+            .stall_watchdog(StallWatchdog::new(Duration::from_millis(100)).dump_path(&dump_path));
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        // the input of the execution is never provided, the evaluation is stalled
+        let stdin = File::new("stdin");
+        let exec = make_execution(1, 0, &stdin);
+        let exec_uuid = exec.uuid;
+        let mut dag = ExecutionDAG::new();
+        dag.add_execution(exec);
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks: ExecutionDAGWatchSet::default(),
+        })
+        .unwrap();
+        let _workers = vec![connect_worker(&tx, Uuid::new_v4())];
+        thread::sleep(Duration::from_millis(50));
+        assert!(!dump_path.exists());
+        // the status requests are not a progress
+        for _ in 0..5 {
+            tx.send(SchedulerInMessage::Status).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        thread::sleep(Duration::from_millis(300));
+        tx.send(SchedulerInMessage::Exit).unwrap();
+        scheduler.join().unwrap();
+
+        let dump = std::fs::read_to_string(&dump_path).unwrap();
+        assert!(dump.contains("Ready executions (0)"), "{}", dump);
+        assert!(dump.contains("Waiting executions (1)"), "{}", dump);
+        assert!(dump.contains(&format!("Execution 1 ({}), missing:", exec_uuid)));
+        assert!(dump.contains(&format!("{} from the client", stdin.uuid)));
+        assert!(dump.contains("Workers (1)"), "{}", dump);
+        assert!(dump.contains(": idle"), "{}", dump);
+    }
+
+    #[test]
+    fn test_requeue_job_of_disconnected_worker() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        let stdin = File::new("stdin");
+        let mut dag = ExecutionDAG::new();
+        let mut exec = make_execution(1, 0, &stdin);
+        let output = exec.stdout();
+        let dependent = make_execution(2, 0, &output);
+        let (exec_uuid, dependent_uuid) = (exec.uuid, dependent.uuid);
+        dag.add_execution(exec);
+        dag.add_execution(dependent);
+        dag.config_mut().max_executions(Some(2));
+        let mut callbacks = ExecutionDAGWatchSet::default();
+        callbacks.executions.insert(exec_uuid);
+        callbacks.executions.insert(dependent_uuid);
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks,
+        })
+        .unwrap();
+        let mut workers = vec![
+            connect_worker(&tx, Uuid::new_v4()),
+            connect_worker(&tx, Uuid::new_v4()),
+        ];
+        let store = |content: Vec<u8>| {
+            file_store
+                .store(&FileStoreKey::from_content(&content), vec![content])
+                .unwrap()
+        };
+        tx.send(SchedulerInMessage::FileReady {
+            uuid: stdin.uuid,
+            handle: store(b"hello".to_vec()),
+        })
+        .unwrap();
+
+        // the worker dies in the middle of the execution
+        let (dead, job) = next_job(&workers);
+        assert_eq!(job, exec_uuid);
+        workers.retain(|(uuid, _)| *uuid != dead);
+        tx.send(SchedulerInMessage::WorkerDisconnected { uuid: dead })
+            .unwrap();
+        // the idle worker gets the job, and the lost run is not counted in the budget
+        let (worker, job) = next_job(&workers);
+        assert_eq!(job, exec_uuid);
+        let mut outputs = HashMap::new();
+        outputs.insert(output.uuid, store(b"world".to_vec()));
+        complete_job_with_outputs(&tx, worker, outputs);
+        workers = vec![connect_worker(&tx, worker)];
+        let (worker, job) = next_job(&workers);
+        assert_eq!(job, dependent_uuid);
+        complete_job(&tx, worker);
+        scheduler.join().unwrap();
+
+        let done: Vec<_> = executor_rx
+            .try_iter()
+            .filter_map(|message| match message {
+                SchedulerOutMessage::ExecutionDone(uuid, _, _) => Some(uuid),
+                SchedulerOutMessage::ExecutionSkipped(uuid, _) => {
+                    panic!("Execution {} skipped", uuid)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(done, vec![exec_uuid, dependent_uuid]);
+    }
+
+    #[test]
+    fn test_cache_written_during_evaluation() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let cache = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let (executor_tx, _executor_rx) = channel();
+        let scheduler = Scheduler::new(cache, file_store.clone(), executor_tx, None);
+        let (tx, rx) = channel();
+        let scheduler = thread::spawn(move || scheduler.work(rx).unwrap());
+
+        let stdin = File::new("stdin");
+        let mut dag = ExecutionDAG::new();
+        let first = make_execution(2, 0, &stdin);
+        let second = make_execution(1, 0, &stdin);
+        dag.add_execution(first.clone());
+        dag.add_execution(second);
+        tx.send(SchedulerInMessage::DAG {
+            dag: dag.data,
+            callbacks: ExecutionDAGWatchSet::default(),
+        })
+        .unwrap();
+        let workers = vec![connect_worker(&tx, Uuid::new_v4())];
+        let content = b"hello".to_vec();
+        let handle = file_store
+            .store(&FileStoreKey::from_content(&content), vec![content])
+            .unwrap();
+        tx.send(SchedulerInMessage::FileReady {
+            uuid: stdin.uuid,
+            handle: handle.clone(),
+        })
+        .unwrap();
+
+        let (worker, job) = next_job(&workers);
+        assert_eq!(job, first.uuid);
+        complete_job(&tx, worker);
+        let workers = vec![connect_worker(&tx, worker)];
+        let (worker, _) = next_job(&workers);
+        // the evaluation is still going on, but the first execution would already be a cache hit
+        // for a new one
+        let mut other = Cache::new(tmpdir.path().join("cache")).unwrap();
+        let mut file_handles = HashMap::new();
+        file_handles.insert(stdin.uuid, handle);
+        match other.get(&first, &file_handles, &file_store) {
+            CacheResult::Hit { result, .. } => assert!(result.was_cached),
+            CacheResult::Miss => panic!("The execution is not in the cache"),
+        }
+        complete_job(&tx, worker);
+        scheduler.join().unwrap();
+    }
+}
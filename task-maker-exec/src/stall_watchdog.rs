@@ -0,0 +1,32 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A watchdog that detects the stalled evaluations: when no execution starts or completes for
+/// `interval` the scheduler dumps its state, the ready, waiting and running executions with the
+/// dependencies they are missing, to the log and optionally to a file. The dump is produced once
+/// per stall, a new one is produced only after the evaluation makes some progress and then stalls
+/// again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StallWatchdog {
+    /// How long the evaluation can go on without progress before being considered stalled.
+    pub interval: Duration,
+    /// The file where the diagnostics are written, overwriting it, if any.
+    pub dump_path: Option<PathBuf>,
+}
+
+impl StallWatchdog {
+    /// Make a new watchdog that considers the evaluation stalled after `interval` without progress.
+    /// The diagnostics are only logged.
+    pub fn new(interval: Duration) -> StallWatchdog {
+        StallWatchdog {
+            interval,
+            dump_path: None,
+        }
+    }
+
+    /// Write the diagnostics also to the specified file.
+    pub fn dump_path<P: AsRef<Path>>(mut self, path: P) -> StallWatchdog {
+        self.dump_path = Some(path.as_ref().to_owned());
+        self
+    }
+}
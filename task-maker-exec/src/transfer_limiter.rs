@@ -0,0 +1,112 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+/// The default number of files the client can transfer to the server at the same time.
+pub const DEFAULT_MAX_CONCURRENT_TRANSFERS: usize = 4;
+
+/// Bound on the number of file transfers in flight at the same time. A transfer is in flight from
+/// when its permit is acquired until the permit is dropped, all the other transfers wait for a
+/// free slot.
+///
+/// Cloning the limiter gives a handle to the same set of slots.
+#[derive(Debug, Clone)]
+pub struct TransferLimiter {
+    /// The maximum number of transfers in flight.
+    limit: usize,
+    /// The number of transfers currently in flight, with the condition variable notified when a
+    /// slot is released.
+    in_flight: Arc<(Mutex<usize>, Condvar)>,
+}
+
+/// A slot of a `TransferLimiter`, the slot is released when this is dropped.
+#[derive(Debug)]
+pub struct TransferPermit {
+    /// The limiter that issued this permit.
+    in_flight: Arc<(Mutex<usize>, Condvar)>,
+}
+
+// the counter is a mutex since it's used with the condition variable
+#[allow(clippy::mutex_atomic)]
+impl TransferLimiter {
+    /// Make a new limiter allowing at most `limit` transfers at the same time. A limit of zero is
+    /// treated as one, otherwise no transfer could ever start.
+    pub fn new(limit: usize) -> TransferLimiter {
+        TransferLimiter {
+            limit: std::cmp::max(limit, 1),
+            in_flight: Arc::new((Mutex::new(0), Condvar::new())),
+        }
+    }
+
+    /// The maximum number of transfers in flight.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// The number of transfers currently in flight.
+    pub fn in_flight(&self) -> usize {
+        *self.in_flight.0.lock().unwrap()
+    }
+
+    /// Wait until a slot is free and take it. The slot is released when the returned permit is
+    /// dropped.
+    pub fn acquire(&self) -> TransferPermit {
+        let (lock, cvar) = &*self.in_flight;
+        let mut in_flight = lock.lock().unwrap();
+        while *in_flight >= self.limit {
+            in_flight = cvar.wait(in_flight).unwrap();
+        }
+        *in_flight += 1;
+        TransferPermit {
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+impl Drop for TransferPermit {
+    fn drop(&mut self) {
+        let (lock, cvar) = &*self.in_flight;
+        *lock.lock().unwrap() -= 1;
+        cvar.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_transfer_limiter_bounds_in_flight() {
+        let limiter = TransferLimiter::new(3);
+        let max_seen = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..12)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let max_seen = max_seen.clone();
+                thread::spawn(move || {
+                    let _permit = limiter.acquire();
+                    max_seen.fetch_max(limiter.in_flight(), Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let max_seen = max_seen.load(Ordering::SeqCst);
+        assert!(max_seen <= 3, "{} transfers in flight", max_seen);
+        assert!(max_seen > 1, "the transfers never overlapped");
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_transfer_limiter_zero_limit() {
+        let limiter = TransferLimiter::new(0);
+        assert_eq!(limiter.limit(), 1);
+        let permit = limiter.acquire();
+        assert_eq!(limiter.in_flight(), 1);
+        drop(permit);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+}
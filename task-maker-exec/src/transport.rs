@@ -0,0 +1,352 @@
+//! Abstraction over the channels used for the communication between the client, the executor and
+//! the workers.
+//!
+//! The messages are exchanged as serialized chunks of bytes, so any medium able to move `Vec<u8>`
+//! between two parties can be used. Since the two directions of the communication are usually
+//! owned by different threads a transport is split in a sending and a receiving half.
+
+use failure::{bail, Error};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The maximum size, in bytes, of a frame sent over a TCP transport. It's a bit more than the size
+/// of the chunks of the files, bigger chunks of data are split in more frames. Bigger frames are
+/// considered a protocol error and rejected before allocating their buffer, so that a bogus length
+/// does not exhaust the memory.
+pub const MAX_TCP_FRAME_SIZE: u64 = 64 * 1024;
+
+/// The maximum size, in bytes, of a chunk of data received from a TCP transport, i.e. of the sum of
+/// its frames.
+pub const MAX_TCP_CHUNK_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// The bit of the length of a frame set when the chunk continues in the next frame.
+const TCP_FRAME_CONTINUES: u64 = 1 << 63;
+
+/// The sending half of a transport.
+pub trait TransportSender: Send {
+    /// Send a chunk of data to the other party.
+    fn send(&self, data: Vec<u8>) -> Result<(), Error>;
+}
+
+/// The receiving half of a transport.
+pub trait TransportReceiver: Send {
+    /// Receive a chunk of data from the other party, blocking until it's available. Fails if the
+    /// other party is gone.
+    fn recv(&self) -> Result<Vec<u8>, Error>;
+
+    /// Same as [`recv`](#tymethod.recv), but wait at most `timeout` for the chunk to start
+    /// arriving. Returns `None` if nothing has been received in time.
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<Vec<u8>>, Error>;
+}
+
+/// A bidirectional transport, able to both send and receive chunks of data.
+pub trait Transport: TransportSender + TransportReceiver {}
+
+impl<T: TransportSender + TransportReceiver> Transport for T {}
+
+impl TransportSender for Sender<Vec<u8>> {
+    fn send(&self, data: Vec<u8>) -> Result<(), Error> {
+        Sender::send(self, data).map_err(|e| e.into())
+    }
+}
+
+impl TransportReceiver for Receiver<Vec<u8>> {
+    fn recv(&self) -> Result<Vec<u8>, Error> {
+        Receiver::recv(self).map_err(|e| e.into())
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+        match Receiver::recv_timeout(self, timeout) {
+            Ok(data) => Ok(Some(data)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            // report a closed channel like `recv` does
+            Err(RecvTimeoutError::Disconnected) => Err(RecvError.into()),
+        }
+    }
+}
+
+/// The sending half of a transport over a TCP connection. Each chunk of data is split in frames of
+/// at most `MAX_TCP_FRAME_SIZE` bytes, each one prefixed by its length as a little-endian `u64`.
+/// The highest bit of the length is set in all the frames of the chunk but the last. The clones of this sender share the same connection, like
+/// for a channel when all of them are dropped the `recv` of the other party fails.
+#[derive(Clone)]
+pub struct TcpTransportSender {
+    /// The connection, shared by all the clones.
+    writer: Arc<TcpWriter>,
+}
+
+/// The writing side of a TCP connection, closed when dropped.
+struct TcpWriter {
+    /// The connection, locked while a whole chunk is written.
+    stream: Mutex<TcpStream>,
+}
+
+/// The receiving half of a transport over a TCP connection, see
+/// [`TcpTransportSender`](struct.TcpTransportSender.html) for the format of the data.
+pub struct TcpTransportReceiver {
+    /// The connection.
+    stream: TcpStream,
+}
+
+/// Split a TCP connection into the two halves of a transport.
+pub fn tcp_transport(
+    stream: TcpStream,
+) -> Result<(TcpTransportSender, TcpTransportReceiver), Error> {
+    stream.set_nodelay(true)?;
+    let receiver = TcpTransportReceiver {
+        stream: stream.try_clone()?,
+    };
+    let sender = TcpTransportSender {
+        writer: Arc::new(TcpWriter {
+            stream: Mutex::new(stream),
+        }),
+    };
+    Ok((sender, receiver))
+}
+
+impl TcpTransportSender {
+    /// Close the connection in both directions. The pending and the future `recv` of the other
+    /// half fail.
+    pub fn shutdown(&self) {
+        let _ = self.writer.stream.lock().unwrap().shutdown(Shutdown::Both);
+    }
+}
+
+impl Drop for TcpWriter {
+    fn drop(&mut self) {
+        let _ = self.stream.lock().unwrap().shutdown(Shutdown::Write);
+    }
+}
+
+impl TransportSender for TcpTransportSender {
+    fn send(&self, data: Vec<u8>) -> Result<(), Error> {
+        let mut frames = Vec::with_capacity(8 + data.len());
+        let mut chunks = data.chunks(MAX_TCP_FRAME_SIZE as usize).peekable();
+        if chunks.peek().is_none() {
+            frames.extend_from_slice(&0u64.to_le_bytes());
+        }
+        while let Some(chunk) = chunks.next() {
+            let mut len = chunk.len() as u64;
+            if chunks.peek().is_some() {
+                len |= TCP_FRAME_CONTINUES;
+            }
+            frames.extend_from_slice(&len.to_le_bytes());
+            frames.extend_from_slice(chunk);
+        }
+        self.writer.stream.lock().unwrap().write_all(&frames)?;
+        Ok(())
+    }
+}
+
+impl TransportReceiver for TcpTransportReceiver {
+    fn recv(&self) -> Result<Vec<u8>, Error> {
+        let mut stream = &self.stream;
+        let mut data = Vec::new();
+        loop {
+            let mut len = [0; 8];
+            stream.read_exact(&mut len)?;
+            let len = u64::from_le_bytes(len);
+            let continues = len & TCP_FRAME_CONTINUES != 0;
+            let len = len & !TCP_FRAME_CONTINUES;
+            if len > MAX_TCP_FRAME_SIZE {
+                bail!(
+                    "Received a frame of {} bytes, the connection is broken",
+                    len
+                );
+            }
+            if data.len() as u64 + len > MAX_TCP_CHUNK_SIZE {
+                bail!("Received a chunk too big, the connection is broken");
+            }
+            let start = data.len();
+            data.resize(start + len as usize, 0);
+            stream.read_exact(&mut data[start..])?;
+            if !continues {
+                return Ok(data);
+            }
+        }
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+        // wait for the first byte without consuming it, then read the whole chunk
+        self.stream
+            .set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+        let ready = self.stream.peek(&mut [0]);
+        self.stream.set_read_timeout(None)?;
+        match ready {
+            Ok(_) => self.recv().map(Some),
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Spawn a thread that forwards all the chunks received from `receiver` to `sender`, until one of
+/// the two parties is gone. `on_exit` is called just before the thread exits.
+pub(crate) fn spawn_forwarder<R, S, F>(
+    name: String,
+    receiver: R,
+    sender: S,
+    on_exit: F,
+) -> Result<thread::JoinHandle<()>, Error>
+where
+    R: TransportReceiver + 'static,
+    S: TransportSender + 'static,
+    F: FnOnce() + Send + 'static,
+{
+    Ok(thread::Builder::new().name(name).spawn(move || {
+        while let Ok(data) = receiver.recv() {
+            if sender.send(data).is_err() {
+                break;
+            }
+        }
+        on_exit();
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{ChannelFileIterator, ChannelFileSender};
+    use crate::{deserialize_from, serialize_into};
+    use failure::format_err;
+    use serde::{Deserialize, Serialize};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// A transport that sends the messages to itself.
+    #[derive(Default)]
+    struct MockTransport {
+        queue: Mutex<VecDeque<Vec<u8>>>,
+    }
+
+    impl TransportSender for MockTransport {
+        fn send(&self, data: Vec<u8>) -> Result<(), Error> {
+            self.queue.lock().unwrap().push_back(data);
+            Ok(())
+        }
+    }
+
+    impl TransportReceiver for MockTransport {
+        fn recv(&self) -> Result<Vec<u8>, Error> {
+            self.queue
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| format_err!("No message"))
+        }
+
+        fn recv_timeout(&self, _timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.queue.lock().unwrap().pop_front())
+        }
+    }
+
+    #[test]
+    fn test_mock_transport_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Message {
+            Foo(u32),
+            Bar { name: String },
+        }
+
+        let transport = MockTransport::default();
+        serialize_into(&Message::Foo(42), &transport).unwrap();
+        serialize_into(&Message::Bar { name: "bar".into() }, &transport).unwrap();
+        ChannelFileSender::send_data(b"file content".to_vec(), &transport).unwrap();
+
+        assert_eq!(
+            deserialize_from::<Message>(&transport).unwrap(),
+            Message::Foo(42)
+        );
+        assert_eq!(
+            deserialize_from::<Message>(&transport).unwrap(),
+            Message::Bar { name: "bar".into() }
+        );
+        let content: Vec<u8> = ChannelFileIterator::new(&transport).flatten().collect();
+        assert_eq!(content, b"file content");
+        assert!(deserialize_from::<Message>(&transport).is_err());
+    }
+
+    #[test]
+    fn test_tcp_transport() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let (sender, receiver) = tcp_transport(TcpStream::connect(addr).unwrap()).unwrap();
+            // echo back all the chunks
+            while let Ok(data) = receiver.recv() {
+                sender.send(data).unwrap();
+            }
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let (sender, receiver) = tcp_transport(stream).unwrap();
+        serialize_into(&42u32, &sender).unwrap();
+        sender.send(vec![]).unwrap();
+        ChannelFileSender::send_data(vec![7; 100_000], &sender).unwrap();
+
+        assert_eq!(deserialize_from::<u32>(&receiver).unwrap(), 42);
+        assert_eq!(receiver.recv().unwrap(), Vec::<u8>::new());
+        let content: Vec<u8> = ChannelFileIterator::new(&receiver).flatten().collect();
+        assert_eq!(content, vec![7; 100_000]);
+        // like a channel, dropping the sender closes the connection
+        drop(sender);
+        client.join().unwrap();
+        assert!(receiver.recv().is_err());
+    }
+
+    #[test]
+    fn test_tcp_transport_split_frames() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let data: Vec<u8> = (0..3 * MAX_TCP_FRAME_SIZE + 42).map(|i| i as u8).collect();
+        let expected = data.clone();
+        let client = thread::spawn(move || {
+            let (sender, _receiver) = tcp_transport(TcpStream::connect(addr).unwrap()).unwrap();
+            sender.send(data).unwrap();
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let (_sender, receiver) = tcp_transport(stream).unwrap();
+        assert_eq!(receiver.recv().unwrap(), expected);
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_tcp_transport_recv_timeout() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (client_sender, _client_receiver) =
+            tcp_transport(TcpStream::connect(addr).unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let (_sender, receiver) = tcp_transport(stream).unwrap();
+        let timeout = Duration::from_millis(50);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), None);
+        client_sender.send(vec![1, 2, 3]).unwrap();
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), Some(vec![1, 2, 3]));
+        // the connection is blocking again
+        client_sender.send(vec![4]).unwrap();
+        assert_eq!(receiver.recv().unwrap(), vec![4]);
+        drop(client_sender);
+        assert!(receiver.recv_timeout(timeout).is_err());
+    }
+
+    #[test]
+    fn test_tcp_transport_frame_too_big() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(&(MAX_TCP_FRAME_SIZE + 1).to_le_bytes())
+                .unwrap();
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let (_sender, receiver) = tcp_transport(stream).unwrap();
+        assert!(receiver.recv().is_err());
+        client.join().unwrap();
+    }
+}
@@ -1,23 +1,33 @@
 use crate::proto::*;
 use crate::*;
-use failure::{Error, Fail};
+use failure::{bail, format_err, Error, Fail};
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 use task_maker_dag::*;
 use task_maker_store::*;
 use uuid::Uuid;
 
+/// The directory, inside the sandbox directory, where the files received only partially are kept.
+const PARTIAL_FILES_DIR: &str = "partial";
+
 /// The information about the current job the worker is doing.
 struct WorkerCurrentJob {
     /// Job currently waiting for, when there is a job running this should be `None`
     current_job: Option<(Box<WorkerJob>, HashMap<FileUuid, FileStoreHandle>)>,
-    /// The currently running sandbox.
-    current_sandbox: Option<Sandbox>,
+    /// The currently running sandboxes, the one of the execution followed by the ones of its
+    /// concurrent executions.
+    current_sandboxes: Vec<Sandbox>,
+    /// The thread running the last sandbox, it's joined before exiting so that the sandbox
+    /// directory is cleaned up.
+    sandbox_thread: Option<thread::JoinHandle<()>>,
     /// The dependencies that are missing and required for the execution start.
     missing_deps: HashMap<FileStoreKey, FileUuid>,
+    /// Whether the server asked to kill the job before its sandbox has been started.
+    killed: bool,
 }
 
 /// The worker is the component that receives the work from the server and sends the results back.
@@ -38,6 +48,17 @@ pub(crate) struct Worker {
     current_job: Arc<Mutex<WorkerCurrentJob>>,
     /// Where to put the sandboxes.
     sandbox_path: PathBuf,
+    /// How many times a file received corrupted is asked again to the server.
+    pub(crate) transfer_retries: usize,
+    /// The implementation of the sandbox to use.
+    pub(crate) sandbox_backend: SandboxBackend,
+    /// The token to authenticate with to the server, if any.
+    pub(crate) auth_token: Option<AuthToken>,
+    /// Whether the files are transferred compressed.
+    pub(crate) compression: bool,
+    /// Whether the files received only partially are kept, for resuming their transfer after
+    /// reconnecting. Useful only when the server is reached through the network.
+    pub(crate) resume_transfers: bool,
 }
 
 /// An handle of the connection to the worker.
@@ -65,8 +86,10 @@ impl WorkerCurrentJob {
     fn new() -> WorkerCurrentJob {
         WorkerCurrentJob {
             current_job: None,
-            current_sandbox: None,
+            current_sandboxes: vec![],
+            sandbox_thread: None,
             missing_deps: HashMap::new(),
+            killed: false,
         }
     }
 }
@@ -94,6 +117,11 @@ impl Worker {
                 file_store,
                 current_job: Arc::new(Mutex::new(WorkerCurrentJob::new())),
                 sandbox_path,
+                transfer_retries: DEFAULT_TRANSFER_RETRIES,
+                sandbox_backend: SandboxBackend::default(),
+                auth_token: None,
+                compression: false,
+                resume_transfers: false,
             },
             WorkerConn {
                 uuid,
@@ -106,15 +134,51 @@ impl Worker {
 
     /// The worker body, this function will block until the worker disconnects.
     pub fn work(self) -> Result<(), Error> {
-        trace!("Worker {} ready, asking for work", self);
-        serialize_into(&WorkerClientMessage::GetWork, &self.sender)?;
+        serialize_into(
+            &WorkerClientMessage::Hello(PROTOCOL_VERSION.to_string()),
+            &self.sender,
+        )?;
 
         let start_job = || -> Result<(), Error> {
-            let sandbox = execute_job(self.current_job.clone(), &self.sender, &self.sandbox_path)?;
-            self.current_job.lock().unwrap().current_sandbox = Some(sandbox);
+            let (sandboxes, sandbox_thread) = execute_job(
+                self.current_job.clone(),
+                &self.sender,
+                &self.sandbox_path,
+                &self.sandbox_backend,
+                self.compression,
+            )?;
+            let mut current_job = self.current_job.lock().unwrap();
+            if std::mem::replace(&mut current_job.killed, false) {
+                sandboxes.iter().for_each(Sandbox::kill);
+            }
+            current_job.current_sandboxes = sandboxes;
+            current_job.sandbox_thread = Some(sandbox_thread);
             Ok(())
         };
 
+        // the files received only partially before the connection dropped are kept there, so that
+        // after reconnecting only the rest of them is asked to the server
+        let partial_files = if self.resume_transfers {
+            Some(PartialFileReceiver::new(
+                self.sandbox_path.join(PARTIAL_FILES_DIR),
+            )?)
+        } else {
+            None
+        };
+        let ask_file = |key: FileStoreKey| -> Result<(), Error> {
+            let offset = partial_files
+                .as_ref()
+                .map_or(0, |partial| partial.offset(&key));
+            if offset > 0 {
+                info!("Resuming the transfer of {:?} from {}", key, offset);
+                serialize_into(&WorkerClientMessage::ResumeFile(key, offset), &self.sender)
+            } else {
+                serialize_into(&WorkerClientMessage::AskFile(key), &self.sender)
+            }
+        };
+
+        // the number of times each file has been received corrupted
+        let mut corrupted_transfers: HashMap<FileStoreKey, usize> = HashMap::new();
         loop {
             let message = deserialize_from::<WorkerServerMessage>(&self.receiver);
             match message {
@@ -130,10 +194,7 @@ impl Worker {
                             .ok_or(WorkerError::MissingDependencyKey { uuid: *input })?;
                         match self.file_store.get(&key) {
                             None => {
-                                serialize_into(
-                                    &WorkerClientMessage::AskFile(key.clone()),
-                                    &self.sender,
-                                )?;
+                                ask_file(key.clone())?;
                                 missing_deps.insert(key.clone(), *input);
                             }
                             Some(handle) => {
@@ -151,37 +212,95 @@ impl Worker {
                         start_job()?;
                     }
                 }
-                Ok(WorkerServerMessage::ProvideFile(key)) => {
+                Ok(WorkerServerMessage::ProvideFile(key, compressed)) => {
                     info!("Server sent file {:?}", key);
-                    let reader = ChannelFileIterator::new(&self.receiver);
-                    let handle = self.file_store.store(&key, reader)?;
-                    let mut job = self.current_job.lock().unwrap();
-                    let uuid = job
-                        .missing_deps
-                        .remove(&key)
-                        .expect("Server sent a not required dependency");
-                    job.current_job
-                        .as_mut()
-                        .expect("Received file while doing nothing")
-                        .1
-                        .insert(uuid, handle);
-                    if job.missing_deps.is_empty() {
+                    let handle = match &partial_files {
+                        Some(partial_files) => {
+                            let path = partial_files.receive(&key, &self.receiver, compressed)?;
+                            let handle = self
+                                .file_store
+                                .store_verified(&key, ReadFileIterator::new(&path)?);
+                            partial_files.discard(&key)?;
+                            handle?
+                        }
+                        None => {
+                            let reader =
+                                ChannelFileIterator::with_compression(&self.receiver, compressed);
+                            self.file_store.store_verified(&key, reader)?
+                        }
+                    };
+                    let handle = match handle {
+                        Some(handle) => handle,
+                        None => {
+                            let attempts = corrupted_transfers.entry(key.clone()).or_default();
+                            *attempts += 1;
+                            if *attempts > self.transfer_retries {
+                                bail!("File {:?} received corrupted {} times", key, attempts);
+                            }
+                            warn!("File {:?} received corrupted, asking it again", key);
+                            ask_file(key)?;
+                            continue;
+                        }
+                    };
+                    let job_ready = {
+                        let mut job = self.current_job.lock().unwrap();
+                        let uuid = job
+                            .missing_deps
+                            .remove(&key)
+                            .expect("Server sent a not required dependency");
+                        job.current_job
+                            .as_mut()
+                            .expect("Received file while doing nothing")
+                            .1
+                            .insert(uuid, handle);
+                        job.missing_deps.is_empty()
+                    };
+                    // the lock must be released before starting the job, since it's taken again
+                    if job_ready {
                         start_job()?;
                     }
                 }
+                Ok(WorkerServerMessage::KillJob) => {
+                    info!(
+                        "Worker {} ({}) is asked to kill its job",
+                        self.name, self.uuid
+                    );
+                    let mut current_job = self.current_job.lock().unwrap();
+                    if !current_job.current_sandboxes.is_empty() {
+                        current_job.current_sandboxes.iter().for_each(Sandbox::kill);
+                    } else if current_job.current_job.is_some() {
+                        // the sandbox is killed as soon as it's created
+                        current_job.killed = true;
+                    }
+                }
                 Ok(WorkerServerMessage::Exit) => {
                     info!("Worker {} ({}) is asked to exit", self.name, self.uuid);
+                    let sandbox_thread = self.current_job.lock().unwrap().sandbox_thread.take();
+                    if let Some(sandbox_thread) = sandbox_thread {
+                        sandbox_thread
+                            .join()
+                            .map_err(|e| format_err!("Sandbox thread panicked: {:?}", e))?;
+                    }
                     break;
                 }
+                Ok(WorkerServerMessage::Rejected(reason)) => {
+                    bail!("Worker {} rejected by the server: {}", self, reason);
+                }
+                Ok(WorkerServerMessage::Challenge(nonce)) => {
+                    let response = self.auth_token.as_ref().map(|token| token.sign(&nonce));
+                    serialize_into(
+                        &WorkerClientMessage::Authenticate(response, self.compression),
+                        &self.sender,
+                    )?;
+                    trace!("Worker {} ready, asking for work", self);
+                    serialize_into(&WorkerClientMessage::GetWork, &self.sender)?;
+                }
                 Err(e) => {
                     let cause = e.find_root_cause().to_string();
                     if cause == "receiving on a closed channel" {
                         trace!("Connection closed: {}", cause);
-                        if let Some(sandbox) =
-                            self.current_job.lock().unwrap().current_sandbox.as_ref()
-                        {
-                            sandbox.kill();
-                        }
+                        let current_job = self.current_job.lock().unwrap();
+                        current_job.current_sandboxes.iter().for_each(Sandbox::kill);
                         break;
                     } else {
                         error!("Connection error: {}", cause);
@@ -193,81 +312,173 @@ impl Worker {
     }
 }
 
-/// Spawn a new thread that will start the sandbox and will send the results back to the server.
+/// Spawn a new thread that will start the sandboxes of the execution and of its concurrent
+/// executions, and will send the results back to the server, compressing the outputs if
+/// `compression` is true. Returns the sandboxes and the handle of the spawned thread.
 fn execute_job(
     current_job: Arc<Mutex<WorkerCurrentJob>>,
     sender: &ChannelSender,
     sandbox_path: &Path,
-) -> Result<Sandbox, Error> {
-    let (job, mut sandbox) = {
+    sandbox_backend: &SandboxBackend,
+    compression: bool,
+) -> Result<(Vec<Sandbox>, thread::JoinHandle<()>), Error> {
+    let setup_start = Instant::now();
+    let (job, mut sandboxes) = {
         let current_job = current_job.lock().unwrap();
         let job = current_job
             .current_job
             .as_ref()
             .expect("Worker job is gone");
-        (
-            job.0.clone(),
-            Sandbox::new(sandbox_path, &job.0.execution, &job.1)?,
-        )
+        let mut sandboxes = vec![];
+        for execution in executions_of(&job.0.execution) {
+            sandboxes.push(Sandbox::new(sandbox_path, execution, &job.1)?);
+        }
+        (job.0.clone(), sandboxes)
     };
-    if job.execution.config().keep_sandboxes {
-        sandbox.keep();
+    let setup_time = setup_start.elapsed().as_secs_f64();
+    // the fifos are inside the sandbox of the main execution
+    let fifo_dir = sandboxes[0].box_path();
+    let fifos = open_fifos(&fifo_dir, &job.execution.fifos)?;
+    for sandbox in sandboxes.iter_mut() {
+        sandbox.backend(sandbox_backend.clone());
+        sandbox.fifo_dir(&fifo_dir);
+        if job.execution.config().keep_sandboxes {
+            sandbox.keep();
+        }
     }
     let thread_sender = sender.clone();
-    let thread_sandbox = sandbox.clone();
+    let thread_sandboxes = sandboxes.clone();
     let thread_job = job.clone();
     // FIXME: if the sandbox fails badly this may deadlock
-    thread::Builder::new()
+    let sandbox_thread = thread::Builder::new()
         .name(format!("Sandbox of {}", job.execution.description))
         .spawn(move || {
             let sender = thread_sender;
-            let sandbox = thread_sandbox;
+            let sandboxes = thread_sandboxes;
             let job = thread_job;
 
-            let result = sandbox.run().expect("The sandbox failed");
-            let result = compute_execution_result(&job.execution, result);
-
+            let results = run_sandboxes(&sandboxes, fifos);
             let mut outputs = HashMap::new();
             let mut output_paths = HashMap::new();
-            if let Some(stdout) = job.execution.stdout {
-                let path = sandbox.stdout_path();
-                outputs.insert(stdout.uuid, FileStoreKey::from_file(&path).unwrap());
-                output_paths.insert(stdout.uuid, path);
-            }
-            if let Some(stderr) = job.execution.stderr {
-                let path = sandbox.stderr_path();
-                outputs.insert(stderr.uuid, FileStoreKey::from_file(&path).unwrap());
-                output_paths.insert(stderr.uuid, path);
-            }
-            for (path, file) in job.execution.outputs.iter() {
-                let path = sandbox.output_path(path);
-                // the sandbox process may want to remove a file, consider missing files as empty
-                if path.exists() {
-                    outputs.insert(file.uuid, FileStoreKey::from_file(&path).unwrap());
-                    output_paths.insert(file.uuid, path.clone());
-                } else {
-                    // FIXME: /dev/null may not be used
-                    outputs.insert(file.uuid, FileStoreKey::from_file("/dev/null").unwrap());
-                    output_paths.insert(file.uuid, "/dev/null".into());
-                }
-            }
+            let mut results: Vec<_> = executions_of(&job.execution)
+                .into_iter()
+                .zip(sandboxes.iter())
+                .zip(results)
+                .map(|((execution, sandbox), result)| {
+                    let mut result = compute_execution_result(execution, result);
+                    result.resources.setup_time = setup_time;
+                    collect_outputs(execution, sandbox, &mut outputs, &mut output_paths);
+                    result
+                })
+                .collect();
+            let result = results.remove(0);
 
             serialize_into(
-                &WorkerClientMessage::WorkerDone(result, outputs.clone()),
+                &WorkerClientMessage::WorkerDone(result, results, outputs.clone()),
                 &sender,
             )
             .unwrap();
 
             for (uuid, key) in outputs.into_iter() {
-                serialize_into(&WorkerClientMessage::ProvideFile(uuid, key), &sender).unwrap();
-                ChannelFileSender::send(&output_paths[&uuid], &sender).unwrap();
+                serialize_into(
+                    &WorkerClientMessage::ProvideFile(uuid, key, compression),
+                    &sender,
+                )
+                .unwrap();
+                if compression {
+                    ChannelFileSender::send_compressed(&output_paths[&uuid], &sender).unwrap();
+                } else {
+                    ChannelFileSender::send(&output_paths[&uuid], &sender).unwrap();
+                }
             }
 
             current_job.lock().unwrap().current_job = None;
-            current_job.lock().unwrap().current_sandbox = None;
+            current_job.lock().unwrap().current_sandboxes.clear();
             serialize_into(&WorkerClientMessage::GetWork, &sender).unwrap();
         })?;
-    Ok(sandbox)
+    Ok((sandboxes, sandbox_thread))
+}
+
+/// The execution followed by its concurrent executions, in the order of their sandboxes.
+fn executions_of(execution: &Execution) -> Vec<&Execution> {
+    std::iter::once(execution)
+        .chain(execution.concurrent.iter())
+        .collect()
+}
+
+/// Open both the ends of the fifos inside `dir`. Keeping them open makes the processes never
+/// block opening a fifo, even if the other end is not opened yet (or never because the process
+/// failed to start).
+fn open_fifos(dir: &Path, fifos: &[PathBuf]) -> Result<Vec<std::fs::File>, Error> {
+    let mut files = vec![];
+    for fifo in fifos {
+        let path = dir.join(fifo);
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| format_err!("Cannot open the fifo {}: {}", path.display(), e))?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+/// Run the sandboxes at the same time, blocking until all of them exit. As soon as one of them
+/// exits the fifos are closed, so that the other processes see the end of the fifos it used
+/// instead of waiting forever.
+fn run_sandboxes(sandboxes: &[Sandbox], fifos: Vec<std::fs::File>) -> Vec<SandboxResult> {
+    if let [sandbox] = sandboxes {
+        return vec![sandbox.run().expect("The sandbox failed")];
+    }
+    let fifos = Arc::new(Mutex::new(fifos));
+    let runners: Vec<_> = sandboxes
+        .iter()
+        .map(|sandbox| {
+            let sandbox = sandbox.clone();
+            let fifos = fifos.clone();
+            thread::spawn(move || {
+                let result = sandbox.run().expect("The sandbox failed");
+                fifos.lock().unwrap().clear();
+                result
+            })
+        })
+        .collect();
+    runners
+        .into_iter()
+        .map(|runner| runner.join().expect("Sandbox thread panicked"))
+        .collect()
+}
+
+/// Add to `outputs` the keys of the files produced by an execution, and to `output_paths` their
+/// paths.
+fn collect_outputs(
+    execution: &Execution,
+    sandbox: &Sandbox,
+    outputs: &mut HashMap<FileUuid, FileStoreKey>,
+    output_paths: &mut HashMap<FileUuid, PathBuf>,
+) {
+    if let Some(stdout) = &execution.stdout {
+        let path = sandbox.stdout_path();
+        outputs.insert(stdout.uuid, FileStoreKey::from_file(&path).unwrap());
+        output_paths.insert(stdout.uuid, path);
+    }
+    if let Some(stderr) = &execution.stderr {
+        let path = sandbox.stderr_path();
+        outputs.insert(stderr.uuid, FileStoreKey::from_file(&path).unwrap());
+        output_paths.insert(stderr.uuid, path);
+    }
+    for (path, file) in execution.outputs.iter() {
+        let path = sandbox.output_path(path);
+        // the sandbox process may want to remove a file, consider missing files as empty
+        if path.exists() {
+            outputs.insert(file.uuid, FileStoreKey::from_file(&path).unwrap());
+            output_paths.insert(file.uuid, path.clone());
+        } else {
+            // FIXME: /dev/null may not be used
+            outputs.insert(file.uuid, FileStoreKey::from_file("/dev/null").unwrap());
+            output_paths.insert(file.uuid, "/dev/null".into());
+        }
+    }
 }
 
 /// Compute the [`ExecutionResult`](../task_maker_dag/struct.ExecutionResult.html) based on the
@@ -279,23 +490,36 @@ fn compute_execution_result(execution: &Execution, result: SandboxResult) -> Exe
             signal,
             resources,
             was_killed,
+            truncated_output,
         } => ExecutionResult {
             status: execution.status(exit_status, signal, &resources),
             resources,
             was_killed,
             was_cached: false,
+            truncated_output,
+            provenance: ExecutionProvenance::Unknown,
         },
-        SandboxResult::Failed { error } => ExecutionResult {
-            status: ExecutionStatus::InternalError(error.to_string()),
-            resources: ExecutionResourcesUsage {
-                cpu_time: 0.0,
-                sys_time: 0.0,
-                wall_time: 0.0,
-                memory: 0,
-            },
-            was_killed: false,
-            was_cached: false,
+        SandboxResult::Failed { error } => internal_error_result(error),
+        SandboxResult::Killed => internal_error_result("The sandbox has been killed".into()),
+    }
+}
+
+/// The result of an execution that the sandbox failed to run.
+fn internal_error_result(error: String) -> ExecutionResult {
+    ExecutionResult {
+        status: ExecutionStatus::InternalError(error),
+        resources: ExecutionResourcesUsage {
+            cpu_time: 0.0,
+            sys_time: 0.0,
+            wall_time: 0.0,
+            memory: 0,
+            processes: None,
+            setup_time: 0.0,
         },
+        was_killed: false,
+        was_cached: false,
+        truncated_output: false,
+        provenance: ExecutionProvenance::Unknown,
     }
 }
 
@@ -310,3 +534,238 @@ impl std::fmt::Display for Worker {
         write!(f, "'{}' ({})", self.name, self.uuid)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    /// Receive the next message sent by the worker to the server.
+    fn next_message(conn: &WorkerConn) -> WorkerClientMessage {
+        deserialize_from::<WorkerClientMessage>(&conn.receiver).unwrap()
+    }
+
+    /// Accept the worker without authentication, waiting for its first `GetWork`.
+    fn handshake(conn: &WorkerConn) {
+        match next_message(conn) {
+            WorkerClientMessage::Hello(_) => {}
+            message => panic!("Unexpected message {:?}", message),
+        }
+        serialize_into(&WorkerServerMessage::Challenge(vec![42]), &conn.sender).unwrap();
+        match next_message(conn) {
+            WorkerClientMessage::Authenticate(None, false) => {}
+            message => panic!("Unexpected message {:?}", message),
+        }
+        match next_message(conn) {
+            WorkerClientMessage::GetWork => {}
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn test_retry_corrupted_transfer() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let (worker, conn) = Worker::new("Test worker", file_store, tmpdir.path().join("boxes"));
+        let worker = thread::spawn(move || worker.work());
+        handshake(&conn);
+
+        let input = File::new("Input");
+        let key = FileStoreKey::from_content(b"hello");
+        let mut exec = Execution::new("Cat", ExecutionCommand::system("cat"));
+        exec.stdin(&input);
+        exec.stdout();
+        let mut dep_keys = HashMap::new();
+        dep_keys.insert(input.uuid, key.clone());
+        let job = WorkerJob {
+            execution: exec,
+            dep_keys,
+        };
+        serialize_into(&WorkerServerMessage::Work(Box::new(job)), &conn.sender).unwrap();
+
+        // the first transfer is corrupted, the file is asked again
+        for content in &["hellp", "hello"] {
+            match next_message(&conn) {
+                WorkerClientMessage::AskFile(asked) => assert_eq!(asked, key),
+                message => panic!("Unexpected message {:?}", message),
+            }
+            serialize_into(
+                &WorkerServerMessage::ProvideFile(key.clone(), false),
+                &conn.sender,
+            )
+            .unwrap();
+            ChannelFileSender::send_data(content.as_bytes().to_vec(), &conn.sender).unwrap();
+        }
+        match next_message(&conn) {
+            WorkerClientMessage::WorkerDone(result, _, _) => {
+                assert_eq!(result.status, ExecutionStatus::Success)
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+        match next_message(&conn) {
+            WorkerClientMessage::ProvideFile(_, output, _) => assert_eq!(output, key),
+            message => panic!("Unexpected message {:?}", message),
+        }
+        serialize_into(&WorkerServerMessage::Exit, &conn.sender).unwrap();
+        worker.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_resume_partial_transfer() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let key = FileStoreKey::from_content(b"hello world");
+        // the first bytes of the file were received before the connection dropped
+        let partial_dir = tmpdir.path().join("boxes").join(PARTIAL_FILES_DIR);
+        std::fs::create_dir_all(&partial_dir).unwrap();
+        std::fs::write(
+            partial_dir.join(format!("{}.partial", key.to_string())),
+            "hello",
+        )
+        .unwrap();
+        let (mut worker, conn) =
+            Worker::new("Test worker", file_store, tmpdir.path().join("boxes"));
+        worker.resume_transfers = true;
+        let worker = thread::spawn(move || worker.work());
+        handshake(&conn);
+
+        let input = File::new("Input");
+        let mut exec = Execution::new("Cat", ExecutionCommand::system("cat"));
+        exec.stdin(&input);
+        exec.stdout();
+        let mut dep_keys = HashMap::new();
+        dep_keys.insert(input.uuid, key.clone());
+        let job = WorkerJob {
+            execution: exec,
+            dep_keys,
+        };
+        serialize_into(&WorkerServerMessage::Work(Box::new(job)), &conn.sender).unwrap();
+
+        match next_message(&conn) {
+            WorkerClientMessage::ResumeFile(asked, offset) => {
+                assert_eq!(asked, key);
+                assert_eq!(offset, 5);
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+        serialize_into(
+            &WorkerServerMessage::ProvideFile(key.clone(), false),
+            &conn.sender,
+        )
+        .unwrap();
+        ChannelFileSender::send_data(b" world".to_vec(), &conn.sender).unwrap();
+        match next_message(&conn) {
+            WorkerClientMessage::WorkerDone(result, _, _) => {
+                assert_eq!(result.status, ExecutionStatus::Success)
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+        match next_message(&conn) {
+            WorkerClientMessage::ProvideFile(_, output, _) => assert_eq!(output, key),
+            message => panic!("Unexpected message {:?}", message),
+        }
+        serialize_into(&WorkerServerMessage::Exit, &conn.sender).unwrap();
+        worker.join().unwrap().unwrap();
+        // the partial file is gone once the transfer completes
+        assert_eq!(std::fs::read_dir(&partial_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_concurrent_executions_fifos() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // a non-hermetic backend that runs the command with the standard input and output of tmbox
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let backend = tmpdir.path().join("fake-tmbox");
+        std::fs::write(
+            &backend,
+            "#!/bin/sh\n\
+             while [ \"$1\" != -- ]; do\n\
+               case \"$1\" in\n\
+                 --directory) dir=$2;; --stdin) stdin=$2;; --stdout) stdout=$2;;\n\
+               esac\n\
+               shift\n\
+             done\n\
+             shift\n\
+             cd \"$dir\" && \"$@\" < \"$stdin\" > \"$stdout\"\n\
+             echo \"{\\\"error\\\":false,\\\"cpu_time\\\":0,\\\"sys_time\\\":0,\\\"wall_time\\\":0,\
+             \\\"memory_usage\\\":0,\\\"status_code\\\":$?,\\\"signal\\\":0,\
+             \\\"killed_by_sandbox\\\":false}\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&backend, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let (mut worker, conn) =
+            Worker::new("Test worker", file_store, tmpdir.path().join("boxes"));
+        worker.sandbox_backend = SandboxBackend::Custom(backend);
+        let worker = thread::spawn(move || worker.work());
+        handshake(&conn);
+
+        let mut manager = Execution::new("Manager", ExecutionCommand::system("sh"));
+        manager.args(vec!["-c", "echo 21 > to_sol; read x < to_man; echo $x"]);
+        manager.fifo("to_sol").fifo("to_man");
+        let stdout = manager.stdout();
+        let mut solution = Execution::new("Solution", ExecutionCommand::system("sh"));
+        solution.args(vec!["-c", "read x; echo $((x * 2))"]);
+        solution.stdin_fifo("to_sol").stdout_fifo("to_man");
+        manager.add_concurrent(solution);
+        let job = WorkerJob {
+            execution: manager,
+            dep_keys: HashMap::new(),
+        };
+        serialize_into(&WorkerServerMessage::Work(Box::new(job)), &conn.sender).unwrap();
+        match next_message(&conn) {
+            WorkerClientMessage::WorkerDone(result, concurrent, outputs) => {
+                assert_eq!(result.status, ExecutionStatus::Success);
+                assert_eq!(concurrent.len(), 1);
+                assert_eq!(concurrent[0].status, ExecutionStatus::Success);
+                assert_eq!(outputs[&stdout.uuid], FileStoreKey::from_content(b"42\n"));
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+        match next_message(&conn) {
+            WorkerClientMessage::ProvideFile(_, _, _) => {}
+            message => panic!("Unexpected message {:?}", message),
+        }
+        serialize_into(&WorkerServerMessage::Exit, &conn.sender).unwrap();
+        worker.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_sandbox_failure_diagnostic() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let backend = tmpdir.path().join("broken-tmbox");
+        std::fs::write(
+            &backend,
+            "#!/bin/sh\necho 'cannot create the cgroup' >&2\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&backend, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path().join("store")).unwrap());
+        let (mut worker, conn) =
+            Worker::new("Test worker", file_store, tmpdir.path().join("boxes"));
+        worker.sandbox_backend = SandboxBackend::Custom(backend);
+        let worker = thread::spawn(move || worker.work());
+        handshake(&conn);
+
+        let job = WorkerJob {
+            execution: Execution::new("True", ExecutionCommand::system("true")),
+            dep_keys: HashMap::new(),
+        };
+        serialize_into(&WorkerServerMessage::Work(Box::new(job)), &conn.sender).unwrap();
+        // the diagnostic of the sandbox is sent to the server, and then to the client and the UI
+        match next_message(&conn) {
+            WorkerClientMessage::WorkerDone(result, _, _) => match result.status {
+                ExecutionStatus::InternalError(error) => {
+                    assert!(error.contains("cannot create the cgroup"), "{}", error)
+                }
+                status => panic!("Unexpected status {:?}", status),
+            },
+            message => panic!("Unexpected message {:?}", message),
+        }
+        serialize_into(&WorkerServerMessage::Exit, &conn.sender).unwrap();
+        worker.join().unwrap().unwrap();
+    }
+}
@@ -1,9 +1,11 @@
 use crate::proto::{
     ChannelFileIterator, ChannelFileSender, WorkerClientMessage, WorkerServerMessage,
+    PROTOCOL_VERSION,
 };
 use crate::SchedulerInMessage;
+use crate::{check_auth_response, AuthToken};
 use crate::{deserialize_from, serialize_into, ChannelSender, WorkerConn};
-use failure::{format_err, Error};
+use failure::{bail, format_err, Error};
 use std::collections::HashMap;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
@@ -20,15 +22,23 @@ pub(crate) struct WorkerManager {
     file_store: Arc<FileStore>,
     /// The channel to use to send messages to the `Scheduler`.
     scheduler: Sender<SchedulerInMessage>,
+    /// The token the workers have to authenticate with, if any.
+    auth_token: Option<AuthToken>,
 }
 
 impl WorkerManager {
-    /// Make a new `WorkerManager` bound to the specified `Scheduler`.
-    pub fn new(file_store: Arc<FileStore>, scheduler: Sender<SchedulerInMessage>) -> WorkerManager {
+    /// Make a new `WorkerManager` bound to the specified `Scheduler`. If `auth_token` is set only
+    /// the workers that know it are accepted.
+    pub fn new(
+        file_store: Arc<FileStore>,
+        scheduler: Sender<SchedulerInMessage>,
+        auth_token: Option<AuthToken>,
+    ) -> WorkerManager {
         WorkerManager {
             connected_workers: HashMap::new(),
             file_store,
             scheduler,
+            auth_token,
         }
     }
 
@@ -39,19 +49,27 @@ impl WorkerManager {
             .insert(worker.uuid, worker.sender.clone());
         let scheduler = self.scheduler.clone();
         let file_store = self.file_store.clone();
+        let auth_token = self.auth_token.clone();
         std::thread::Builder::new()
             .name(format!(
                 "Manager of worker {} ({})",
                 worker.name, worker.uuid
             ))
-            .spawn(move || WorkerManager::worker_thread(worker, scheduler, file_store).unwrap())
+            .spawn(move || {
+                if let Err(e) =
+                    WorkerManager::worker_thread(worker, scheduler, file_store, auth_token)
+                {
+                    warn!("The connection with the worker failed: {:?}", e);
+                }
+            })
             .expect("Failed to spawn manager of worker")
     }
 
-    /// Stop all the workers by sending to them the `Exit` command and dropping the sender.
+    /// Stop all the workers by sending to them the `Exit` command and dropping the sender. The
+    /// workers that have already left (e.g. because they have been rejected) are ignored.
     pub fn stop(&mut self) -> Result<(), Error> {
         for (_, sender) in self.connected_workers.drain() {
-            serialize_into(&WorkerServerMessage::Exit, &sender)?;
+            let _ = serialize_into(&WorkerServerMessage::Exit, &sender);
         }
         Ok(())
     }
@@ -61,6 +79,71 @@ impl WorkerManager {
         worker: WorkerConn,
         scheduler: Sender<SchedulerInMessage>,
         file_store: Arc<FileStore>,
+        auth_token: Option<AuthToken>,
+    ) -> Result<(), Error> {
+        let rejection = match deserialize_from::<WorkerClientMessage>(&worker.receiver) {
+            Ok(WorkerClientMessage::Hello(version)) => {
+                if version == PROTOCOL_VERSION {
+                    None
+                } else {
+                    Some(format!(
+                        "version mismatch: worker has {}, server has {}",
+                        version, PROTOCOL_VERSION
+                    ))
+                }
+            }
+            Ok(message) => Some(format!("expecting Hello, got {:?}", message)),
+            // the worker left before the handshake
+            Err(_) => return Ok(()),
+        };
+        // whether the worker wants the files compressed
+        let mut compression = false;
+        let rejection = match rejection {
+            Some(reason) => Some(reason),
+            None => {
+                let nonce = AuthToken::nonce();
+                let challenge = WorkerServerMessage::Challenge(nonce.clone());
+                match serialize_into(&challenge, &worker.sender)
+                    .and_then(|_| deserialize_from::<WorkerClientMessage>(&worker.receiver))
+                {
+                    Ok(WorkerClientMessage::Authenticate(response, compressed)) => {
+                        compression = compressed;
+                        check_auth_response(auth_token.as_ref(), &nonce, response.as_deref()).err()
+                    }
+                    Ok(message) => Some(format!("expecting Authenticate, got {:?}", message)),
+                    // the worker left during the handshake
+                    Err(_) => return Ok(()),
+                }
+            }
+        };
+        if let Some(reason) = rejection {
+            warn!(
+                "Rejecting worker {} ({}): {}",
+                worker.name, worker.uuid, reason
+            );
+            serialize_into(&WorkerServerMessage::Rejected(reason), &worker.sender)?;
+            return Ok(());
+        }
+        let result = WorkerManager::serve_worker(&worker, &scheduler, &file_store, compression);
+        // the worker is gone, either because it left or because of an error, and its job (if any)
+        // has to be requeued
+        if scheduler
+            .send(SchedulerInMessage::WorkerDisconnected { uuid: worker.uuid })
+            .is_err()
+        {
+            debug!("Cannot tell the scheduler that a worker left, maybe it's gone");
+        }
+        result
+    }
+
+    /// Serve the requests of an authenticated worker until it leaves, the scheduler is gone or the
+    /// worker misbehaves, in which case an error is returned. If `compression` is true the files
+    /// are sent to the worker compressed.
+    fn serve_worker(
+        worker: &WorkerConn,
+        scheduler: &Sender<SchedulerInMessage>,
+        file_store: &FileStore,
+        compression: bool,
     ) -> Result<(), Error> {
         loop {
             let message = deserialize_from::<WorkerClientMessage>(&worker.receiver);
@@ -79,46 +162,276 @@ impl WorkerManager {
                     }
                 }
                 Ok(WorkerClientMessage::AskFile(key)) => {
-                    let handle = file_store
-                        .get(&key)
-                        .expect("Worker is asking for an unknown file");
-                    serialize_into(&WorkerServerMessage::ProvideFile(key), &worker.sender)?;
-                    ChannelFileSender::send(handle.path(), &worker.sender)?;
+                    let handle = file_store.get(&key).ok_or_else(|| {
+                        format_err!("Worker is asking for an unknown file: {:?}", key)
+                    })?;
+                    serialize_into(
+                        &WorkerServerMessage::ProvideFile(key, compression),
+                        &worker.sender,
+                    )?;
+                    if compression {
+                        ChannelFileSender::send_compressed(handle.path(), &worker.sender)?;
+                    } else {
+                        ChannelFileSender::send(handle.path(), &worker.sender)?;
+                    }
+                }
+                Ok(WorkerClientMessage::ResumeFile(key, offset)) => {
+                    let handle = file_store.get(&key).ok_or_else(|| {
+                        format_err!("Worker is resuming an unknown file: {:?}", key)
+                    })?;
+                    let size = std::fs::metadata(handle.path())?.len();
+                    if offset > size {
+                        bail!(
+                            "Worker is resuming {:?} from {}, but the file has {} bytes",
+                            key,
+                            offset,
+                            size
+                        );
+                    }
+                    serialize_into(
+                        &WorkerServerMessage::ProvideFile(key, compression),
+                        &worker.sender,
+                    )?;
+                    if compression {
+                        ChannelFileSender::send_compressed_from(
+                            handle.path(),
+                            offset,
+                            &worker.sender,
+                        )?;
+                    } else {
+                        ChannelFileSender::send_from(handle.path(), offset, &worker.sender)?;
+                    }
                 }
-                Ok(WorkerClientMessage::ProvideFile(_, _)) => {
-                    unreachable!("Unexpected ProvideFile from worker");
+                Ok(message @ WorkerClientMessage::ProvideFile(_, _, _))
+                | Ok(message @ WorkerClientMessage::Hello(_))
+                | Ok(message @ WorkerClientMessage::Authenticate(_, _)) => {
+                    bail!("Unexpected message from worker: {:?}", message);
                 }
-                Ok(WorkerClientMessage::WorkerDone(result, outputs)) => {
+                Ok(WorkerClientMessage::WorkerDone(result, concurrent_results, outputs)) => {
                     let mut output_handlers = HashMap::new();
                     for _ in 0..outputs.len() {
                         let message = deserialize_from::<WorkerClientMessage>(&worker.receiver)?;
-                        if let WorkerClientMessage::ProvideFile(uuid, key) = message {
-                            let handle = file_store
-                                .store(&key, ChannelFileIterator::new(&worker.receiver))?;
+                        if let WorkerClientMessage::ProvideFile(uuid, key, compressed) = message {
+                            let iterator =
+                                ChannelFileIterator::with_compression(&worker.receiver, compressed);
+                            let handle =
+                                file_store.store_verified(&key, iterator)?.ok_or_else(|| {
+                                    format_err!("Worker sent a corrupted output {}", uuid)
+                                })?;
                             output_handlers.insert(uuid, handle);
                         } else {
-                            panic!("Unexpected message from worker: {:?}", message);
+                            bail!("Unexpected message from worker: {:?}", message);
                         }
                     }
                     scheduler
                         .send(SchedulerInMessage::WorkerResult {
                             worker: worker.uuid,
                             result,
+                            concurrent_results,
                             outputs: output_handlers,
                         })
                         .map_err(|e| format_err!("Failed to send message to scheduler: {:?}", e))?;
                 }
-                Err(_) => {
-                    if scheduler
-                        .send(SchedulerInMessage::WorkerDisconnected { uuid: worker.uuid })
-                        .is_err()
-                    {
-                        debug!("Cannot tell the scheduler that a worker left, maybe it's gone");
-                    }
-                    break;
-                }
+                // the worker left
+                Err(_) => break,
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use task_maker_store::FileStoreKey;
+    use tempdir::TempDir;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_reject_mismatched_worker() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path()).unwrap());
+        let (scheduler_tx, scheduler_rx) = channel();
+        let mut manager = WorkerManager::new(file_store, scheduler_tx, None);
+
+        let (tx, rx_worker) = channel();
+        let (tx_worker, rx) = channel();
+        let handle = manager.add(WorkerConn {
+            uuid: Uuid::new_v4(),
+            name: "Old worker".into(),
+            sender: tx,
+            receiver: rx,
+        });
+        serialize_into(&WorkerClientMessage::Hello("0.0.0".into()), &tx_worker).unwrap();
+        serialize_into(&WorkerClientMessage::GetWork, &tx_worker).unwrap();
+        match deserialize_from::<WorkerServerMessage>(&rx_worker).unwrap() {
+            WorkerServerMessage::Rejected(reason) => assert!(reason.contains("0.0.0")),
+            message => panic!("Expecting Rejected, got {:?}", message),
+        }
+        handle.join().unwrap();
+        // the worker never reached the scheduler
+        assert!(scheduler_rx.try_recv().is_err());
+        manager.stop().unwrap();
+    }
+
+    /// Connect a worker to a manager expecting `token`, answering the challenge with `worker_token`.
+    /// Returns the reason of the rejection, if any.
+    fn authenticate(token: &str, worker_token: Option<&str>) -> Option<String> {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path()).unwrap());
+        let (scheduler_tx, scheduler_rx) = channel();
+        let token = Some(AuthToken::new(token));
+        let mut manager = WorkerManager::new(file_store, scheduler_tx, token);
+
+        let (tx, rx_worker) = channel();
+        let (tx_worker, rx) = channel();
+        let handle = manager.add(WorkerConn {
+            uuid: Uuid::new_v4(),
+            name: "Worker".into(),
+            sender: tx,
+            receiver: rx,
+        });
+        serialize_into(
+            &WorkerClientMessage::Hello(PROTOCOL_VERSION.into()),
+            &tx_worker,
+        )
+        .unwrap();
+        let nonce = match deserialize_from::<WorkerServerMessage>(&rx_worker).unwrap() {
+            WorkerServerMessage::Challenge(nonce) => nonce,
+            message => panic!("Expecting Challenge, got {:?}", message),
+        };
+        let response = worker_token.map(|token| AuthToken::new(token).sign(&nonce));
+        serialize_into(
+            &WorkerClientMessage::Authenticate(response, false),
+            &tx_worker,
+        )
+        .unwrap();
+        // a rejected worker may be already disconnected
+        let _ = serialize_into(&WorkerClientMessage::GetWork, &tx_worker);
+        drop(tx_worker);
+        handle.join().unwrap();
+        manager.stop().unwrap();
+        let rejection = match deserialize_from::<WorkerServerMessage>(&rx_worker).unwrap() {
+            WorkerServerMessage::Rejected(reason) => Some(reason),
+            WorkerServerMessage::Exit => None,
+            message => panic!("Expecting Rejected or Exit, got {:?}", message),
+        };
+        let connected = match scheduler_rx.try_recv() {
+            Ok(SchedulerInMessage::WorkerConnected { .. }) => true,
+            Err(_) => false,
+            Ok(_) => panic!("Expecting WorkerConnected"),
+        };
+        // only the authenticated workers reach the scheduler
+        assert_eq!(connected, rejection.is_none());
+        rejection
+    }
+
+    #[test]
+    fn test_authenticated_worker() {
+        assert_eq!(authenticate("secret", Some("secret")), None);
+    }
+
+    #[test]
+    fn test_reject_unauthenticated_worker() {
+        let reason = authenticate("secret", None).unwrap();
+        assert!(reason.contains("token required"));
+        let reason = authenticate("secret", Some("guess")).unwrap();
+        assert!(reason.contains("wrong authentication token"));
+    }
+
+    #[test]
+    fn test_resume_file() {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path()).unwrap());
+        let content = b"hello world".to_vec();
+        let key = FileStoreKey::from_content(&content);
+        let _handle = file_store.store(&key, vec![content]).unwrap();
+        let (scheduler_tx, _scheduler_rx) = channel();
+        let mut manager = WorkerManager::new(file_store, scheduler_tx, None);
+
+        let (tx, rx_worker) = channel();
+        let (tx_worker, rx) = channel();
+        let handle = manager.add(WorkerConn {
+            uuid: Uuid::new_v4(),
+            name: "Worker".into(),
+            sender: tx,
+            receiver: rx,
+        });
+        serialize_into(
+            &WorkerClientMessage::Hello(PROTOCOL_VERSION.into()),
+            &tx_worker,
+        )
+        .unwrap();
+        match deserialize_from::<WorkerServerMessage>(&rx_worker).unwrap() {
+            WorkerServerMessage::Challenge(_) => {}
+            message => panic!("Expecting Challenge, got {:?}", message),
+        }
+        serialize_into(&WorkerClientMessage::Authenticate(None, false), &tx_worker).unwrap();
+        serialize_into(&WorkerClientMessage::ResumeFile(key.clone(), 6), &tx_worker).unwrap();
+        match deserialize_from::<WorkerServerMessage>(&rx_worker).unwrap() {
+            WorkerServerMessage::ProvideFile(provided, _) => assert_eq!(provided, key),
+            message => panic!("Expecting ProvideFile, got {:?}", message),
+        }
+        let data: Vec<u8> = ChannelFileIterator::new(&rx_worker).flatten().collect();
+        assert_eq!(data, b"world");
+        drop(tx_worker);
+        handle.join().unwrap();
+        manager.stop().unwrap();
+    }
+
+    /// Connect an authenticated worker that sends `message`, checking that it gets disconnected.
+    /// The store of the server contains only the file `hello world`.
+    fn disconnect_misbehaving(message: WorkerClientMessage) {
+        let tmpdir = TempDir::new("tm-test").unwrap();
+        let file_store = Arc::new(FileStore::new(tmpdir.path()).unwrap());
+        let content = b"hello world".to_vec();
+        let key = FileStoreKey::from_content(&content);
+        let _handle = file_store.store(&key, vec![content]).unwrap();
+        let (scheduler_tx, scheduler_rx) = channel();
+        let mut manager = WorkerManager::new(file_store, scheduler_tx, None);
+
+        let (tx, rx_worker) = channel();
+        let (tx_worker, rx) = channel();
+        let uuid = Uuid::new_v4();
+        let handle = manager.add(WorkerConn {
+            uuid,
+            name: "Worker".into(),
+            sender: tx,
+            receiver: rx,
+        });
+        serialize_into(
+            &WorkerClientMessage::Hello(PROTOCOL_VERSION.into()),
+            &tx_worker,
+        )
+        .unwrap();
+        deserialize_from::<WorkerServerMessage>(&rx_worker).unwrap();
+        serialize_into(&WorkerClientMessage::Authenticate(None, false), &tx_worker).unwrap();
+        serialize_into(&message, &tx_worker).unwrap();
+        // the manager thread does not panic and tells the scheduler that the worker is gone,
+        // without waiting for the worker to leave
+        handle.join().unwrap();
+        match scheduler_rx.try_recv() {
+            Ok(SchedulerInMessage::WorkerDisconnected { uuid: worker }) => assert_eq!(worker, uuid),
+            _ => panic!("Expecting WorkerDisconnected"),
+        }
+        manager.stop().unwrap();
+    }
+
+    #[test]
+    fn test_worker_asking_unknown_file() {
+        let key = FileStoreKey::from_content(b"unknown");
+        disconnect_misbehaving(WorkerClientMessage::AskFile(key));
+    }
+
+    #[test]
+    fn test_worker_resuming_beyond_the_end() {
+        let key = FileStoreKey::from_content(b"hello world");
+        disconnect_misbehaving(WorkerClientMessage::ResumeFile(key, 100));
+    }
+
+    #[test]
+    fn test_worker_unexpected_message() {
+        disconnect_misbehaving(WorkerClientMessage::Hello(PROTOCOL_VERSION.into()));
+    }
+}
@@ -0,0 +1,190 @@
+//! Parsing of the diagnostics emitted by the compilers and their export in the
+//! [SARIF](https://sarifweb.azurewebsites.net/) format.
+//!
+//! The messages are extracted from the standard error of the compilations, the supported format is
+//! the one used by GCC and Clang: `file:line:column: severity: message`.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// The version of the SARIF specification used by the exporter.
+const SARIF_VERSION: &str = "2.1.0";
+/// The URI of the JSON schema of the SARIF specification used by the exporter.
+const SARIF_SCHEMA: &str = "https://schemastore.azurewebsites.net/schemas/json/sarif-2.1.0.json";
+
+lazy_static! {
+    /// Regex matching a single diagnostic line in the GCC/Clang format.
+    static ref DIAGNOSTIC_REGEX: Regex = Regex::new(
+        r"^(?P<file>[^:\n]+):(?P<line>\d+):(?:(?P<column>\d+):)? (?:fatal )?(?P<level>error|warning|note): (?P<message>.*)$"
+    )
+    .expect("Invalid diagnostic regex");
+}
+
+/// The severity of a diagnostic message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiagnosticLevel {
+    /// The message is an error, the compilation has failed.
+    Error,
+    /// The message is a warning.
+    Warning,
+    /// The message is a note attached to a previous diagnostic.
+    Note,
+}
+
+/// A diagnostic message emitted by a compiler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// The file the message refers to.
+    pub file: PathBuf,
+    /// The line of the file, 1-based.
+    pub line: usize,
+    /// The column of the line, 1-based, if known.
+    pub column: Option<usize>,
+    /// The severity of the message.
+    pub level: DiagnosticLevel,
+    /// The text of the message.
+    pub message: String,
+}
+
+impl DiagnosticLevel {
+    /// The name of the level in the SARIF specification.
+    fn sarif_level(self) -> &'static str {
+        match self {
+            DiagnosticLevel::Error => "error",
+            DiagnosticLevel::Warning => "warning",
+            DiagnosticLevel::Note => "note",
+        }
+    }
+}
+
+/// Parse the standard error of a compilation extracting all the diagnostics in the GCC/Clang
+/// format. The lines not matching the format (code snippets, "In function" headers, ...) are
+/// ignored.
+pub fn parse_diagnostics(stderr: &str) -> Vec<Diagnostic> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let caps = DIAGNOSTIC_REGEX.captures(line)?;
+            let level = match &caps["level"] {
+                "error" => DiagnosticLevel::Error,
+                "warning" => DiagnosticLevel::Warning,
+                _ => DiagnosticLevel::Note,
+            };
+            Some(Diagnostic {
+                file: PathBuf::from(&caps["file"]),
+                line: caps["line"].parse().ok()?,
+                column: caps.name("column").and_then(|c| c.as_str().parse().ok()),
+                level,
+                message: caps["message"].to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Export the diagnostics as a SARIF log with a single run, attributed to the specified tool.
+pub fn to_sarif(tool_name: &str, diagnostics: &[Diagnostic]) -> Value {
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diag| {
+            let mut region = json!({ "startLine": diag.line });
+            if let Some(column) = diag.column {
+                region["startColumn"] = json!(column);
+            }
+            json!({
+                "level": diag.level.sarif_level(),
+                "message": { "text": diag.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": diag.file.to_string_lossy() },
+                        "region": region,
+                    }
+                }],
+            })
+        })
+        .collect();
+    json!({
+        "version": SARIF_VERSION,
+        "$schema": SARIF_SCHEMA,
+        "runs": [{
+            "tool": { "driver": { "name": tool_name } },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &str = "sol.cpp: In function 'int main()':
+sol.cpp:5:9: warning: unused variable 'x' [-Wunused-variable]
+    5 |     int x;
+      |         ^
+sol.cpp:7:5: error: 'foo' was not declared in this scope
+    7 |     foo();
+      |     ^~~
+In file included from sol.cpp:1:
+lib.h:3: note: previous definition is here
+grader.cpp:12:1: fatal error: missing.h: No such file or directory
+compilation terminated.
+";
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let diagnostics = parse_diagnostics(STDERR);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    file: "sol.cpp".into(),
+                    line: 5,
+                    column: Some(9),
+                    level: DiagnosticLevel::Warning,
+                    message: "unused variable 'x' [-Wunused-variable]".into(),
+                },
+                Diagnostic {
+                    file: "sol.cpp".into(),
+                    line: 7,
+                    column: Some(5),
+                    level: DiagnosticLevel::Error,
+                    message: "'foo' was not declared in this scope".into(),
+                },
+                Diagnostic {
+                    file: "lib.h".into(),
+                    line: 3,
+                    column: None,
+                    level: DiagnosticLevel::Note,
+                    message: "previous definition is here".into(),
+                },
+                Diagnostic {
+                    file: "grader.cpp".into(),
+                    line: 12,
+                    column: Some(1),
+                    level: DiagnosticLevel::Error,
+                    message: "missing.h: No such file or directory".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_sarif() {
+        let diagnostics = parse_diagnostics(STDERR);
+        let sarif = to_sarif("g++", &diagnostics);
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "g++");
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0]["level"], "warning");
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "sol.cpp");
+        assert_eq!(location["region"]["startLine"], 5);
+        assert_eq!(location["region"]["startColumn"], 9);
+        assert!(results[2]["locations"][0]["physicalLocation"]["region"]
+            .get("startColumn")
+            .is_none());
+    }
+}
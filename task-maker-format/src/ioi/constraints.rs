@@ -0,0 +1,155 @@
+use std::fmt;
+use std::str::FromStr;
+
+use failure::{bail, format_err, Error};
+use serde::{Deserialize, Serialize};
+
+/// A constraint on a variable of the input files of a subtask, like `1 <= N <= 1000`. The
+/// validator receives the bounds in the `TM_MIN_<variable>` and `TM_MAX_<variable>` environment
+/// variables, so that a single validator can enforce the tighter bounds of the easier subtasks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Constraint {
+    /// The name of the constrained variable, like `N`.
+    pub variable: String,
+    /// The minimum value of the variable (included), if any.
+    pub min: Option<i64>,
+    /// The maximum value of the variable (included), if any.
+    pub max: Option<i64>,
+}
+
+impl Constraint {
+    /// The environment variables with the bounds of the constraint for the validator.
+    pub fn env(&self) -> Vec<(String, String)> {
+        let mut env = vec![];
+        if let Some(min) = self.min {
+            env.push((format!("TM_MIN_{}", self.variable), min.to_string()));
+        }
+        if let Some(max) = self.max {
+            env.push((format!("TM_MAX_{}", self.variable), max.to_string()));
+        }
+        env
+    }
+}
+
+/// Parse a bound of a constraint, accepting the powers of ten written like `10^9`.
+fn parse_bound(bound: &str) -> Result<i64, Error> {
+    let bound = bound.trim();
+    if let Some(pos) = bound.find('^') {
+        let base = bound[..pos].trim().parse::<i64>()?;
+        let exp = bound[pos + 1..].trim().parse::<u32>()?;
+        return base
+            .checked_pow(exp)
+            .ok_or_else(|| format_err!("Bound too large: {}", bound));
+    }
+    Ok(bound.parse::<i64>()?)
+}
+
+impl FromStr for Constraint {
+    type Err = Error;
+
+    /// Parse a constraint in the form `A <= X <= B`, `A <= X` or `X <= B`. The strict inequalities
+    /// (`<`) are also supported, the bounds are adjusted to be inclusive. `≤` can be used instead
+    /// of `<=`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s: &str = &s.replace('≤', "<=");
+        // split the constraint in the operands and the operators between them
+        let mut operands = vec![];
+        let mut strict = vec![];
+        let mut rest = s;
+        while let Some(pos) = rest.find('<') {
+            operands.push(rest[..pos].trim());
+            rest = &rest[pos + 1..];
+            if rest.starts_with('=') {
+                strict.push(false);
+                rest = &rest[1..];
+            } else {
+                strict.push(true);
+            }
+        }
+        operands.push(rest.trim());
+        let is_variable = |op: &str| {
+            op.chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && op.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        let variable = match operands.iter().position(|op| is_variable(op)) {
+            Some(pos) if operands.len() == 2 || (operands.len() == 3 && pos == 1) => pos,
+            _ => bail!("Invalid constraint: {}", s),
+        };
+        let mut constraint = Constraint {
+            variable: operands[variable].to_string(),
+            min: None,
+            max: None,
+        };
+        if variable > 0 {
+            let min = parse_bound(operands[variable - 1])?;
+            constraint.min = Some(if strict[variable - 1] { min + 1 } else { min });
+        }
+        if variable + 1 < operands.len() {
+            let max = parse_bound(operands[variable + 1])?;
+            constraint.max = Some(if strict[variable] { max - 1 } else { max });
+        }
+        Ok(constraint)
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(min) = self.min {
+            write!(f, "{} <= ", min)?;
+        }
+        write!(f, "{}", self.variable)?;
+        if let Some(max) = self.max {
+            write!(f, " <= {}", max)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_constraint() {
+        let constraint: Constraint = "1 <= N <= 1000".parse().unwrap();
+        assert_eq!(constraint.variable, "N");
+        assert_eq!(constraint.min, Some(1));
+        assert_eq!(constraint.max, Some(1000));
+        assert_eq!(constraint.to_string(), "1 <= N <= 1000");
+        assert_eq!("1 ≤ N ≤ 1000".parse::<Constraint>().unwrap(), constraint);
+    }
+
+    #[test]
+    fn test_parse_constraint_one_side() {
+        let constraint: Constraint = "M_1<10^9".parse().unwrap();
+        assert_eq!(constraint.variable, "M_1");
+        assert_eq!(constraint.min, None);
+        assert_eq!(constraint.max, Some(999_999_999));
+        let constraint: Constraint = "0 < K".parse().unwrap();
+        assert_eq!(constraint.min, Some(1));
+        assert_eq!(constraint.max, None);
+    }
+
+    #[test]
+    fn test_parse_constraint_invalid() {
+        assert!("N".parse::<Constraint>().is_err());
+        assert!("1 <= 2 <= 3".parse::<Constraint>().is_err());
+        assert!("N <= M".parse::<Constraint>().is_err());
+        assert!("N <= 1 <= 2".parse::<Constraint>().is_err());
+        assert!("1 <= N <= x2".parse::<Constraint>().is_err());
+    }
+
+    #[test]
+    fn test_constraint_env() {
+        let constraint: Constraint = "1 <= N <= 1000".parse().unwrap();
+        assert_eq!(
+            constraint.env(),
+            vec![
+                ("TM_MIN_N".to_string(), "1".to_string()),
+                ("TM_MAX_N".to_string(), "1000".to_string())
+            ]
+        );
+    }
+}
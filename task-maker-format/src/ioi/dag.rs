@@ -1,16 +1,44 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use failure::{bail, Error};
 use serde::{Deserialize, Serialize};
 
-use task_maker_dag::{Execution, ExecutionCommand, ExecutionStatus, File, FileUuid};
+use task_maker_dag::{
+    Execution, ExecutionCommand, ExecutionResult, ExecutionStatus, File, FileUuid,
+};
 
 use crate::ioi::*;
 use crate::ui::UIMessage;
 use crate::{EvaluationData, SourceFile, UISender};
 
 const STDERR_CONTENT_LENGTH: usize = 10 * 1024;
+/// The environment variable set on the second run of a checker whose determinism is verified.
+const CHECKER_REPETITION_ENV: &str = "TM_CHECKER_REPETITION";
+/// A solution that timed out using less than this fraction of its wall time on the CPU is
+/// considered blocked waiting for some input.
+const BLOCKED_MAX_CPU_RATIO: f64 = 0.05;
+/// The fifo, inside the sandbox of the manager of a communication task, connected to the standard
+/// output of the solution.
+const SOLUTION_TO_MANAGER_FIFO: &str = "sol_to_man";
+/// The fifo, inside the sandbox of the manager of a communication task, connected to the standard
+/// input of the solution.
+const MANAGER_TO_SOLUTION_FIFO: &str = "man_to_sol";
+/// How many seconds the manager of a communication task may run after the wall time limit of the
+/// solution. The manager may block forever opening a fifo the solution never opened.
+const MANAGER_WALL_TIME_GRACE: f64 = 2.0;
+
+/// The `awk` program comparing the multisets of the lines of the files `correct` and `test`, after
+/// normalizing the white spaces. It exits with 0 if they are equal and with 1 otherwise, like
+/// `diff`. `{normalize}` is replaced with additional normalization of the line.
+const UNORDERED_LINES_PROGRAM: &str = "
+NF == 0 { next }
+{ $1 = $1 {normalize} }
+FILENAME == \"correct\" { count[$0]++; next }
+{ if (--count[$0] < 0) wrong = 1 }
+END { for (line in count) if (count[line] != 0) wrong = 1; exit wrong }
+";
 
 /// Which tool to use to compute the score on a testcase given the input file, the _correct_ output
 /// file and the output file to evaluate.
@@ -18,7 +46,7 @@ const STDERR_CONTENT_LENGTH: usize = 10 * 1024;
 pub enum Checker {
     /// Use a built-in white diff checker that scores 1.0 if the two output files are identical
     /// except for white spaces. It internally uses `diff --ignore-all-spaces`
-    WhiteDiff,
+    WhiteDiff(WhiteDiffOptions),
     /// Use a custom checker based on an executable that can output a score (from 0.0 to 1.0) to
     /// stdout as well as a custom message on stderr.
     ///
@@ -27,6 +55,19 @@ pub enum Checker {
     Custom(Arc<SourceFile>),
 }
 
+/// The options of the built-in white diff checker.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WhiteDiffOptions {
+    /// Whether the comparison should ignore the case of the letters, for example `YES` and `yes`
+    /// would be considered equal. It internally uses `diff --ignore-case`.
+    pub ignore_case: bool,
+    /// Whether the lines of the output can be in any order: the two files are equal if they
+    /// contain the same lines the same number of times. The white spaces inside the lines are
+    /// normalized and the empty lines are ignored.
+    #[serde(default)]
+    pub unordered_lines: bool,
+}
+
 /// The source of the input files. It can either be a statically provided input file or a custom
 /// command that will generate an input file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +98,9 @@ pub enum OutputGenerator {
     /// Use a custom command to generate the output file. The task specification for input/output
     /// files are used.
     Custom(Arc<SourceFile>, Vec<String>),
+    /// There is no correct output, for example in the communication tasks where the solution
+    /// cannot run without the manager. An empty file is used instead.
+    NotAvailable,
 }
 
 /// The aggregator of testcase scores for computing the subtask score.
@@ -70,6 +114,48 @@ pub enum TestcaseScoreAggregator {
     ///
     /// `st_score = st_max_score * sum(*testcase_scores) / len(*testcase_scores)`
     Sum,
+    /// Use a custom function of the testcase scores, sorted by testcase id. The result is clamped
+    /// between 0.0 and 1.0 and scaled by the maximum score of the subtask.
+    Custom(CustomScoreAggregator),
+}
+
+/// The aggregator of subtask scores for computing the score of the task. The same aggregation is
+/// applied to the maximum scores of the subtasks for computing the maximum score of the task.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum TaskScoreAggregator {
+    /// Sum the score of all the subtasks, formally:
+    ///
+    /// `task_score = sum(*subtask_scores)`
+    #[default]
+    Sum,
+    /// Take the maximum of all the subtasks, formally:
+    ///
+    /// `task_score = max(*subtask_scores)`
+    Max,
+    /// Use a custom function of the subtask scores, sorted by subtask id.
+    Custom(CustomScoreAggregator),
+}
+
+/// A custom function aggregating some scores into a single one: the scores of the testcases of a
+/// subtask, sorted by testcase id, or the scores of the subtasks, sorted by subtask id.
+///
+/// The function cannot be serialized: the aggregator is serialized as a unit value and it's
+/// deserialized as an aggregator that always returns NaN.
+#[derive(Clone)]
+pub struct CustomScoreAggregator(pub Arc<ScoreAggregatorFn>);
+
+/// The function of a `CustomScoreAggregator`.
+pub type ScoreAggregatorFn = dyn Fn(&[f64]) -> f64 + Send + Sync;
+
+/// What to do with the checker of a testcase when the solution fails, for example when it exceeds
+/// the time limit or crashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckerPolicy {
+    /// Do not run the checker, the testcase scores zero with the failure as message.
+    Skip,
+    /// Run the checker anyway on the partial output of the solution. The testcase still scores
+    /// zero, but the message is the one produced by the checker.
+    Run,
 }
 
 /// The type of the task. This changes the behaviour of the solutions.
@@ -79,6 +165,19 @@ pub enum TaskType {
     /// file and reading the output file. The solution may be compiled with additional graders
     /// (called `grader.LANG`). The output is checked with an external program.
     Batch,
+    /// There is no solution to execute, the contestants submit the output files directly. Each
+    /// solution is a directory with an `output_NNN.txt` file for each testcase (`NNN` is the
+    /// 0-padded testcase number). The output files are checked with the checker as in `Batch`.
+    OutputOnly,
+    /// The solution interacts with a manager program, both are executed once per testcase at the
+    /// same time. The standard output of the solution is connected to the fifo `sol_to_man` and its
+    /// standard input to the fifo `man_to_sol`, the manager receives their names as arguments and
+    /// opens them.
+    ///
+    /// The manager reads the input file from its standard input, and it can also read the correct
+    /// output from `correct_output`. Like a custom checker it prints the score (from 0.0 to 1.0) to
+    /// its standard output and a message for the user to its standard error.
+    Communication(Arc<SourceFile>),
 }
 
 /// Bind the start/done/skip callbacks of an execution to a ui message sender which sends to the UI
@@ -170,6 +269,23 @@ macro_rules! bind_exec_io {
     }};
 }
 
+/// Send to the UI a preview of the first `size` bytes of a file, the message is built by
+/// `make_message`.
+pub(crate) fn bind_preview<F>(
+    eval: &mut EvaluationData,
+    file: FileUuid,
+    size: usize,
+    make_message: F,
+) where
+    F: FnOnce(FilePreview) -> UIMessage + 'static,
+{
+    let sender = eval.sender.clone();
+    // ask for an extra byte for knowing if the file is longer than the preview
+    eval.dag.get_file_content(file, size + 1, move |content| {
+        sender.send(make_message(FilePreview::new(&content, size)))
+    });
+}
+
 impl InputGenerator {
     /// Add the generation of the input file to the DAG and the callbacks to the UI, returning the
     /// handle to the input file.
@@ -244,12 +360,16 @@ impl InputValidator {
     /// Add the validation of the input file to the DAG and the callbacks to the UI, optionally
     /// returning a fake file that blocks the usage of the actual input until the validation
     /// succeeds. If the validation is ignored, `None` is returned.
+    ///
+    /// The bounds of the constraints of the subtask are passed to the validator in the
+    /// environment, a failed validation is also reported as a warning.
     pub(crate) fn validate(
         &self,
         eval: &mut EvaluationData,
         subtask_id: SubtaskId,
         testcase_id: TestcaseId,
         input: FileUuid,
+        constraints: &[Constraint],
     ) -> Result<Option<FileUuid>, Error> {
         match self {
             InputValidator::AssumeValid => Ok(None),
@@ -266,6 +386,9 @@ impl InputValidator {
                     .tag(Tag::Generation.into())
                     .env("TM_SUBTASK", subtask_id.to_string())
                     .env("TM_TESTCASE", testcase_id.to_string());
+                for (key, value) in constraints.iter().flat_map(Constraint::env) {
+                    exec.env(key, value);
+                }
                 let stdout = exec.stdout();
                 bind_exec_callbacks!(eval, exec.uuid, |status| UIMessage::IOIValidation {
                     subtask: subtask_id,
@@ -273,6 +396,24 @@ impl InputValidator {
                     status
                 })?;
                 let sender = eval.sender.clone();
+                let constraints = constraints
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>();
+                eval.dag.on_execution_done(&exec.uuid, move |result| {
+                    if let ExecutionStatus::Success = result.status {
+                        return Ok(());
+                    }
+                    let mut message = format!(
+                        "The input file of testcase {}, subtask {} is not valid: {:?}",
+                        testcase_id, subtask_id, result.status
+                    );
+                    if !constraints.is_empty() {
+                        message += &format!(" (constraints: {})", constraints.join(", "));
+                    }
+                    sender.send(UIMessage::Warning { message })
+                });
+                let sender = eval.sender.clone();
                 eval.dag
                     .get_file_content(exec.stderr(), STDERR_CONTENT_LENGTH, move |content| {
                         let content = String::from_utf8_lossy(&content);
@@ -321,6 +462,15 @@ impl OutputGenerator {
                 eval.dag.provide_file(file, &path)?;
                 Ok(uuid)
             }
+            OutputGenerator::NotAvailable => {
+                let file = File::new(format!(
+                    "Missing output file of testcase {}, subtask {}",
+                    testcase_id, subtask_id
+                ));
+                let uuid = file.uuid;
+                eval.dag.provide_content(file, vec![]);
+                Ok(uuid)
+            }
             OutputGenerator::Custom(source_file, args) => {
                 let mut exec = source_file.execute(
                     eval,
@@ -365,25 +515,50 @@ impl Checker {
         test_output: FileUuid,
         callback: F,
     ) -> Result<(), Error>
+    where
+        F: FnOnce(f64, String) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.check_with_policy(
+            eval,
+            subtask_id,
+            testcase_id,
+            solution,
+            input,
+            correct_output,
+            test_output,
+            CheckerPolicy::Skip,
+            callback,
+        )
+    }
+
+    /// Same as [`check`](#method.check), but with `CheckerPolicy::Run` the checker is run even if
+    /// the solution that produced `test_output` has failed.
+    pub(crate) fn check_with_policy<S: Into<PathBuf>, F>(
+        &self,
+        eval: &mut EvaluationData,
+        subtask_id: SubtaskId,
+        testcase_id: TestcaseId,
+        solution: S,
+        input: FileUuid,
+        correct_output: FileUuid,
+        test_output: FileUuid,
+        policy: CheckerPolicy,
+        callback: F,
+    ) -> Result<(), Error>
     where
         F: FnOnce(f64, String) -> Result<(), Error> + Send + Sync + 'static,
     {
         let solution = solution.into();
+        let run_on_failure = policy == CheckerPolicy::Run;
         match self {
-            Checker::WhiteDiff => {
-                let mut exec = Execution::new(
-                    format!(
-                        "Checking output of {:?} of testcase {}, subtask {}",
-                        solution.file_name().unwrap(),
-                        testcase_id,
-                        subtask_id
-                    ),
-                    ExecutionCommand::system("diff"),
+            Checker::WhiteDiff(options) => {
+                let exec = Checker::white_diff_execution(
+                    options,
+                    checker_description(&solution, subtask_id, testcase_id),
+                    correct_output,
+                    test_output,
+                    run_on_failure,
                 );
-                exec.args(vec!["--ignore-all-space", "correct", "test"])
-                    .input(correct_output, "correct", false)
-                    .input(test_output, "test", false)
-                    .tag(Tag::Checking.into());
                 bind_exec_callbacks!(
                     eval,
                     exec.uuid,
@@ -395,35 +570,18 @@ impl Checker {
                     },
                     solution
                 )?;
-                eval.dag.on_execution_done(&exec.uuid, move |result| {
-                    match result.status {
-                        // diff exits with 0 if the files are equal
-                        ExecutionStatus::Success => callback(1.0, "Output is correct".into())?,
-                        // return code 1 means the files are different
-                        ExecutionStatus::ReturnCode(1) => {
-                            callback(0.0, "Output is incorrect".into())?
-                        }
-                        _ => unreachable!("diff died badly?"),
-                    };
-                    Ok(())
-                });
-                eval.dag.add_execution(exec);
+                Checker::bind_white_diff_outcome(eval, exec, callback);
             }
             Checker::Custom(source_file) => {
-                let mut exec = source_file.execute(
+                let exec = Checker::custom_checker_execution(
+                    source_file,
                     eval,
-                    format!(
-                        "Checking output of {:?} of testcase {}, subtask {}",
-                        solution.file_name().unwrap(),
-                        testcase_id,
-                        subtask_id
-                    ),
-                    vec!["input", "correct_output", "test_output"],
+                    checker_description(&solution, subtask_id, testcase_id),
+                    input,
+                    correct_output,
+                    test_output,
+                    run_on_failure,
                 )?;
-                exec.input(input, "input", false)
-                    .input(correct_output, "correct_output", false)
-                    .input(test_output, "test_output", false)
-                    .tag(Tag::Checking.into());
                 bind_exec_callbacks!(
                     eval,
                     exec.uuid,
@@ -435,51 +593,471 @@ impl Checker {
                     },
                     solution
                 )?;
-                let stdout = exec.stdout();
-                let stderr = exec.stderr();
-                eval.dag.add_execution(exec);
-                // wait for both the stdout and the stderr
-                let state_stdout: Arc<Mutex<(Option<f64>, Option<String>)>> =
-                    Arc::new(Mutex::new((None, None)));
-                let state_stderr = state_stdout.clone();
-                let callback_stdout = Arc::new(Mutex::new(Some(callback)));
-                let callback_stderr = callback_stdout.clone();
-                macro_rules! send_state {
-                    ($callback:expr, $state:expr) => {{
-                        // if both the score and the message are present
-                        if let (Some(ref score), Some(ref message)) = *$state {
-                            if let Some(f) = $callback.lock().unwrap().take() {
-                                f(*score, message.clone())?;
-                            }
-                        }
-                    }};
-                }
-                eval.dag.get_file_content(stdout, 128, move |content| {
-                    let score = String::from_utf8_lossy(&content);
-                    let score: f64 = score
-                        .trim()
-                        .parse()
-                        .map_err(|e| format_err!("Invalid score from checker: {:?}", e))?;
-                    let mut state = state_stdout.lock().unwrap();
-                    state.0 = Some(score);
-                    send_state!(callback_stdout, state);
-                    Ok(())
-                });
-                eval.dag.get_file_content(stderr, 1024, move |content| {
-                    let mut state = state_stderr.lock().unwrap();
-                    state.1 = Some(String::from_utf8_lossy(&content).trim().to_string());
-                    send_state!(callback_stderr, state);
-                    Ok(())
-                });
+                Checker::bind_checker_outcome(eval, exec, callback);
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as [`check_with_policy`](#method.check_with_policy), but a custom checker is run a
+    /// second time on the same files, sending a warning to the UI if the two runs give a different
+    /// outcome. `callback` is called with the outcome of the first run.
+    pub(crate) fn check_deterministic<S: Into<PathBuf>, F>(
+        &self,
+        eval: &mut EvaluationData,
+        subtask_id: SubtaskId,
+        testcase_id: TestcaseId,
+        solution: S,
+        input: FileUuid,
+        correct_output: FileUuid,
+        test_output: FileUuid,
+        policy: CheckerPolicy,
+        callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(f64, String) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        let solution = solution.into();
+        let source_file = match self {
+            Checker::Custom(source_file) => source_file,
+            // diff is deterministic
+            Checker::WhiteDiff(_) => {
+                return self.check_with_policy(
+                    eval,
+                    subtask_id,
+                    testcase_id,
+                    solution,
+                    input,
+                    correct_output,
+                    test_output,
+                    policy,
+                    callback,
+                );
+            }
+        };
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        let first_outcomes = outcomes.clone();
+        let first_sender = eval.sender.clone();
+        let first_solution = solution.clone();
+        self.check_with_policy(
+            eval,
+            subtask_id,
+            testcase_id,
+            solution.clone(),
+            input,
+            correct_output,
+            test_output,
+            policy,
+            move |score, message| {
+                let outcome = (score, message.clone());
+                record_checker_outcome(
+                    &first_outcomes,
+                    outcome,
+                    &first_sender,
+                    &first_solution,
+                    subtask_id,
+                    testcase_id,
+                )?;
+                callback(score, message)
+            },
+        )?;
+        // the repetition has a different environment so that it's not a cache hit of the first run
+        let mut exec = Checker::custom_checker_execution(
+            source_file,
+            eval,
+            checker_description(&solution, subtask_id, testcase_id),
+            input,
+            correct_output,
+            test_output,
+            policy == CheckerPolicy::Run,
+        )?;
+        exec.env(CHECKER_REPETITION_ENV, "1");
+        exec.description = format!("{} (repeated)", exec.description);
+        let sender = eval.sender.clone();
+        Checker::bind_checker_outcome(eval, exec, move |score, message| {
+            record_checker_outcome(
+                &outcomes,
+                (score, message),
+                &sender,
+                &solution,
+                subtask_id,
+                testcase_id,
+            )
+        });
+        Ok(())
+    }
+
+    /// Same as [`check_with_policy`](#method.check_with_policy), but the output is also compared
+    /// with white diff: if the custom checker cannot run because it failed to compile, the outcome
+    /// of white diff is used instead, sending a warning to the UI.
+    pub(crate) fn check_with_fallback<S: Into<PathBuf>, F>(
+        &self,
+        eval: &mut EvaluationData,
+        subtask_id: SubtaskId,
+        testcase_id: TestcaseId,
+        solution: S,
+        input: FileUuid,
+        correct_output: FileUuid,
+        test_output: FileUuid,
+        policy: CheckerPolicy,
+        callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(f64, String) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        let solution = solution.into();
+        let source_file = match self {
+            Checker::Custom(source_file) => source_file,
+            Checker::WhiteDiff(_) => {
+                return self.check_with_policy(
+                    eval,
+                    subtask_id,
+                    testcase_id,
+                    solution,
+                    input,
+                    correct_output,
+                    test_output,
+                    policy,
+                    callback,
+                );
+            }
+        };
+        let run_on_failure = policy == CheckerPolicy::Run;
+        let description = checker_description(&solution, subtask_id, testcase_id);
+        let state = Arc::new(Mutex::new(FallbackState {
+            callback: Some(callback),
+            fallback_outcome: None,
+            checker_skipped: false,
+        }));
+
+        let exec = Checker::custom_checker_execution(
+            source_file,
+            eval,
+            description.clone(),
+            input,
+            correct_output,
+            test_output,
+            run_on_failure,
+        )?;
+        bind_exec_callbacks!(
+            eval,
+            exec.uuid,
+            |status, solution| UIMessage::IOIChecker {
+                subtask: subtask_id,
+                testcase: testcase_id,
+                solution,
+                status
+            },
+            solution
+        )?;
+        // the checker is skipped if its executable is missing
+        {
+            let state = state.clone();
+            let sender = eval.sender.clone();
+            let checker = source_file.path.clone();
+            eval.dag.on_execution_skip(&exec.uuid, move || {
+                let mut state = state.lock().unwrap();
+                state.checker_skipped = true;
+                state.use_fallback(&sender, &checker)
+            });
+        }
+        let checker_state = state.clone();
+        Checker::bind_checker_outcome(eval, exec, move |score, message| {
+            let callback = checker_state.lock().unwrap().callback.take();
+            match callback {
+                Some(callback) => callback(score, message),
+                None => Ok(()),
+            }
+        });
+
+        let diff = Checker::white_diff_execution(
+            &WhiteDiffOptions::default(),
+            format!("{} (white diff fallback)", description),
+            correct_output,
+            test_output,
+            run_on_failure,
+        );
+        let sender = eval.sender.clone();
+        let checker = source_file.path.clone();
+        Checker::bind_white_diff_outcome(eval, diff, move |score, message| {
+            let mut state = state.lock().unwrap();
+            state.fallback_outcome = Some((score, message));
+            state.use_fallback(&sender, &checker)
+        });
+        Ok(())
+    }
+
+    /// Add to the DAG the checking of the output of `solution` using the output of `reference` as
+    /// the correct one, warning if the checker does not fully accept it. No other message is sent
+    /// to the UI: the outcome does not affect the score of the solutions.
+    pub(crate) fn cross_check(
+        &self,
+        eval: &mut EvaluationData,
+        subtask_id: SubtaskId,
+        testcase_id: TestcaseId,
+        reference: &Path,
+        solution: &Path,
+        input: FileUuid,
+        reference_output: FileUuid,
+        test_output: FileUuid,
+    ) -> Result<(), Error> {
+        let description = format!(
+            "Cross-checking output of {:?} against {:?} of testcase {}, subtask {}",
+            solution.file_name().unwrap(),
+            reference.file_name().unwrap(),
+            testcase_id,
+            subtask_id
+        );
+        let sender = eval.sender.clone();
+        let reference_name = reference.file_name().unwrap().to_string_lossy().to_string();
+        let solution_name = solution.file_name().unwrap().to_string_lossy().to_string();
+        let callback = move |score: f64, message: String| {
+            if score < 1.0 - 1e-9 {
+                sender.send(UIMessage::Warning {
+                    message: format!(
+                        "The outputs of {} and {} disagree on testcase {}, subtask {}: {} ({:?})",
+                        reference_name, solution_name, testcase_id, subtask_id, score, message
+                    ),
+                })?;
+            }
+            Ok(())
+        };
+        match self {
+            Checker::WhiteDiff(options) => {
+                let exec = Checker::white_diff_execution(
+                    options,
+                    description,
+                    reference_output,
+                    test_output,
+                    false,
+                );
+                Checker::bind_white_diff_outcome(eval, exec, callback);
+            }
+            Checker::Custom(source_file) => {
+                let exec = Checker::custom_checker_execution(
+                    source_file,
+                    eval,
+                    description,
+                    input,
+                    reference_output,
+                    test_output,
+                    false,
+                )?;
+                Checker::bind_checker_outcome(eval, exec, callback);
+            }
+        }
+        Ok(())
+    }
+
+    /// Make the execution of `diff` comparing the output of a solution with the correct one, or of
+    /// `awk` if the lines can be in any order.
+    fn white_diff_execution(
+        options: &WhiteDiffOptions,
+        description: String,
+        correct_output: FileUuid,
+        test_output: FileUuid,
+        run_on_failure: bool,
+    ) -> Execution {
+        let mut exec = if options.unordered_lines {
+            let normalize = if options.ignore_case {
+                "; $0 = tolower($0)"
+            } else {
+                ""
+            };
+            let program = UNORDERED_LINES_PROGRAM.replace("{normalize}", normalize);
+            let mut exec = Execution::new(description, ExecutionCommand::system("awk"));
+            exec.args(vec![program.as_str(), "correct", "test"]);
+            exec
+        } else {
+            let mut exec = Execution::new(description, ExecutionCommand::system("diff"));
+            let mut args = vec!["--ignore-all-space"];
+            if options.ignore_case {
+                args.push("--ignore-case");
             }
+            args.extend(&["correct", "test"]);
+            exec.args(args);
+            exec
+        };
+        exec.input(correct_output, "correct", false)
+            .input(test_output, "test", false)
+            .allow_failed_inputs(run_on_failure)
+            .tag(Tag::Checking.into());
+        exec
+    }
+
+    /// Add the execution of `diff` (or of the unordered lines comparison, which exits in the same
+    /// way) to the DAG, calling `callback` with the outcome of the comparison.
+    fn bind_white_diff_outcome<F>(eval: &mut EvaluationData, exec: Execution, callback: F)
+    where
+        F: FnOnce(f64, String) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        eval.dag.on_execution_done(&exec.uuid, move |result| {
+            match result.status {
+                // diff exits with 0 if the files are equal
+                ExecutionStatus::Success => callback(1.0, "Output is correct".into())?,
+                // return code 1 means the files are different
+                ExecutionStatus::ReturnCode(1) => callback(0.0, "Output is incorrect".into())?,
+                _ => unreachable!("diff died badly?"),
+            };
+            Ok(())
+        });
+        eval.dag.add_execution(exec);
+    }
+
+    /// Make the execution of a custom checker on the output of a solution.
+    fn custom_checker_execution(
+        source_file: &SourceFile,
+        eval: &mut EvaluationData,
+        description: String,
+        input: FileUuid,
+        correct_output: FileUuid,
+        test_output: FileUuid,
+        run_on_failure: bool,
+    ) -> Result<Execution, Error> {
+        let mut exec = source_file.execute(
+            eval,
+            description,
+            vec!["input", "correct_output", "test_output"],
+        )?;
+        exec.input(input, "input", false)
+            .input(correct_output, "correct_output", false)
+            .input(test_output, "test_output", false)
+            .allow_failed_inputs(run_on_failure)
+            .tag(Tag::Checking.into());
+        Ok(exec)
+    }
+
+    /// Add the execution of a custom checker to the DAG, calling `callback` with the score and the
+    /// message it reports.
+    fn bind_checker_outcome<F>(eval: &mut EvaluationData, mut exec: Execution, callback: F)
+    where
+        F: FnOnce(f64, String) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        let stdout = exec.stdout();
+        let stderr = exec.stderr();
+        eval.dag.add_execution(exec);
+        // wait for both the stdout and the stderr
+        let state_stdout: Arc<Mutex<(Option<f64>, Option<String>)>> =
+            Arc::new(Mutex::new((None, None)));
+        let state_stderr = state_stdout.clone();
+        let callback_stdout = Arc::new(Mutex::new(Some(callback)));
+        let callback_stderr = callback_stdout.clone();
+        macro_rules! send_state {
+            ($callback:expr, $state:expr) => {{
+                // if both the score and the message are present
+                if let (Some(ref score), Some(ref message)) = *$state {
+                    if let Some(f) = $callback.lock().unwrap().take() {
+                        f(*score, message.clone())?;
+                    }
+                }
+            }};
+        }
+        eval.dag.get_file_content(stdout, 128, move |content| {
+            let score = String::from_utf8_lossy(&content);
+            let score: f64 = score
+                .trim()
+                .parse()
+                .map_err(|e| format_err!("Invalid score from checker: {:?}", e))?;
+            let mut state = state_stdout.lock().unwrap();
+            state.0 = Some(score);
+            send_state!(callback_stdout, state);
+            Ok(())
+        });
+        eval.dag.get_file_content(stderr, 1024, move |content| {
+            let mut state = state_stderr.lock().unwrap();
+            state.1 = Some(String::from_utf8_lossy(&content).trim().to_string());
+            send_state!(callback_stderr, state);
+            Ok(())
+        });
+    }
+}
+
+/// The description of the execution of the checker on the output of a solution.
+fn checker_description(solution: &Path, subtask_id: SubtaskId, testcase_id: TestcaseId) -> String {
+    format!(
+        "Checking output of {:?} of testcase {}, subtask {}",
+        solution.file_name().unwrap(),
+        testcase_id,
+        subtask_id
+    )
+}
+
+/// The outcomes of a custom checker and of its white diff fallback, see
+/// [`Checker::check_with_fallback`](enum.Checker.html#method.check_with_fallback).
+struct FallbackState<F> {
+    /// The callback to call with the outcome, `None` after it has been called.
+    callback: Option<F>,
+    /// The outcome of white diff, if known.
+    fallback_outcome: Option<(f64, String)>,
+    /// Whether the custom checker has been skipped.
+    checker_skipped: bool,
+}
+
+impl<F> FallbackState<F>
+where
+    F: FnOnce(f64, String) -> Result<(), Error>,
+{
+    /// Call the callback with the outcome of white diff if the custom checker has been skipped and
+    /// the outcome is known, warning that the checker is not used.
+    fn use_fallback(
+        &mut self,
+        sender: &Arc<Mutex<UIMessageSender>>,
+        checker: &Path,
+    ) -> Result<(), Error> {
+        if !self.checker_skipped || self.fallback_outcome.is_none() {
+            return Ok(());
+        }
+        if let Some(callback) = self.callback.take() {
+            sender.send(UIMessage::Warning {
+                message: format!(
+                    "The checker {} cannot be run, probably it failed to compile: the outputs are \
+                     compared with white diff",
+                    checker.display()
+                ),
+            })?;
+            let (score, message) = self.fallback_outcome.take().unwrap();
+            callback(score, message)?;
         }
         Ok(())
     }
 }
 
+/// Store the outcome of one of the two runs of a checker whose determinism is being verified. When
+/// both the outcomes are known they are compared, warning if they differ.
+fn record_checker_outcome(
+    outcomes: &Mutex<Vec<(f64, String)>>,
+    outcome: (f64, String),
+    sender: &Arc<Mutex<UIMessageSender>>,
+    solution: &Path,
+    subtask_id: SubtaskId,
+    testcase_id: TestcaseId,
+) -> Result<(), Error> {
+    let mut outcomes = outcomes.lock().unwrap();
+    outcomes.push(outcome);
+    if let [(score1, message1), (score2, message2)] = outcomes.as_slice() {
+        if (score1 - score2).abs() > 1e-9 || message1 != message2 {
+            sender.send(UIMessage::Warning {
+                message: format!(
+                    "The checker is not deterministic on the output of {} on testcase {}, \
+                     subtask {}: {} ({:?}) and {} ({:?})",
+                    solution.file_name().unwrap().to_string_lossy(),
+                    testcase_id,
+                    subtask_id,
+                    score1,
+                    message1,
+                    score2,
+                    message2
+                ),
+            })?;
+        }
+    }
+    Ok(())
+}
+
 impl TaskType {
     /// Evaluate a solution on a testcase, eventually adding to the `ScoreManager` the result of the
-    /// evaluation. This will add both the execution as well as the checking to the DAG.
+    /// evaluation. This will add both the execution as well as the checking to the DAG. Returns the
+    /// output file of the solution, if it produces one.
     pub(crate) fn evaluate(
         &self,
         task: &Task,
@@ -491,7 +1069,7 @@ impl TaskType {
         validation_handle: Option<FileUuid>,
         correct_output: FileUuid,
         score_manager: Arc<Mutex<ScoreManager>>,
-    ) -> Result<(), Error> {
+    ) -> Result<Option<FileUuid>, Error> {
         match self {
             TaskType::Batch => {
                 let mut exec = source_file.execute(
@@ -529,73 +1107,407 @@ impl TaskType {
                 let sender = eval.sender.clone();
                 let path = source_file.path.clone();
                 let score_manager_err = score_manager.clone();
+                let infile = task.infile.clone();
+                let policy = task.checker_policy;
+                // with CheckerPolicy::Run the testcase is scored by the checker callback, knowing
+                // that the solution has failed
+                let solution_failed = Arc::new(AtomicBool::new(false));
+                let solution_failed_checker = solution_failed.clone();
                 eval.dag
                     .on_execution_done(&exec.uuid, move |result| match result.status {
                         ExecutionStatus::Success => Ok(()),
-                        _ => score_manager_err.lock().unwrap().score(
-                            subtask_id,
-                            testcase_id,
-                            0.0,
-                            format!("{:?}", result.status),
-                            sender,
-                            path,
-                        ),
+                        _ => {
+                            if let Some(hint) = io_mode_mismatch_hint(infile.as_ref(), &result) {
+                                sender.send(UIMessage::Warning {
+                                    message: format!(
+                                        "{} on testcase {}: {}",
+                                        path.file_name()
+                                            .expect("Invalid file name")
+                                            .to_string_lossy(),
+                                        testcase_id,
+                                        hint
+                                    ),
+                                })?;
+                            }
+                            if policy == CheckerPolicy::Run {
+                                solution_failed.store(true, Ordering::SeqCst);
+                                return Ok(());
+                            }
+                            score_manager_err.lock().unwrap().score(
+                                subtask_id,
+                                testcase_id,
+                                0.0,
+                                format!("{:?}", result.status),
+                                sender,
+                                path,
+                            )
+                        }
                     });
                 eval.dag.add_execution(exec);
 
                 let sender = eval.sender.clone();
                 let path = source_file.path.clone();
-                task.checker.check(
+                let callback = move |score, message| {
+                    // a failed solution never scores, whatever the checker says
+                    let score = if solution_failed_checker.load(Ordering::SeqCst) {
+                        0.0
+                    } else {
+                        score
+                    };
+                    score_manager.lock().unwrap().score(
+                        subtask_id,
+                        testcase_id,
+                        score,
+                        message,
+                        sender,
+                        path,
+                    )
+                };
+                if task.verify_checker {
+                    task.checker.check_deterministic(
+                        eval,
+                        subtask_id,
+                        testcase_id,
+                        source_file.path.clone(),
+                        input,
+                        correct_output,
+                        output.uuid,
+                        policy,
+                        callback,
+                    )?;
+                } else if task.checker_fallback {
+                    task.checker.check_with_fallback(
+                        eval,
+                        subtask_id,
+                        testcase_id,
+                        source_file.path.clone(),
+                        input,
+                        correct_output,
+                        output.uuid,
+                        policy,
+                        callback,
+                    )?;
+                } else {
+                    task.checker.check_with_policy(
+                        eval,
+                        subtask_id,
+                        testcase_id,
+                        source_file.path.clone(),
+                        input,
+                        correct_output,
+                        output.uuid,
+                        policy,
+                        callback,
+                    )?;
+                }
+                Ok(Some(output.uuid))
+            }
+            TaskType::OutputOnly => {
+                bail!("The solutions of output-only tasks cannot be executed");
+            }
+            TaskType::Communication(manager) => {
+                let mut exec = source_file.execute(
                     eval,
-                    subtask_id,
-                    testcase_id,
-                    source_file.path.clone(),
-                    input,
-                    correct_output,
-                    output.uuid,
-                    move |score, message| {
-                        score_manager.lock().unwrap().score(
-                            subtask_id,
-                            testcase_id,
-                            score,
-                            message,
-                            sender,
-                            path,
-                        )
-                    },
+                    format!(
+                        "Evaluation of {} on testcase {}, subtask {}",
+                        source_file.name(),
+                        testcase_id,
+                        subtask_id
+                    ),
+                    Vec::<String>::new(),
                 )?;
-            }
-        };
-        Ok(())
-    }
-}
-
-impl TestcaseScoreAggregator {
-    /// Aggregate the scores of a subtask from an iterator with the scores of the testcases.
-    pub(crate) fn aggregate<I: IntoIterator<Item = f64>>(&self, iter: I) -> f64 {
-        match self {
-            TestcaseScoreAggregator::Min => iter
-                .into_iter()
-                .min_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap_or(1.0),
-            TestcaseScoreAggregator::Sum => {
-                let sum_count = iter
-                    .into_iter()
-                    .fold((0.0, 0), |prev, cur| (prev.0 + cur, prev.1 + 1));
-                if sum_count.1 == 0 {
-                    return 1.0;
+                exec.tag(Tag::Evaluation.into())
+                    .stdin_fifo(MANAGER_TO_SOLUTION_FIFO)
+                    .stdout_fifo(SOLUTION_TO_MANAGER_FIFO);
+                let limits = exec.limits_mut();
+                if let Some(time_limit) = task.time_limit {
+                    limits.cpu_time(time_limit);
+                    limits.wall_time(time_limit * 1.5 + 1.0); // some margin
                 }
-                sum_count.0 / (f64::from(sum_count.1))
-            }
-        }
-    }
-}
+                if let Some(memory_limit) = task.memory_limit {
+                    limits.memory(memory_limit * 1024); // MiB -> KiB
+                }
+                let solution_wall_time = exec.limits.wall_time;
+                let path = source_file.path.clone();
+                bind_exec_callbacks!(
+                    eval,
+                    exec.uuid,
+                    |status, solution| UIMessage::IOIEvaluation {
+                        subtask: subtask_id,
+                        testcase: testcase_id,
+                        solution,
+                        status
+                    },
+                    path
+                )?;
+                // the solution is done before its manager, a failed solution never scores
+                let solution_failure = Arc::new(Mutex::new(None));
+                let manager_failure = solution_failure.clone();
+                eval.dag
+                    .on_execution_done(&exec.uuid, move |result| match result.status {
+                        ExecutionStatus::Success => Ok(()),
+                        status => {
+                            *solution_failure.lock().unwrap() = Some(format!("{:?}", status));
+                            Ok(())
+                        }
+                    });
+
+                let mut manager_exec = manager.execute(
+                    eval,
+                    format!(
+                        "Manager of {} on testcase {}, subtask {}",
+                        source_file.name(),
+                        testcase_id,
+                        subtask_id
+                    ),
+                    vec![SOLUTION_TO_MANAGER_FIFO, MANAGER_TO_SOLUTION_FIFO],
+                )?;
+                manager_exec
+                    .stdin(input)
+                    .input(correct_output, "correct_output", false)
+                    .fifo(SOLUTION_TO_MANAGER_FIFO)
+                    .fifo(MANAGER_TO_SOLUTION_FIFO)
+                    .tag(Tag::Checking.into());
+                if let Some(wall_time) = solution_wall_time {
+                    manager_exec
+                        .limits_mut()
+                        .wall_time(wall_time + MANAGER_WALL_TIME_GRACE);
+                }
+                if let Some(validation_handle) = validation_handle {
+                    manager_exec.input(validation_handle, "wait_for_validation", false);
+                }
+                manager_exec.add_concurrent(exec);
+                let path = source_file.path.clone();
+                bind_exec_callbacks!(
+                    eval,
+                    manager_exec.uuid,
+                    |status, solution| UIMessage::IOIChecker {
+                        subtask: subtask_id,
+                        testcase: testcase_id,
+                        solution,
+                        status
+                    },
+                    path
+                )?;
+                let sender = eval.sender.clone();
+                let path = source_file.path.clone();
+                Checker::bind_checker_outcome(eval, manager_exec, move |score, message| {
+                    let (score, message) = match manager_failure.lock().unwrap().take() {
+                        Some(failure) => (0.0, failure),
+                        None => (score, message),
+                    };
+                    score_manager.lock().unwrap().score(
+                        subtask_id,
+                        testcase_id,
+                        score,
+                        message,
+                        sender,
+                        path,
+                    )
+                });
+                Ok(None)
+            }
+        }
+    }
+
+    /// Evaluate a submitted output file of an output-only task on a testcase, adding to the
+    /// `ScoreManager` the result of the checker. If the output file is missing the testcase scores
+    /// zero.
+    pub(crate) fn evaluate_output(
+        &self,
+        task: &Task,
+        eval: &mut EvaluationData,
+        subtask_id: SubtaskId,
+        testcase_id: TestcaseId,
+        outputs_dir: &Path,
+        input: FileUuid,
+        correct_output: FileUuid,
+        score_manager: Arc<Mutex<ScoreManager>>,
+    ) -> Result<(), Error> {
+        let path = outputs_dir.join(format!("output_{:03}.txt", testcase_id));
+        if !path.exists() {
+            return score_manager.lock().unwrap().score(
+                subtask_id,
+                testcase_id,
+                0.0,
+                format!(
+                    "Missing output file {}",
+                    path.file_name().unwrap().to_string_lossy()
+                ),
+                eval.sender.clone(),
+                outputs_dir.to_owned(),
+            );
+        }
+        let output = File::new(format!(
+            "Submitted output of testcase {}, subtask {} from {:?}",
+            testcase_id, subtask_id, path
+        ));
+        let output_uuid = output.uuid;
+        eval.dag.provide_file(output, &path)?;
+        let sender = eval.sender.clone();
+        let solution = outputs_dir.to_owned();
+        let callback = move |score, message| {
+            score_manager.lock().unwrap().score(
+                subtask_id,
+                testcase_id,
+                score,
+                message,
+                sender,
+                solution,
+            )
+        };
+        if task.verify_checker {
+            task.checker.check_deterministic(
+                eval,
+                subtask_id,
+                testcase_id,
+                outputs_dir,
+                input,
+                correct_output,
+                output_uuid,
+                CheckerPolicy::Skip,
+                callback,
+            )
+        } else if task.checker_fallback {
+            task.checker.check_with_fallback(
+                eval,
+                subtask_id,
+                testcase_id,
+                outputs_dir,
+                input,
+                correct_output,
+                output_uuid,
+                CheckerPolicy::Skip,
+                callback,
+            )
+        } else {
+            task.checker.check(
+                eval,
+                subtask_id,
+                testcase_id,
+                outputs_dir,
+                input,
+                correct_output,
+                output_uuid,
+                callback,
+            )
+        }
+    }
+}
+
+/// Heuristic for detecting a solution that reads from stdin when the task uses files: if the
+/// solution timed out almost without using the CPU it was probably blocked waiting for an input
+/// that never arrived. Returns a suggestion for the user, only for the tasks that use files.
+pub(crate) fn io_mode_mismatch_hint(
+    infile: Option<&PathBuf>,
+    result: &ExecutionResult,
+) -> Option<String> {
+    let infile = infile?;
+    match result.status {
+        ExecutionStatus::TimeLimitExceeded | ExecutionStatus::WallTimeLimitExceeded => {}
+        _ => return None,
+    }
+    let resources = &result.resources;
+    if resources.cpu_time + resources.sys_time > resources.wall_time * BLOCKED_MAX_CPU_RATIO {
+        return None;
+    }
+    Some(format!(
+        "the solution timed out without using the CPU, is it reading from stdin instead of {}?",
+        infile.display()
+    ))
+}
+
+impl TestcaseScoreAggregator {
+    /// Aggregate the scores of a subtask from an iterator with the scores of the testcases, sorted
+    /// by testcase id.
+    pub(crate) fn aggregate<I: IntoIterator<Item = f64>>(&self, iter: I) -> f64 {
+        match self {
+            TestcaseScoreAggregator::Min => iter
+                .into_iter()
+                .map(nan_to_zero)
+                .min_by(|a, b| a.total_cmp(b))
+                .unwrap_or(1.0),
+            TestcaseScoreAggregator::Sum => {
+                let sum_count = iter
+                    .into_iter()
+                    .fold((0.0, 0), |prev, cur| (prev.0 + cur, prev.1 + 1));
+                if sum_count.1 == 0 {
+                    return 1.0;
+                }
+                sum_count.0 / (f64::from(sum_count.1))
+            }
+            TestcaseScoreAggregator::Custom(CustomScoreAggregator(aggregator)) => {
+                let score = aggregator(&iter.into_iter().collect::<Vec<_>>());
+                // a broken aggregator is worth nothing
+                if score.is_nan() {
+                    0.0
+                } else {
+                    score.clamp(0.0, 1.0)
+                }
+            }
+        }
+    }
+}
+
+impl TaskScoreAggregator {
+    /// Aggregate the score of the task from an iterator with the scores of the subtasks, sorted by
+    /// subtask id.
+    pub(crate) fn aggregate<I: IntoIterator<Item = f64>>(&self, iter: I) -> f64 {
+        match self {
+            TaskScoreAggregator::Sum => iter.into_iter().sum(),
+            TaskScoreAggregator::Max => iter
+                .into_iter()
+                .map(nan_to_zero)
+                .max_by(|a, b| a.total_cmp(b))
+                .unwrap_or(0.0),
+            TaskScoreAggregator::Custom(CustomScoreAggregator(aggregator)) => {
+                aggregator(&iter.into_iter().collect::<Vec<_>>())
+            }
+        }
+    }
+}
+
+/// A NaN score, for example printed by a broken checker, is worth nothing.
+fn nan_to_zero(score: f64) -> f64 {
+    if score.is_nan() {
+        0.0
+    } else {
+        score
+    }
+}
+
+impl CustomScoreAggregator {
+    /// Make a new aggregator based on the specified function.
+    pub fn new<F: Fn(&[f64]) -> f64 + Send + Sync + 'static>(aggregator: F) -> Self {
+        CustomScoreAggregator(Arc::new(aggregator))
+    }
+}
+
+impl std::fmt::Debug for CustomScoreAggregator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("CustomScoreAggregator")
+    }
+}
+
+impl Serialize for CustomScoreAggregator {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+impl<'de> Deserialize<'de> for CustomScoreAggregator {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <()>::deserialize(deserializer)?;
+        Ok(CustomScoreAggregator::new(|_| f64::NAN))
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::atomic::{AtomicBool, Ordering};
-    use task_maker_dag::{ExecutionResourcesUsage, ExecutionResult};
+    use task_maker_dag::{
+        ExecutionProvenance, ExecutionResourcesUsage, ExecutionResult, SkipReason,
+    };
     use task_maker_lang::GraderMap;
 
     fn make_task<P: Into<PathBuf>>(path: P) -> Task {
@@ -609,8 +1521,14 @@ mod tests {
             infile: None,
             outfile: None,
             subtasks: Default::default(),
-            checker: Checker::WhiteDiff,
+            checker: Checker::WhiteDiff(WhiteDiffOptions::default()),
             testcase_score_aggregator: TestcaseScoreAggregator::Min,
+            task_score_aggregator: TaskScoreAggregator::Sum,
+            score_precision: 2,
+            checker_policy: CheckerPolicy::Skip,
+            verify_checker: false,
+            checker_fallback: false,
+            readable_dirs: vec![],
             grader_map: Arc::new(GraderMap::new(Vec::<PathBuf>::new())),
             booklets: vec![],
             difficulty: None,
@@ -618,6 +1536,51 @@ mod tests {
         }
     }
 
+    fn timed_out(cpu_time: f64, wall_time: f64) -> ExecutionResult {
+        ExecutionResult {
+            status: ExecutionStatus::WallTimeLimitExceeded,
+            was_killed: true,
+            was_cached: false,
+            truncated_output: false,
+            provenance: ExecutionProvenance::Unknown,
+            resources: ExecutionResourcesUsage {
+                cpu_time,
+                sys_time: 0.0,
+                wall_time,
+                memory: 0,
+                processes: None,
+                setup_time: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_io_mode_mismatch_hint_blocked_on_stdin() {
+        let infile = PathBuf::from("input.txt");
+        let hint = io_mode_mismatch_hint(Some(&infile), &timed_out(0.001, 2.5)).unwrap();
+        assert!(hint.contains("stdin"));
+        assert!(hint.contains("input.txt"));
+    }
+
+    #[test]
+    fn test_io_mode_mismatch_hint_busy_solution() {
+        let infile = PathBuf::from("input.txt");
+        assert!(io_mode_mismatch_hint(Some(&infile), &timed_out(2.0, 2.5)).is_none());
+    }
+
+    #[test]
+    fn test_io_mode_mismatch_hint_stdin_task() {
+        assert!(io_mode_mismatch_hint(None, &timed_out(0.001, 2.5)).is_none());
+    }
+
+    #[test]
+    fn test_io_mode_mismatch_hint_not_timed_out() {
+        let infile = PathBuf::from("input.txt");
+        let mut result = timed_out(0.001, 2.5);
+        result.status = ExecutionStatus::ReturnCode(1);
+        assert!(io_mode_mismatch_hint(Some(&infile), &result).is_none());
+    }
+
     #[test]
     fn test_aggregate_min() {
         let aggregator = TestcaseScoreAggregator::Min;
@@ -646,6 +1609,40 @@ mod tests {
         assert_abs_diff_eq!(1.0, sum);
     }
 
+    #[test]
+    fn test_aggregate_custom_clamped() {
+        let aggregator = TestcaseScoreAggregator::Custom(CustomScoreAggregator::new(|scores| {
+            scores.iter().sum::<f64>()
+        }));
+        assert_abs_diff_eq!(0.5, aggregator.aggregate(vec![0.2, 0.3]));
+        assert_abs_diff_eq!(1.0, aggregator.aggregate(vec![1.0, 0.7]));
+        let aggregator =
+            TestcaseScoreAggregator::Custom(CustomScoreAggregator::new(|_| std::f64::NAN));
+        assert_abs_diff_eq!(0.0, aggregator.aggregate(vec![1.0]));
+    }
+
+    #[test]
+    fn test_aggregate_nan() {
+        let aggregator = TestcaseScoreAggregator::Min;
+        assert_abs_diff_eq!(0.0, aggregator.aggregate(vec![1.0, f64::NAN]));
+        let aggregator = TaskScoreAggregator::Max;
+        assert_abs_diff_eq!(10.0, aggregator.aggregate(vec![f64::NAN, 10.0]));
+    }
+
+    #[test]
+    fn test_custom_aggregator_serde() {
+        let aggregator =
+            TaskScoreAggregator::Custom(CustomScoreAggregator::new(|scores| scores[0]));
+        let json = serde_json::to_string(&aggregator).unwrap();
+        let aggregator: TaskScoreAggregator = serde_json::from_str(&json).unwrap();
+        match aggregator {
+            TaskScoreAggregator::Custom(CustomScoreAggregator(aggregator)) => {
+                assert!(aggregator(&[1.0]).is_nan())
+            }
+            aggregator => panic!("Unexpected aggregator {:?}", aggregator),
+        }
+    }
+
     #[test]
     fn test_input_generator_static() {
         let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
@@ -708,7 +1705,7 @@ mod tests {
         let validator = InputValidator::AssumeValid;
         let file = File::new("input");
         let (mut eval, _recv) = EvaluationData::new();
-        let out = validator.validate(&mut eval, 0, 0, file.uuid).unwrap();
+        let out = validator.validate(&mut eval, 0, 0, file.uuid, &[]).unwrap();
         assert_eq!(eval.dag.data.provided_files.len(), 0);
         assert_eq!(eval.dag.data.executions.len(), 0);
         assert!(out.is_none());
@@ -723,7 +1720,7 @@ mod tests {
         let validator = InputValidator::Custom(Arc::new(source), vec![]);
         let file = File::new("input");
         let (mut eval, _recv) = EvaluationData::new();
-        let out = validator.validate(&mut eval, 0, 0, file.uuid).unwrap();
+        let out = validator.validate(&mut eval, 0, 0, file.uuid, &[]).unwrap();
         assert_eq!(eval.dag.data.provided_files.len(), 1);
         assert_eq!(eval.dag.data.executions.len(), 1);
         let exec = eval.dag.data.executions.values().next().unwrap();
@@ -733,6 +1730,55 @@ mod tests {
         assert_eq!(exec.env["TM_TESTCASE"], "0");
     }
 
+    #[test]
+    fn test_input_validator_constraints() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("val.py");
+        std::fs::write(&path, "x").unwrap();
+        let source = SourceFile::new(&path, "", None, None::<PathBuf>).unwrap();
+        let validator = InputValidator::Custom(Arc::new(source), vec![]);
+        let file = File::new("input");
+        let (mut eval, recv) = EvaluationData::new();
+        let constraints = vec!["1 <= N <= 1000".parse().unwrap()];
+        validator
+            .validate(&mut eval, 0, 0, file.uuid, &constraints)
+            .unwrap();
+        let exec = eval.dag.data.executions.values().next().unwrap();
+        assert_eq!(exec.env["TM_MIN_N"], "1");
+        assert_eq!(exec.env["TM_MAX_N"], "1000");
+
+        // a failed validation is reported as a warning, with the constraints
+        let callbacks = eval.dag.execution_callbacks.remove(&exec.uuid).unwrap();
+        for cb in callbacks.on_done {
+            cb.call(ExecutionResult {
+                status: ExecutionStatus::ReturnCode(1),
+                was_killed: false,
+                was_cached: false,
+                truncated_output: false,
+                provenance: ExecutionProvenance::Unknown,
+                resources: ExecutionResourcesUsage {
+                    cpu_time: 0.0,
+                    sys_time: 0.0,
+                    wall_time: 0.0,
+                    memory: 0,
+                    processes: None,
+                    setup_time: 0.0,
+                },
+            })
+            .unwrap();
+        }
+        let warnings: Vec<_> = recv
+            .try_iter()
+            .filter_map(|message| match message {
+                UIMessage::Warning { message } => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("not valid"), "{}", warnings[0]);
+        assert!(warnings[0].contains("1 <= N <= 1000"), "{}", warnings[0]);
+    }
+
     #[test]
     fn test_output_generator_static() {
         let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
@@ -802,7 +1848,7 @@ mod tests {
 
     #[test]
     fn test_checker_whitediff() {
-        let checker = Checker::WhiteDiff;
+        let checker = Checker::WhiteDiff(WhiteDiffOptions::default());
         let (mut eval, _recv) = EvaluationData::new();
         let input = File::new("input").uuid;
         let output = File::new("output").uuid;
@@ -817,13 +1863,99 @@ mod tests {
         let exec = eval.dag.data.executions.values().next().unwrap();
         assert_eq!(exec.tag.as_ref().unwrap(), &Tag::Checking.into());
         assert!(exec.args.contains(&"--ignore-all-space".into()));
+        assert!(!exec.args.contains(&"--ignore-case".into()));
         assert!(exec.dependencies().contains(&output));
         assert!(exec.dependencies().contains(&test));
     }
 
+    #[test]
+    fn test_checker_whitediff_ignore_case() {
+        let checker = Checker::WhiteDiff(WhiteDiffOptions {
+            ignore_case: true,
+            ..Default::default()
+        });
+        let (mut eval, _recv) = EvaluationData::new();
+        let input = File::new("input").uuid;
+        let output = File::new("output").uuid;
+        let test = File::new("test").uuid;
+        checker
+            .check(&mut eval, 0, 0, "sol", input, output, test, |_, _| {
+                panic!("the callback should not be called here")
+            })
+            .unwrap();
+        let exec = eval.dag.data.executions.values().next().unwrap();
+        assert_eq!(
+            exec.args,
+            vec!["--ignore-all-space", "--ignore-case", "correct", "test"]
+        );
+    }
+
+    /// Run the comparison of the built-in checker with the specified options on the two files,
+    /// returning its exit code.
+    fn run_white_diff(options: WhiteDiffOptions, correct: &str, test: &str) -> i32 {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        std::fs::write(tmpdir.path().join("correct"), correct).unwrap();
+        std::fs::write(tmpdir.path().join("test"), test).unwrap();
+        let exec = Checker::white_diff_execution(
+            &options,
+            "check".into(),
+            File::new("correct").uuid,
+            File::new("test").uuid,
+            false,
+        );
+        let program = match &exec.command {
+            ExecutionCommand::System(program) => program.clone(),
+            command => panic!("Unexpected command {:?}", command),
+        };
+        std::process::Command::new(program)
+            .args(&exec.args)
+            .current_dir(tmpdir.path())
+            .status()
+            .unwrap()
+            .code()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_checker_unordered_lines() {
+        let options = WhiteDiffOptions {
+            unordered_lines: true,
+            ..Default::default()
+        };
+        let correct = "1 2\n3 4\n5 6\n";
+        // a permutation of the lines, with different white spaces
+        assert_eq!(
+            run_white_diff(options.clone(), correct, "5 6\n  1\t2\n\n3 4"),
+            0
+        );
+        // a missing line
+        assert_eq!(run_white_diff(options.clone(), correct, "5 6\n3 4\n"), 1);
+        // a duplicated line
+        assert_eq!(
+            run_white_diff(options.clone(), correct, "5 6\n1 2\n3 4\n3 4\n"),
+            1
+        );
+        // a different line
+        assert_eq!(run_white_diff(options, correct, "5 6\n1 2\n3 5\n"), 1);
+    }
+
+    #[test]
+    fn test_checker_unordered_lines_ignore_case() {
+        let options = WhiteDiffOptions {
+            ignore_case: true,
+            unordered_lines: true,
+        };
+        assert_eq!(run_white_diff(options, "YES\nno\n", "No\nyes\n"), 0);
+        let options = WhiteDiffOptions {
+            ignore_case: false,
+            unordered_lines: true,
+        };
+        assert_eq!(run_white_diff(options, "YES\nno\n", "No\nyes\n"), 1);
+    }
+
     #[test]
     fn test_checker_whitediff_correct() {
-        let checker = Checker::WhiteDiff;
+        let checker = Checker::WhiteDiff(WhiteDiffOptions::default());
         let (mut eval, _recv) = EvaluationData::new();
         let input = File::new("input").uuid;
         let output = File::new("output").uuid;
@@ -845,11 +1977,15 @@ mod tests {
                 status: ExecutionStatus::Success,
                 was_killed: false,
                 was_cached: false,
+                truncated_output: false,
+                provenance: ExecutionProvenance::Unknown,
                 resources: ExecutionResourcesUsage {
                     cpu_time: 0.0,
                     sys_time: 0.0,
                     wall_time: 0.0,
                     memory: 0,
+                    processes: None,
+                    setup_time: 0.0,
                 },
             })
             .unwrap();
@@ -859,7 +1995,7 @@ mod tests {
 
     #[test]
     fn test_checker_whitediff_incorrect() {
-        let checker = Checker::WhiteDiff;
+        let checker = Checker::WhiteDiff(WhiteDiffOptions::default());
         let (mut eval, _recv) = EvaluationData::new();
         let input = File::new("input").uuid;
         let output = File::new("output").uuid;
@@ -881,11 +2017,15 @@ mod tests {
                 status: ExecutionStatus::ReturnCode(1),
                 was_killed: false,
                 was_cached: false,
+                truncated_output: false,
+                provenance: ExecutionProvenance::Unknown,
                 resources: ExecutionResourcesUsage {
                     cpu_time: 0.0,
                     sys_time: 0.0,
                     wall_time: 0.0,
                     memory: 0,
+                    processes: None,
+                    setup_time: 0.0,
                 },
             })
             .unwrap();
@@ -952,6 +2092,346 @@ mod tests {
         assert!(cb_called.load(Ordering::Relaxed));
     }
 
+    /// Run `check_deterministic` with a custom checker, feeding the two runs with the specified
+    /// stdout and stderr. Returns the outcomes passed to the callback and the warnings sent to the
+    /// UI.
+    fn check_twice(
+        first: (&[u8], &[u8]),
+        second: (&[u8], &[u8]),
+    ) -> (Vec<(f64, String)>, Vec<String>) {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("check.py");
+        std::fs::write(&path, "x").unwrap();
+        let source = SourceFile::new(&path, "", None, None::<PathBuf>).unwrap();
+        let checker = Checker::Custom(Arc::new(source));
+        let (mut eval, recv) = EvaluationData::new();
+        let input = File::new("input").uuid;
+        let output = File::new("output").uuid;
+        let test = File::new("test").uuid;
+        let outcomes = Arc::new(Mutex::new(vec![]));
+        let outcomes2 = outcomes.clone();
+        let cb = move |score, mex| {
+            outcomes2.lock().unwrap().push((score, mex));
+            Ok(())
+        };
+        checker
+            .check_deterministic(
+                &mut eval,
+                0,
+                0,
+                "sol",
+                input,
+                output,
+                test,
+                CheckerPolicy::Skip,
+                cb,
+            )
+            .unwrap();
+        assert_eq!(eval.dag.data.executions.len(), 2);
+        let mut execs: Vec<_> = eval.dag.data.executions.values().cloned().collect();
+        execs.sort_by_key(|exec| exec.env.contains_key(CHECKER_REPETITION_ENV));
+        for (exec, (stdout, stderr)) in execs.iter().zip(vec![first, second]) {
+            let stdout_uuid = exec.stdout.as_ref().unwrap().uuid;
+            let callbacks = eval.dag.file_callbacks.remove(&stdout_uuid).unwrap();
+            let (_, callback) = callbacks.get_content.unwrap();
+            callback.call(stdout.to_vec()).unwrap();
+            let stderr_uuid = exec.stderr.as_ref().unwrap().uuid;
+            let callbacks = eval.dag.file_callbacks.remove(&stderr_uuid).unwrap();
+            let (_, callback) = callbacks.get_content.unwrap();
+            callback.call(stderr.to_vec()).unwrap();
+        }
+        drop(eval);
+        let warnings = recv
+            .into_iter()
+            .filter_map(|message| match message {
+                UIMessage::Warning { message } => Some(message),
+                _ => None,
+            })
+            .collect();
+        let outcomes = outcomes.lock().unwrap().clone();
+        (outcomes, warnings)
+    }
+
+    #[test]
+    fn test_checker_deterministic() {
+        let (outcomes, warnings) = check_twice((b"1.0", b"Ok!"), (b"1.0", b"Ok!"));
+        assert_eq!(outcomes, vec![(1.0, "Ok!".to_string())]);
+        assert!(warnings.is_empty(), "Unexpected warnings: {:?}", warnings);
+    }
+
+    #[test]
+    fn test_checker_not_deterministic() {
+        let (outcomes, warnings) = check_twice((b"1.0", b"Ok!"), (b"0.0", b"Ko!"));
+        assert_eq!(outcomes, vec![(1.0, "Ok!".to_string())]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("not deterministic"));
+    }
+
+    /// Cross-check the output of a solution with a custom checker that prints the specified stdout
+    /// and stderr. Returns the warnings sent to the UI.
+    fn cross_check(stdout: &[u8], stderr: &[u8]) -> Vec<String> {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("check.py");
+        std::fs::write(&path, "x").unwrap();
+        let source = SourceFile::new(&path, "", None, None::<PathBuf>).unwrap();
+        let checker = Checker::Custom(Arc::new(source));
+        let (mut eval, recv) = EvaluationData::new();
+        let input = File::new("input").uuid;
+        let reference_output = File::new("reference").uuid;
+        let test_output = File::new("test").uuid;
+        checker
+            .cross_check(
+                &mut eval,
+                0,
+                1,
+                Path::new("sol/sol1.cpp"),
+                Path::new("sol/sol2.py"),
+                input,
+                reference_output,
+                test_output,
+            )
+            .unwrap();
+        assert_eq!(eval.dag.data.executions.len(), 1);
+        let exec = eval.dag.data.executions.values().next().unwrap().clone();
+        assert!(exec.dependencies().contains(&reference_output));
+        assert!(exec.dependencies().contains(&test_output));
+        let stdout_uuid = exec.stdout.as_ref().unwrap().uuid;
+        let callbacks = eval.dag.file_callbacks.remove(&stdout_uuid).unwrap();
+        callbacks
+            .get_content
+            .unwrap()
+            .1
+            .call(stdout.to_vec())
+            .unwrap();
+        let stderr_uuid = exec.stderr.as_ref().unwrap().uuid;
+        let callbacks = eval.dag.file_callbacks.remove(&stderr_uuid).unwrap();
+        callbacks
+            .get_content
+            .unwrap()
+            .1
+            .call(stderr.to_vec())
+            .unwrap();
+        drop(eval);
+        recv.into_iter()
+            .filter_map(|message| match message {
+                UIMessage::Warning { message } => Some(message),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cross_check_agree() {
+        let warnings = cross_check(b"1.0", b"Ok!");
+        assert!(warnings.is_empty(), "Unexpected warnings: {:?}", warnings);
+    }
+
+    #[test]
+    fn test_cross_check_disagree() {
+        let warnings = cross_check(b"0.0", b"Ko!");
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].contains("sol1.cpp and sol2.py disagree on testcase 1, subtask 0"),
+            "Unexpected warning: {}",
+            warnings[0]
+        );
+    }
+
+    /// Run `check_with_fallback` with a custom checker that needs to be compiled. If
+    /// `checker_outcome` is `None` the checker is skipped, otherwise it prints that stdout and
+    /// stderr. The diff exits with `diff_status`, before the checker if `diff_first`. Returns the
+    /// outcomes passed to the callback and the warnings sent to the UI.
+    fn check_with_fallback(
+        checker_outcome: Option<(&[u8], &[u8])>,
+        diff_status: ExecutionStatus,
+        diff_first: bool,
+    ) -> (Vec<(f64, String)>, Vec<String>) {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("check.cpp");
+        std::fs::write(&path, "x").unwrap();
+        let source = SourceFile::new(&path, "", None, None::<PathBuf>).unwrap();
+        let checker = Checker::Custom(Arc::new(source));
+        let (mut eval, recv) = EvaluationData::new();
+        let input = File::new("input").uuid;
+        let output = File::new("output").uuid;
+        let test = File::new("test").uuid;
+        let outcomes = Arc::new(Mutex::new(vec![]));
+        let outcomes2 = outcomes.clone();
+        let cb = move |score, mex| {
+            outcomes2.lock().unwrap().push((score, mex));
+            Ok(())
+        };
+        checker
+            .check_with_fallback(
+                &mut eval,
+                0,
+                0,
+                "sol",
+                input,
+                output,
+                test,
+                CheckerPolicy::Skip,
+                cb,
+            )
+            .unwrap();
+        // the compilation, the checker and the diff
+        assert_eq!(eval.dag.data.executions.len(), 3);
+        let find = |fallback: bool| {
+            eval.dag
+                .data
+                .executions
+                .values()
+                .find(|exec| {
+                    exec.description.starts_with("Checking output")
+                        && exec.description.ends_with("(white diff fallback)") == fallback
+                })
+                .unwrap()
+                .clone()
+        };
+        let diff = find(true);
+        let exec = find(false);
+
+        let run_diff = |eval: &mut EvaluationData| {
+            let callbacks = eval.dag.execution_callbacks.remove(&diff.uuid).unwrap();
+            for cb in callbacks.on_done {
+                cb.call(ExecutionResult {
+                    status: diff_status.clone(),
+                    was_killed: false,
+                    was_cached: false,
+                    truncated_output: false,
+                    provenance: ExecutionProvenance::Unknown,
+                    resources: ExecutionResourcesUsage {
+                        cpu_time: 0.0,
+                        sys_time: 0.0,
+                        wall_time: 0.0,
+                        memory: 0,
+                        processes: None,
+                        setup_time: 0.0,
+                    },
+                })
+                .unwrap();
+            }
+        };
+        if diff_first {
+            run_diff(&mut eval);
+        }
+        match checker_outcome {
+            None => {
+                let callbacks = eval.dag.execution_callbacks.remove(&exec.uuid).unwrap();
+                for cb in callbacks.on_skip {
+                    cb.call(SkipReason::FailedDependency).unwrap();
+                }
+            }
+            Some((stdout, stderr)) => {
+                for (file, content) in &[(&exec.stdout, stdout), (&exec.stderr, stderr)] {
+                    let uuid = file.as_ref().unwrap().uuid;
+                    let callbacks = eval.dag.file_callbacks.remove(&uuid).unwrap();
+                    callbacks
+                        .get_content
+                        .unwrap()
+                        .1
+                        .call(content.to_vec())
+                        .unwrap();
+                }
+            }
+        }
+        if !diff_first {
+            run_diff(&mut eval);
+        }
+        drop(eval);
+        let warnings = recv
+            .into_iter()
+            .filter_map(|message| match message {
+                UIMessage::Warning { message } => Some(message),
+                _ => None,
+            })
+            .collect();
+        let outcomes = outcomes.lock().unwrap().clone();
+        (outcomes, warnings)
+    }
+
+    #[test]
+    fn test_checker_fallback_not_compiled() {
+        for &diff_first in &[true, false] {
+            let (outcomes, warnings) =
+                check_with_fallback(None, ExecutionStatus::Success, diff_first);
+            assert_eq!(outcomes, vec![(1.0, "Output is correct".to_string())]);
+            assert_eq!(warnings.len(), 1);
+            assert!(
+                warnings[0].contains("check.cpp cannot be run"),
+                "Unexpected warning: {}",
+                warnings[0]
+            );
+        }
+        let (outcomes, _) = check_with_fallback(None, ExecutionStatus::ReturnCode(1), true);
+        assert_eq!(outcomes, vec![(0.0, "Output is incorrect".to_string())]);
+    }
+
+    #[test]
+    fn test_checker_fallback_compiled() {
+        for &diff_first in &[true, false] {
+            let (outcomes, warnings) = check_with_fallback(
+                Some((b"0.5", b"Almost")),
+                ExecutionStatus::ReturnCode(1),
+                diff_first,
+            );
+            assert_eq!(outcomes, vec![(0.5, "Almost".to_string())]);
+            assert!(warnings.is_empty(), "Unexpected warnings: {:?}", warnings);
+        }
+    }
+
+    #[test]
+    fn test_cross_check_whitediff() {
+        let checker = Checker::WhiteDiff(WhiteDiffOptions::default());
+        for (status, num_warnings) in vec![
+            (ExecutionStatus::Success, 0),
+            (ExecutionStatus::ReturnCode(1), 1),
+        ] {
+            let (mut eval, recv) = EvaluationData::new();
+            let input = File::new("input").uuid;
+            let reference_output = File::new("reference").uuid;
+            let test_output = File::new("test").uuid;
+            checker
+                .cross_check(
+                    &mut eval,
+                    0,
+                    0,
+                    Path::new("sol1.cpp"),
+                    Path::new("sol2.cpp"),
+                    input,
+                    reference_output,
+                    test_output,
+                )
+                .unwrap();
+            // only the outcome of the diff is bound, the UI is not notified of the checking
+            let callbacks = eval.dag.execution_callbacks.into_iter().next().unwrap().1;
+            assert!(callbacks.on_start.is_empty());
+            callbacks.on_done.into_iter().for_each(|cb| {
+                cb.call(ExecutionResult {
+                    status: status.clone(),
+                    was_killed: false,
+                    was_cached: false,
+                    truncated_output: false,
+                    provenance: ExecutionProvenance::Unknown,
+                    resources: ExecutionResourcesUsage {
+                        cpu_time: 0.0,
+                        sys_time: 0.0,
+                        wall_time: 0.0,
+                        memory: 0,
+                        processes: None,
+                        setup_time: 0.0,
+                    },
+                })
+                .unwrap();
+            });
+            let warnings = recv
+                .try_iter()
+                .filter(|message| matches!(message, UIMessage::Warning { .. }))
+                .count();
+            assert_eq!(warnings, num_warnings);
+        }
+    }
+
     #[test]
     fn test_checker_custom_incorrect() {
         let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
@@ -1017,4 +2497,67 @@ mod tests {
         let stderr = eval.dag.file_callbacks.remove(&stderr).unwrap();
         stderr.get_content.unwrap().1.call(b"Ko!".to_vec()).unwrap();
     }
+
+    #[test]
+    fn test_bind_preview() {
+        let (mut eval, recv) = EvaluationData::new();
+        let file = File::new("input");
+        bind_preview(&mut eval, file.uuid, 4, |preview| {
+            UIMessage::IOIInputPreview {
+                subtask: 0,
+                testcase: 1,
+                preview,
+            }
+        });
+        let (limit, callback) = eval
+            .dag
+            .file_callbacks
+            .remove(&file.uuid)
+            .unwrap()
+            .get_content
+            .unwrap();
+        assert_eq!(limit, 5);
+        // the client sends at most `limit` bytes of the file
+        callback.call(b"1 2 3\n4 5 6\n"[..limit].to_vec()).unwrap();
+        match recv.try_recv().unwrap() {
+            UIMessage::IOIInputPreview {
+                subtask,
+                testcase,
+                preview,
+            } => {
+                assert_eq!((subtask, testcase), (0, 1));
+                assert_eq!(preview.content, "1 2 ");
+                assert!(preview.truncated);
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn test_bind_preview_short_file() {
+        let (mut eval, recv) = EvaluationData::new();
+        let file = File::new("output");
+        bind_preview(&mut eval, file.uuid, 4, |preview| {
+            UIMessage::IOIOutputPreview {
+                subtask: 0,
+                testcase: 1,
+                preview,
+            }
+        });
+        let (_, callback) = eval
+            .dag
+            .file_callbacks
+            .remove(&file.uuid)
+            .unwrap()
+            .get_content
+            .unwrap();
+        callback.call(b"3\n".to_vec()).unwrap();
+        match recv.try_recv().unwrap() {
+            UIMessage::IOIOutputPreview { preview, .. } => {
+                assert_eq!(preview.content, "3\n");
+                assert!(!preview.truncated);
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
 }
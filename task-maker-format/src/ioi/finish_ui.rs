@@ -6,9 +6,10 @@ use termcolor::{Color, ColorChoice, ColorSpec, StandardStream};
 use task_maker_dag::{ExecutionResourcesUsage, ExecutionStatus};
 
 use crate::ioi::ui_state::{
-    CompilationStatus, SolutionEvaluationState, TestcaseEvaluationStatus, UIState,
+    CompilationStatus, FilePreview, SolutionEvaluationState, TestcaseEvaluationStatus, UIState,
+    VerdictCategory,
 };
-use crate::ui::UIExecutionStatus;
+use crate::ui::{signal_text, UIExecutionStatus};
 use crate::{cwrite, cwriteln};
 
 lazy_static! {
@@ -51,6 +52,9 @@ lazy_static! {
     };
 }
 
+/// The number of checker executions to show in the list of the slowest ones.
+const SLOWEST_CHECKERS: usize = 5;
+
 /// UI that prints to `stdout` the ending result of the evaluation of a IOI task.
 pub struct FinishUI {
     /// Stream where to print to.
@@ -67,12 +71,18 @@ impl FinishUI {
         println!();
         ui.print_compilations(state);
         println!();
+        ui.print_toolchains(state);
         ui.print_booklets(state);
         println!();
         ui.print_generations(state);
         println!();
         ui.print_evaluations(state);
         ui.print_summary(state);
+        ui.print_verdict_groups(state);
+        ui.print_peak_concurrency(state);
+        ui.print_critical_path(state);
+        ui.print_sandbox_overhead(state);
+        ui.print_slowest_checkers(state);
         ui.print_messages(state);
     }
 
@@ -185,8 +195,12 @@ impl FinishUI {
     fn print_generations(&mut self, state: &UIState) {
         cwriteln!(self, BLUE, "Generations");
         for (st_num, subtask) in state.generations.iter().sorted_by_key(|(n, _)| *n) {
+            let info = &state.task.subtasks[&st_num];
             cwrite!(self, BOLD, "Subtask {}", st_num);
-            println!(": {} points", state.task.subtasks[&st_num].max_score);
+            if let Some(name) = &info.name {
+                print!(" ({})", name);
+            }
+            println!(": {} points", info.max_score);
             for (tc_num, testcase) in subtask.testcases.iter().sorted_by_key(|(n, _)| *n) {
                 print!("#{:<3} ", tc_num);
 
@@ -241,10 +255,26 @@ impl FinishUI {
                         }
                     }
                 }
+                if let Some(preview) = &testcase.input_preview {
+                    cwriteln!(self, BOLD, "Input preview:");
+                    self.print_preview(preview);
+                }
+                if let Some(preview) = &testcase.output_preview {
+                    cwriteln!(self, BOLD, "Output preview:");
+                    self.print_preview(preview);
+                }
             }
         }
     }
 
+    /// Print the preview of a file, marking it if truncated.
+    fn print_preview(&self, preview: &FilePreview) {
+        println!("{}", preview.content.trim_end());
+        if preview.truncated {
+            println!("[...]");
+        }
+    }
+
     /// Print all the evaluation states.
     fn print_evaluations(&mut self, state: &UIState) {
         cwriteln!(self, BLUE, "Evaluations");
@@ -278,12 +308,34 @@ impl FinishUI {
         print!(": ");
         self.print_score_frac(score, max_score);
         println!();
+        if let Some(peaks) = eval.resource_peaks() {
+            print!(
+                "Peak memory: {:.1}MiB, slowest testcase: {} of subtask {} ({:.3}s)",
+                (peaks.memory as f64) / 1024.0,
+                peaks.slowest_testcase,
+                peaks.slowest_subtask,
+                peaks.slowest_time
+            );
+            println!();
+        }
+        let explanation = eval.explain(&state.task);
         for (st_num, subtask) in eval.subtasks.iter().sorted_by_key(|(n, _)| *n) {
             cwrite!(self, BOLD, "Subtask #{}", st_num);
+            if let Some(name) = &state.task.subtasks[&st_num].name {
+                print!(" ({})", name);
+            }
             print!(": ");
             let max_score = state.task.subtasks[&st_num].max_score;
             let score = subtask.score.unwrap_or(0.0);
             self.print_score_frac(score, max_score);
+            let limiting = explanation
+                .subtasks
+                .iter()
+                .find(|st| st.subtask == *st_num)
+                .and_then(|st| st.limiting_testcase);
+            if let Some(limiting) = limiting {
+                print!(" (limited by testcase {})", limiting);
+            }
             println!();
             for (tc_num, testcase) in subtask.testcases.iter().sorted_by_key(|(n, _)| *n) {
                 print!("{:3}) ", tc_num);
@@ -304,7 +356,9 @@ impl FinishUI {
                 if let Some(result) = &testcase.result {
                     match &result.status {
                         ExecutionStatus::ReturnCode(code) => print!(": Exited with {}", code),
-                        ExecutionStatus::Signal(sig, name) => print!(": Signal {} ({})", sig, name),
+                        ExecutionStatus::Signal(sig, name) => {
+                            print!(": Signal {}", signal_text(*sig, name))
+                        }
                         ExecutionStatus::InternalError(err) => print!(": Internal error: {}", err),
                         _ => {}
                     }
@@ -385,11 +439,123 @@ impl FinishUI {
         println!();
     }
 
+    /// Print the solutions grouped by the category of their outcome.
+    fn print_verdict_groups(&mut self, state: &UIState) {
+        if state.evaluations.is_empty() {
+            return;
+        }
+        cwriteln!(self, BLUE, "Verdicts");
+        for (category, solutions) in state.verdict_groups() {
+            let color = match category {
+                VerdictCategory::Correct => &*GREEN,
+                VerdictCategory::Partial => &*YELLOW,
+                VerdictCategory::Wrong => &*RED,
+                VerdictCategory::Error => &*BOLD,
+            };
+            cwrite!(self, color, "{:>14}", category.description());
+            print!(": {:>2}", solutions.len());
+            if !solutions.is_empty() {
+                let names = solutions
+                    .iter()
+                    .map(|p| p.file_name().expect("Invalid file name").to_string_lossy())
+                    .join(", ");
+                print!(" ({})", names);
+            }
+            println!();
+        }
+        println!();
+    }
+
+    /// Print the versions of the toolchains used, if any.
+    fn print_toolchains(&mut self, state: &UIState) {
+        if state.toolchains.is_empty() {
+            return;
+        }
+        cwriteln!(self, BLUE, "Toolchains");
+        for (language, version) in state.toolchains.iter().sorted() {
+            cwrite!(self, BOLD, "{}", language);
+            println!(": {}", version);
+        }
+        println!();
+    }
+
+    /// Print the length of the critical path of the evaluation, if known.
+    fn print_critical_path(&mut self, state: &UIState) {
+        if let Some(critical_path) = &state.critical_path {
+            cwrite!(self, BOLD, "Critical path: ");
+            print!(
+                "{:.2}s of {:.2}s of executions",
+                critical_path.length, critical_path.total_time
+            );
+            if critical_path.length > 0.0 {
+                print!(
+                    " (at most {:.1}x faster with enough workers)",
+                    critical_path.total_time / critical_path.length
+                );
+            }
+            println!();
+            for execution in &critical_path.executions {
+                println!("  {}", execution);
+            }
+            println!();
+        }
+    }
+
+    /// Print the time spent preparing the sandboxes, if known.
+    fn print_sandbox_overhead(&mut self, state: &UIState) {
+        if let Some(overhead) = &state.sandbox_overhead {
+            cwrite!(self, BOLD, "Sandbox setup: ");
+            print!(
+                "{:.2}s for {} executions running for {:.2}s",
+                overhead.setup_time, overhead.executions, overhead.wall_time
+            );
+            let total = overhead.setup_time + overhead.wall_time;
+            if total > 0.0 {
+                print!(" ({:.1}% of the time)", overhead.setup_time / total * 100.0);
+            }
+            println!();
+            println!();
+        }
+    }
+
+    /// Print the maximum number of executions that ran at the same time, if known.
+    fn print_peak_concurrency(&mut self, state: &UIState) {
+        if let Some(status) = &state.executor_status {
+            cwrite!(self, BOLD, "Peak concurrent executions: ");
+            println!("{}", status.peak_running_execs);
+            println!();
+        }
+    }
+
+    /// Print the checker executions that took the most time.
+    fn print_slowest_checkers(&mut self, state: &UIState) {
+        let checkers = state.slowest_checkers(SLOWEST_CHECKERS);
+        if checkers.is_empty() {
+            return;
+        }
+        cwriteln!(self, BLUE, "Slowest checkers");
+        for usage in checkers {
+            let name = usage
+                .solution
+                .file_name()
+                .expect("Invalid file name")
+                .to_string_lossy();
+            cwrite!(self, BOLD, "{}", name);
+            print!(
+                " on testcase {} of subtask {}: ",
+                usage.testcase, usage.subtask
+            );
+            self.print_time_memory(&usage.resources);
+            println!();
+        }
+        println!();
+    }
+
     /// Print the warnings.
     fn print_messages(&mut self, state: &UIState) {
         if !state.warnings.is_empty() {
             cwriteln!(self, YELLOW, "Warnings:");
-            for warning in state.warnings.iter() {
+            for warning in state.warnings_summary() {
                 println!(" - {}", warning);
             }
         }
@@ -1,16 +1,17 @@
 use std::collections::HashMap;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use failure::{format_err, Error};
 use pest::Parser;
 
-use crate::find_source_file;
 use crate::ioi::format::italian_yaml::TaskInputEntry;
 use crate::ioi::{
-    InputGenerator, InputValidator, OutputGenerator, SubtaskId, SubtaskInfo, TestcaseId,
-    TestcaseInfo,
+    Constraint, InputGenerator, InputValidator, OutputGenerator, SubtaskId, SubtaskInfo,
+    TestcaseId, TestcaseInfo, TestcaseScoreAggregator,
 };
+use crate::{find_manager, find_source_file};
 
 /// This module exists because of a `pest`'s bug: https://github.com/pest-parser/pest/issues/326
 #[allow(missing_docs)]
@@ -43,27 +44,37 @@ where
     let mut testcase_count = 0;
     let mut subtask_id: SubtaskId = 0;
     let mut entries = vec![];
+    // the constraints specified before the first subtask, they apply to all the subtasks
+    let mut global_constraints: Vec<Constraint> = vec![];
+    // the score mode specified before the first subtask, it applies to all the subtasks
+    let mut global_score_mode: Option<TestcaseScoreAggregator> = None;
 
     let mut default_subtask = Some(SubtaskInfo {
         id: 0,
+        name: None,
         max_score: 100.0,
+        score_mode: None,
+        constraints: vec![],
         testcases: HashMap::new(),
     });
 
-    let generator = find_source_file(
-        task_dir,
-        vec![
-            "gen/generator.*",
-            "gen/generatore.*",
-            "gen/generator",
-            "gen/generatore",
-        ],
-        task_dir,
-        None,
-        Some(task_dir.join("bin").join("generator")),
-    )
-    .map(Arc::new)
-    .ok_or_else(|| format_err!("No generator found"))?;
+    let generator = find_manager(task_dir, &["generator", "generatore"])
+        .or_else(|| {
+            find_source_file(
+                task_dir,
+                vec![
+                    "gen/generator.*",
+                    "gen/generatore.*",
+                    "gen/generator",
+                    "gen/generatore",
+                ],
+                task_dir,
+                None,
+                Some(task_dir.join("bin").join("generator")),
+            )
+        })
+        .map(Arc::new)
+        .ok_or_else(|| format_err!("No generator found"))?;
     debug!("Detected input generator: {:?}", generator);
 
     for line in file.into_inner() {
@@ -76,20 +87,58 @@ where
                 match line.as_rule() {
                     parser::Rule::subtask => {
                         default_subtask.take(); // ignore the default subtask ever
+                        let mut line = line.into_inner();
                         let score = line
-                            .into_inner()
                             .next()
                             .ok_or_else(|| format_err!("Corrupted parser"))?
                             .as_str();
+                        let name = line.next().map(|name| name.as_str().trim().to_owned());
                         entries.push(TaskInputEntry::Subtask(SubtaskInfo {
                             id: subtask_id,
+                            name,
                             max_score: score.parse::<f64>().expect("Invalid subtask score"),
+                            score_mode: global_score_mode.clone(),
+                            constraints: global_constraints.clone(),
                             testcases: HashMap::new(),
                         }));
                         subtask_id += 1;
                     }
+                    parser::Rule::constraint => {
+                        let text = line
+                            .into_inner()
+                            .next()
+                            .ok_or_else(|| format_err!("Corrupted parser"))?
+                            .as_str();
+                        let constraint = Constraint::from_str(text.trim())?;
+                        let subtask = entries.iter_mut().rev().find_map(|entry| match entry {
+                            TaskInputEntry::Subtask(subtask) => Some(subtask),
+                            _ => None,
+                        });
+                        match subtask {
+                            Some(subtask) => subtask.constraints.push(constraint),
+                            None => global_constraints.push(constraint),
+                        }
+                    }
+                    parser::Rule::score_mode => {
+                        let text = line
+                            .into_inner()
+                            .next()
+                            .ok_or_else(|| format_err!("Corrupted parser"))?
+                            .as_str();
+                        let score_mode = TestcaseScoreAggregator::from_str(text)?;
+                        let subtask = entries.iter_mut().rev().find_map(|entry| match entry {
+                            TaskInputEntry::Subtask(subtask) => Some(subtask),
+                            _ => None,
+                        });
+                        match subtask {
+                            Some(subtask) => subtask.score_mode = Some(score_mode),
+                            None => global_score_mode = Some(score_mode),
+                        }
+                    }
                     parser::Rule::copy => {
-                        if let Some(default) = default_subtask.take() {
+                        if let Some(mut default) = default_subtask.take() {
+                            default.constraints = global_constraints.clone();
+                            default.score_mode = global_score_mode.clone();
                             entries.push(TaskInputEntry::Subtask(default));
                             subtask_id += 1;
                         }
@@ -107,7 +156,9 @@ where
                         testcase_count += 1;
                     }
                     parser::Rule::command => {
-                        if let Some(default) = default_subtask.take() {
+                        if let Some(mut default) = default_subtask.take() {
+                            default.constraints = global_constraints.clone();
+                            default.score_mode = global_score_mode.clone();
                             entries.push(TaskInputEntry::Subtask(default));
                             subtask_id += 1;
                         }
@@ -306,6 +357,99 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parser_subtask_names() {
+        let task = make_task("#ST: 30 N ≤ 1000  # small\n1234\n#ST: 70\n5678\n#ST: 0 full\n");
+        let entries = get_entries(task.path());
+        let names: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Subtask(subtask) => Some(subtask.name.clone()),
+                Testcase(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![Some("N ≤ 1000".to_string()), None, Some("full".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parser_constraints() {
+        let task = make_task(
+            "#CONSTRAINT: 1 <= M <= 10\n#ST: 30\n#CONSTRAINT: 1 <= N <= 1000\n1234\n#ST: 70\n5678\n",
+        );
+        let entries = get_entries(task.path());
+        let constraints: Vec<Vec<String>> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Subtask(subtask) => {
+                    Some(subtask.constraints.iter().map(|c| c.to_string()).collect())
+                }
+                Testcase(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            constraints,
+            vec![
+                vec!["1 <= M <= 10".to_string(), "1 <= N <= 1000".to_string()],
+                vec!["1 <= M <= 10".to_string()]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_score_modes() {
+        let task = make_task(
+            "#SCORE_MODE: sum
+#ST: 30
+1234
+#ST: 70
+#SCORE_MODE: min
+5678
+",
+        );
+        let entries = get_entries(task.path());
+        let modes: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Subtask(subtask) => Some(format!("{:?}", subtask.score_mode)),
+                Testcase(_) => None,
+            })
+            .collect();
+        assert_eq!(modes, vec!["Some(Sum)", "Some(Min)"]);
+
+        // without subtasks the score mode applies to the only one
+        let task = make_task(
+            "#SCORE_MODE: sum
+1234
+",
+        );
+        match &get_entries(task.path())[0] {
+            Subtask(subtask) => assert_eq!(format!("{:?}", subtask.score_mode), "Some(Sum)"),
+            Testcase(_) => panic!("Expecting a subtask"),
+        }
+    }
+
+    #[test]
+    fn test_parser_invalid_score_mode() {
+        let task = make_task(
+            "#ST: 30
+#SCORE_MODE: max
+1234
+",
+        );
+        let gen_file = task.path().join("gen/GEN");
+        assert!(parse_gen_gen(&gen_file, get_validator, get_output_generator).is_err());
+    }
+
+    #[test]
+    fn test_parser_invalid_constraint() {
+        let task = make_task("#CONSTRAINT: N\n1234\n");
+        let gen_file = task.path().join("gen/GEN");
+        assert!(parse_gen_gen(&gen_file, get_validator, get_output_generator).is_err());
+    }
+
     #[test]
     fn test_parser_subtasks() {
         let task = make_task("#ST: 123\n#COPY: random/file\n5678\n#ST: 321\n1234\n");
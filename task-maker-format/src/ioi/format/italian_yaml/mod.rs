@@ -13,10 +13,11 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use task_maker_lang::GraderMap;
 
 use crate::ioi::{
-    make_booklets, Checker, InputValidator, OutputGenerator, SubtaskId, SubtaskInfo, Task,
-    TaskType, TestcaseId, TestcaseInfo, TestcaseScoreAggregator,
+    make_booklets, Checker, CheckerPolicy, InputValidator, OutputGenerator, SubtaskId, SubtaskInfo,
+    Task, TaskScoreAggregator, TaskType, TestcaseId, TestcaseInfo, TestcaseScoreAggregator,
+    WhiteDiffOptions,
 };
-use crate::{find_source_file, list_files, EvaluationConfig};
+use crate::{find_manager, find_source_file, list_files, EvaluationConfig, SourceFile};
 
 mod gen_gen;
 mod static_inputs;
@@ -32,6 +33,24 @@ struct TaskYAML {
     pub title: String,
     /// The score type to use for this task.
     pub score_type: Option<String>,
+    /// How to compute the score of the task from the subtask scores: `sum` (the default) or `max`.
+    pub task_score_type: Option<String>,
+    /// The number of decimal digits the scores are rounded to. Defaults to 2.
+    #[serde(default = "default_score_precision")]
+    pub score_precision: usize,
+    /// Whether to run the checker when the solution fails: `skip` (the default) or `run`.
+    pub checker_policy: Option<String>,
+    /// Whether the built-in checker should ignore the case of the letters. Defaults to false.
+    #[serde(default = "bool::default")]
+    #[serde(serialize_with = "python_bool_serializer")]
+    #[serde(deserialize_with = "python_bool_deserializer")]
+    pub ignore_case: bool,
+    /// Whether the built-in checker should accept the lines of the output in any order. Defaults
+    /// to false.
+    #[serde(default = "bool::default")]
+    #[serde(serialize_with = "python_bool_serializer")]
+    #[serde(deserialize_with = "python_bool_deserializer")]
+    pub unordered_lines: bool,
 
     /// The time limit for the execution of the solutions, if not set it's unlimited.
     #[serde(alias = "timeout")]
@@ -51,6 +70,14 @@ struct TaskYAML {
     /// The output file for the solutions, usually 'output.txt' or '' (stdout). Defaults to `''`.
     #[serde(default = "default_outfile")]
     pub outfile: String,
+    /// The directories of the host that the solutions and the checkers can read, relative to the
+    /// task directory if not absolute.
+    #[serde(default)]
+    pub readable_dirs: Vec<PathBuf>,
+    /// The extra flags to pass to the compiler when compiling the solutions, indexed by the name of
+    /// the language (e.g. `C++14 / gcc`).
+    #[serde(default)]
+    pub compilation_flags: HashMap<String, Vec<String>>,
 
     /// An integer that defines the difficulty of the task. Used only in booklet compilations.
     pub difficulty: Option<u8>,
@@ -81,14 +108,18 @@ pub(crate) enum TaskInputEntry {
 /// * `sol/` - folder with solutions, graders and stubs
 ///     * `solution.xxx` the official solution (also `soluzione`)
 ///     * other solutions with different names
+///     * in the output-only tasks (`output_only: true`) each solution is a directory with the
+///       submitted `output_NNN.txt` files, which are checked without executing anything
 /// * `check/` - folder with the checker (also `cor/`)
 ///     * `checker.xxx` (also `correttore`)
+///     * `manager.xxx` the manager of the communication tasks, the solutions interact with it
 /// * `input/` - folder with the input files
 /// * `output/` - folder with the output files
 /// * `statement/` - folder with the statement (also `testo`)
 ///
 /// A task must have a generator (and a GEN file) or the input files should be  put in `input/`.
-/// The official solution must be present or the output files should be put in `output/`.
+/// The official solution must be present or the output files should be put in `output/`, except
+/// for the communication tasks where the official solution is not used to generate the outputs.
 pub fn parse_task<P: AsRef<Path>>(
     task_dir: P,
     eval_config: &EvaluationConfig,
@@ -107,23 +138,34 @@ pub fn parse_task<P: AsRef<Path>>(
     let outfile = map_file(yaml.outfile);
 
     let graders = list_files(task_dir, vec!["sol/grader.*", "sol/stub.*"]);
-    let grader_map = Arc::new(GraderMap::new(graders));
+    let mut grader_map = GraderMap::new(graders);
+    for (language, flags) in &yaml.compilation_flags {
+        grader_map.set_compilation_flags(language, flags)?;
+    }
+    let grader_map = Arc::new(grader_map);
     debug!("The graders are: {:#?}", grader_map);
 
+    let manager = if yaml.output_only {
+        None
+    } else {
+        detect_manager(task_dir)
+    };
+    let communication = manager.is_some();
+
     let gen_gen = task_dir.join("gen").join("GEN");
     let inputs = if gen_gen.exists() {
         debug!("Parsing testcases from gen/GEN");
         gen_gen::parse_gen_gen(
             &gen_gen,
             detect_validator(task_dir.to_path_buf()),
-            detect_output_generator(task_dir.to_path_buf(), grader_map.clone()),
+            detect_output_generator(task_dir.to_path_buf(), grader_map.clone(), communication),
         )?
     } else {
         debug!("Using testcases inside input/");
         static_inputs::static_inputs(
             task_dir,
             detect_validator(task_dir.to_path_buf()),
-            detect_output_generator(task_dir.to_path_buf(), grader_map.clone()),
+            detect_output_generator(task_dir.to_path_buf(), grader_map.clone(), communication),
         )
     };
 
@@ -151,31 +193,41 @@ pub fn parse_task<P: AsRef<Path>>(
         subtasks.insert(subtask.id, subtask);
     }
 
-    let custom_checker = find_source_file(
-        task_dir,
-        vec![
-            "check/checker.*",
-            "cor/correttore.*",
-            "check/checker",
-            "cor/correttore",
-        ],
-        task_dir,
-        None,
-        Some(task_dir.join("check").join("checker")),
-    )
-    .map(Arc::new)
-    .map(Checker::Custom);
+    let custom_checker = find_manager(task_dir, &["checker", "correttore"])
+        .or_else(|| {
+            find_source_file(
+                task_dir,
+                vec![
+                    "check/checker.*",
+                    "cor/correttore.*",
+                    "check/checker",
+                    "cor/correttore",
+                ],
+                task_dir,
+                None,
+                Some(task_dir.join("check").join("checker")),
+            )
+        })
+        .map(Arc::new)
+        .map(Checker::Custom);
 
     let mut task = Task {
         path: task_dir.into(),
-        task_type: TaskType::Batch,
+        task_type: match (yaml.output_only, manager) {
+            (true, _) => TaskType::OutputOnly,
+            (false, Some(manager)) => TaskType::Communication(manager),
+            (false, None) => TaskType::Batch,
+        },
         name: yaml.name,
         title: yaml.title,
         time_limit: yaml.time_limit,
         memory_limit: yaml.memory_limit,
         infile,
         outfile,
-        checker: custom_checker.unwrap_or(Checker::WhiteDiff),
+        checker: custom_checker.unwrap_or(Checker::WhiteDiff(WhiteDiffOptions {
+            ignore_case: yaml.ignore_case,
+            unordered_lines: yaml.unordered_lines,
+        })),
         testcase_score_aggregator: yaml
             .score_type
             .as_ref()
@@ -187,6 +239,24 @@ pub fn parse_task<P: AsRef<Path>>(
                     Ok(TestcaseScoreAggregator::Min)
                 }
             })?,
+        task_score_aggregator: yaml
+            .task_score_type
+            .as_ref()
+            .map(|s| TaskScoreAggregator::from_str(s))
+            .unwrap_or(Ok(TaskScoreAggregator::Sum))?,
+        score_precision: yaml.score_precision,
+        checker_policy: yaml
+            .checker_policy
+            .as_ref()
+            .map(|s| CheckerPolicy::from_str(s))
+            .unwrap_or(Ok(CheckerPolicy::Skip))?,
+        verify_checker: eval_config.verify_checker,
+        checker_fallback: eval_config.checker_fallback,
+        readable_dirs: yaml
+            .readable_dirs
+            .into_iter()
+            .map(|dir| task_dir.join(dir))
+            .collect(),
         subtasks,
         grader_map,
         booklets: Vec::new(),
@@ -202,19 +272,22 @@ pub fn parse_task<P: AsRef<Path>>(
 /// a subtask id, returns an `InputValidator` using that validator. If no validator is found,
 /// `InputValidator::AssumeValid` is used.
 fn detect_validator(task_dir: PathBuf) -> impl Fn(SubtaskId) -> InputValidator {
-    let validator = find_source_file(
-        &task_dir,
-        vec![
-            "gen/validator.*",
-            "gen/valida.*",
-            "gen/validator",
-            "gen/valida",
-        ],
-        &task_dir,
-        None,
-        Some(task_dir.join("bin").join("validator")),
-    )
-    .map(Arc::new);
+    let validator = find_manager(&task_dir, &["validator", "valida"])
+        .or_else(|| {
+            find_source_file(
+                &task_dir,
+                vec![
+                    "gen/validator.*",
+                    "gen/valida.*",
+                    "gen/validator",
+                    "gen/valida",
+                ],
+                &task_dir,
+                None,
+                Some(task_dir.join("bin").join("validator")),
+            )
+        })
+        .map(Arc::new);
     debug!("Detected input validator: {:?}", validator);
     move |st: SubtaskId| -> InputValidator {
         if let Some(validator) = validator.as_ref() {
@@ -229,12 +302,39 @@ fn detect_validator(task_dir: PathBuf) -> impl Fn(SubtaskId) -> InputValidator {
     }
 }
 
+/// Search for the manager of a communication task inside the task directory. If no manager is
+/// found the task is not a communication task.
+fn detect_manager(task_dir: &Path) -> Option<Arc<SourceFile>> {
+    let manager = find_manager(task_dir, &["manager"])
+        .or_else(|| {
+            find_source_file(
+                task_dir,
+                vec![
+                    "check/manager.*",
+                    "cor/manager.*",
+                    "check/manager",
+                    "cor/manager",
+                ],
+                task_dir,
+                None,
+                Some(task_dir.join("check").join("manager")),
+            )
+        })
+        .map(Arc::new);
+    debug!("Detected manager: {:?}", manager);
+    manager
+}
+
 /// Search for a valid output generator (aka official solution) inside the task directory. Will
 /// return a function that, given a testcase id, returns an `OutputGenerator` using that generator.
 /// If no generator is found, `OutputGenerator::StaticFile` is used instead.
+///
+/// In communication tasks the official solution cannot run without the manager: the static output
+/// files are used if present, otherwise the outputs are not available.
 fn detect_output_generator(
     task_dir: PathBuf,
     grader_map: Arc<GraderMap>,
+    communication: bool,
 ) -> impl Fn(TestcaseId) -> OutputGenerator {
     let official_solution = find_source_file(
         &task_dir,
@@ -252,10 +352,17 @@ fn detect_output_generator(
     debug!("Detected output generator: {:?}", official_solution);
     let output_directory = task_dir.join("output");
     move |tc: TestcaseId| -> OutputGenerator {
-        if let Some(solution) = official_solution.as_ref() {
+        let static_output = output_directory.join(format!("output{}.txt", tc));
+        if communication {
+            if static_output.exists() {
+                OutputGenerator::StaticFile(static_output)
+            } else {
+                OutputGenerator::NotAvailable
+            }
+        } else if let Some(solution) = official_solution.as_ref() {
             OutputGenerator::Custom(solution.clone(), vec![])
         } else {
-            OutputGenerator::StaticFile(output_directory.join(format!("output{}.txt", tc)))
+            OutputGenerator::StaticFile(static_output)
         }
     }
 }
@@ -294,6 +401,11 @@ where
     }
 }
 
+/// The default value for the `score_precision` field of task.yaml.
+fn default_score_precision() -> usize {
+    2
+}
+
 /// The default value for the `infile` field of task.yaml.
 fn default_infile() -> String {
     "input.txt".into()
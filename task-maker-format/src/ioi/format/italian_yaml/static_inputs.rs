@@ -38,7 +38,11 @@ where
             self.index = 1;
             return Some(TaskInputEntry::Subtask(SubtaskInfo {
                 id: 0,
+                name: None,
                 max_score: 100.0,
+                // the only subtask is scored with the score type of the task
+                score_mode: None,
+                constraints: vec![],
                 testcases: HashMap::new(),
             }));
         }
@@ -0,0 +1,182 @@
+//! The list of the files of a task that should never be removed when cleaning it.
+//!
+//! The list is read from the `.tmignore` file in the task directory, with a syntax similar to the
+//! one of `.gitignore`: each line is a glob pattern, the empty lines and the ones starting with `#`
+//! are skipped. A pattern with a `/` is matched against the path relative to the task directory,
+//! otherwise against the name of the file or of any of the directories containing it. A trailing
+//! `/` is ignored, and keeping a directory keeps all of its content.
+
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error};
+use glob::Pattern;
+
+/// The name of the file, in the task directory, with the ignore list.
+pub(crate) const IGNORE_FILE_NAME: &str = ".tmignore";
+
+/// A parsed ignore list.
+#[derive(Debug, Clone)]
+pub(crate) struct IgnoreList {
+    /// The directory of the task, the patterns are relative to it.
+    base: PathBuf,
+    /// The patterns matched against the paths relative to the task directory.
+    path_patterns: Vec<Pattern>,
+    /// The patterns matched against the names of the files and directories.
+    name_patterns: Vec<Pattern>,
+}
+
+impl IgnoreList {
+    /// Read the ignore list of the task in the specified directory. If the task doesn't have an
+    /// ignore list an empty one is returned.
+    pub fn load<P: AsRef<Path>>(task_dir: P) -> Result<IgnoreList, Error> {
+        let task_dir = task_dir.as_ref();
+        let path = task_dir.join(IGNORE_FILE_NAME);
+        if !path.exists() {
+            return IgnoreList::parse(task_dir, "");
+        }
+        let content = std::fs::read_to_string(&path)?;
+        IgnoreList::parse(task_dir, &content)
+    }
+
+    /// Parse the content of an ignore list of the task in the specified directory.
+    pub fn parse<P: Into<PathBuf>>(task_dir: P, content: &str) -> Result<IgnoreList, Error> {
+        let mut list = IgnoreList {
+            base: task_dir.into(),
+            path_patterns: vec![],
+            name_patterns: vec![],
+        };
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.trim_end_matches('/');
+            let pattern = Pattern::new(line.trim_start_matches('/'))
+                .map_err(|e| format_err!("Invalid pattern {:?} in the ignore list: {}", line, e))?;
+            if line.contains('/') {
+                list.path_patterns.push(pattern);
+            } else {
+                list.name_patterns.push(pattern);
+            }
+        }
+        Ok(list)
+    }
+
+    /// Whether the path, inside the task directory, must be kept.
+    pub fn is_ignored<P: AsRef<Path>>(&self, path: P) -> bool {
+        let relative = match path.as_ref().strip_prefix(&self.base) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+        relative.ancestors().any(|path| {
+            if path.as_os_str().is_empty() {
+                return false;
+            }
+            let name_matches = path
+                .file_name()
+                .map(|name| {
+                    let name = name.to_string_lossy();
+                    self.name_patterns.iter().any(|p| p.matches(&name))
+                })
+                .unwrap_or(false);
+            name_matches || self.path_patterns.iter().any(|p| p.matches_path(path))
+        })
+    }
+
+    /// Whether the list keeps some paths.
+    pub fn is_empty(&self) -> bool {
+        self.path_patterns.is_empty() && self.name_patterns.is_empty()
+    }
+
+    /// Remove the directory with all its content, except for the paths in the list. Returns
+    /// `true` if the directory has been removed, `false` if something has been kept. The symlinks
+    /// are removed without following them, so nothing outside the directory is touched.
+    pub fn remove_dir_all<P: AsRef<Path>>(&self, dir: P) -> Result<bool, Error> {
+        let dir = dir.as_ref();
+        if self.is_ignored(dir) {
+            return Ok(false);
+        }
+        if !std::fs::symlink_metadata(dir)?.is_dir() {
+            std::fs::remove_file(dir)?;
+            return Ok(true);
+        }
+        if self.is_empty() {
+            std::fs::remove_dir_all(dir)?;
+            return Ok(true);
+        }
+        let mut removed = true;
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if std::fs::symlink_metadata(&path)?.is_dir() {
+                removed &= self.remove_dir_all(&path)?;
+            } else if self.is_ignored(&path) {
+                info!("Keeping {:?}", path);
+                removed = false;
+            } else {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        if removed {
+            std::fs::remove_dir(dir)?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_list_patterns() {
+        let list = IgnoreList::parse(
+            "/task",
+            "# comment\n\n*.keep\n/input/input1.txt\nbin/sol/\nresources/\n",
+        )
+        .unwrap();
+        assert!(list.is_ignored("/task/output/output0.keep"));
+        assert!(list.is_ignored("/task/input/input1.txt"));
+        assert!(!list.is_ignored("/task/input/input2.txt"));
+        assert!(list.is_ignored("/task/bin/sol/foo"));
+        assert!(!list.is_ignored("/task/bin/foo"));
+        assert!(list.is_ignored("/task/bin/resources/data"));
+        assert!(!list.is_ignored("/other/input/input1.txt"));
+    }
+
+    #[test]
+    fn test_ignore_list_invalid_pattern() {
+        assert!(IgnoreList::parse("/task", "input/[").is_err());
+    }
+
+    #[test]
+    fn test_ignore_list_remove_dir_all() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let bin = tmpdir.path().join("bin");
+        std::fs::create_dir_all(bin.join("sol")).unwrap();
+        std::fs::create_dir_all(bin.join("gen")).unwrap();
+        std::fs::write(bin.join("sol").join("sol"), "x").unwrap();
+        std::fs::write(bin.join("sol").join("keep"), "x").unwrap();
+        std::fs::write(bin.join("gen").join("gen"), "x").unwrap();
+        let list = IgnoreList::parse(tmpdir.path(), "bin/sol/keep").unwrap();
+        assert!(!list.remove_dir_all(&bin).unwrap());
+        assert!(bin.join("sol").join("keep").exists());
+        assert!(!bin.join("sol").join("sol").exists());
+        assert!(!bin.join("gen").exists());
+    }
+    #[test]
+    fn test_ignore_list_remove_dir_all_symlinks() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let outside = tmpdir.path().join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("file"), "x").unwrap();
+        let bin = tmpdir.path().join("task").join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("keep"), "x").unwrap();
+        std::os::unix::fs::symlink(&outside, bin.join("link")).unwrap();
+        let list = IgnoreList::parse(tmpdir.path().join("task"), "bin/keep").unwrap();
+        assert!(!list.remove_dir_all(&bin).unwrap());
+        assert!(!bin.join("link").exists());
+        // the target of the symlink is untouched
+        assert!(outside.join("file").exists());
+    }
+}
@@ -0,0 +1,174 @@
+//! Export of the results of the evaluation in the JUnit XML format, useful for reporting the
+//! outcome of the solutions in the CI systems.
+//!
+//! Each solution is a `<testsuite>` and each testcase of each subtask is a `<testcase>` of that
+//! suite. The testcases that are not accepted have a `<failure>` with the verdict as message,
+//! the ones that have been skipped or not completed are marked as `<skipped>`.
+
+use crate::ioi::{SolutionEvaluationState, TestcaseEvaluationStatus, UIState};
+use failure::Error;
+use itertools::Itertools;
+use std::fmt::Write;
+use std::path::Path;
+
+impl UIState {
+    /// Serialize the results of the evaluations of the solutions as a JUnit XML document.
+    pub fn to_junit_xml(&self) -> String {
+        let suites: Vec<_> = self
+            .evaluations
+            .iter()
+            .map(|(path, eval)| {
+                let name = path.strip_prefix(&self.task.path).unwrap_or(path);
+                (name.to_string_lossy().to_string(), eval)
+            })
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .collect();
+        let tests: usize = suites.iter().map(|(_, eval)| num_testcases(eval)).sum();
+        let failures: usize = suites.iter().map(|(_, eval)| num_failures(eval)).sum();
+
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(
+            xml,
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\">",
+            escape(&self.task.name),
+            tests,
+            failures
+        )
+        .unwrap();
+        for (name, eval) in suites {
+            write_suite(&mut xml, &name, eval);
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+
+    /// Write the results of the evaluations of the solutions as a JUnit XML file at the
+    /// specified path.
+    pub fn write_junit_xml<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_junit_xml())?;
+        Ok(())
+    }
+}
+
+/// Write the `<testsuite>` of a solution.
+fn write_suite(xml: &mut String, name: &str, eval: &SolutionEvaluationState) {
+    let skipped = eval
+        .subtasks
+        .values()
+        .flat_map(|st| st.testcases.values())
+        .filter(|tc| is_skipped(&tc.status))
+        .count();
+    writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">",
+        escape(name),
+        num_testcases(eval),
+        num_failures(eval),
+        skipped
+    )
+    .unwrap();
+    for st_num in eval.subtasks.keys().sorted() {
+        let subtask = &eval.subtasks[st_num];
+        for tc_num in subtask.testcases.keys().sorted() {
+            let testcase = &subtask.testcases[tc_num];
+            write!(
+                xml,
+                "    <testcase name=\"Testcase {}\" classname=\"{}.Subtask {}\"",
+                tc_num,
+                escape(name),
+                st_num
+            )
+            .unwrap();
+            if let Some(result) = &testcase.result {
+                write!(xml, " time=\"{:.3}\"", result.resources.cpu_time).unwrap();
+            }
+            let status = &testcase.status;
+            if status.is_success() {
+                xml.push_str("/>\n");
+            } else if is_skipped(status) {
+                writeln!(
+                    xml,
+                    ">\n      <skipped message=\"{}\"/>\n    </testcase>",
+                    escape(&status.message())
+                )
+                .unwrap();
+            } else {
+                writeln!(
+                    xml,
+                    ">\n      <failure message=\"{}\" type=\"{}\"/>\n    </testcase>",
+                    escape(&status.message()),
+                    failure_type(status)
+                )
+                .unwrap();
+            }
+        }
+    }
+    xml.push_str("  </testsuite>\n");
+}
+
+/// The number of testcases evaluated by the solution.
+fn num_testcases(eval: &SolutionEvaluationState) -> usize {
+    eval.subtasks.values().map(|st| st.testcases.len()).sum()
+}
+
+/// The number of testcases in which the solution is not accepted.
+fn num_failures(eval: &SolutionEvaluationState) -> usize {
+    eval.subtasks
+        .values()
+        .flat_map(|st| st.testcases.values())
+        .filter(|tc| !tc.status.is_success() && !is_skipped(&tc.status))
+        .count()
+}
+
+/// Whether the testcase is reported as skipped: it has been explicitly skipped or its evaluation
+/// has not completed.
+fn is_skipped(status: &TestcaseEvaluationStatus) -> bool {
+    match status {
+        TestcaseEvaluationStatus::Skipped => true,
+        _ => !status.has_completed(),
+    }
+}
+
+/// A short identifier of the kind of failure, used as the `type` of the `<failure>`.
+fn failure_type(status: &TestcaseEvaluationStatus) -> &'static str {
+    use TestcaseEvaluationStatus::*;
+    match status {
+        WrongAnswer(_) => "WrongAnswer",
+        Partial(_) => "Partial",
+        TimeLimitExceeded => "TimeLimitExceeded",
+        WallTimeLimitExceeded => "WallTimeLimitExceeded",
+        MemoryLimitExceeded => "MemoryLimitExceeded",
+        RuntimeError => "RuntimeError",
+        _ => "Failed",
+    }
+}
+
+/// Escape a string for its usage inside an XML attribute.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            '\n' => res.push_str("&#10;"),
+            c if c.is_control() => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&apos;");
+        assert_eq!(escape("line1\nline2\x07"), "line1&#10;line2");
+    }
+}
@@ -7,7 +7,8 @@
 //!
 //! A subtask is a group of testcases, it has a `max_score` parameter which scales its value from
 //! 0.0 to `max_score` points. For computing the score of the subtask a `TestcaseScoreAggregator` is
-//! used. The score of the task for a solution is the sum of all the subtask scores.
+//! used, the one of the task or the one of the subtask if it specifies it. The score of the task
+//! for a solution is the sum of all the subtask scores.
 //!
 //! There are many different valid task types, the most common is `Batch` where the solution is
 //! simply executed once per testcase, feeding in the input file (either via stdin or normal file)
@@ -23,26 +24,39 @@ use std::sync::{Arc, Mutex};
 use failure::{bail, format_err, Error};
 use serde::{Deserialize, Serialize};
 
+use task_maker_dag::{Execution, ExecutionTag, File, FileUuid};
 use task_maker_lang::GraderMap;
 
 use crate::ui::*;
+use crate::validation::ValidationMessage;
 use crate::{list_files, EvaluationData, SourceFile, TaskFormat};
 use crate::{EvaluationConfig, UISender};
 
+mod constraints;
 mod curses_ui;
 mod dag;
 mod finish_ui;
 mod format;
+mod ignore_list;
+mod junit;
 mod print;
+mod report;
 pub mod sanity_checks;
 mod statement;
 mod tag;
+#[cfg(test)]
+pub(crate) mod test_utils;
 mod ui_state;
+mod validation;
 
+pub use constraints::*;
 use curses_ui::CursesUI;
 pub use dag::*;
+use ignore_list::IgnoreList;
 use itertools::Itertools;
 pub use print::PrintUI;
+use print::RATE_LIMIT_WINDOW;
+pub use report::*;
 pub use statement::*;
 use std::ops::Deref;
 pub use tag::*;
@@ -63,8 +77,12 @@ pub struct ScoreManager {
     max_subtask_scores: HashMap<SubtaskId, f64>,
     /// The scores of each testcase.
     testcase_scores: HashMap<SubtaskId, HashMap<TestcaseId, Option<f64>>>,
-    /// The aggregator to use for computing the subtask scores.
-    aggregator: TestcaseScoreAggregator,
+    /// The aggregator to use for computing the score of each subtask.
+    aggregators: HashMap<SubtaskId, TestcaseScoreAggregator>,
+    /// The aggregator to use for computing the task score.
+    task_aggregator: TaskScoreAggregator,
+    /// The number of decimal digits the scores are rounded to.
+    precision: usize,
 }
 
 /// Information about a generic IOI task.
@@ -93,6 +111,19 @@ pub struct Task {
     /// The aggregator to use to compute the score of the subtask based on the score of the
     /// testcases.
     pub testcase_score_aggregator: TestcaseScoreAggregator,
+    /// The aggregator to use to compute the score of the task based on the score of the subtasks.
+    pub task_score_aggregator: TaskScoreAggregator,
+    /// The number of decimal digits the subtask and task scores are rounded to.
+    pub score_precision: usize,
+    /// Whether to run the checker on the testcases where the solution failed.
+    pub checker_policy: CheckerPolicy,
+    /// Whether to run the custom checker twice on each output, warning if the outcomes differ.
+    pub verify_checker: bool,
+    /// Whether to compare the outputs with white diff if the custom checker fails to compile.
+    pub checker_fallback: bool,
+    /// The directories of the host that the solutions and the checkers can read, in addition to
+    /// the ones readable by every execution.
+    pub readable_dirs: Vec<PathBuf>,
     /// The graders registered for this task.
     pub grader_map: Arc<GraderMap>,
     /// The booklets to compile for this task.
@@ -109,8 +140,18 @@ pub struct Task {
 pub struct SubtaskInfo {
     /// The id of the subtask.
     pub id: SubtaskId,
+    /// The name of the subtask, like `N ≤ 1000`, used only for displaying it.
+    #[serde(default)]
+    pub name: Option<String>,
     /// The maximum score of the subtask, must be >= 0.
     pub max_score: f64,
+    /// The aggregator of the scores of the testcases of this subtask, if `None` the one of the
+    /// task is used.
+    #[serde(default)]
+    pub score_mode: Option<TestcaseScoreAggregator>,
+    /// The constraints on the input files of this subtask, enforced by the validator.
+    #[serde(default)]
+    pub constraints: Vec<Constraint>,
     /// The testcases inside this subtask.
     pub testcases: HashMap<TestcaseId, TestcaseInfo>,
 }
@@ -137,19 +178,118 @@ impl Task {
     pub fn new<P: AsRef<Path>>(path: P, eval_config: &EvaluationConfig) -> Result<Task, Error> {
         format::italian_yaml::parse_task(path, eval_config)
     }
+
+    /// Check the configuration of the task: the scores of the subtasks, the limits, the checker,
+    /// the generators and validators of the testcases and the solutions. The informative messages
+    /// summarize the task, the warnings and errors report the misconfigurations.
+    pub fn validate(&self) -> Vec<ValidationMessage> {
+        validation::validate(self)
+    }
+
+    /// The maximum score of the task, aggregating the maximum scores of the subtasks.
+    pub fn max_score(&self) -> f64 {
+        self.task_score_aggregator.aggregate(
+            self.subtasks
+                .values()
+                .sorted_by_key(|st| st.id)
+                .map(|st| st.max_score),
+        )
+    }
+
+    /// The aggregator of the testcase scores of the specified subtask: its own if it has one,
+    /// otherwise the one of the task.
+    pub fn subtask_aggregator(&self, subtask: SubtaskId) -> &TestcaseScoreAggregator {
+        self.subtasks
+            .get(&subtask)
+            .and_then(|st| st.score_mode.as_ref())
+            .unwrap_or(&self.testcase_score_aggregator)
+    }
+
+    /// The number of executions the evaluation of a single solution adds to the DAG: for each
+    /// testcase the solution is run and its output is checked.
+    pub fn executions_per_solution(&self, config: &EvaluationConfig) -> usize {
+        let num_testcases: usize = self.subtasks.values().map(|st| st.testcases.len()).sum();
+        // the custom checker is run twice, or it's run along with its white diff fallback
+        let checkers = match (
+            &self.checker,
+            config.verify_checker || config.checker_fallback,
+        ) {
+            (Checker::Custom(_), true) => 2,
+            _ => 1,
+        };
+        let per_testcase = match self.task_type {
+            TaskType::Batch => 1 + checkers,
+            // the outputs are already there, they are only checked
+            TaskType::OutputOnly => checkers,
+            // the solution and the manager, which checks the solution itself
+            TaskType::Communication(_) => 2,
+        };
+        num_testcases * per_testcase
+    }
+
+    /// Add to the DAG the input and output files of a testcase generated by a previous run,
+    /// without generating them again. Fails if one of them is missing.
+    fn provide_generated_testcase(
+        &self,
+        eval: &mut EvaluationData,
+        subtask_id: SubtaskId,
+        testcase_id: TestcaseId,
+    ) -> Result<(FileUuid, FileUuid), Error> {
+        let mut provide = |kind: &str| -> Result<FileUuid, Error> {
+            let path = self
+                .path
+                .join(kind)
+                .join(format!("{}{}.txt", kind, testcase_id));
+            if !path.exists() {
+                bail!(
+                    "The {} file of testcase {} has not been generated ({:?} is missing), run \
+                     the generation first",
+                    kind,
+                    testcase_id,
+                    path
+                );
+            }
+            let file = File::new(format!(
+                "Generated {} file of testcase {}, subtask {} from {:?}",
+                kind, testcase_id, subtask_id, path
+            ));
+            let uuid = file.uuid;
+            eval.dag.provide_file(file, &path)?;
+            Ok(uuid)
+        };
+        let input = provide("input")?;
+        let output = provide("output")?;
+        Ok((input, output))
+    }
 }
 
 impl TaskFormat for Task {
-    fn ui(&self, ui_type: UIType) -> Result<Box<dyn UI>, Error> {
+    fn ui(&self, ui_type: UIType, config: &EvaluationConfig) -> Result<Box<dyn UI>, Error> {
         match ui_type {
             UIType::Raw => Ok(Box::new(RawUI::new())),
-            UIType::Print => Ok(Box::new(PrintUI::new(self))),
+            UIType::Print => {
+                let mut ui = PrintUI::new(self);
+                ui.rate_limit(
+                    config
+                        .ui_rate_limit
+                        .map(|messages| (RATE_LIMIT_WINDOW, messages)),
+                );
+                Ok(Box::new(ui))
+            }
+            UIType::Silent => {
+                let mut ui = PrintUI::new(self);
+                ui.quiet(true);
+                Ok(Box::new(ui))
+            }
             UIType::Curses => Ok(Box::new(CursesUI::new(self)?)),
             UIType::Json => Ok(Box::new(JsonUI::new())),
         }
     }
 
     fn execute(&self, eval: &mut EvaluationData, config: &EvaluationConfig) -> Result<(), Error> {
+        if self.verify_checker && self.checker_fallback {
+            bail!("The checker cannot be both verified and replaced by its white diff fallback");
+        }
         eval.sender
             .send(UIMessage::IOITask { task: self.clone() })?;
         sanity_checks::pre_hook(&self, eval)?;
@@ -178,7 +318,7 @@ impl TaskFormat for Task {
         } else {
             config.solution_paths.clone()
         };
-        let solutions: Vec<_> = solution_paths
+        let solution_paths: Vec<_> = solution_paths
             .into_iter()
             .filter(|p| !graders.contains(p)) // the graders are not solutions
             .filter(|p| {
@@ -188,23 +328,71 @@ impl TaskFormat for Task {
                 let name = p.file_name().unwrap().to_string_lossy();
                 filter.iter().any(|filter| name.starts_with(filter.deref()))
             })
-            .map(|p| {
-                SourceFile::new(
-                    &p,
-                    &self.path,
-                    Some(self.grader_map.clone()),
-                    Some(
-                        self.path
-                            .join("bin")
-                            .join("sol")
-                            .join(p.file_name().unwrap()),
-                    ),
-                )
-            })
-            .filter(Option::is_some) // ignore the unknown languages
-            .map(Option::unwrap)
-            .map(|source| (source, Arc::new(Mutex::new(empty_score_manager.clone()))))
             .collect();
+        // in output-only tasks the solutions are directories with the output files
+        let outputs: Vec<_> = match self.task_type {
+            TaskType::OutputOnly => solution_paths
+                .iter()
+                .filter(|p| p.is_dir())
+                .map(|p| (p.clone(), Arc::new(Mutex::new(empty_score_manager.clone()))))
+                .collect(),
+            _ => vec![],
+        };
+        let solutions: Vec<_> = match self.task_type {
+            TaskType::OutputOnly => vec![],
+            _ => solution_paths
+                .into_iter()
+                .map(|p| {
+                    SourceFile::new(
+                        &p,
+                        &self.path,
+                        Some(self.grader_map.clone()),
+                        Some(
+                            self.path
+                                .join("bin")
+                                .join("sol")
+                                .join(p.file_name().unwrap()),
+                        ),
+                    )
+                })
+                .filter(Option::is_some) // ignore the unknown languages
+                .map(Option::unwrap)
+                .map(|source| (source, Arc::new(Mutex::new(empty_score_manager.clone()))))
+                .collect(),
+        };
+
+        // refuse to build huge DAGs, before adding anything to them
+        let evaluates_solutions = !solutions.is_empty() || !outputs.is_empty();
+        if let (Some(limit), true) = (config.max_solution_executions, config.phase.evaluates()) {
+            let executions = self.executions_per_solution(config);
+            if executions > limit && evaluates_solutions {
+                bail!(
+                    "Each solution would run {} executions, more than the limit of {}",
+                    executions,
+                    limit
+                );
+            }
+        }
+        // the indexes in `solutions` of the solutions whose outputs are cross-checked
+        let mut cross_checked = Vec::new();
+        for name in &config.cross_check {
+            let name = PathBuf::from(name);
+            let name = name
+                .file_name()
+                .expect("Invalid cross-check solution provided");
+            match solutions
+                .iter()
+                .position(|(source, _)| source.path.file_name() == Some(name))
+            {
+                Some(index) => cross_checked.push(index),
+                None => eval.sender.send(UIMessage::Warning {
+                    message: format!(
+                        "The solution {} to cross-check is not evaluated",
+                        name.to_string_lossy()
+                    ),
+                })?,
+            }
+        }
 
         for subtask in self.subtasks.values() {
             trace!("Executing the generation of subtask {}", subtask.id);
@@ -216,23 +404,54 @@ impl TaskFormat for Task {
                     subtask.id
                 );
 
-                let input =
-                    testcase
-                        .input_generator
-                        .generate(&self, eval, subtask.id, testcase.id)?;
-                let val_handle =
-                    testcase
-                        .input_validator
-                        .validate(eval, subtask.id, testcase.id, input)?;
-                let output = testcase.output_generator.generate(
-                    &self,
-                    eval,
-                    subtask.id,
-                    testcase.id,
-                    input,
-                    val_handle,
-                )?;
+                let (input, val_handle, output) = if config.phase.generates() {
+                    let input =
+                        testcase
+                            .input_generator
+                            .generate(&self, eval, subtask.id, testcase.id)?;
+                    let val_handle = testcase.input_validator.validate(
+                        eval,
+                        subtask.id,
+                        testcase.id,
+                        input,
+                        &subtask.constraints,
+                    )?;
+                    let output = testcase.output_generator.generate(
+                        &self,
+                        eval,
+                        subtask.id,
+                        testcase.id,
+                        input,
+                        val_handle,
+                    )?;
+                    (input, val_handle, output)
+                } else {
+                    let (input, output) =
+                        self.provide_generated_testcase(eval, subtask.id, testcase.id)?;
+                    (input, None, output)
+                };
+                if let Some(size) = config.preview_size {
+                    let (subtask, testcase) = (subtask.id, testcase.id);
+                    bind_preview(eval, input, size, move |preview| {
+                        UIMessage::IOIInputPreview {
+                            subtask,
+                            testcase,
+                            preview,
+                        }
+                    });
+                    bind_preview(eval, output, size, move |preview| {
+                        UIMessage::IOIOutputPreview {
+                            subtask,
+                            testcase,
+                            preview,
+                        }
+                    });
+                }
 
+                if !config.phase.evaluates() {
+                    continue;
+                }
+                let mut solution_outputs = Vec::with_capacity(solutions.len());
                 for (solution, score_manager) in solutions.iter() {
                     trace!(
                         "Evaluation of the solution {:?} against subtask {} / testcase {}",
@@ -241,7 +460,7 @@ impl TaskFormat for Task {
                         testcase.id
                     );
 
-                    self.task_type.evaluate(
+                    let solution_output = self.task_type.evaluate(
                         &self,
                         eval,
                         subtask.id,
@@ -252,9 +471,66 @@ impl TaskFormat for Task {
                         output,
                         score_manager.clone(),
                     )?;
+                    solution_outputs.push(solution_output);
+                }
+                if let Some((&reference, others)) = cross_checked.split_first() {
+                    for &other in others {
+                        // only the solutions producing an output file can be cross-checked
+                        if let (Some(reference_output), Some(other_output)) =
+                            (solution_outputs[reference], solution_outputs[other])
+                        {
+                            self.checker.cross_check(
+                                eval,
+                                subtask.id,
+                                testcase.id,
+                                &solutions[reference].0.path,
+                                &solutions[other].0.path,
+                                input,
+                                reference_output,
+                                other_output,
+                            )?;
+                        }
+                    }
+                }
+                for (outputs_dir, score_manager) in outputs.iter() {
+                    trace!(
+                        "Evaluation of the outputs in {:?} against subtask {} / testcase {}",
+                        outputs_dir,
+                        subtask.id,
+                        testcase.id
+                    );
+
+                    self.task_type.evaluate_output(
+                        &self,
+                        eval,
+                        subtask.id,
+                        testcase.id,
+                        outputs_dir,
+                        input,
+                        output,
+                        score_manager.clone(),
+                    )?;
                 }
             }
         }
+        // the solutions and the checkers can also read the directories of the task
+        if !self.readable_dirs.is_empty() {
+            let tags: Vec<ExecutionTag> = vec![Tag::Evaluation.into(), Tag::Checking.into()];
+            let readable_dirs = &self.readable_dirs;
+            let add_readable_dirs = |exec: &mut Execution| {
+                if exec.tag.as_ref().is_some_and(|tag| tags.contains(tag)) {
+                    for dir in readable_dirs {
+                        exec.limits_mut().add_extra_readable_dir(dir);
+                    }
+                }
+            };
+            for exec in eval.dag.data.executions.values_mut() {
+                for concurrent in exec.concurrent.iter_mut() {
+                    add_readable_dirs(concurrent);
+                }
+                add_readable_dirs(exec);
+            }
+        }
         for booklet in self.booklets.iter() {
             booklet.build(eval)?;
         }
@@ -265,12 +541,18 @@ impl TaskFormat for Task {
         sanity_checks::post_hook(&self, ui)
     }
 
+    fn validate(&self) -> Vec<ValidationMessage> {
+        Task::validate(self)
+    }
+
     fn clean(&self) -> Result<(), Error> {
+        let ignore_list = IgnoreList::load(&self.path)?;
         for dir in &["input", "output"] {
             let dir = self.path.join(dir);
             if !dir.exists() {
                 continue;
             }
+            let mut kept = false;
             for file in glob::glob(dir.join("*.txt").to_string_lossy().as_ref()).unwrap() {
                 let file = match file {
                     Ok(file) => file,
@@ -292,9 +574,17 @@ impl TaskFormat for Task {
                 {
                     continue;
                 }
+                if ignore_list.is_ignored(&file) {
+                    info!("Keeping {:?}", file);
+                    kept = true;
+                    continue;
+                }
                 info!("Removing {:?}", file);
                 std::fs::remove_file(file)?;
             }
+            if kept {
+                continue;
+            }
             info!("Removing {:?}", dir);
             if let Err(e) = std::fs::remove_dir(&dir) {
                 if let std::io::ErrorKind::Other = e.kind() {
@@ -308,13 +598,13 @@ impl TaskFormat for Task {
         let bin_path = self.path.join("bin");
         if bin_path.exists() {
             info!("Removing {:?}", bin_path);
-            std::fs::remove_dir_all(bin_path)?;
+            ignore_list.remove_dir_all(bin_path)?;
         }
         // remove the compiled checkers
         if let Checker::Custom(_) = self.checker {
             for checker in &["check/checker", "cor/correttore"] {
                 let path = self.path.join(checker);
-                if path.exists() {
+                if path.exists() && !ignore_list.is_ignored(&path) {
                     info!("Removing {:?}", path);
                     std::fs::remove_file(path)?;
                 }
@@ -336,6 +626,30 @@ impl FromStr for TestcaseScoreAggregator {
     }
 }
 
+impl FromStr for TaskScoreAggregator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sum" => Ok(TaskScoreAggregator::Sum),
+            "max" => Ok(TaskScoreAggregator::Max),
+            _ => bail!("Invalid task score aggregator: {}", s),
+        }
+    }
+}
+
+impl FromStr for CheckerPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(CheckerPolicy::Skip),
+            "run" => Ok(CheckerPolicy::Run),
+            _ => bail!("Invalid checker policy: {}", s),
+        }
+    }
+}
+
 impl ScoreManager {
     /// Make a new `ScoreManager` based on the subtasks and testcases of the specified task.
     pub fn new(task: &Task) -> ScoreManager {
@@ -351,7 +665,13 @@ impl ScoreManager {
                 .values()
                 .map(|st| (st.id, st.testcases.keys().map(|tc| (*tc, None)).collect()))
                 .collect(),
-            aggregator: task.testcase_score_aggregator.clone(),
+            aggregators: task
+                .subtasks
+                .keys()
+                .map(|st| (*st, task.subtask_aggregator(*st).clone()))
+                .collect(),
+            task_aggregator: task.task_score_aggregator.clone(),
+            precision: task.score_precision,
         }
     }
 
@@ -381,32 +701,42 @@ impl ScoreManager {
             .values()
             .all(Option::is_some)
         {
-            let normalized_score = self.aggregator.aggregate(
+            let normalized_score = self.aggregators[&subtask_id].aggregate(
                 self.testcase_scores[&subtask_id]
-                    .values()
-                    .map(|score| score.unwrap()),
+                    .iter()
+                    .sorted_by_key(|(tc, _)| **tc)
+                    .map(|(_, score)| score.unwrap()),
             );
-            let subtask_score = self.max_subtask_scores[&subtask_id]
-                * normalized_score;
+            let subtask_score = self.max_subtask_scores[&subtask_id] * normalized_score;
             self.subtask_scores.insert(subtask_id, Some(subtask_score));
             sender.send(UIMessage::IOISubtaskScore {
                 subtask: subtask_id,
                 solution: solution.clone(),
-                score: subtask_score,
+                score: self.round(subtask_score),
+                raw_score: subtask_score,
                 normalized_score,
             })?;
             if self.subtask_scores.values().all(Option::is_some) {
-                let task_score: f64 = self
-                    .subtask_scores
-                    .values()
-                    .map(|score| score.unwrap())
-                    .sum();
+                let task_score = self.task_aggregator.aggregate(
+                    self.subtask_scores
+                        .iter()
+                        .sorted_by_key(|(st, _)| **st)
+                        .map(|(_, score)| score.unwrap()),
+                );
                 sender.send(UIMessage::IOITaskScore {
                     solution: solution.clone(),
-                    score: task_score,
+                    score: self.round(task_score),
+                    raw_score: task_score,
                 })?;
             }
         }
         Ok(())
     }
+
+    /// Round the score to the precision of the task, removing the errors accumulated summing the
+    /// floating point scores (i.e. `99.99999999` becomes `100`).
+    fn round(&self, score: f64) -> f64 {
+        let factor = 10f64.powi(self.precision as i32);
+        (score * factor).round() / factor
+    }
 }
@@ -1,10 +1,11 @@
 use crate::cwrite;
 use crate::ioi::finish_ui::FinishUI;
-use crate::ioi::ui_state::UIState;
+use crate::ioi::ui_state::{FilePreview, UIState};
 use crate::ioi::Task;
 use crate::ui::*;
 use itertools::Itertools;
-use task_maker_dag::ExecutionStatus;
+use std::time::{Duration, Instant};
+use task_maker_dag::{ExecutionProvenance, ExecutionStatus};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream};
 
 lazy_static! {
@@ -39,11 +40,82 @@ lazy_static! {
     };
 }
 
+/// The time window of the rate limiting of the `PrintUI` enabled from the command line.
+pub(crate) const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
 /// A simple UI that will print to stdout the human readable messages. Useful
 /// for debugging or for when curses is not available.
+///
+/// The per-testcase messages can be rate limited: in each time window only some of them are
+/// printed, the others are only counted. All the messages are still applied to the state, so the
+/// final summary is complete.
+///
+/// In quiet mode only the scores of the solutions, the warnings, the failed compilations and the
+/// internal errors are printed, followed by the usual final summary.
 pub struct PrintUI {
     stream: StandardStream,
     state: UIState,
+    /// The rate limiting of the per-testcase messages, if enabled.
+    rate_limit: Option<RateLimit>,
+    /// Whether to print only the scores and the errors.
+    quiet: bool,
+}
+
+/// The state of the rate limiting of the messages inside a time window.
+#[derive(Debug)]
+struct RateLimit {
+    /// The duration of a time window.
+    window: Duration,
+    /// The maximum number of messages to print in a time window.
+    max_messages: usize,
+    /// When the current time window started, if any message has been received.
+    window_start: Option<Instant>,
+    /// The number of messages printed in the current time window.
+    printed: usize,
+    /// The number of messages not printed in the current time window.
+    coalesced: usize,
+}
+
+impl RateLimit {
+    /// Make a new `RateLimit` printing at most `max_messages` messages every `window`.
+    fn new(window: Duration, max_messages: usize) -> RateLimit {
+        RateLimit {
+            window,
+            max_messages,
+            window_start: None,
+            printed: 0,
+            coalesced: 0,
+        }
+    }
+
+    /// Account for a message received at `now`, returning whether it should be printed and the
+    /// number of messages not printed in the previous time window, if it has just ended.
+    fn accept(&mut self, now: Instant) -> (bool, usize) {
+        let mut coalesced = 0;
+        match self.window_start {
+            Some(start) if now.duration_since(start) < self.window => {}
+            _ => {
+                coalesced = self.flush();
+                self.window_start = Some(now);
+            }
+        }
+        if self.printed < self.max_messages {
+            self.printed += 1;
+            (true, coalesced)
+        } else {
+            self.coalesced += 1;
+            (false, coalesced)
+        }
+    }
+
+    /// End the current time window, returning the number of messages not printed in it.
+    fn flush(&mut self) -> usize {
+        let coalesced = self.coalesced;
+        self.window_start = None;
+        self.printed = 0;
+        self.coalesced = 0;
+        coalesced
+    }
 }
 
 impl PrintUI {
@@ -52,6 +124,69 @@ impl PrintUI {
         PrintUI {
             stream: StandardStream::stdout(ColorChoice::Auto),
             state: UIState::new(task),
+            rate_limit: None,
+            quiet: false,
+        }
+    }
+
+    /// Print only the scores of the solutions, the warnings, the failed compilations and the
+    /// internal errors.
+    pub fn quiet(&mut self, quiet: bool) -> &mut Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Print at most `max_messages` per-testcase messages every `window`, or all of them if `None`.
+    pub fn rate_limit(&mut self, limit: Option<(Duration, usize)>) -> &mut Self {
+        self.rate_limit = limit.map(|(window, max_messages)| RateLimit::new(window, max_messages));
+        self
+    }
+
+    /// Whether the message is sent for each testcase, possibly many times per second.
+    fn is_per_testcase(message: &UIMessage) -> bool {
+        matches!(
+            message,
+            UIMessage::IOIGeneration { .. }
+                | UIMessage::IOIValidation { .. }
+                | UIMessage::IOISolution { .. }
+                | UIMessage::IOIEvaluation { .. }
+                | UIMessage::IOIChecker { .. }
+                | UIMessage::IOITestcaseScore { .. }
+        )
+    }
+
+    /// Whether the message is printed also in quiet mode.
+    fn is_reported_when_quiet(message: &UIMessage) -> bool {
+        let status = match message {
+            UIMessage::IOITaskScore { .. } | UIMessage::Warning { .. } => return true,
+            UIMessage::Compilation { status, .. } => {
+                return match status {
+                    UIExecutionStatus::Done { result } => result.status != ExecutionStatus::Success,
+                    _ => false,
+                }
+            }
+            UIMessage::IOIGeneration { status, .. }
+            | UIMessage::IOIValidation { status, .. }
+            | UIMessage::IOISolution { status, .. }
+            | UIMessage::IOIEvaluation { status, .. }
+            | UIMessage::IOIChecker { status, .. }
+            | UIMessage::IOIBooklet { status, .. }
+            | UIMessage::IOIBookletDependency { status, .. } => status,
+            _ => return false,
+        };
+        match status {
+            UIExecutionStatus::Done { result } => {
+                matches!(result.status, ExecutionStatus::InternalError(_))
+            }
+            _ => false,
+        }
+    }
+
+    /// Write how many messages have not been printed, if any.
+    fn write_coalesced(&mut self, coalesced: usize) {
+        if coalesced > 0 {
+            cwrite!(self, BOLD, "[...]     ");
+            println!("{} testcase updates not shown", coalesced);
         }
     }
 
@@ -77,6 +212,9 @@ impl PrintUI {
             }
             UIExecutionStatus::Done { result } => {
                 self.write_execution_status(&result.status);
+                if result.provenance != ExecutionProvenance::Unknown {
+                    print!(" from {}", result.provenance);
+                }
             }
             UIExecutionStatus::Skipped => {}
         }
@@ -87,10 +225,21 @@ impl PrintUI {
         match status {
             ExecutionStatus::Success => cwrite!(self, SUCCESS, "[{:?}]", status),
             ExecutionStatus::InternalError(_) => cwrite!(self, ERROR, "[{:?}]", status),
+            ExecutionStatus::Signal(signal, name) => {
+                cwrite!(self, WARNING, "[Signal {}]", signal_text(*signal, name))
+            }
             _ => cwrite!(self, WARNING, "[{:?}]", status),
         }
     }
 
+    /// Write the preview of a file, marking it if truncated.
+    fn write_preview(&self, preview: &FilePreview) {
+        println!("{}", preview.content.trim_end());
+        if preview.truncated {
+            println!("[...]");
+        }
+    }
+
     /// Write a message, padding it to at least 80 chars.
     fn write_message(&mut self, message: String) {
         print!("{:<80}", message);
@@ -100,12 +249,36 @@ impl PrintUI {
 impl UI for PrintUI {
     fn on_message(&mut self, message: UIMessage) {
         self.state.apply(message.clone());
+        if let UIMessage::Warning { message } = &message {
+            // the repeated warnings are counted in the final summary
+            if self.state.warning_counts[message] > 1 {
+                return;
+            }
+        }
+        if self.quiet && !PrintUI::is_reported_when_quiet(&message) {
+            return;
+        }
+        if !self.quiet && PrintUI::is_per_testcase(&message) {
+            if let Some(rate_limit) = &mut self.rate_limit {
+                let (print, coalesced) = rate_limit.accept(Instant::now());
+                self.write_coalesced(coalesced);
+                if !print {
+                    return;
+                }
+            }
+        }
         match message {
             UIMessage::ServerStatus { status } => {
                 println!(
                     "[STATUS]  Server status: {} ready exec, {} waiting exec",
                     status.ready_execs, status.waiting_execs
                 );
+                if let Some(available) = status.memory_available {
+                    println!(
+                        "[STATUS]  Memory: {} KiB in use, {} KiB available",
+                        status.memory_in_use, available
+                    );
+                }
                 for worker in status.connected_workers {
                     if let Some((job, _)) = &worker.current_job {
                         println!(" - {} ({}): {}", worker.name, worker.uuid, job);
@@ -186,6 +359,28 @@ impl UI for PrintUI {
                 );
                 print!("{}", content.trim());
             }
+            UIMessage::IOIInputPreview {
+                subtask,
+                testcase,
+                preview,
+            } => {
+                println!(
+                    "[PREVIEW] Input of testcase {} of subtask {}",
+                    testcase, subtask
+                );
+                self.write_preview(&preview);
+            }
+            UIMessage::IOIOutputPreview {
+                subtask,
+                testcase,
+                preview,
+            } => {
+                println!(
+                    "[PREVIEW] Output of testcase {} of subtask {}",
+                    testcase, subtask
+                );
+                self.write_preview(&preview);
+            }
             UIMessage::IOISolution {
                 subtask,
                 testcase,
@@ -241,6 +436,7 @@ impl UI for PrintUI {
                 solution,
                 score,
                 normalized_score,
+                ..
             } => {
                 print!("[SUBTASK] ");
                 self.write_message(format!(
@@ -248,10 +444,25 @@ impl UI for PrintUI {
                     solution, score, subtask, normalized_score,
                 ));
             }
-            UIMessage::IOITaskScore { solution, score } => {
+            UIMessage::IOITaskScore {
+                solution, score, ..
+            } => {
                 print!("[TASK]    ");
                 self.write_message(format!("Solution {:?} scored {} ", solution, score));
             }
+            UIMessage::IOIScoreExplanation {
+                solution,
+                explanation,
+            } => {
+                print!("[TASK]    ");
+                self.write_message(format!(
+                    "Solution {:?} scored {} out of {} in {} subtasks",
+                    solution,
+                    explanation.score,
+                    explanation.max_score,
+                    explanation.subtasks.len()
+                ));
+            }
             UIMessage::IOIBooklet { name, status } => {
                 self.write_status(&status);
                 self.write_message(format!("Compilation of booklet {}", name));
@@ -272,6 +483,34 @@ impl UI for PrintUI {
                     num_steps
                 ));
             }
+            UIMessage::ToolchainVersion { language, version } => {
+                cwrite!(self, BOLD, "[TOOLCHAIN] ");
+                print!("{}: {}", language, version);
+            }
+            UIMessage::CriticalPath {
+                length,
+                total_time,
+                executions,
+            } => {
+                cwrite!(self, BOLD, "[CRITICAL PATH] ");
+                print!(
+                    "{:.2}s of {:.2}s, {} executions",
+                    length,
+                    total_time,
+                    executions.len()
+                );
+            }
+            UIMessage::SandboxOverhead {
+                setup_time,
+                wall_time,
+                executions,
+            } => {
+                cwrite!(self, BOLD, "[SANDBOX SETUP] ");
+                print!(
+                    "{:.2}s of setup, {:.2}s of execution, {} executions",
+                    setup_time, wall_time, executions
+                );
+            }
             UIMessage::Warning { message } => {
                 cwrite!(self, WARNING, "[WARNING] ");
                 print!("{}", message);
@@ -281,8 +520,132 @@ impl UI for PrintUI {
     }
 
     fn finish(&mut self) {
+        if let Some(coalesced) = self.rate_limit.as_mut().map(RateLimit::flush) {
+            self.write_coalesced(coalesced);
+        }
         println!();
         println!();
         FinishUI::print(&self.state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ioi::test_utils::make_task;
+    use std::path::PathBuf;
+    use task_maker_dag::{ExecutionResourcesUsage, ExecutionResult};
+
+    #[test]
+    fn test_rate_limit_window() {
+        let mut limit = RateLimit::new(Duration::from_secs(1), 2);
+        let start = Instant::now();
+        assert_eq!(limit.accept(start), (true, 0));
+        assert_eq!(limit.accept(start), (true, 0));
+        assert_eq!(limit.accept(start), (false, 0));
+        assert_eq!(limit.accept(start + Duration::from_millis(500)), (false, 0));
+        // a new window starts, reporting the messages not printed in the previous one
+        assert_eq!(limit.accept(start + Duration::from_secs(1)), (true, 2));
+        assert_eq!(limit.accept(start + Duration::from_secs(1)), (true, 0));
+        assert_eq!(limit.accept(start + Duration::from_secs(1)), (false, 0));
+        assert_eq!(limit.flush(), 1);
+        assert_eq!(limit.flush(), 0);
+    }
+
+    #[test]
+    fn test_no_rate_limit_by_default() {
+        let ui = PrintUI::new(&make_task(1));
+        assert!(ui.rate_limit.is_none());
+    }
+
+    #[test]
+    fn test_rate_limit_messages() {
+        let num_testcases = 1000;
+        let task = make_task(num_testcases);
+        let mut ui = PrintUI::new(&task);
+        ui.rate_limit(Some((Duration::from_secs(3600), 10)));
+        for testcase in 0..num_testcases {
+            ui.on_message(UIMessage::IOITestcaseScore {
+                subtask: 0,
+                testcase,
+                solution: "sol".into(),
+                score: 1.0,
+                message: "".into(),
+            });
+        }
+        // only the first 10 messages are printed
+        let coalesced = ui.rate_limit.as_mut().unwrap().flush();
+        assert_eq!(coalesced, num_testcases as usize - 10);
+        let scores = &ui.state.evaluations[&PathBuf::from("sol")].subtasks[&0].testcases;
+        assert_eq!(scores.len(), num_testcases as usize);
+        assert!(scores.values().all(|tc| tc.score == Some(1.0)));
+    }
+
+    #[test]
+    fn test_quiet() {
+        let task = make_task(2);
+        let mut ui = PrintUI::new(&task);
+        ui.quiet(true);
+        let done = |status| UIExecutionStatus::Done {
+            result: ExecutionResult {
+                status,
+                was_killed: false,
+                was_cached: false,
+                truncated_output: false,
+                provenance: ExecutionProvenance::Unknown,
+                resources: ExecutionResourcesUsage {
+                    cpu_time: 0.0,
+                    sys_time: 0.0,
+                    wall_time: 0.0,
+                    memory: 0,
+                    processes: None,
+                    setup_time: 0.0,
+                },
+            },
+        };
+        let hidden = vec![
+            UIMessage::IOITask { task: task.clone() },
+            UIMessage::IOIEvaluation {
+                subtask: 0,
+                testcase: 0,
+                solution: "sol".into(),
+                status: done(ExecutionStatus::Success),
+            },
+            UIMessage::IOITestcaseScore {
+                subtask: 0,
+                testcase: 0,
+                solution: "sol".into(),
+                score: 1.0,
+                message: "".into(),
+            },
+        ];
+        let reported = vec![
+            UIMessage::IOIEvaluation {
+                subtask: 0,
+                testcase: 1,
+                solution: "sol".into(),
+                status: done(ExecutionStatus::InternalError("boom".into())),
+            },
+            UIMessage::Warning {
+                message: "careful".into(),
+            },
+            UIMessage::IOITaskScore {
+                solution: "sol".into(),
+                score: 50.0,
+                raw_score: 50.0,
+            },
+        ];
+        for message in hidden {
+            assert!(!PrintUI::is_reported_when_quiet(&message));
+            ui.on_message(message);
+        }
+        for message in reported {
+            assert!(PrintUI::is_reported_when_quiet(&message));
+            ui.on_message(message);
+        }
+        // the state is still complete for the final summary
+        let testcases = &ui.state.evaluations[&PathBuf::from("sol")].subtasks[&0].testcases;
+        assert_eq!(testcases[&0].score, Some(1.0));
+        assert!(testcases[&1].result.is_some());
+    }
+}
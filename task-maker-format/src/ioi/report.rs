@@ -0,0 +1,249 @@
+//! Export of the summary of the evaluation, with the explanation of the score of each solution.
+//!
+//! The report can be written as JSON, readable by humans, or in a dense binary format (bincode)
+//! which is much smaller and faster to load for the tasks with many testcases. Both formats carry
+//! the same information and can be read back as an `EvaluationReport`.
+//!
+//! The report can also be written as a CSV table, with a row per solution with its score and the
+//! score of each subtask, for keeping the history of the scores across the revisions of the task.
+//! The CSV tables cannot be read back.
+
+use crate::ioi::{CompilationStatus, ScoreExplanation, UIState};
+use failure::{bail, Error};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use task_maker_dag::ExecutionResult;
+
+/// The format of an `EvaluationReport` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A JSON document.
+    Json,
+    /// The compact binary encoding of bincode.
+    Binary,
+    /// A CSV table with the scores of the solutions and of their subtasks.
+    Csv,
+}
+
+impl ReportFormat {
+    /// Guess the format of a report from the extension of its path: `.json`, `.bin` or `.csv`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<ReportFormat, Error> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(ReportFormat::Json),
+            Some("bin") => Ok(ReportFormat::Binary),
+            Some("csv") => Ok(ReportFormat::Csv),
+            _ => bail!("Cannot detect the report format of {:?}", path),
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ReportFormat, Error> {
+        match s {
+            "json" => Ok(ReportFormat::Json),
+            "binary" => Ok(ReportFormat::Binary),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => bail!("Unknown report format: {}", s),
+        }
+    }
+}
+
+/// The summary of the evaluation of a task: the score explanation of each solution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    /// The name of the task.
+    pub task: String,
+    /// The maximum score of the task.
+    pub max_score: f64,
+    /// The report of each solution, sorted by path.
+    pub solutions: Vec<SolutionReport>,
+}
+
+/// The summary of the evaluation of a solution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolutionReport {
+    /// The path of the solution, relative to the task directory.
+    pub solution: PathBuf,
+    /// How the score of the solution has been computed.
+    pub explanation: ScoreExplanation,
+}
+
+/// The final state of the evaluation of a task: the report with the scores of the solutions and
+/// the outcome of the compilations, the warnings and the toolchains.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationSummary {
+    /// The scores of the solutions.
+    pub report: EvaluationReport,
+    /// The outcome of each compilation, sorted by path.
+    pub compilations: Vec<CompilationSummary>,
+    /// The distinct warnings in the order they were first emitted.
+    pub warnings: Vec<WarningSummary>,
+    /// The versions of the toolchains of the used languages, indexed by language name.
+    pub toolchains: BTreeMap<String, String>,
+}
+
+/// A warning emitted during the evaluation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarningSummary {
+    /// The text of the warning.
+    pub message: String,
+    /// The number of times the warning has been emitted.
+    pub count: usize,
+}
+
+/// The outcome of the compilation of a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompilationSummary {
+    /// The path of the compiled file, relative to the task directory.
+    pub path: PathBuf,
+    /// Whether the compilation succeeded.
+    pub success: bool,
+    /// The result of the compilation, `None` if it has not been completed.
+    pub result: Option<ExecutionResult>,
+}
+
+impl EvaluationReport {
+    /// Encode the report in the specified format.
+    pub fn encode(&self, format: ReportFormat) -> Result<Vec<u8>, Error> {
+        Ok(match format {
+            ReportFormat::Json => serde_json::to_vec(self)?,
+            ReportFormat::Binary => bincode::serialize(self)?,
+            ReportFormat::Csv => self.to_csv().into_bytes(),
+        })
+    }
+
+    /// Decode a report encoded in the specified format. The CSV tables cannot be decoded.
+    pub fn decode(data: &[u8], format: ReportFormat) -> Result<EvaluationReport, Error> {
+        Ok(match format {
+            ReportFormat::Json => serde_json::from_slice(data)?,
+            ReportFormat::Binary => bincode::deserialize(data)?,
+            ReportFormat::Csv => bail!("A CSV report cannot be read back"),
+        })
+    }
+
+    /// The CSV table of the report: after the header, a row per solution with its path, its score
+    /// and the score of each subtask, sorted by id.
+    pub fn to_csv(&self) -> String {
+        let subtasks = self
+            .solutions
+            .iter()
+            .flat_map(|sol| sol.explanation.subtasks.iter().map(|st| st.subtask))
+            .unique()
+            .sorted()
+            .collect_vec();
+        let mut csv = String::from("solution,score");
+        for subtask in subtasks.iter() {
+            csv += &format!(",subtask {}", subtask);
+        }
+        csv.push('\n');
+        for solution in self.solutions.iter() {
+            csv += &csv_field(&solution.solution.to_string_lossy());
+            csv += &format!(",{}", solution.explanation.score);
+            for subtask in subtasks.iter() {
+                csv.push(',');
+                let score = solution
+                    .explanation
+                    .subtasks
+                    .iter()
+                    .find(|st| st.subtask == *subtask);
+                if let Some(score) = score {
+                    csv += &score.score.to_string();
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Write the report to a file in the specified format.
+    pub fn write<P: AsRef<Path>>(&self, path: P, format: ReportFormat) -> Result<(), Error> {
+        std::fs::write(path, self.encode(format)?)?;
+        Ok(())
+    }
+
+    /// Read a report from a file written in the specified format.
+    pub fn read<P: AsRef<Path>>(path: P, format: ReportFormat) -> Result<EvaluationReport, Error> {
+        EvaluationReport::decode(&std::fs::read(path)?, format)
+    }
+}
+
+/// Quote a field of a CSV table if it contains a separator, a quote or a new line.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl UIState {
+    /// Summarize the evaluation of the solutions in an `EvaluationReport`. The missing scores are
+    /// considered zero.
+    pub fn report(&self) -> EvaluationReport {
+        let solutions = self
+            .evaluations
+            .iter()
+            .map(|(path, eval)| SolutionReport {
+                solution: path.strip_prefix(&self.task.path).unwrap_or(path).into(),
+                explanation: eval.explain(&self.task),
+            })
+            .sorted_by(|a, b| a.solution.cmp(&b.solution))
+            .collect();
+        EvaluationReport {
+            task: self.task.name.clone(),
+            max_score: self.max_score,
+            solutions,
+        }
+    }
+
+    /// Write the report of the evaluation to a file, in the format detected from the extension of
+    /// the path (see [`ReportFormat::from_path`](enum.ReportFormat.html#method.from_path)).
+    pub fn write_report<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let format = ReportFormat::from_path(&path)?;
+        self.report().write(path, format)
+    }
+
+    /// Summarize the final state of the evaluation in an `EvaluationSummary`.
+    pub fn summary(&self) -> EvaluationSummary {
+        let compilations = self
+            .compilations
+            .iter()
+            .map(|(path, status)| {
+                let (success, result) = match status {
+                    CompilationStatus::Done { result, .. } => (true, Some(result.clone())),
+                    CompilationStatus::Failed { result, .. } => (false, Some(result.clone())),
+                    _ => (false, None),
+                };
+                CompilationSummary {
+                    path: path.strip_prefix(&self.task.path).unwrap_or(path).into(),
+                    success,
+                    result,
+                }
+            })
+            .sorted_by(|a, b| a.path.cmp(&b.path))
+            .collect();
+        EvaluationSummary {
+            report: self.report(),
+            compilations,
+            warnings: self
+                .warnings
+                .iter()
+                .map(|warning| WarningSummary {
+                    message: warning.clone(),
+                    count: self.warning_counts.get(warning).cloned().unwrap_or(1),
+                })
+                .collect(),
+            toolchains: self
+                .toolchains
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+}
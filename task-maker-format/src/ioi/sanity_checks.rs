@@ -14,7 +14,7 @@ use std::process::Command;
 use task_maker_lang::LanguageManager;
 
 /// The default maximum score of a task.
-const DEFAULT_TASK_MAX_SCORE: f64 = 100.0;
+pub(crate) const DEFAULT_TASK_MAX_SCORE: f64 = 100.0;
 
 /// Function called for the first pass of sanity checks of the task.
 pub fn pre_hook(task: &Task, eval: &mut EvaluationData) -> Result<(), Error> {
@@ -39,7 +39,7 @@ pub fn post_hook(task: &Task, ui: &mut UIMessageSender) -> Result<(), Error> {
 
 /// Check that the task has the usual maximum score.
 fn check_task_max_score(task: &Task, eval: &mut EvaluationData) -> Result<(), Error> {
-    let task_score: f64 = task.subtasks.values().map(|st| st.max_score).sum();
+    let task_score = task.max_score();
     if approx::abs_diff_ne!(task_score, DEFAULT_TASK_MAX_SCORE) {
         eval.sender.send(UIMessage::Warning {
             message: format!(
@@ -0,0 +1,58 @@
+//! Helpers shared by the unit tests of the UIs.
+
+use crate::ioi::{
+    Checker, CheckerPolicy, InputGenerator, InputValidator, OutputGenerator, SubtaskInfo, Task,
+    TaskScoreAggregator, TaskType, TestcaseInfo, TestcaseScoreAggregator, WhiteDiffOptions,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use task_maker_lang::GraderMap;
+
+/// Make a batch task with a single subtask of `num_testcases` testcases, with static inputs and
+/// outputs.
+pub(crate) fn make_task(num_testcases: u32) -> Task {
+    let mut subtask = SubtaskInfo {
+        id: 0,
+        name: None,
+        max_score: 100.0,
+        score_mode: None,
+        constraints: vec![],
+        testcases: Default::default(),
+    };
+    for id in 0..num_testcases {
+        subtask.testcases.insert(
+            id,
+            TestcaseInfo {
+                id,
+                input_generator: InputGenerator::StaticFile("input".into()),
+                input_validator: InputValidator::AssumeValid,
+                output_generator: OutputGenerator::StaticFile("output".into()),
+            },
+        );
+    }
+    let mut task = Task {
+        path: "".into(),
+        task_type: TaskType::Batch,
+        name: "task".to_string(),
+        title: "The Task".to_string(),
+        time_limit: None,
+        memory_limit: None,
+        infile: None,
+        outfile: None,
+        subtasks: Default::default(),
+        checker: Checker::WhiteDiff(WhiteDiffOptions::default()),
+        testcase_score_aggregator: TestcaseScoreAggregator::Min,
+        task_score_aggregator: TaskScoreAggregator::Sum,
+        score_precision: 2,
+        checker_policy: CheckerPolicy::Skip,
+        verify_checker: false,
+        checker_fallback: false,
+        readable_dirs: vec![],
+        grader_map: Arc::new(GraderMap::new(Vec::<PathBuf>::new())),
+        booklets: vec![],
+        difficulty: None,
+        syllabus_level: None,
+    };
+    task.subtasks.insert(0, subtask);
+    task
+}
@@ -1,6 +1,9 @@
+use crate::diagnostics::{parse_diagnostics, Diagnostic};
 use crate::ioi::*;
 use crate::ui::{UIExecutionStatus, UIMessage};
-use std::collections::HashMap;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::time::SystemTime;
 use task_maker_dag::*;
@@ -104,6 +107,19 @@ pub struct TestcaseGenerationState {
     pub validation_stderr: Option<String>,
     /// Result of the solution.
     pub solution: Option<ExecutionResult>,
+    /// The first bytes of the input file, if the preview is enabled.
+    pub input_preview: Option<FilePreview>,
+    /// The first bytes of the output file, if the preview is enabled.
+    pub output_preview: Option<FilePreview>,
+}
+
+/// The first bytes of a file, for a quick look at its content.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FilePreview {
+    /// The first bytes of the file, decoded as UTF-8 replacing the invalid sequences.
+    pub content: String,
+    /// Whether the file is longer than the preview.
+    pub truncated: bool,
 }
 
 /// State of the generation of a subtask.
@@ -180,6 +196,132 @@ impl SolutionEvaluationState {
     }
 }
 
+/// Breakdown of how the score of a solution has been computed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreExplanation {
+    /// The score of the solution.
+    pub score: f64,
+    /// The maximum score of the task.
+    pub max_score: f64,
+    /// The explanation of the score of each subtask, sorted by id.
+    pub subtasks: Vec<SubtaskScoreExplanation>,
+}
+
+/// Breakdown of how the score of a subtask has been computed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtaskScoreExplanation {
+    /// The id of the subtask.
+    pub subtask: SubtaskId,
+    /// The name of the subtask, if any.
+    pub name: Option<String>,
+    /// The score of the solution on the subtask.
+    pub score: f64,
+    /// The maximum score of the subtask.
+    pub max_score: f64,
+    /// With the `Min` aggregator, the testcase with the lowest score that limited the score of the
+    /// subtask. `None` if all the testcases scored full or with the `Sum` and `Custom` aggregators,
+    /// where all the testcases contribute to the score.
+    pub limiting_testcase: Option<TestcaseId>,
+    /// The score and verdict of each testcase of the subtask, sorted by id.
+    pub testcases: Vec<TestcaseScoreExplanation>,
+}
+
+/// The score and verdict of a solution on a testcase.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestcaseScoreExplanation {
+    /// The id of the testcase.
+    pub testcase: TestcaseId,
+    /// The score of the testcase, from 0.0 to 1.0.
+    pub score: f64,
+    /// The verdict of the testcase.
+    pub verdict: String,
+}
+
+impl SolutionEvaluationState {
+    /// Whether the evaluation of the solution has been completed, i.e. the solution and all its
+    /// subtasks have been scored.
+    pub fn is_completed(&self) -> bool {
+        self.score.is_some() && self.subtasks.values().all(|st| st.score.is_some())
+    }
+
+    /// The peak memory and the slowest testcase of the solution, among the testcases where the
+    /// solution has been executed. `None` if it has not been executed on any testcase.
+    pub fn resource_peaks(&self) -> Option<SolutionResourcePeaks> {
+        let mut peaks: Option<SolutionResourcePeaks> = None;
+        for (subtask, st) in self.subtasks.iter().sorted_by_key(|(id, _)| **id) {
+            for (testcase, tc) in st.testcases.iter().sorted_by_key(|(id, _)| **id) {
+                let resources = match &tc.result {
+                    Some(result) => &result.resources,
+                    None => continue,
+                };
+                match &mut peaks {
+                    Some(peaks) => {
+                        peaks.memory = peaks.memory.max(resources.memory);
+                        if resources.cpu_time > peaks.slowest_time {
+                            peaks.slowest_subtask = *subtask;
+                            peaks.slowest_testcase = *testcase;
+                            peaks.slowest_time = resources.cpu_time;
+                        }
+                    }
+                    None => {
+                        peaks = Some(SolutionResourcePeaks {
+                            memory: resources.memory,
+                            slowest_subtask: *subtask,
+                            slowest_testcase: *testcase,
+                            slowest_time: resources.cpu_time,
+                        })
+                    }
+                }
+            }
+        }
+        peaks
+    }
+
+    /// Explain how the score of this solution has been computed: the score of each subtask, the
+    /// verdict of each testcase and, for the subtasks scored with the minimum, which testcase was
+    /// the limiting one. The missing scores are considered zero.
+    pub fn explain(&self, task: &Task) -> ScoreExplanation {
+        let mut subtasks: Vec<_> = self
+            .subtasks
+            .iter()
+            .map(|(st_num, subtask)| {
+                let mut testcases: Vec<_> = subtask
+                    .testcases
+                    .iter()
+                    .map(|(tc_num, testcase)| TestcaseScoreExplanation {
+                        testcase: *tc_num,
+                        score: testcase.score.unwrap_or(0.0),
+                        verdict: testcase.status.message(),
+                    })
+                    .collect();
+                testcases.sort_by_key(|tc| tc.testcase);
+                let limiting_testcase = match task.subtask_aggregator(*st_num) {
+                    TestcaseScoreAggregator::Min => testcases
+                        .iter()
+                        .filter(|tc| tc.score < 1.0)
+                        .min_by(|a, b| a.score.total_cmp(&b.score))
+                        .map(|tc| tc.testcase),
+                    TestcaseScoreAggregator::Sum | TestcaseScoreAggregator::Custom(_) => None,
+                };
+                SubtaskScoreExplanation {
+                    subtask: *st_num,
+                    name: task.subtasks[st_num].name.clone(),
+                    score: subtask.score.unwrap_or(0.0),
+                    max_score: task.subtasks[st_num].max_score,
+                    limiting_testcase,
+                    testcases,
+                }
+            })
+            .collect();
+        subtasks.sort_by_key(|st| st.subtask);
+        ScoreExplanation {
+            score: self.score.unwrap_or(0.0),
+            max_score: task.max_score(),
+            subtasks,
+        }
+    }
+}
+
 /// The status of the compilation of a dependency of a booklet.
 #[derive(Debug, Clone)]
 pub struct BookletDependencyState {
@@ -187,6 +329,20 @@ pub struct BookletDependencyState {
     pub status: UIExecutionStatus,
 }
 
+/// The category of the outcome of the evaluation of a solution, for a quick check that the
+/// solutions of the task cover all the expected outcomes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum VerdictCategory {
+    /// The solution got the maximum score.
+    Correct,
+    /// The solution got some points, but not all of them.
+    Partial,
+    /// The solution got no points.
+    Wrong,
+    /// The solution failed to compile, or some of its evaluations failed or have been skipped.
+    Error,
+}
+
 /// The status of the compilation of a booklet.
 #[derive(Debug, Clone)]
 pub struct BookletState {
@@ -196,6 +352,32 @@ pub struct BookletState {
     pub dependencies: HashMap<String, Vec<BookletDependencyState>>,
 }
 
+/// The resources used by the checker on a testcase of a solution.
+#[derive(Debug, Clone)]
+pub struct CheckerUsage {
+    /// The path of the solution.
+    pub solution: PathBuf,
+    /// The id of the subtask.
+    pub subtask: SubtaskId,
+    /// The id of the testcase.
+    pub testcase: TestcaseId,
+    /// The resources used by the checker.
+    pub resources: ExecutionResourcesUsage,
+}
+
+/// The peak resources used by a solution on its testcases.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolutionResourcePeaks {
+    /// The maximum memory used on a testcase, in KiB.
+    pub memory: u64,
+    /// The id of the subtask of the testcase that used the most cpu time.
+    pub slowest_subtask: SubtaskId,
+    /// The id of the testcase that used the most cpu time.
+    pub slowest_testcase: TestcaseId,
+    /// The cpu time used on the slowest testcase, in seconds.
+    pub slowest_time: f64,
+}
+
 /// The state of a IOI task, all the information for the UI are stored here.
 #[derive(Debug, Clone)]
 pub struct UIState {
@@ -213,8 +395,58 @@ pub struct UIState {
     pub executor_status: Option<ExecutorStatus<SystemTime>>,
     /// The status of the booklets
     pub booklets: HashMap<String, BookletState>,
-    /// All the emitted warnings.
+    /// All the distinct emitted warnings, in the order they were first emitted.
     pub warnings: Vec<String>,
+    /// How many times each warning has been emitted.
+    pub warning_counts: HashMap<String, usize>,
+    /// The versions of the toolchains of the used languages, indexed by language name.
+    pub toolchains: HashMap<String, String>,
+    /// The critical path of the evaluation, known only at the end of it.
+    pub critical_path: Option<CriticalPathState>,
+    /// The time spent preparing the sandboxes, known only at the end of the evaluation.
+    pub sandbox_overhead: Option<SandboxOverheadState>,
+}
+
+/// The critical path of the evaluation, see
+/// [`UIMessage::CriticalPath`](../ui/enum.UIMessage.html#variant.CriticalPath).
+#[derive(Debug, Clone)]
+pub struct CriticalPathState {
+    /// The sum of the wall times of the executions in the critical path, in seconds.
+    pub length: f64,
+    /// The sum of the wall times of all the executions, in seconds.
+    pub total_time: f64,
+    /// The descriptions of the executions in the critical path, in the order of execution.
+    pub executions: Vec<String>,
+}
+
+/// The time spent preparing the sandboxes, see
+/// [`UIMessage::SandboxOverhead`](../ui/enum.UIMessage.html#variant.SandboxOverhead).
+#[derive(Debug, Clone)]
+pub struct SandboxOverheadState {
+    /// The sum of the setup times of the sandboxes, in seconds.
+    pub setup_time: f64,
+    /// The sum of the wall times of the executions, in seconds.
+    pub wall_time: f64,
+    /// The number of executions that ran in a sandbox.
+    pub executions: usize,
+}
+
+impl CompilationStatus {
+    /// The diagnostics emitted by the compiler, parsed from the standard error of the
+    /// compilation. If the compilation is not completed yet an empty list is returned.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            CompilationStatus::Done {
+                stderr: Some(stderr),
+                ..
+            }
+            | CompilationStatus::Failed {
+                stderr: Some(stderr),
+                ..
+            } => parse_diagnostics(stderr),
+            _ => vec![],
+        }
+    }
 }
 
 impl TestcaseEvaluationStatus {
@@ -284,6 +516,39 @@ impl TestcaseEvaluationStatus {
     }
 }
 
+impl VerdictCategory {
+    /// All the categories, in the order they are shown.
+    pub const ALL: [VerdictCategory; 4] = [
+        VerdictCategory::Correct,
+        VerdictCategory::Partial,
+        VerdictCategory::Wrong,
+        VerdictCategory::Error,
+    ];
+
+    /// A short description of the category.
+    pub fn description(self) -> &'static str {
+        match self {
+            VerdictCategory::Correct => "correct (100%)",
+            VerdictCategory::Partial => "partial",
+            VerdictCategory::Wrong => "wrong (0%)",
+            VerdictCategory::Error => "error",
+        }
+    }
+}
+
+impl FilePreview {
+    /// Make the preview of a file given its first bytes, keeping at most `size` bytes. The content
+    /// is truncated if longer than that.
+    pub fn new(content: &[u8], size: usize) -> FilePreview {
+        let truncated = content.len() > size;
+        let content = &content[..std::cmp::min(size, content.len())];
+        FilePreview {
+            content: String::from_utf8_lossy(content).into(),
+            truncated,
+        }
+    }
+}
+
 impl UIState {
     /// Make a new `UIState`.
     pub fn new(task: &Task) -> UIState {
@@ -307,6 +572,8 @@ impl UIState {
                                         validation: None,
                                         validation_stderr: None,
                                         solution: None,
+                                        input_preview: None,
+                                        output_preview: None,
                                     },
                                 )
                             })
@@ -316,7 +583,7 @@ impl UIState {
             })
             .collect();
         UIState {
-            max_score: task.subtasks.values().map(|s| s.max_score).sum(),
+            max_score: task.max_score(),
             task: task.clone(),
             compilations: HashMap::new(),
             generations,
@@ -324,9 +591,94 @@ impl UIState {
             executor_status: None,
             booklets: HashMap::new(),
             warnings: Vec::new(),
+            warning_counts: HashMap::new(),
+            toolchains: HashMap::new(),
+            critical_path: None,
+            sandbox_overhead: None,
         }
     }
 
+    /// The `count` checker executions that used the most cpu time, sorted from the slowest.
+    pub fn slowest_checkers(&self, count: usize) -> Vec<CheckerUsage> {
+        let mut usages = Vec::new();
+        for (solution, eval) in self.evaluations.iter() {
+            for (subtask, st) in eval.subtasks.iter() {
+                for (testcase, tc) in st.testcases.iter() {
+                    if let Some(checker) = &tc.checker {
+                        usages.push(CheckerUsage {
+                            solution: solution.clone(),
+                            subtask: *subtask,
+                            testcase: *testcase,
+                            resources: checker.resources.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        let time = |usage: &CheckerUsage| usage.resources.cpu_time + usage.resources.sys_time;
+        usages.sort_by(|a, b| time(b).partial_cmp(&time(a)).unwrap());
+        usages.truncate(count);
+        usages
+    }
+
+    /// The distinct warnings in the order they were first emitted, with the number of times they
+    /// have been emitted appended (e.g. `"message (x12)"`) if they were emitted more than once.
+    pub fn warnings_summary(&self) -> Vec<String> {
+        self.warnings
+            .iter()
+            .map(|warning| match self.warning_counts.get(warning) {
+                Some(&count) if count > 1 => format!("{} (x{})", warning, count),
+                _ => warning.clone(),
+            })
+            .collect()
+    }
+
+    /// The category of the outcome of the evaluation of a solution, `None` if the solution is not
+    /// known.
+    pub fn verdict_category(&self, solution: &PathBuf) -> Option<VerdictCategory> {
+        let eval = self.evaluations.get(solution)?;
+        let compilation_failed = match self.compilations.get(solution) {
+            Some(CompilationStatus::Failed { .. }) | Some(CompilationStatus::Skipped) => true,
+            _ => false,
+        };
+        let evaluation_failed = eval
+            .subtasks
+            .values()
+            .flat_map(|subtask| subtask.testcases.values())
+            .any(|testcase| match testcase.status {
+                TestcaseEvaluationStatus::Failed | TestcaseEvaluationStatus::Skipped => true,
+                _ => false,
+            });
+        let category = match eval.score {
+            _ if compilation_failed || evaluation_failed => VerdictCategory::Error,
+            None => VerdictCategory::Error,
+            Some(score) if abs_diff_eq!(score, self.max_score) => VerdictCategory::Correct,
+            Some(score) if abs_diff_eq!(score, 0.0) => VerdictCategory::Wrong,
+            Some(_) => VerdictCategory::Partial,
+        };
+        Some(category)
+    }
+
+    /// The solutions grouped by the category of the outcome of their evaluation, sorted by path.
+    /// All the categories are present, possibly with no solutions.
+    pub fn verdict_groups(&self) -> BTreeMap<VerdictCategory, Vec<PathBuf>> {
+        let mut groups: BTreeMap<_, _> = VerdictCategory::ALL
+            .iter()
+            .map(|category| (*category, Vec::new()))
+            .collect();
+        let mut solutions: Vec<_> = self.evaluations.keys().collect();
+        solutions.sort();
+        for solution in solutions {
+            if let Some(category) = self.verdict_category(solution) {
+                groups
+                    .get_mut(&category)
+                    .expect("Missing category")
+                    .push(solution.clone());
+            }
+        }
+        groups
+    }
+
     /// Apply a `UIMessage` to this state.
     pub fn apply(&mut self, message: UIMessage) {
         match message {
@@ -363,10 +715,12 @@ impl UIState {
                     .entry(file.clone())
                     .or_insert(CompilationStatus::Pending);
                 match comp {
+                    // the compilation may have more steps, each with its own messages
                     CompilationStatus::Done { stdout, .. }
-                    | CompilationStatus::Failed { stdout, .. } => {
-                        stdout.replace(content);
-                    }
+                    | CompilationStatus::Failed { stdout, .. } => match stdout {
+                        Some(stdout) => stdout.push_str(&content),
+                        None => *stdout = Some(content),
+                    },
                     _ => {}
                 }
             }
@@ -377,9 +731,10 @@ impl UIState {
                     .or_insert(CompilationStatus::Pending);
                 match comp {
                     CompilationStatus::Done { stderr, .. }
-                    | CompilationStatus::Failed { stderr, .. } => {
-                        stderr.replace(content);
-                    }
+                    | CompilationStatus::Failed { stderr, .. } => match stderr {
+                        Some(stderr) => stderr.push_str(&content),
+                        None => *stderr = Some(content),
+                    },
                     _ => {}
                 }
             }
@@ -426,6 +781,34 @@ impl UIState {
                     .expect("Testcase is gone");
                 gen.generation_stderr = Some(content);
             }
+            UIMessage::IOIInputPreview {
+                subtask,
+                testcase,
+                preview,
+            } => {
+                let gen = self
+                    .generations
+                    .get_mut(&subtask)
+                    .expect("Subtask is gone")
+                    .testcases
+                    .get_mut(&testcase)
+                    .expect("Testcase is gone");
+                gen.input_preview = Some(preview);
+            }
+            UIMessage::IOIOutputPreview {
+                subtask,
+                testcase,
+                preview,
+            } => {
+                let gen = self
+                    .generations
+                    .get_mut(&subtask)
+                    .expect("Subtask is gone")
+                    .testcases
+                    .get_mut(&testcase)
+                    .expect("Testcase is gone");
+                gen.output_preview = Some(preview);
+            }
             UIMessage::IOIValidation {
                 subtask,
                 testcase,
@@ -578,7 +961,9 @@ impl UIState {
                     .get_mut(&testcase)
                     .expect("Missing testcase");
                 match status {
-                    UIExecutionStatus::Started { .. } => {
+                    // the checker may run also after a failure of the solution, keep the failure
+                    // as the verdict
+                    UIExecutionStatus::Started { .. } if !testcase.status.has_completed() => {
                         testcase.status = TestcaseEvaluationStatus::Checking;
                     }
                     UIExecutionStatus::Done { result } => {
@@ -619,7 +1004,7 @@ impl UIState {
                 subtask,
                 solution,
                 score,
-                normalized_score: _,
+                ..
             } => {
                 let task = &self.task;
                 let eval = self
@@ -629,7 +1014,9 @@ impl UIState {
                 let mut subtask = eval.subtasks.get_mut(&subtask).expect("Missing subtask");
                 subtask.score = Some(score);
             }
-            UIMessage::IOITaskScore { solution, score } => {
+            UIMessage::IOITaskScore {
+                solution, score, ..
+            } => {
                 let task = &self.task;
                 let eval = self
                     .evaluations
@@ -637,6 +1024,8 @@ impl UIState {
                     .or_insert_with(|| SolutionEvaluationState::new(task));
                 eval.score = Some(score);
             }
+            // the explanation is derived from the rest of the state
+            UIMessage::IOIScoreExplanation { .. } => {}
             UIMessage::IOIBooklet { name, status } => {
                 self.booklets
                     .entry(name)
@@ -672,8 +1061,38 @@ impl UIState {
                     .expect("Statement dependency step is gone")
                     .status = status;
             }
+            UIMessage::ToolchainVersion { language, version } => {
+                self.toolchains.insert(language, version);
+            }
+            UIMessage::CriticalPath {
+                length,
+                total_time,
+                executions,
+            } => {
+                self.critical_path = Some(CriticalPathState {
+                    length,
+                    total_time,
+                    executions,
+                });
+            }
+            UIMessage::SandboxOverhead {
+                setup_time,
+                wall_time,
+                executions,
+            } => {
+                self.sandbox_overhead = Some(SandboxOverheadState {
+                    setup_time,
+                    wall_time,
+                    executions,
+                });
+            }
             UIMessage::Warning { message } => {
-                self.warnings.push(message);
+                let count = self.warning_counts.entry(message.clone()).or_insert(0);
+                *count += 1;
+                // the same warning is often emitted by many testcases, keep it only once
+                if *count == 1 {
+                    self.warnings.push(message);
+                }
             }
         }
     }
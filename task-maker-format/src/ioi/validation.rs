@@ -0,0 +1,219 @@
+//! Upfront validation of IOI-like tasks, see [`Task::validate`](../struct.Task.html#method.validate).
+
+use crate::ioi::sanity_checks::DEFAULT_TASK_MAX_SCORE;
+use crate::ioi::{Checker, InputGenerator, InputValidator, OutputGenerator, Task, TaskType};
+use crate::list_files;
+use crate::validation::ValidationMessage;
+use itertools::Itertools;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use task_maker_lang::LanguageManager;
+
+/// Run all the checks on the task, returning the messages in the order of the checks.
+pub(crate) fn validate(task: &Task) -> Vec<ValidationMessage> {
+    let mut messages = Vec::new();
+    check_testcases(task, &mut messages);
+    check_score(task, &mut messages);
+    check_limits(task, &mut messages);
+    check_checker(task, &mut messages);
+    check_generation(task, &mut messages);
+    check_solutions(task, &mut messages);
+    messages
+}
+
+/// Report the number of subtasks and testcases, checking that none of them is empty.
+fn check_testcases(task: &Task, messages: &mut Vec<ValidationMessage>) {
+    let num_testcases: usize = task.subtasks.values().map(|st| st.testcases.len()).sum();
+    messages.push(ValidationMessage::info(format!(
+        "{} subtasks, {} testcases",
+        task.subtasks.len(),
+        num_testcases
+    )));
+    if task.subtasks.is_empty() {
+        messages.push(ValidationMessage::error("The task has no subtasks"));
+    }
+    for st_num in task.subtasks.keys().sorted() {
+        if task.subtasks[st_num].testcases.is_empty() {
+            messages.push(ValidationMessage::warning(format!(
+                "Subtask {} has no testcases",
+                st_num
+            )));
+        }
+    }
+}
+
+/// Check that the scores of the subtasks are valid and sum to the usual maximum score.
+fn check_score(task: &Task, messages: &mut Vec<ValidationMessage>) {
+    for st_num in task.subtasks.keys().sorted() {
+        let max_score = task.subtasks[st_num].max_score;
+        if max_score < 0.0 {
+            messages.push(ValidationMessage::error(format!(
+                "Subtask {} has a negative score: {}",
+                st_num, max_score
+            )));
+        }
+    }
+    let task_score = task.max_score();
+    if abs_diff_ne!(task_score, DEFAULT_TASK_MAX_SCORE) {
+        messages.push(ValidationMessage::warning(format!(
+            "The score of the task is {} (not {})",
+            task_score, DEFAULT_TASK_MAX_SCORE
+        )));
+    }
+}
+
+/// Check that the solutions are limited in time and memory.
+fn check_limits(task: &Task, messages: &mut Vec<ValidationMessage>) {
+    if let TaskType::OutputOnly = task.task_type {
+        return;
+    }
+    if task.time_limit.is_none() {
+        messages.push(ValidationMessage::warning("The time limit is not set"));
+    }
+    if task.memory_limit.is_none() {
+        messages.push(ValidationMessage::warning("The memory limit is not set"));
+    }
+}
+
+/// Report the checker, or the manager of a communication task, warning if the checker directory is
+/// present but no checker is found in it.
+fn check_checker(task: &Task, messages: &mut Vec<ValidationMessage>) {
+    // in communication tasks the manager checks the solutions
+    if let TaskType::Communication(manager) = &task.task_type {
+        messages.push(ValidationMessage::info(format!(
+            "Manager: {}",
+            relative_path(task, &manager.path).display()
+        )));
+        return;
+    }
+    match &task.checker {
+        Checker::Custom(checker) => {
+            messages.push(ValidationMessage::info(format!(
+                "Checker: {}",
+                relative_path(task, &checker.path).display()
+            )));
+        }
+        Checker::WhiteDiff(options) => {
+            if options.unordered_lines {
+                messages.push(ValidationMessage::info(
+                    "Checker: white diff, lines in any order",
+                ));
+            } else {
+                messages.push(ValidationMessage::info("Checker: white diff"));
+            }
+            for dir in &["check", "cor"] {
+                if task.path.join(dir).is_dir() {
+                    messages.push(ValidationMessage::warning(format!(
+                        "The {}/ directory exists but no checker is found in it, the outputs are \
+                         compared with white diff",
+                        dir
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// Report the generators, the validators and the sources of the outputs of the testcases.
+fn check_generation(task: &Task, messages: &mut Vec<ValidationMessage>) {
+    let testcases = task.subtasks.values().flat_map(|st| st.testcases.values());
+    let mut generators = HashSet::new();
+    let mut validators = HashSet::new();
+    let mut solutions = HashSet::new();
+    let mut static_inputs = 0;
+    let mut static_outputs = 0;
+    let mut unvalidated = 0;
+    for testcase in testcases {
+        match &testcase.input_generator {
+            InputGenerator::StaticFile(_) => static_inputs += 1,
+            InputGenerator::Custom(generator, _) => {
+                generators.insert(generator.path.clone());
+            }
+        }
+        match (&testcase.input_validator, &testcase.input_generator) {
+            (InputValidator::AssumeValid, InputGenerator::Custom(_, _)) => unvalidated += 1,
+            (InputValidator::AssumeValid, InputGenerator::StaticFile(_)) => {}
+            (InputValidator::Custom(validator, _), _) => {
+                validators.insert(validator.path.clone());
+            }
+        }
+        match &testcase.output_generator {
+            OutputGenerator::StaticFile(_) => static_outputs += 1,
+            OutputGenerator::Custom(solution, _) => {
+                solutions.insert(solution.path.clone());
+            }
+            OutputGenerator::NotAvailable => {}
+        }
+    }
+    let describe = |paths: HashSet<PathBuf>| {
+        paths
+            .iter()
+            .map(|p| relative_path(task, p).display().to_string())
+            .sorted()
+            .join(", ")
+    };
+    if !generators.is_empty() {
+        messages.push(ValidationMessage::info(format!(
+            "Generators: {}",
+            describe(generators)
+        )));
+    }
+    if static_inputs > 0 {
+        messages.push(ValidationMessage::info(format!(
+            "{} static input files",
+            static_inputs
+        )));
+    }
+    if !validators.is_empty() {
+        messages.push(ValidationMessage::info(format!(
+            "Validators: {}",
+            describe(validators)
+        )));
+    }
+    if unvalidated > 0 {
+        messages.push(ValidationMessage::warning(format!(
+            "{} generated inputs are not validated",
+            unvalidated
+        )));
+    }
+    if !solutions.is_empty() {
+        messages.push(ValidationMessage::info(format!(
+            "Official solution: {}",
+            describe(solutions)
+        )));
+    }
+    if static_outputs > 0 {
+        messages.push(ValidationMessage::info(format!(
+            "{} static output files",
+            static_outputs
+        )));
+    }
+}
+
+/// Report the solutions found in the task.
+fn check_solutions(task: &Task, messages: &mut Vec<ValidationMessage>) {
+    let graders: HashSet<_> = task.grader_map.all_paths().collect();
+    let solutions = list_files(&task.path, vec!["sol/*"])
+        .into_iter()
+        .filter(|p| !graders.contains(p.as_path()))
+        .filter(|p| match task.task_type {
+            TaskType::Batch | TaskType::Communication(_) => {
+                LanguageManager::detect_language(p).is_some()
+            }
+            TaskType::OutputOnly => p.is_dir(),
+        })
+        .count();
+    if solutions == 0 {
+        messages.push(ValidationMessage::warning("No solution found in sol/"));
+    } else {
+        messages.push(ValidationMessage::info(format!(
+            "{} solutions found",
+            solutions
+        )));
+    }
+}
+
+/// The path relative to the task directory, if it's inside it.
+fn relative_path<'a>(task: &Task, path: &'a Path) -> &'a Path {
+    path.strip_prefix(&task.path).unwrap_or(path)
+}
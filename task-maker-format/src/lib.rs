@@ -19,12 +19,15 @@ extern crate approx;
 
 use crate::ui::UI;
 
+pub mod diagnostics;
 pub mod ioi;
 mod source_file;
 pub mod ui;
+pub mod validation;
 pub use source_file::SourceFile;
 
 use failure::Error;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use task_maker_dag::ExecutionDAG;
@@ -33,8 +36,8 @@ use task_maker_lang::{GraderMap, LanguageManager};
 /// Trait that defines the capabilities of a task format, providing a UI and the parsing and
 /// execution abilities.
 pub trait TaskFormat {
-    /// Get an appropriate `UI` for this task.
-    fn ui(&self, ui_type: ui::UIType) -> Result<Box<dyn UI>, Error>;
+    /// Get an appropriate `UI` for this task, configured with the UI options of `config`.
+    fn ui(&self, ui_type: ui::UIType, config: &EvaluationConfig) -> Result<Box<dyn UI>, Error>;
 
     /// Execute the evaluation of this task by adding the executions to the provided DAG.
     fn execute(&self, eval: &mut EvaluationData, config: &EvaluationConfig) -> Result<(), Error>;
@@ -45,6 +48,10 @@ pub trait TaskFormat {
 
     /// Clean the task folder removing the files that can be generated automatically.
     fn clean(&self) -> Result<(), Error>;
+
+    /// Check the configuration of the task before the evaluation, reporting a summary of it and
+    /// the misconfigurations that can be detected.
+    fn validate(&self) -> Vec<validation::ValidationMessage>;
 }
 
 /// Configuration of the evaluation of a task.
@@ -58,6 +65,63 @@ pub struct EvaluationConfig {
     /// Execute only the solution with the specified paths, that can reside anywhere in the
     /// filesystem.
     pub solution_paths: Vec<PathBuf>,
+    /// Capture a preview of at most this many bytes of the input and output files of each
+    /// testcase.
+    pub preview_size: Option<usize>,
+    /// The phases of the evaluation to run.
+    pub phase: EvaluationPhase,
+    /// Run the custom checker twice on each output, warning if the outcomes differ.
+    pub verify_checker: bool,
+    /// If the custom checker fails to compile compare the outputs with white diff, warning that
+    /// the checker is not used.
+    pub checker_fallback: bool,
+    /// The names of the solutions that are all correct: the output of each of them is checked
+    /// against the output of the first one, warning on the testcases where it's not accepted.
+    pub cross_check: Vec<String>,
+    /// The maximum number of executions the evaluation of each solution can add to the DAG, the
+    /// evaluation fails before building the DAG if it's exceeded.
+    pub max_solution_executions: Option<usize>,
+    /// Print at most this many per-testcase messages per second with the print UI, only counting
+    /// the others. If `None` all of them are printed.
+    pub ui_rate_limit: Option<usize>,
+}
+
+/// The phases of the evaluation of a task to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvaluationPhase {
+    /// Generate the testcases and evaluate the solutions on them.
+    #[default]
+    Full,
+    /// Only generate the testcases, without evaluating the solutions.
+    Generation,
+    /// Only evaluate the solutions, using the testcases generated by a previous run. Fails if some
+    /// of them are missing.
+    Evaluation,
+}
+
+impl EvaluationPhase {
+    /// Whether the testcases should be generated.
+    pub fn generates(self) -> bool {
+        self != EvaluationPhase::Evaluation
+    }
+
+    /// Whether the solutions should be evaluated.
+    pub fn evaluates(self) -> bool {
+        self != EvaluationPhase::Generation
+    }
+}
+
+impl std::str::FromStr for EvaluationPhase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EvaluationPhase, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(EvaluationPhase::Full),
+            "generation" => Ok(EvaluationPhase::Generation),
+            "evaluation" => Ok(EvaluationPhase::Evaluation),
+            _ => Err(format!("Unknown phase: {}", s)),
+        }
+    }
 }
 
 /// The data for an evaluation, including the DAG and the UI channel.
@@ -66,6 +130,8 @@ pub struct EvaluationData {
     pub dag: ExecutionDAG,
     /// The sender of the UI.
     pub sender: Arc<Mutex<ui::UIMessageSender>>,
+    /// The names of the languages whose toolchain version has already been sent to the UI.
+    reported_toolchains: HashSet<String>,
 }
 
 impl EvaluationData {
@@ -76,6 +142,7 @@ impl EvaluationData {
             EvaluationData {
                 dag: ExecutionDAG::new(),
                 sender: Arc::new(Mutex::new(sender)),
+                reported_toolchains: HashSet::new(),
             },
             receiver,
         )
@@ -133,6 +200,18 @@ pub(crate) fn find_source_file<
     None
 }
 
+/// Make a `SourceFile` with the first precompiled executable inside the `managers/` directory of
+/// the task with one of the specified names. The managers are used as-is, without compiling them.
+/// Returns `None` if none of them is present.
+pub(crate) fn find_manager<P: AsRef<Path>>(task_dir: P, names: &[&str]) -> Option<SourceFile> {
+    let task_dir = task_dir.as_ref();
+    names
+        .iter()
+        .map(|name| task_dir.join("managers").join(name))
+        .find(|path| path.is_file())
+        .map(|path| SourceFile::new_precompiled(path, task_dir))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -188,4 +267,16 @@ mod tests {
         );
         assert!(source.is_none());
     }
+
+    #[test]
+    fn test_find_manager() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        std::fs::create_dir_all(tmpdir.path().join("managers")).unwrap();
+        std::fs::write(tmpdir.path().join("managers/correttore"), "x").unwrap();
+        let manager = find_manager(tmpdir.path(), &["checker", "correttore"]);
+        assert!(manager.is_some());
+        let manager = manager.unwrap();
+        assert_eq!(manager.path, tmpdir.path().join("managers/correttore"));
+        assert!(find_manager(tmpdir.path(), &["generator"]).is_none());
+    }
 }
@@ -2,13 +2,13 @@ use crate::bind_exec_callbacks;
 use crate::ui::*;
 use crate::EvaluationData;
 use crate::UISender;
-use failure::Error;
 use failure::_core::ops::{Deref, DerefMut};
+use failure::Error;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
 use task_maker_dag::*;
-use task_maker_lang::GraderMap;
+use task_maker_lang::{GraderMap, Language};
 
 const COMPILATION_CONTENT_LENGTH: usize = 10 * 1024;
 
@@ -34,6 +34,17 @@ impl SourceFile {
         })
     }
 
+    /// Make a new `SourceFile` from a precompiled executable. See
+    /// [`task_maker_lang::SourceFile`](../task_maker_lang/struct.SourceFile.html) for the details.
+    pub fn new_precompiled<P: Into<PathBuf>, P2: Into<PathBuf>>(
+        path: P,
+        base_path: P2,
+    ) -> SourceFile {
+        SourceFile {
+            base: task_maker_lang::SourceFile::new_precompiled(path, base_path),
+        }
+    }
+
     /// Prepare an execution of the source file, eventually adding the compilation to the DAG.
     /// The compilation messages are sent to the UI.
     ///
@@ -46,6 +57,7 @@ impl SourceFile {
         description: S,
         args: I,
     ) -> Result<Execution, Error> {
+        report_toolchain_version(eval, self.language())?;
         let (comp, exec) = self.base.execute(
             &mut eval.dag,
             description,
@@ -60,7 +72,7 @@ impl SourceFile {
                 |status, file| UIMessage::Compilation { file, status },
                 path
             )?;
-            if let Some(stdout) = self.base.compilation_stdout() {
+            for stdout in self.base.compilation_stdout() {
                 let path = path.clone();
                 let sender = eval.sender.clone();
                 eval.dag
@@ -72,7 +84,7 @@ impl SourceFile {
                         })
                     });
             }
-            if let Some(stderr) = self.base.compilation_stderr() {
+            for stderr in self.base.compilation_stderr() {
                 let path = path.clone();
                 let sender = eval.sender.clone();
                 eval.dag
@@ -89,6 +101,24 @@ impl SourceFile {
     }
 }
 
+/// Send to the UI the version of the toolchain of the language, if known. The version of each
+/// language is sent only once.
+pub(crate) fn report_toolchain_version(
+    eval: &mut EvaluationData,
+    language: &dyn Language,
+) -> Result<(), Error> {
+    if !eval.reported_toolchains.insert(language.name().to_string()) {
+        return Ok(());
+    }
+    if let Some(version) = language.toolchain_version() {
+        eval.sender.send(UIMessage::ToolchainVersion {
+            language: language.name().to_string(),
+            version,
+        })?;
+    }
+    Ok(())
+}
+
 impl Deref for SourceFile {
     type Target = task_maker_lang::SourceFile;
 
@@ -1,21 +1,350 @@
+use crate::ioi::{EvaluationSummary, UIState};
 use crate::ui::*;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-/// This UI will print to stdout the UI messages as json.
-#[derive(Default)]
-pub struct JsonUI;
+/// This UI will print to stdout the UI messages as json, one per line. For IOI tasks the
+/// explanation of the score of each solution is printed as soon as the solution has been completely
+/// evaluated, and at the end of the evaluation the summary of its final state is printed.
+pub struct JsonUI {
+    /// The state of the IOI task, if the task is an IOI one.
+    state: Option<UIState>,
+    /// The solutions whose score explanation has already been printed.
+    explained: HashSet<PathBuf>,
+    /// Where to print the messages to.
+    stream: Box<dyn Write + Send>,
+}
+
+/// The final state of the evaluation, printed by the JSON UI after all the messages. It's not a
+/// `UIMessage` since the other UIs don't need it: they keep the state themselves. The readers of
+/// the output should try to parse each line as a `JsonSummary` before as a `UIMessage`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonSummary {
+    /// The summary of the evaluation of the IOI task.
+    #[serde(rename = "IOIEvaluationSummary")]
+    pub summary: EvaluationSummary,
+}
 
 impl JsonUI {
     /// Make a new `JsonUI`.
     pub fn new() -> JsonUI {
-        JsonUI {}
+        JsonUI::with_writer(Box::new(std::io::stdout()))
+    }
+
+    /// Make a new `JsonUI` that writes the messages to the specified stream instead of stdout.
+    pub fn with_writer(stream: Box<dyn Write + Send>) -> JsonUI {
+        JsonUI {
+            state: None,
+            explained: HashSet::new(),
+            stream,
+        }
+    }
+
+    /// Print a message to the stream.
+    fn print<T: Serialize>(&mut self, message: &T) {
+        let message = serde_json::to_string(message).expect("Failed to serialize message");
+        writeln!(self.stream, "{}", message).expect("Failed to write message");
+    }
+
+    /// Print the score explanation of the solution, if it has not been printed yet.
+    fn explain(&mut self, solution: &Path) {
+        if self.explained.contains(solution) {
+            return;
+        }
+        let explanation = match &self.state {
+            Some(state) => state.evaluations[solution].explain(&state.task),
+            None => return,
+        };
+        self.explained.insert(solution.to_owned());
+        self.print(&UIMessage::IOIScoreExplanation {
+            solution: solution.to_owned(),
+            explanation,
+        });
+    }
+}
+
+impl Default for JsonUI {
+    fn default() -> JsonUI {
+        JsonUI::new()
     }
 }
 
 impl UI for JsonUI {
     fn on_message(&mut self, message: UIMessage) {
-        let message = serde_json::to_string(&message).expect("Failed to serialize message");
-        println!("{}", message);
+        self.print(&message);
+        if let UIMessage::IOITask { task } = &message {
+            self.state = Some(UIState::new(task));
+        }
+        let solution = match &message {
+            UIMessage::IOITaskScore { solution, .. } => Some(solution.clone()),
+            _ => None,
+        };
+        if let Some(state) = &mut self.state {
+            state.apply(message);
+        }
+        if let (Some(solution), Some(state)) = (solution, &self.state) {
+            if state.evaluations[&solution].is_completed() {
+                self.explain(&solution);
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        let solutions: Vec<PathBuf> = match &self.state {
+            Some(state) => state.evaluations.keys().sorted().cloned().collect(),
+            None => return,
+        };
+        for solution in solutions {
+            self.explain(&solution);
+        }
+        if let Some(state) = &self.state {
+            let summary = state.summary();
+            self.print(&JsonSummary { summary });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ioi::test_utils::make_task;
+    use crate::ioi::WarningSummary;
+    use crate::source_file::report_toolchain_version;
+    use crate::EvaluationData;
+    use std::sync::{Arc, Mutex};
+    use task_maker_lang::{GraderMap, Language};
+
+    /// A writer that keeps the written data, shared with the test.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A line printed by the UI.
+    enum Line {
+        Message(UIMessage),
+        Summary(JsonSummary),
+    }
+
+    impl SharedBuffer {
+        /// The lines written so far.
+        fn lines(&self) -> Vec<Line> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| match serde_json::from_str(line) {
+                    Ok(summary) => Line::Summary(summary),
+                    Err(_) => Line::Message(serde_json::from_str(line).unwrap()),
+                })
+                .collect()
+        }
+
+        /// The messages written so far, one per line.
+        fn messages(&self) -> Vec<UIMessage> {
+            self.lines()
+                .into_iter()
+                .filter_map(|line| match line {
+                    Line::Message(message) => Some(message),
+                    Line::Summary(_) => None,
+                })
+                .collect()
+        }
+    }
+
+    fn score_messages(solution: &str, score: f64) -> Vec<UIMessage> {
+        vec![
+            UIMessage::IOITestcaseScore {
+                subtask: 0,
+                testcase: 0,
+                solution: solution.into(),
+                score,
+                message: "".into(),
+            },
+            UIMessage::IOISubtaskScore {
+                subtask: 0,
+                solution: solution.into(),
+                normalized_score: score,
+                score: score * 100.0,
+                raw_score: score * 100.0,
+            },
+            UIMessage::IOITaskScore {
+                solution: solution.into(),
+                score: score * 100.0,
+                raw_score: score * 100.0,
+            },
+        ]
+    }
+
+    fn explained_solution(message: &UIMessage) -> Option<&Path> {
+        match message {
+            UIMessage::IOIScoreExplanation { solution, .. } => Some(solution),
+            _ => None,
+        }
     }
 
-    fn finish(&mut self) {}
+    #[test]
+    fn test_explanation_emitted_when_solution_completes() {
+        let buffer = SharedBuffer::default();
+        let mut ui = JsonUI::with_writer(Box::new(buffer.clone()));
+        ui.on_message(UIMessage::IOITask { task: make_task(1) });
+        for message in score_messages("fast", 1.0) {
+            ui.on_message(message);
+        }
+        // the fast solution is explained while the slow one is still running
+        let messages = buffer.messages();
+        let explained: Vec<_> = messages.iter().filter_map(explained_solution).collect();
+        assert_eq!(explained, vec![Path::new("fast")]);
+
+        for message in score_messages("slow", 0.0) {
+            ui.on_message(message);
+        }
+        ui.finish();
+        let messages = buffer.messages();
+        let explained: Vec<_> = messages.iter().filter_map(explained_solution).collect();
+        assert_eq!(explained, vec![Path::new("fast"), Path::new("slow")]);
+    }
+
+    #[test]
+    fn test_explanation_of_incomplete_solution_at_finish() {
+        let buffer = SharedBuffer::default();
+        let mut ui = JsonUI::with_writer(Box::new(buffer.clone()));
+        ui.on_message(UIMessage::IOITask { task: make_task(1) });
+        ui.on_message(score_messages("partial", 1.0).remove(0));
+        assert!(buffer
+            .messages()
+            .iter()
+            .all(|m| explained_solution(m).is_none()));
+        ui.finish();
+        let messages = buffer.messages();
+        let explained: Vec<_> = messages.iter().filter_map(explained_solution).collect();
+        assert_eq!(explained, vec![Path::new("partial")]);
+    }
+
+    #[test]
+    fn test_summary_at_finish() {
+        let buffer = SharedBuffer::default();
+        let mut ui = JsonUI::with_writer(Box::new(buffer.clone()));
+        ui.on_message(UIMessage::IOITask { task: make_task(1) });
+        for message in score_messages("sol", 1.0) {
+            ui.on_message(message);
+        }
+        for _ in 0..2 {
+            ui.on_message(UIMessage::Warning {
+                message: "careful".into(),
+            });
+        }
+        ui.on_message(UIMessage::Warning {
+            message: "once".into(),
+        });
+        assert!(!buffer
+            .lines()
+            .iter()
+            .any(|line| matches!(line, Line::Summary(_))));
+        ui.finish();
+        let summary = match buffer.lines().pop() {
+            Some(Line::Summary(JsonSummary { summary })) => summary,
+            _ => panic!("Expecting the summary as the last line"),
+        };
+        assert_eq!(summary.report.task, "task");
+        assert_eq!(summary.report.solutions.len(), 1);
+        assert_eq!(summary.report.solutions[0].explanation.score, 100.0);
+        assert_eq!(
+            summary.warnings,
+            vec![
+                WarningSummary {
+                    message: "careful".into(),
+                    count: 2
+                },
+                WarningSummary {
+                    message: "once".into(),
+                    count: 1
+                }
+            ]
+        );
+        assert!(summary.compilations.is_empty());
+    }
+
+    #[test]
+    fn test_explanation_subtask_name() {
+        let buffer = SharedBuffer::default();
+        let mut ui = JsonUI::with_writer(Box::new(buffer.clone()));
+        let mut task = make_task(1);
+        task.subtasks.get_mut(&0).unwrap().name = Some("N ≤ 1000".into());
+        ui.on_message(UIMessage::IOITask { task });
+        for message in score_messages("sol", 1.0) {
+            ui.on_message(message);
+        }
+        let names: Vec<_> = buffer
+            .messages()
+            .into_iter()
+            .filter_map(|message| match message {
+                UIMessage::IOIScoreExplanation { explanation, .. } => Some(explanation),
+                _ => None,
+            })
+            .flat_map(|explanation| explanation.subtasks)
+            .map(|subtask| subtask.name)
+            .collect();
+        assert_eq!(names, vec![Some("N ≤ 1000".to_string())]);
+    }
+
+    /// A language whose toolchain version is mocked.
+    #[derive(Debug)]
+    struct MockLanguage;
+
+    impl Language for MockLanguage {
+        fn name(&self) -> &'static str {
+            "Mock"
+        }
+
+        fn extensions(&self) -> Vec<&'static str> {
+            vec!["mock"]
+        }
+
+        fn need_compilation(&self) -> bool {
+            false
+        }
+
+        fn toolchain_version(&self) -> Option<String> {
+            Some("mockc 1.2.3".into())
+        }
+    }
+
+    #[test]
+    fn test_toolchain_version() {
+        let (mut eval, receiver) = EvaluationData::new();
+        report_toolchain_version(&mut eval, &MockLanguage).unwrap();
+        // the version of the same language is reported only once
+        report_toolchain_version(&mut eval, &MockLanguage).unwrap();
+        drop(eval);
+
+        let buffer = SharedBuffer::default();
+        let mut ui = JsonUI::with_writer(Box::new(buffer.clone()));
+        for message in receiver.iter() {
+            ui.on_message(message);
+        }
+        ui.finish();
+        let versions: Vec<_> = buffer
+            .messages()
+            .into_iter()
+            .filter_map(|message| match message {
+                UIMessage::ToolchainVersion { language, version } => Some((language, version)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            versions,
+            vec![("Mock".to_string(), "mockc 1.2.3".to_string())]
+        );
+    }
 }
@@ -11,10 +11,10 @@ use std::sync::mpsc::{channel, Receiver, Sender};
 mod json;
 mod raw;
 
-pub use json::JsonUI;
+pub use json::{JsonSummary, JsonUI};
 pub use raw::RawUI;
 use std::time::SystemTime;
-use task_maker_exec::ExecutorStatus;
+use task_maker_exec::{signal_name, ExecutorStatus};
 
 /// Channel type for sending `UIMessage`s.
 pub type UIChannelSender = Sender<UIMessage>;
@@ -120,6 +120,26 @@ pub enum UIMessage {
         content: String,
     },
 
+    /// The preview of the input file of a testcase.
+    IOIInputPreview {
+        /// The id of the subtask.
+        subtask: SubtaskId,
+        /// The id of the testcase.
+        testcase: TestcaseId,
+        /// The first bytes of the input file.
+        preview: FilePreview,
+    },
+
+    /// The preview of the output file of a testcase.
+    IOIOutputPreview {
+        /// The id of the subtask.
+        subtask: SubtaskId,
+        /// The id of the testcase.
+        testcase: TestcaseId,
+        /// The first bytes of the output file.
+        preview: FilePreview,
+    },
+
     /// The solution of a testcase in a IOI task.
     IOISolution {
         /// The id of the subtask.
@@ -175,18 +195,33 @@ pub enum UIMessage {
         subtask: SubtaskId,
         /// The path of the solution.
         solution: PathBuf,
-        /// The normalized score, a value between 0 and 1
+        /// The normalized score, a value between 0 and 1: the score of the subtask divided by its
+        /// maximum score. With the `Sum` aggregator it's the sum of the scores of the testcases
+        /// divided by the number of testcases, with the `Custom` one it's the value computed by
+        /// the aggregator clamped between 0 and 1.
         normalized_score: f64,
-        /// The score of the subtask.
+        /// The score of the subtask, rounded to the precision of the task.
         score: f64,
+        /// The score of the subtask before the rounding.
+        raw_score: f64,
     },
 
     /// The score of a task is ready.
     IOITaskScore {
         /// The path of the solution.
         solution: PathBuf,
-        /// The score of the task.
+        /// The score of the task, rounded to the precision of the task.
         score: f64,
+        /// The score of the task before the rounding.
+        raw_score: f64,
+    },
+
+    /// The breakdown of the score of a solution, sent when the evaluation is completed.
+    IOIScoreExplanation {
+        /// The path of the solution.
+        solution: PathBuf,
+        /// How the score of the solution has been computed.
+        explanation: ScoreExplanation,
     },
 
     /// The compilation of a booklet.
@@ -212,6 +247,36 @@ pub enum UIMessage {
         status: UIExecutionStatus,
     },
 
+    /// The version of the toolchain used by a language has been found.
+    ToolchainVersion {
+        /// The name of the language.
+        language: String,
+        /// The version of the toolchain, usually the first line of `--version`.
+        version: String,
+    },
+
+    /// The critical path of the evaluation has been computed: the longest chain of dependent
+    /// executions, weighted by their wall time.
+    CriticalPath {
+        /// The sum of the wall times of the executions in the critical path, in seconds.
+        length: f64,
+        /// The sum of the wall times of all the executions, in seconds.
+        total_time: f64,
+        /// The descriptions of the executions in the critical path, in the order of execution.
+        executions: Vec<String>,
+    },
+
+    /// The time spent preparing the sandboxes of the executions that actually ran, without the
+    /// cached ones.
+    SandboxOverhead {
+        /// The sum of the setup times of the sandboxes, in seconds.
+        setup_time: f64,
+        /// The sum of the wall times of the executions, in seconds.
+        wall_time: f64,
+        /// The number of executions that ran in a sandbox.
+        executions: usize,
+    },
+
     /// A warning has been emitted.
     Warning {
         /// The message of the warning.
@@ -219,6 +284,15 @@ pub enum UIMessage {
     },
 }
 
+/// The text describing the signal that killed a process, with its symbolic name (if known) and its
+/// description, like `SIGSEGV (Segmentation fault)`.
+pub(crate) fn signal_text(signal: u32, description: &str) -> String {
+    match signal_name(signal) {
+        Some(name) => format!("{} ({})", name, description),
+        None => format!("{} ({})", signal, description),
+    }
+}
+
 /// The sender of the UIMessage
 pub struct UIMessageSender {
     sender: UIChannelSender,
@@ -250,6 +324,8 @@ pub trait UI: Send {
 pub enum UIType {
     /// The `PrintUI`.
     Print,
+    /// The `PrintUI` in quiet mode, printing only the scores and the errors.
+    Silent,
     /// The `RawUI`.
     Raw,
     /// The `CursesUI`.
@@ -264,6 +340,7 @@ impl std::str::FromStr for UIType {
     fn from_str(s: &str) -> Result<UIType, Self::Err> {
         match s.to_ascii_lowercase().as_str() {
             "print" => Ok(UIType::Print),
+            "silent" => Ok(UIType::Silent),
             "raw" => Ok(UIType::Raw),
             "curses" => Ok(UIType::Curses),
             "json" => Ok(UIType::Json),
@@ -331,3 +408,21 @@ macro_rules! cwriteln {
         $self.stream.reset().unwrap();
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_text() {
+        assert_eq!(
+            signal_text(11, "Segmentation fault"),
+            "SIGSEGV (Segmentation fault)"
+        );
+        assert_eq!(
+            signal_text(8, "Floating point exception"),
+            "SIGFPE (Floating point exception)"
+        );
+        assert_eq!(signal_text(1000, "Unknown signal"), "1000 (Unknown signal)");
+    }
+}
@@ -0,0 +1,79 @@
+//! Upfront validation of a task, reporting its health before the evaluation starts.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The severity of a validation message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ValidationLevel {
+    /// The message is informative, for example the number of testcases.
+    Info,
+    /// The task is probably misconfigured, but it can be evaluated anyway.
+    Warning,
+    /// The task is misconfigured and its evaluation is probably meaningless.
+    Error,
+}
+
+/// A message produced by the validation of a task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationMessage {
+    /// The severity of the message.
+    pub level: ValidationLevel,
+    /// The text of the message.
+    pub message: String,
+}
+
+impl ValidationMessage {
+    /// Make a new informative message.
+    pub fn info<S: Into<String>>(message: S) -> ValidationMessage {
+        ValidationMessage {
+            level: ValidationLevel::Info,
+            message: message.into(),
+        }
+    }
+
+    /// Make a new warning message.
+    pub fn warning<S: Into<String>>(message: S) -> ValidationMessage {
+        ValidationMessage {
+            level: ValidationLevel::Warning,
+            message: message.into(),
+        }
+    }
+
+    /// Make a new error message.
+    pub fn error<S: Into<String>>(message: S) -> ValidationMessage {
+        ValidationMessage {
+            level: ValidationLevel::Error,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ValidationLevel {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ValidationLevel::Info => write!(f, "INFO"),
+            ValidationLevel::Warning => write!(f, "WARNING"),
+            ValidationLevel::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+impl Display for ValidationMessage {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.level, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let message = ValidationMessage::warning("The score of the task is 50");
+        assert_eq!(message.to_string(), "[WARNING] The score of the task is 50");
+        assert!(ValidationLevel::Info < ValidationLevel::Warning);
+        assert!(ValidationLevel::Warning < ValidationLevel::Error);
+    }
+}
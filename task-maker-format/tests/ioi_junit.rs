@@ -0,0 +1,159 @@
+use regex::Regex;
+use std::path::PathBuf;
+use task_maker_dag::ExecutionStatus;
+use task_maker_format::ioi::UIState;
+use task_maker_format::ui::{UIExecutionStatus, UIMessage};
+
+mod utils;
+
+/// A testcase of the JUnit XML report.
+#[derive(Debug)]
+struct JUnitTestcase {
+    name: String,
+    classname: String,
+    failure: Option<String>,
+    skipped: bool,
+}
+
+/// Parse the JUnit XML report, returning the name of each suite with its testcases.
+fn parse_junit(xml: &str) -> Vec<(String, Vec<JUnitTestcase>)> {
+    let suite_re = Regex::new(r#"(?s)<testsuite name="([^"]*)"[^>]*>(.*?)</testsuite>"#).unwrap();
+    let testcase_re = Regex::new(
+        r#"(?s)<testcase name="([^"]*)" classname="([^"]*)"[^>]*?(/>|>(.*?)</testcase>)"#,
+    )
+    .unwrap();
+    let failure_re = Regex::new(r#"<failure message="([^"]*)""#).unwrap();
+    suite_re
+        .captures_iter(xml)
+        .map(|suite| {
+            let testcases = testcase_re
+                .captures_iter(&suite[2])
+                .map(|tc| {
+                    let body = tc.get(4).map(|b| b.as_str()).unwrap_or("");
+                    JUnitTestcase {
+                        name: tc[1].to_string(),
+                        classname: tc[2].to_string(),
+                        failure: failure_re.captures(body).map(|f| f[1].to_string()),
+                        skipped: body.contains("<skipped"),
+                    }
+                })
+                .collect();
+            (suite[1].to_string(), testcases)
+        })
+        .collect()
+}
+
+fn evaluate(ui: &mut UIState, solution: &PathBuf, subtask: u32, testcase: u32, score: f64) {
+    ui.apply(UIMessage::IOIEvaluation {
+        subtask,
+        testcase,
+        solution: solution.clone(),
+        status: UIExecutionStatus::Done {
+            result: utils::good_result(),
+        },
+    });
+    ui.apply(UIMessage::IOITestcaseScore {
+        subtask,
+        testcase,
+        solution: solution.clone(),
+        score,
+        message: if score == 0.0 {
+            "Wrong <answer>".into()
+        } else {
+            "".into()
+        },
+    });
+}
+
+#[test]
+fn test_junit_xml() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let good = PathBuf::from("sol/good.cpp");
+    let bad = PathBuf::from("sol/bad.cpp");
+    evaluate(&mut ui, &good, 0, 0, 1.0);
+    evaluate(&mut ui, &good, 1, 1, 1.0);
+    evaluate(&mut ui, &good, 1, 2, 1.0);
+    evaluate(&mut ui, &bad, 0, 0, 0.0);
+    let mut result = utils::bad_result();
+    result.status = ExecutionStatus::TimeLimitExceeded;
+    ui.apply(UIMessage::IOIEvaluation {
+        subtask: 1,
+        testcase: 1,
+        solution: bad,
+        status: UIExecutionStatus::Done { result },
+    });
+
+    let xml = ui.to_junit_xml();
+    assert!(xml.starts_with("<?xml"));
+    assert!(xml.contains(r#"<testsuites name="task" tests="6" failures="2">"#));
+    let suites = parse_junit(&xml);
+    assert_eq!(suites.len(), 2);
+
+    let (name, testcases) = &suites[0];
+    assert_eq!(name, "sol/bad.cpp");
+    assert_eq!(testcases.len(), 3);
+    assert_eq!(testcases[0].name, "Testcase 0");
+    assert_eq!(testcases[0].classname, "sol/bad.cpp.Subtask 0");
+    assert_eq!(
+        testcases[0].failure.as_deref(),
+        Some("Wrong &lt;answer&gt;")
+    );
+    assert_eq!(testcases[1].classname, "sol/bad.cpp.Subtask 1");
+    assert_eq!(testcases[1].failure.as_deref(), Some("Time limit exceeded"));
+    assert_eq!(testcases[2].name, "Testcase 2");
+    assert!(testcases[2].failure.is_none());
+    assert!(testcases[2].skipped);
+
+    let (name, testcases) = &suites[1];
+    assert_eq!(name, "sol/good.cpp");
+    assert_eq!(testcases.len(), 3);
+    assert!(testcases
+        .iter()
+        .all(|tc| tc.failure.is_none() && !tc.skipped));
+}
+
+#[test]
+fn test_junit_xml_skipped() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let sol = PathBuf::from("sol/sol.py");
+    evaluate(&mut ui, &sol, 0, 0, 1.0);
+    ui.apply(UIMessage::IOIEvaluation {
+        subtask: 1,
+        testcase: 1,
+        solution: sol.clone(),
+        status: UIExecutionStatus::Skipped,
+    });
+    evaluate(&mut ui, &sol, 1, 2, 0.5);
+
+    let xml = ui.to_junit_xml();
+    assert!(xml.contains(r#"tests="3" failures="1" skipped="1""#));
+    let suites = parse_junit(&xml);
+    assert_eq!(suites.len(), 1);
+    let testcases = &suites[0].1;
+    assert_eq!(testcases.len(), 3);
+    assert!(!testcases[0].skipped);
+    assert!(testcases[1].skipped);
+    assert!(testcases[1].failure.is_none());
+    assert_eq!(testcases[2].failure.as_deref(), Some("Partially correct"));
+}
+
+#[test]
+fn test_write_junit_xml() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let sol = PathBuf::from("sol/sol.py");
+    evaluate(&mut ui, &sol, 0, 0, 0.0);
+    let path = tmpdir.path().join("junit.xml");
+    ui.write_junit_xml(&path).unwrap();
+    let xml = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(xml, ui.to_junit_xml());
+    let suites = parse_junit(&xml);
+    assert_eq!(suites.len(), 1);
+    assert_eq!(suites[0].1.len(), 3);
+    assert!(suites[0].1[0].failure.is_some());
+    assert!(suites[0].1[1].skipped);
+    assert!(suites[0].1[2].skipped);
+}
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+use task_maker_format::ioi::{EvaluationReport, ReportFormat, UIState};
+use task_maker_format::ui::{UIExecutionStatus, UIMessage};
+
+mod utils;
+
+fn evaluate(ui: &mut UIState, solution: &PathBuf, subtask: u32, testcase: u32, score: f64) {
+    ui.apply(UIMessage::IOIEvaluation {
+        subtask,
+        testcase,
+        solution: solution.clone(),
+        status: UIExecutionStatus::Done {
+            result: utils::good_result(),
+        },
+    });
+    ui.apply(UIMessage::IOITestcaseScore {
+        subtask,
+        testcase,
+        solution: solution.clone(),
+        score,
+        message: if score == 0.0 {
+            "Wrong answer".into()
+        } else {
+            "".into()
+        },
+    });
+}
+
+fn new_ui_state() -> UIState {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let good = PathBuf::from("sol/good.cpp");
+    let bad = PathBuf::from("sol/bad.cpp");
+    evaluate(&mut ui, &good, 0, 0, 1.0);
+    evaluate(&mut ui, &good, 1, 1, 1.0);
+    evaluate(&mut ui, &good, 1, 2, 1.0);
+    evaluate(&mut ui, &bad, 0, 0, 0.0);
+    evaluate(&mut ui, &bad, 1, 1, 0.5);
+    ui
+}
+
+#[test]
+fn test_report() {
+    let ui = new_ui_state();
+    let report = ui.report();
+    assert_eq!(report.task, "task");
+    assert_eq!(report.max_score, ui.max_score);
+    let solutions: Vec<_> = report.solutions.iter().map(|s| &s.solution).collect();
+    assert_eq!(
+        solutions,
+        vec![
+            &PathBuf::from("sol/bad.cpp"),
+            &PathBuf::from("sol/good.cpp")
+        ]
+    );
+    let bad = &report.solutions[0].explanation;
+    assert_eq!(bad.subtasks.len(), 2);
+    assert_eq!(bad.subtasks[0].testcases[0].score, 0.0);
+    assert_eq!(bad.subtasks[1].testcases[0].score, 0.5);
+}
+
+#[test]
+fn test_report_binary_round_trip() {
+    let report = new_ui_state().report();
+    let json = report.encode(ReportFormat::Json).unwrap();
+    let binary = report.encode(ReportFormat::Binary).unwrap();
+    assert!(binary.len() < json.len());
+    let from_json = EvaluationReport::decode(&json, ReportFormat::Json).unwrap();
+    let from_binary = EvaluationReport::decode(&binary, ReportFormat::Binary).unwrap();
+    assert_eq!(from_binary, report);
+    assert_eq!(from_binary, from_json);
+}
+
+#[test]
+fn test_write_report() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let report = new_ui_state().report();
+    for (format, name) in vec![
+        (ReportFormat::Json, "report.json"),
+        (ReportFormat::Binary, "report.bin"),
+    ] {
+        let path = tmpdir.path().join(name);
+        report.write(&path, format).unwrap();
+        assert_eq!(EvaluationReport::read(&path, format).unwrap(), report);
+    }
+    assert!(EvaluationReport::read(tmpdir.path().join("report.bin"), ReportFormat::Json).is_err());
+}
+
+#[test]
+fn test_report_format_from_str() {
+    assert_eq!("json".parse::<ReportFormat>().unwrap(), ReportFormat::Json);
+    assert_eq!(
+        "binary".parse::<ReportFormat>().unwrap(),
+        ReportFormat::Binary
+    );
+    assert_eq!("csv".parse::<ReportFormat>().unwrap(), ReportFormat::Csv);
+    assert!("xml".parse::<ReportFormat>().is_err());
+}
+
+#[test]
+fn test_report_format_from_path() {
+    assert_eq!(
+        ReportFormat::from_path("history/report.json").unwrap(),
+        ReportFormat::Json
+    );
+    assert_eq!(
+        ReportFormat::from_path("report.bin").unwrap(),
+        ReportFormat::Binary
+    );
+    assert_eq!(
+        ReportFormat::from_path("report.csv").unwrap(),
+        ReportFormat::Csv
+    );
+    assert!(ReportFormat::from_path("report.xml").is_err());
+    assert!(ReportFormat::from_path("report").is_err());
+}
+
+#[test]
+fn test_report_csv() {
+    let mut report = new_ui_state().report();
+    report.solutions[0].solution = PathBuf::from("sol/bad,1.cpp");
+    let good = &mut report.solutions[1].explanation;
+    good.score = 100.0;
+    good.subtasks[0].score = 10.0;
+    good.subtasks[1].score = 90.0;
+    let csv = String::from_utf8(report.encode(ReportFormat::Csv).unwrap()).unwrap();
+    assert_eq!(
+        csv,
+        "solution,score,subtask 0,subtask 1\n\
+         \"sol/bad,1.cpp\",0,0,0\n\
+         sol/good.cpp,100,10,90\n"
+    );
+    assert!(EvaluationReport::decode(csv.as_bytes(), ReportFormat::Csv).is_err());
+}
+
+#[test]
+fn test_write_report_by_extension() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let ui = new_ui_state();
+    let json = tmpdir.path().join("report.json");
+    ui.write_report(&json).unwrap();
+    assert_eq!(
+        EvaluationReport::read(&json, ReportFormat::Json).unwrap(),
+        ui.report()
+    );
+    let csv = tmpdir.path().join("report.csv");
+    ui.write_report(&csv).unwrap();
+    assert!(std::fs::read_to_string(&csv)
+        .unwrap()
+        .starts_with("solution,score,"));
+    assert!(ui.write_report(tmpdir.path().join("report.txt")).is_err());
+}
@@ -82,3 +82,34 @@ fn test_ioi_task_clean_checker() {
     assert!(!check.join("checker").exists());
     assert!(!cor.join("correttore").exists());
 }
+
+#[test]
+fn test_ioi_task_clean_ignore_list() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let task = utils::new_task_with_context(tmpdir.path());
+    let input = tmpdir.path().join("input");
+    let output = tmpdir.path().join("output");
+    let bin = tmpdir.path().join("bin");
+    std::fs::create_dir(&input).unwrap();
+    std::fs::create_dir(&output).unwrap();
+    std::fs::create_dir(&bin).unwrap();
+    for i in 0..3 {
+        std::fs::write(input.join(format!("input{}.txt", i)), "x").unwrap();
+        std::fs::write(output.join(format!("output{}.txt", i)), "x").unwrap();
+    }
+    std::fs::write(bin.join("foo"), "x").unwrap();
+    std::fs::write(bin.join("bar"), "x").unwrap();
+    std::fs::write(
+        tmpdir.path().join(".tmignore"),
+        "# kept on purpose\ninput/input1.txt\nbin/bar\n",
+    )
+    .unwrap();
+
+    task.clean().unwrap();
+    assert!(input.join("input1.txt").exists());
+    assert!(!input.join("input0.txt").exists());
+    assert!(!input.join("input2.txt").exists());
+    assert!(!output.exists());
+    assert!(bin.join("bar").exists());
+    assert!(!bin.join("foo").exists());
+}
@@ -1,10 +1,16 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use task_maker_dag::ExecutionCommand;
+use task_maker_exec::Sandbox;
 use task_maker_format::ioi::{
-    Booklet, BookletConfig, InputGenerator, InputValidator, OutputGenerator, Statement,
-    StatementConfig,
+    Booklet, BookletConfig, Checker, InputGenerator, InputValidator, OutputGenerator, Statement,
+    StatementConfig, Tag, TaskType, TestcaseEvaluationStatus, UIState,
+};
+use task_maker_format::ui::UIMessage;
+use task_maker_format::{
+    EvaluationConfig, EvaluationData, EvaluationPhase, SourceFile, TaskFormat,
 };
-use task_maker_format::{EvaluationConfig, EvaluationData, SourceFile, TaskFormat};
 
 mod utils;
 
@@ -122,3 +128,305 @@ fn test_ioi_task_execute_booklet() {
         .unwrap();
     assert_eq!(eval.dag.data.executions.len(), 1); // latexmk
 }
+
+#[test]
+fn test_ioi_task_execute_generation_only() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let task = utils::new_task_with_context(tmpdir.path());
+
+    std::fs::create_dir(tmpdir.path().join("sol")).unwrap();
+    std::fs::write(tmpdir.path().join("sol").join("sol.py"), "foo").unwrap();
+
+    let (mut eval, _receiver) = EvaluationData::new();
+    let config = EvaluationConfig {
+        phase: EvaluationPhase::Generation,
+        ..Default::default()
+    };
+    task.execute(&mut eval, &config).unwrap();
+    assert_eq!(eval.dag.data.provided_files.len(), 6); // io only
+    assert_eq!(eval.dag.data.executions.len(), 0);
+}
+
+#[test]
+fn test_ioi_task_execute_evaluation_only() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let mut task = utils::new_task_with_context(tmpdir.path());
+
+    std::fs::write(tmpdir.path().join("gen.py"), "x").unwrap();
+    let source = SourceFile::new(tmpdir.path().join("gen.py"), "", None, None::<PathBuf>).unwrap();
+    let gen = InputGenerator::Custom(Arc::new(source), vec![]);
+    task.subtasks
+        .get_mut(&0)
+        .unwrap()
+        .testcases
+        .get_mut(&0)
+        .unwrap()
+        .input_generator = gen;
+    // the files generated by a previous run
+    for dir in &["input", "output"] {
+        std::fs::create_dir(tmpdir.path().join(dir)).unwrap();
+        for testcase in 0..3 {
+            let path = tmpdir
+                .path()
+                .join(dir)
+                .join(format!("{}{}.txt", dir, testcase));
+            std::fs::write(path, "x").unwrap();
+        }
+    }
+    std::fs::create_dir(tmpdir.path().join("sol")).unwrap();
+    std::fs::write(tmpdir.path().join("sol").join("sol.py"), "foo").unwrap();
+
+    let (mut eval, _receiver) = EvaluationData::new();
+    let config = EvaluationConfig {
+        phase: EvaluationPhase::Evaluation,
+        ..Default::default()
+    };
+    task.execute(&mut eval, &config).unwrap();
+    assert_eq!(eval.dag.data.provided_files.len(), 6 + 1); // io + sol/sol.py
+    assert_eq!(eval.dag.data.executions.len(), 3 + 3); // eval + checker, no generator
+}
+
+#[test]
+fn test_ioi_task_execute_evaluation_only_not_generated() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let task = utils::new_task_with_context(tmpdir.path());
+
+    let (mut eval, _receiver) = EvaluationData::new();
+    let config = EvaluationConfig {
+        phase: EvaluationPhase::Evaluation,
+        ..Default::default()
+    };
+    let err = task.execute(&mut eval, &config).unwrap_err();
+    assert!(
+        err.to_string().contains("has not been generated"),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_ioi_task_execute_cross_check() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let task = utils::new_task_with_context(tmpdir.path());
+    std::fs::create_dir(tmpdir.path().join("sol")).unwrap();
+    for sol in &["sol1.py", "sol2.py", "sol3.py"] {
+        std::fs::write(tmpdir.path().join("sol").join(sol), "foo").unwrap();
+    }
+
+    let (mut eval, receiver) = EvaluationData::new();
+    let config = EvaluationConfig {
+        cross_check: vec!["sol1.py".into(), "sol/sol3.py".into(), "missing.py".into()],
+        ..Default::default()
+    };
+    task.execute(&mut eval, &config).unwrap();
+    // 3 testcases: 3 evaluations, 3 checkers and 1 cross-check each
+    assert_eq!(eval.dag.data.executions.len(), 3 * (3 + 3 + 1));
+    let cross_checks = eval
+        .dag
+        .data
+        .executions
+        .values()
+        .filter(|exec| exec.description.starts_with("Cross-checking"))
+        .collect::<Vec<_>>();
+    assert_eq!(cross_checks.len(), 3);
+    assert!(cross_checks
+        .iter()
+        .all(|exec| exec.description.contains("\"sol3.py\" against \"sol1.py\"")));
+
+    drop(eval);
+    let warnings: Vec<_> = receiver
+        .into_iter()
+        .filter_map(|message| match message {
+            UIMessage::Warning { message } => Some(message),
+            _ => None,
+        })
+        .collect();
+    assert!(
+        warnings.contains(&"The solution missing.py to cross-check is not evaluated".to_string()),
+        "Unexpected warnings: {:?}",
+        warnings
+    );
+}
+
+#[test]
+fn test_ioi_task_execute_max_solution_executions() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let task = utils::new_task_with_context(tmpdir.path());
+    std::fs::create_dir(tmpdir.path().join("sol")).unwrap();
+    std::fs::write(tmpdir.path().join("sol").join("sol.py"), "foo").unwrap();
+    // 3 testcases, each with an evaluation and a checker
+    assert_eq!(
+        task.executions_per_solution(&EvaluationConfig::default()),
+        6
+    );
+
+    let (mut eval, _receiver) = EvaluationData::new();
+    let config = EvaluationConfig {
+        max_solution_executions: Some(5),
+        ..Default::default()
+    };
+    let err = task.execute(&mut eval, &config).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("6 executions, more than the limit of 5"),
+        "Unexpected error: {}",
+        err
+    );
+    assert_eq!(eval.dag.data.executions.len(), 0);
+
+    let (mut eval, _receiver) = EvaluationData::new();
+    let config = EvaluationConfig {
+        max_solution_executions: Some(6),
+        ..Default::default()
+    };
+    task.execute(&mut eval, &config).unwrap();
+    assert_eq!(eval.dag.data.executions.len(), 6);
+}
+
+#[test]
+fn test_ioi_task_execute_checker_fallback() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let mut task = utils::new_task_with_context(tmpdir.path());
+    std::fs::write(tmpdir.path().join("check.py"), "x").unwrap();
+    let source =
+        SourceFile::new(tmpdir.path().join("check.py"), "", None, None::<PathBuf>).unwrap();
+    task.checker = Checker::Custom(Arc::new(source));
+    // 3 testcases, each with an evaluation, the checker and its white diff fallback
+    let config = EvaluationConfig {
+        checker_fallback: true,
+        ..Default::default()
+    };
+    assert_eq!(task.executions_per_solution(&config), 9);
+
+    task.verify_checker = true;
+    task.checker_fallback = true;
+    let (mut eval, _receiver) = EvaluationData::new();
+    let err = task.execute(&mut eval, &config).unwrap_err();
+    assert!(
+        err.to_string().contains("both verified and replaced"),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_ioi_task_execute_readable_dirs() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let mut task = utils::new_task_with_context(tmpdir.path());
+    let tables = tmpdir.path().join("tables");
+    std::fs::create_dir(&tables).unwrap();
+    task.readable_dirs = vec![tables.clone()];
+    std::fs::write(tmpdir.path().join("gen.py"), "x").unwrap();
+    let source = SourceFile::new(tmpdir.path().join("gen.py"), "", None, None::<PathBuf>).unwrap();
+    task.subtasks
+        .get_mut(&0)
+        .unwrap()
+        .testcases
+        .get_mut(&0)
+        .unwrap()
+        .input_generator = InputGenerator::Custom(Arc::new(source), vec![]);
+    std::fs::create_dir(tmpdir.path().join("sol")).unwrap();
+    std::fs::write(tmpdir.path().join("sol").join("sol.py"), "foo").unwrap();
+
+    let (mut eval, _receiver) = EvaluationData::new();
+    task.execute(&mut eval, &EvaluationConfig::default())
+        .unwrap();
+    // 1 generation, 3 evaluations and 3 checkers
+    assert_eq!(eval.dag.data.executions.len(), 7);
+    for exec in eval.dag.data.executions.values() {
+        let readable = exec.limits.extra_readable_dirs.contains(&tables);
+        if exec.description.starts_with("Generation") {
+            assert!(
+                !readable,
+                "{} can read the task directories",
+                exec.description
+            );
+        } else {
+            assert!(
+                readable,
+                "{} cannot read the task directories",
+                exec.description
+            );
+        }
+    }
+
+    // the directories reach the command line of the sandbox, only the limits of the execution
+    // matter here so its files are not needed
+    let mut exec = eval
+        .dag
+        .data
+        .executions
+        .values()
+        .find(|exec| exec.description.starts_with("Evaluation"))
+        .unwrap()
+        .clone();
+    exec.command = ExecutionCommand::local("sol");
+    exec.stdin = None;
+    exec.inputs.clear();
+    let sandbox = Sandbox::new(&tmpdir.path().join("sandboxes"), &exec, &HashMap::new()).unwrap();
+    let reproducer = tmpdir.path().join("reproducer");
+    sandbox.dump_reproducer(&reproducer).unwrap();
+    let command = std::fs::read_to_string(reproducer.join("command.txt")).unwrap();
+    assert!(
+        command.contains(&format!("\"--readable-dir\" {:?}", tables)),
+        "{}",
+        command
+    );
+}
+
+#[test]
+fn test_ioi_task_execute_output_only() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let mut task = utils::new_task_with_context(tmpdir.path());
+    task.task_type = TaskType::OutputOnly;
+    // only the output of testcase 0 is submitted
+    let outputs = tmpdir.path().join("sol").join("outputs");
+    std::fs::create_dir_all(&outputs).unwrap();
+    std::fs::write(outputs.join("output_000.txt"), "xxx").unwrap();
+
+    let (mut eval, receiver) = EvaluationData::new();
+    task.execute(&mut eval, &EvaluationConfig::default())
+        .unwrap();
+    // nothing is solved, only the submitted output is checked
+    assert_eq!(eval.dag.data.executions.len(), 1);
+    let checker = eval.dag.data.executions.values().next().unwrap().clone();
+    assert_eq!(checker.tag, Some(Tag::Checking.into()));
+
+    let callbacks = eval.dag.execution_callbacks.remove(&checker.uuid).unwrap();
+    for cb in callbacks.on_start {
+        cb.call(Default::default()).unwrap();
+    }
+    for cb in callbacks.on_done {
+        cb.call(utils::good_result()).unwrap();
+    }
+    drop(eval);
+
+    let mut ui = UIState::new(&task);
+    let mut checked = vec![];
+    let mut scored = vec![];
+    for message in receiver.try_iter() {
+        match &message {
+            UIMessage::IOIEvaluation { .. } => panic!("Output-only tasks have no solving stage"),
+            UIMessage::IOIChecker { testcase, .. } => checked.push(*testcase),
+            UIMessage::IOITestcaseScore { testcase, .. } => scored.push(*testcase),
+            _ => {}
+        }
+        ui.apply(message);
+    }
+    scored.sort();
+    assert_eq!(checked, vec![0, 0, 0]); // pending, started and done
+    assert_eq!(scored, vec![0, 1, 2]);
+
+    let testcase = |st, tc| ui.evaluations[&outputs].subtasks[&st].testcases[&tc].clone();
+    assert_eq!(
+        testcase(0, 0).status,
+        TestcaseEvaluationStatus::Accepted("Output is correct".into())
+    );
+    assert!(testcase(0, 0).result.is_none());
+    assert_eq!(
+        testcase(1, 1).status,
+        TestcaseEvaluationStatus::WrongAnswer("Missing output file output_001.txt".into())
+    );
+    assert!(testcase(1, 1).checker.is_none());
+    assert_eq!(ui.evaluations[&outputs].score, Some(10.0));
+}
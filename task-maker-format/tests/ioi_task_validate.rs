@@ -0,0 +1,52 @@
+use task_maker_format::validation::{ValidationLevel, ValidationMessage};
+
+mod utils;
+
+/// The messages with at least the warning level.
+fn problems(messages: &[ValidationMessage]) -> Vec<&ValidationMessage> {
+    messages
+        .iter()
+        .filter(|m| m.level >= ValidationLevel::Warning)
+        .collect()
+}
+
+#[test]
+fn test_ioi_task_validate_healthy() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let mut task = utils::new_task_with_context(tmpdir.path());
+    task.time_limit = Some(1.0);
+    task.memory_limit = Some(256);
+    std::fs::create_dir(tmpdir.path().join("sol")).unwrap();
+    std::fs::write(tmpdir.path().join("sol/solution.cpp"), "int main() {}").unwrap();
+
+    let messages = task.validate();
+    assert!(problems(&messages).is_empty(), "{:?}", messages);
+    assert!(messages.contains(&ValidationMessage::info("2 subtasks, 3 testcases")));
+    assert!(messages.contains(&ValidationMessage::info("Checker: white diff")));
+    assert!(messages.contains(&ValidationMessage::info("1 solutions found")));
+}
+
+#[test]
+fn test_ioi_task_validate_misconfigured() {
+    let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+    let mut task = utils::new_task_with_context(tmpdir.path());
+    task.time_limit = Some(1.0);
+    task.memory_limit = Some(256);
+    task.subtasks.get_mut(&1).unwrap().max_score = 40.0;
+    // the checker directory is present, but without a checker inside
+    std::fs::create_dir(tmpdir.path().join("check")).unwrap();
+
+    let messages = task.validate();
+    let problems = problems(&messages);
+    assert_eq!(problems.len(), 3, "{:?}", messages);
+    assert_eq!(
+        problems[0],
+        &ValidationMessage::warning("The score of the task is 50 (not 100)")
+    );
+    assert_eq!(problems[1].level, ValidationLevel::Warning);
+    assert!(problems[1].message.contains("check/"), "{:?}", problems[1]);
+    assert_eq!(
+        problems[2],
+        &ValidationMessage::warning("No solution found in sol/")
+    );
+}
@@ -1,8 +1,10 @@
+use approx::assert_abs_diff_eq;
 use std::path::PathBuf;
 use task_maker_dag::ExecutionStatus;
 use task_maker_exec::ExecutorStatus;
 use task_maker_format::ioi::{
-    CompilationStatus, TestcaseEvaluationStatus, TestcaseGenerationStatus, UIState,
+    CompilationStatus, FilePreview, TestcaseEvaluationStatus, TestcaseGenerationStatus,
+    TestcaseScoreAggregator, UIState, VerdictCategory,
 };
 use task_maker_format::ui::{UIExecutionStatus, UIMessage};
 
@@ -16,6 +18,9 @@ fn test_ui_state_server_status() {
         connected_workers: vec![],
         ready_execs: 1,
         waiting_execs: 123,
+        memory_in_use: 0,
+        memory_available: None,
+        peak_running_execs: 4,
     };
     assert_eq!(ui.executor_status, None);
     ui.apply(UIMessage::ServerStatus {
@@ -134,6 +139,34 @@ fn test_ui_state_compilation_stderr() {
     );
 }
 
+#[test]
+fn test_ui_state_compilation_stderr_more_steps() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let file = PathBuf::from("file");
+    let result = utils::bad_result();
+    ui.apply(UIMessage::Compilation {
+        file: file.clone(),
+        status: UIExecutionStatus::Done {
+            result: result.clone(),
+        },
+    });
+    for content in &["unit\n", "link\n"] {
+        ui.apply(UIMessage::CompilationStderr {
+            file: file.clone(),
+            content: content.to_string(),
+        });
+    }
+    assert_eq!(
+        ui.compilations[&file],
+        CompilationStatus::Failed {
+            result,
+            stderr: Some("unit\nlink\n".to_string()),
+            stdout: None
+        }
+    );
+}
+
 #[test]
 fn test_ui_state_generation_skipped() {
     let task = utils::new_task();
@@ -216,6 +249,29 @@ fn test_ui_state_generation_stderr() {
     );
 }
 
+#[test]
+fn test_ui_state_testcase_preview() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    ui.apply(UIMessage::IOIInputPreview {
+        subtask: 0,
+        testcase: 0,
+        preview: FilePreview::new(b"1 2 3\n4 5 6\n", 8),
+    });
+    ui.apply(UIMessage::IOIOutputPreview {
+        subtask: 0,
+        testcase: 0,
+        preview: FilePreview::new(b"6\n", 8),
+    });
+    let testcase = &ui.generations[&0].testcases[&0];
+    let input = testcase.input_preview.as_ref().unwrap();
+    assert_eq!(input.content, "1 2 3\n4 ");
+    assert!(input.truncated);
+    let output = testcase.output_preview.as_ref().unwrap();
+    assert_eq!(output.content, "6\n");
+    assert!(!output.truncated);
+}
+
 #[test]
 fn test_ui_state_validation_skipped() {
     let task = utils::new_task();
@@ -596,6 +652,40 @@ fn test_ui_state_checker_started() {
     );
 }
 
+#[test]
+fn test_ui_state_checker_started_after_failure() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let file = PathBuf::from("file");
+    let mut result = utils::bad_result();
+    result.status = ExecutionStatus::TimeLimitExceeded;
+    ui.apply(UIMessage::IOIEvaluation {
+        subtask: 0,
+        testcase: 0,
+        solution: file.clone(),
+        status: UIExecutionStatus::Done { result },
+    });
+    ui.apply(UIMessage::IOIChecker {
+        subtask: 0,
+        testcase: 0,
+        solution: file.clone(),
+        status: UIExecutionStatus::Started {
+            worker: Default::default(),
+        },
+    });
+    ui.apply(UIMessage::IOITestcaseScore {
+        subtask: 0,
+        testcase: 0,
+        solution: file.clone(),
+        score: 0.0,
+        message: "Output is correct".to_string(),
+    });
+    assert_eq!(
+        ui.evaluations[&file].subtasks[&0].testcases[&0].status,
+        TestcaseEvaluationStatus::TimeLimitExceeded
+    );
+}
+
 #[test]
 fn test_ui_state_checker_done() {
     let task = utils::new_task();
@@ -616,6 +706,131 @@ fn test_ui_state_checker_done() {
     );
 }
 
+#[test]
+fn test_ui_state_slowest_checkers() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let file = PathBuf::from("file");
+    let mut slow = utils::good_result();
+    slow.resources.cpu_time = 4.2;
+    let slow_resources = slow.resources.clone();
+    for (subtask, testcase, result) in vec![
+        (0, 0, utils::good_result()),
+        (1, 1, slow),
+        (1, 2, utils::good_result()),
+    ] {
+        ui.apply(UIMessage::IOIChecker {
+            subtask,
+            testcase,
+            solution: file.clone(),
+            status: UIExecutionStatus::Done { result },
+        });
+    }
+    let slowest = ui.slowest_checkers(2);
+    assert_eq!(slowest.len(), 2);
+    assert_eq!(slowest[0].solution, file);
+    assert_eq!(slowest[0].subtask, 1);
+    assert_eq!(slowest[0].testcase, 1);
+    assert_eq!(slowest[0].resources, slow_resources);
+}
+
+#[test]
+fn test_ui_state_resource_peaks() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let file = PathBuf::from("file");
+    let mut slow = utils::good_result();
+    slow.resources.cpu_time = 4.2;
+    slow.resources.memory = 100;
+    let mut big = utils::good_result();
+    big.resources.cpu_time = 0.1;
+    big.resources.memory = 1234;
+    for (subtask, testcase, result) in vec![(0, 0, big), (1, 1, slow)] {
+        ui.apply(UIMessage::IOIEvaluation {
+            subtask,
+            testcase,
+            solution: file.clone(),
+            status: UIExecutionStatus::Done { result },
+        });
+    }
+    // the testcase not executed yet is ignored
+    ui.apply(UIMessage::IOIEvaluation {
+        subtask: 1,
+        testcase: 2,
+        solution: file.clone(),
+        status: UIExecutionStatus::Started {
+            worker: Default::default(),
+        },
+    });
+    let peaks = ui.evaluations[&file].resource_peaks().unwrap();
+    assert_eq!(peaks.memory, 1234);
+    assert_eq!(peaks.slowest_subtask, 1);
+    assert_eq!(peaks.slowest_testcase, 1);
+    assert_abs_diff_eq!(peaks.slowest_time, 4.2);
+}
+
+#[test]
+fn test_ui_state_resource_peaks_not_executed() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let file = PathBuf::from("file");
+    ui.apply(UIMessage::IOITestcaseScore {
+        subtask: 0,
+        testcase: 0,
+        solution: file.clone(),
+        score: 1.0,
+        message: "".into(),
+    });
+    assert!(ui.evaluations[&file].resource_peaks().is_none());
+}
+
+fn scored_ui_state(aggregator: TestcaseScoreAggregator) -> UIState {
+    let mut task = utils::new_task();
+    task.testcase_score_aggregator = aggregator;
+    let mut ui = UIState::new(&task);
+    for &(subtask, testcase, score, message) in &[
+        (0, 0, 1.0, "yep"),
+        (1, 1, 0.5, "almost"),
+        (1, 2, 0.0, "nope"),
+    ] {
+        ui.apply(UIMessage::IOITestcaseScore {
+            subtask,
+            testcase,
+            solution: PathBuf::from("file"),
+            score,
+            message: message.to_string(),
+        });
+    }
+    ui
+}
+
+#[test]
+fn test_ui_state_explain_min() {
+    let ui = scored_ui_state(TestcaseScoreAggregator::Min);
+    let explanation = ui.evaluations[&PathBuf::from("file")].explain(&ui.task);
+    assert_eq!(explanation.subtasks.len(), 2);
+    assert_eq!(explanation.subtasks[0].subtask, 0);
+    assert_eq!(explanation.subtasks[0].limiting_testcase, None);
+    assert_eq!(explanation.subtasks[1].subtask, 1);
+    assert_eq!(explanation.subtasks[1].limiting_testcase, Some(2));
+    let verdicts: Vec<_> = explanation.subtasks[1]
+        .testcases
+        .iter()
+        .map(|tc| (tc.testcase, tc.verdict.as_str()))
+        .collect();
+    assert_eq!(verdicts, vec![(1, "almost"), (2, "nope")]);
+}
+
+#[test]
+fn test_ui_state_explain_sum() {
+    let ui = scored_ui_state(TestcaseScoreAggregator::Sum);
+    let explanation = ui.evaluations[&PathBuf::from("file")].explain(&ui.task);
+    assert!(explanation
+        .subtasks
+        .iter()
+        .all(|st| st.limiting_testcase.is_none()));
+}
+
 #[test]
 fn test_ui_state_testcase_score_wrong_answer() {
     let task = utils::new_task();
@@ -691,6 +906,7 @@ fn test_ui_state_subtask_score() {
         subtask: 0,
         solution: file.clone(),
         score: 10.0,
+        raw_score: 10.0,
         normalized_score: 1.0,
     });
     assert_eq!(ui.evaluations[&file].subtasks[&0].score, Some(10.0));
@@ -704,10 +920,63 @@ fn test_ui_state_task_score() {
     ui.apply(UIMessage::IOITaskScore {
         solution: file.clone(),
         score: 10.0,
+        raw_score: 10.0,
     });
     assert_eq!(ui.evaluations[&file].score, Some(10.0));
 }
 
+#[test]
+fn test_ui_state_verdict_groups() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    let score = |ui: &mut UIState, name: &str, score: f64| {
+        ui.apply(UIMessage::IOITaskScore {
+            solution: PathBuf::from(name),
+            score,
+            raw_score: score,
+        });
+    };
+    score(&mut ui, "sol2", 100.0);
+    score(&mut ui, "sol1", 100.0);
+    score(&mut ui, "partial", 50.0);
+    score(&mut ui, "wrong", 0.0);
+    score(&mut ui, "skipped", 100.0);
+    ui.apply(UIMessage::IOIEvaluation {
+        subtask: 0,
+        testcase: 0,
+        solution: PathBuf::from("skipped"),
+        status: UIExecutionStatus::Skipped,
+    });
+    ui.apply(UIMessage::IOIEvaluation {
+        subtask: 0,
+        testcase: 0,
+        solution: PathBuf::from("unscored"),
+        status: UIExecutionStatus::Started {
+            worker: Default::default(),
+        },
+    });
+    ui.apply(UIMessage::Compilation {
+        file: PathBuf::from("uncompiled"),
+        status: UIExecutionStatus::Skipped,
+    });
+    score(&mut ui, "uncompiled", 0.0);
+
+    let groups = ui.verdict_groups();
+    let names = |category| {
+        groups[&category]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(VerdictCategory::Correct), vec!["sol1", "sol2"]);
+    assert_eq!(names(VerdictCategory::Partial), vec!["partial"]);
+    assert_eq!(names(VerdictCategory::Wrong), vec!["wrong"]);
+    assert_eq!(
+        names(VerdictCategory::Error),
+        vec!["skipped", "uncompiled", "unscored"]
+    );
+}
+
 #[test]
 fn test_ui_state_booklet() {
     let task = utils::new_task();
@@ -755,3 +1024,61 @@ fn test_ui_state_warning() {
     assert_eq!(ui.warnings.len(), 1);
     assert_eq!(ui.warnings[0], "test");
 }
+
+#[test]
+fn test_ui_state_repeated_warnings() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    for message in &["repeated", "other", "repeated"] {
+        ui.apply(UIMessage::Warning {
+            message: message.to_string(),
+        });
+    }
+    for _ in 0..10 {
+        ui.apply(UIMessage::Warning {
+            message: "repeated".to_string(),
+        });
+    }
+    assert_eq!(ui.warnings, vec!["repeated", "other"]);
+    assert_eq!(ui.warning_counts["repeated"], 12);
+    assert_eq!(ui.warning_counts["other"], 1);
+    assert_eq!(ui.warnings_summary(), vec!["repeated (x12)", "other"]);
+}
+
+#[test]
+fn test_ui_state_critical_path() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    assert!(ui.critical_path.is_none());
+    ui.apply(UIMessage::CriticalPath {
+        length: 3.5,
+        total_time: 10.0,
+        executions: vec![
+            "Generation of input 0".into(),
+            "Evaluation of sol.cpp".into(),
+        ],
+    });
+    let critical_path = ui.critical_path.unwrap();
+    assert_abs_diff_eq!(critical_path.length, 3.5);
+    assert_abs_diff_eq!(critical_path.total_time, 10.0);
+    assert_eq!(
+        critical_path.executions,
+        vec!["Generation of input 0", "Evaluation of sol.cpp"]
+    );
+}
+
+#[test]
+fn test_ui_state_sandbox_overhead() {
+    let task = utils::new_task();
+    let mut ui = UIState::new(&task);
+    assert!(ui.sandbox_overhead.is_none());
+    ui.apply(UIMessage::SandboxOverhead {
+        setup_time: 0.5,
+        wall_time: 4.5,
+        executions: 12,
+    });
+    let overhead = ui.sandbox_overhead.unwrap();
+    assert_abs_diff_eq!(overhead.setup_time, 0.5);
+    assert_abs_diff_eq!(overhead.wall_time, 4.5);
+    assert_eq!(overhead.executions, 12);
+}
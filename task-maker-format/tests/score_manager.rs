@@ -1,3 +1,4 @@
+use approx::assert_abs_diff_eq;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use task_maker_format::ioi::*;
@@ -42,6 +43,7 @@ fn test_score_manager() {
                 solution,
                 score,
                 normalized_score,
+                ..
             } => {
                 assert_eq!(subtask, 0);
                 assert_eq!(solution, PathBuf::from("sol"));
@@ -110,6 +112,7 @@ fn test_score_manager() {
                 solution,
                 score,
                 normalized_score,
+                ..
             } => {
                 assert_eq!(subtask, 1);
                 assert_eq!(solution, PathBuf::from("sol"));
@@ -123,7 +126,9 @@ fn test_score_manager() {
     }
     if let Ok(mex) = receiver.try_recv() {
         match mex {
-            UIMessage::IOITaskScore { solution, score } => {
+            UIMessage::IOITaskScore {
+                solution, score, ..
+            } => {
                 assert_eq!(solution, PathBuf::from("sol"));
                 assert_eq!(score, 10.0);
             }
@@ -134,3 +139,191 @@ fn test_score_manager() {
     }
     assert!(receiver.try_recv().is_err());
 }
+
+#[test]
+fn test_score_manager_rounding() {
+    let mut task = utils::new_task();
+    task.testcase_score_aggregator = TestcaseScoreAggregator::Sum;
+    let mut manager = ScoreManager::new(&task);
+    let (sender, receiver) = UIMessageSender::new();
+    let sender = Arc::new(Mutex::new(sender));
+
+    manager
+        .score(0, 0, 1.0, "foo".into(), sender.clone(), "sol".into())
+        .unwrap();
+    manager
+        .score(1, 1, 0.7, "foo".into(), sender.clone(), "sol".into())
+        .unwrap();
+    manager
+        .score(1, 2, 0.7, "foo".into(), sender.clone(), "sol".into())
+        .unwrap();
+    let messages: Vec<_> = receiver.try_iter().collect();
+    let subtask_score = messages.iter().find_map(|mex| match mex {
+        UIMessage::IOISubtaskScore {
+            subtask: 1,
+            score,
+            raw_score,
+            ..
+        } => Some((*score, *raw_score)),
+        _ => None,
+    });
+    let (score, raw_score) = subtask_score.expect("Missing IOISubtaskScore");
+    assert_eq!(score, 63.0);
+    assert_ne!(raw_score, 63.0);
+    assert!((raw_score - 63.0).abs() < 1e-9);
+    let task_score = messages.iter().find_map(|mex| match mex {
+        UIMessage::IOITaskScore { score, .. } => Some(*score),
+        _ => None,
+    });
+    assert_eq!(task_score, Some(73.0));
+}
+
+#[test]
+fn test_score_manager_sum_normalized_score() {
+    let mut task = utils::new_task();
+    task.testcase_score_aggregator = TestcaseScoreAggregator::Sum;
+    let subtask = task.subtasks.get_mut(&0).unwrap();
+    let testcase = subtask.testcases[&0].clone();
+    for id in 1..5 {
+        subtask.testcases.insert(
+            id,
+            TestcaseInfo {
+                id,
+                ..testcase.clone()
+            },
+        );
+    }
+    let mut manager = ScoreManager::new(&task);
+    let (sender, receiver) = UIMessageSender::new();
+    let sender = Arc::new(Mutex::new(sender));
+
+    // 3 of the 5 testcases are correct
+    for (testcase, score) in [1.0, 0.0, 1.0, 0.0, 1.0].iter().enumerate() {
+        manager
+            .score(
+                0,
+                testcase as u32,
+                *score,
+                "".into(),
+                sender.clone(),
+                "sol".into(),
+            )
+            .unwrap();
+    }
+    let subtask_score = receiver
+        .try_iter()
+        .find_map(|mex| match mex {
+            UIMessage::IOISubtaskScore {
+                subtask,
+                score,
+                normalized_score,
+                ..
+            } => Some((subtask, score, normalized_score)),
+            _ => None,
+        })
+        .expect("Expecting UIMessage::IOISubtaskScore");
+    assert_eq!(subtask_score.0, 0);
+    assert_abs_diff_eq!(subtask_score.1, 6.0);
+    assert_abs_diff_eq!(subtask_score.2, 0.6);
+}
+
+/// Score all the testcases of the task of `utils::new_task` returning the task score.
+fn task_score(task: &Task, scores: &[f64]) -> Option<f64> {
+    let mut manager = ScoreManager::new(&task);
+    let (sender, receiver) = UIMessageSender::new();
+    let sender = Arc::new(Mutex::new(sender));
+    for (testcase, score) in scores.iter().enumerate() {
+        let subtask = if testcase == 0 { 0 } else { 1 };
+        manager
+            .score(
+                subtask,
+                testcase as TestcaseId,
+                *score,
+                "foo".into(),
+                sender.clone(),
+                "sol".into(),
+            )
+            .unwrap();
+    }
+    let messages: Vec<_> = receiver.try_iter().collect();
+    messages.iter().find_map(|mex| match mex {
+        UIMessage::IOITaskScore { score, .. } => Some(*score),
+        _ => None,
+    })
+}
+
+#[test]
+fn test_score_manager_task_score_max() {
+    let mut task = utils::new_task();
+    task.task_score_aggregator = TaskScoreAggregator::Max;
+    assert_abs_diff_eq!(task.max_score(), 90.0);
+    // subtask 0 is worth 10 points, subtask 1 is worth 90
+    assert_eq!(task_score(&task, &[1.0, 0.0, 1.0]), Some(10.0));
+    assert_eq!(task_score(&task, &[1.0, 1.0, 1.0]), Some(90.0));
+}
+
+#[test]
+fn test_score_manager_task_score_custom() {
+    let mut task = utils::new_task();
+    // only the last subtask counts
+    task.task_score_aggregator =
+        TaskScoreAggregator::Custom(CustomScoreAggregator::new(|scores| {
+            scores[scores.len() - 1]
+        }));
+    assert_abs_diff_eq!(task.max_score(), 90.0);
+    assert_eq!(task_score(&task, &[1.0, 0.0, 1.0]), Some(0.0));
+    assert_eq!(task_score(&task, &[0.0, 1.0, 1.0]), Some(90.0));
+}
+
+#[test]
+fn test_score_manager_subtask_score_mode_sum() {
+    let mut task = utils::new_task();
+    task.subtasks.get_mut(&1).unwrap().score_mode = Some(TestcaseScoreAggregator::Sum);
+    let mut manager = ScoreManager::new(&task);
+    let (sender, receiver) = UIMessageSender::new();
+    let sender = Arc::new(Mutex::new(sender));
+
+    manager
+        .score(0, 0, 0.5, "foo".into(), sender.clone(), "sol".into())
+        .unwrap();
+    manager
+        .score(1, 1, 1.0, "foo".into(), sender.clone(), "sol".into())
+        .unwrap();
+    manager
+        .score(1, 2, 0.0, "foo".into(), sender.clone(), "sol".into())
+        .unwrap();
+    let messages: Vec<_> = receiver.try_iter().collect();
+    let subtask_score = |id| {
+        messages
+            .iter()
+            .find_map(|mex| match mex {
+                UIMessage::IOISubtaskScore {
+                    subtask,
+                    score,
+                    normalized_score,
+                    ..
+                } if *subtask == id => Some((*score, *normalized_score)),
+                _ => None,
+            })
+            .expect("Expecting UIMessage::IOISubtaskScore")
+    };
+    // subtask 0 still uses the aggregator of the task
+    assert_eq!(subtask_score(0), (5.0, 0.5));
+    assert_eq!(subtask_score(1), (45.0, 0.5));
+    let task_score = messages.iter().find_map(|mex| match mex {
+        UIMessage::IOITaskScore { score, .. } => Some(*score),
+        _ => None,
+    });
+    assert_eq!(task_score, Some(50.0));
+}
+
+#[test]
+fn test_score_manager_subtask_score_mode_custom() {
+    let mut task = utils::new_task();
+    // only the first testcase counts, the result is clamped to 1.0
+    task.subtasks.get_mut(&1).unwrap().score_mode = Some(TestcaseScoreAggregator::Custom(
+        CustomScoreAggregator::new(|scores| scores[0] * 2.0),
+    ));
+    assert_eq!(task_score(&task, &[1.0, 0.25, 0.0]), Some(55.0));
+    assert_eq!(task_score(&task, &[1.0, 0.75, 0.0]), Some(100.0));
+}
@@ -3,7 +3,9 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use task_maker_dag::{ExecutionResourcesUsage, ExecutionResult, ExecutionStatus};
+use task_maker_dag::{
+    ExecutionProvenance, ExecutionResourcesUsage, ExecutionResult, ExecutionStatus,
+};
 use task_maker_format::ioi::*;
 use task_maker_lang::GraderMap;
 
@@ -26,8 +28,14 @@ pub fn new_task_with_context(path: &Path) -> Task {
         infile: None,
         outfile: None,
         subtasks: HashMap::new(),
-        checker: Checker::WhiteDiff,
+        checker: Checker::WhiteDiff(WhiteDiffOptions::default()),
         testcase_score_aggregator: TestcaseScoreAggregator::Min,
+        task_score_aggregator: TaskScoreAggregator::Sum,
+        score_precision: 2,
+        checker_policy: CheckerPolicy::Skip,
+        verify_checker: false,
+        checker_fallback: false,
+        readable_dirs: vec![],
         grader_map: Arc::new(GraderMap::new(Vec::<PathBuf>::new())),
         booklets: vec![],
         difficulty: None,
@@ -35,7 +43,10 @@ pub fn new_task_with_context(path: &Path) -> Task {
     };
     let st0 = task.subtasks.entry(0).or_insert(SubtaskInfo {
         id: 0,
+        name: None,
         max_score: 10.0,
+        score_mode: None,
+        constraints: vec![],
         testcases: HashMap::default(),
     });
     st0.testcases.entry(0).or_insert(TestcaseInfo {
@@ -46,7 +57,10 @@ pub fn new_task_with_context(path: &Path) -> Task {
     });
     let st1 = task.subtasks.entry(1).or_insert(SubtaskInfo {
         id: 1,
+        name: None,
         max_score: 90.0,
+        score_mode: None,
+        constraints: vec![],
         testcases: HashMap::default(),
     });
     st1.testcases.entry(1).or_insert(TestcaseInfo {
@@ -69,11 +83,15 @@ pub fn good_result() -> ExecutionResult {
         status: ExecutionStatus::Success,
         was_killed: false,
         was_cached: false,
+        truncated_output: false,
+        provenance: ExecutionProvenance::Unknown,
         resources: ExecutionResourcesUsage {
             cpu_time: 0.0,
             sys_time: 0.0,
             wall_time: 0.0,
             memory: 0,
+            processes: None,
+            setup_time: 0.0,
         },
     }
 }
@@ -83,11 +101,15 @@ pub fn bad_result() -> ExecutionResult {
         status: ExecutionStatus::ReturnCode(123),
         was_killed: false,
         was_cached: false,
+        truncated_output: false,
+        provenance: ExecutionProvenance::Unknown,
         resources: ExecutionResourcesUsage {
             cpu_time: 0.0,
             sys_time: 0.0,
             wall_time: 0.0,
             memory: 0,
+            processes: None,
+            setup_time: 0.0,
         },
     }
 }
@@ -1,5 +1,6 @@
 use crate::languages::{Dependency, Language};
 use crate::LanguageManager;
+use failure::{format_err, Error};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -10,10 +11,16 @@ use task_maker_dag::*;
 /// A source file may need some extra dependencies in order to be compiled and/or executed. For
 /// example a C++ file may need a second C++ file to be linked together, or a Python file may need
 /// a second Python file to be run.
+///
+/// The map also carries the extra flags to pass to the compiler of each language, which are
+/// appended to the compilation arguments of the source files using this map.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GraderMap {
     /// The map from the name of the language to the file handle of the grader.
     graders: HashMap<String, Dependency>,
+    /// The map from the name of the language to the extra compilation flags.
+    #[serde(default)]
+    compilation_flags: HashMap<String, Vec<String>>,
 }
 
 impl GraderMap {
@@ -27,6 +34,7 @@ impl GraderMap {
     pub fn new<P: Into<PathBuf>>(graders: Vec<P>) -> GraderMap {
         let mut map = GraderMap {
             graders: HashMap::new(),
+            compilation_flags: HashMap::new(),
         };
         for grader in graders {
             let grader = grader.into();
@@ -87,6 +95,42 @@ impl GraderMap {
         }
     }
 
+    /// Set the extra flags to pass to the compiler of the language with the specified name,
+    /// replacing the previous ones. Fails if the language is unknown.
+    ///
+    /// ```
+    /// use task_maker_lang::{GraderMap, LanguageManager};
+    ///
+    /// let mut map = GraderMap::new(Vec::<String>::new());
+    /// let cpp = LanguageManager::detect_language("source.cpp").unwrap();
+    /// map.set_compilation_flags(cpp.name(), vec!["-fsanitize=address"]).unwrap();
+    /// assert_eq!(map.get_compilation_flags(cpp.as_ref()), vec!["-fsanitize=address"]);
+    /// assert!(map.set_compilation_flags("Cobol", vec!["-O2"]).is_err());
+    /// ```
+    pub fn set_compilation_flags<S1, S2, I>(&mut self, language: S1, flags: I) -> Result<(), Error>
+    where
+        S1: AsRef<str>,
+        S2: Into<String>,
+        I: IntoIterator<Item = S2>,
+    {
+        let lang = LanguageManager::from_name(language.as_ref())
+            .ok_or_else(|| format_err!("Unknown language {:?}", language.as_ref()))?;
+        self.compilation_flags.insert(
+            lang.name().to_string(),
+            flags.into_iter().map(Into::into).collect(),
+        );
+        Ok(())
+    }
+
+    /// The extra flags to pass to the compiler of the specified language, will be an empty `Vec`
+    /// if none are set.
+    pub fn get_compilation_flags(&self, lang: &dyn Language) -> Vec<String> {
+        self.compilation_flags
+            .get(lang.name())
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// Return an iterator over the paths of all the graders in this map.
     ///
     /// ```
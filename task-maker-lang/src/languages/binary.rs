@@ -0,0 +1,27 @@
+use crate::languages::*;
+
+/// A precompiled executable, it's not detected from the extension of the file and it's run as-is
+/// without compilation.
+#[derive(Debug)]
+pub struct LanguageBinary;
+
+impl LanguageBinary {
+    /// Make a new LanguageBinary.
+    pub fn new() -> LanguageBinary {
+        LanguageBinary {}
+    }
+}
+
+impl Language for LanguageBinary {
+    fn name(&self) -> &'static str {
+        "Precompiled binary"
+    }
+
+    fn extensions(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn need_compilation(&self) -> bool {
+        false
+    }
+}
@@ -1,4 +1,4 @@
-use crate::languages::Language;
+use crate::languages::{probe_toolchain_version, Language};
 use std::path::{Path, PathBuf};
 use task_maker_dag::*;
 
@@ -73,6 +73,10 @@ impl Language for LanguageC {
         let name = PathBuf::from(path.file_name().expect("Invalid source file name"));
         PathBuf::from(name.file_stem().expect("Invalid source file name"))
     }
+
+    fn toolchain_version(&self) -> Option<String> {
+        probe_toolchain_version("gcc")
+    }
 }
 
 #[cfg(test)]
@@ -1,4 +1,4 @@
-use crate::languages::Language;
+use crate::languages::{probe_toolchain_version, Language};
 use std::path::{Path, PathBuf};
 use task_maker_dag::*;
 
@@ -25,6 +25,22 @@ impl LanguageCpp {
     pub fn new(version: LanguageCppVersion) -> LanguageCpp {
         LanguageCpp { version }
     }
+
+    /// The compiler to use.
+    fn compiler(&self) -> &'static str {
+        match self.version {
+            LanguageCppVersion::GccCpp11 | LanguageCppVersion::GccCpp14 => "g++",
+            LanguageCppVersion::ClangCpp11 => "clang++",
+        }
+    }
+
+    /// The flag that selects the version of the standard.
+    fn std_flag(&self) -> &'static str {
+        match self.version {
+            LanguageCppVersion::GccCpp11 | LanguageCppVersion::ClangCpp11 => "-std=c++11",
+            LanguageCppVersion::GccCpp14 => "-std=c++14",
+        }
+    }
 }
 
 impl Language for LanguageCpp {
@@ -45,24 +61,21 @@ impl Language for LanguageCpp {
     }
 
     fn compilation_command(&self, _path: &Path) -> ExecutionCommand {
-        match self.version {
-            LanguageCppVersion::GccCpp11 | LanguageCppVersion::GccCpp14 => {
-                ExecutionCommand::system("g++")
-            }
-            LanguageCppVersion::ClangCpp11 => ExecutionCommand::system("clang++"),
-        }
+        ExecutionCommand::system(self.compiler())
     }
 
     fn compilation_args(&self, path: &Path) -> Vec<String> {
         let exe_name = self.executable_name(path);
         let exe_name = exe_name.to_string_lossy();
-        let mut args = vec!["-O2", "-Wall", "-ggdb3", "-DEVAL", "-o", exe_name.as_ref()];
-        match self.version {
-            LanguageCppVersion::GccCpp11 | LanguageCppVersion::ClangCpp11 => {
-                args.push("-std=c++11")
-            }
-            LanguageCppVersion::GccCpp14 => args.push("-std=c++14"),
-        }
+        let args = vec![
+            "-O2",
+            "-Wall",
+            "-ggdb3",
+            "-DEVAL",
+            "-o",
+            exe_name.as_ref(),
+            self.std_flag(),
+        ];
         let mut args: Vec<_> = args.into_iter().map(|s| s.to_string()).collect();
         args.push(
             path.file_name()
@@ -78,11 +91,44 @@ impl Language for LanguageCpp {
         args
     }
 
+    fn supports_separate_compilation(&self) -> bool {
+        true
+    }
+
+    fn unit_compilation_args(&self, _path: &Path, unit: &Path) -> Vec<String> {
+        let object = self.object_name(unit);
+        let object = object.to_string_lossy();
+        let unit = unit.to_string_lossy();
+        let args = vec![
+            "-O2",
+            "-Wall",
+            "-ggdb3",
+            "-DEVAL",
+            "-c",
+            "-o",
+            object.as_ref(),
+            self.std_flag(),
+            unit.as_ref(),
+        ];
+        args.into_iter().map(|s| s.to_string()).collect()
+    }
+
+    fn link_args(&self, path: &Path, objects: &[PathBuf]) -> Vec<String> {
+        let exe_name = self.executable_name(path);
+        let mut args = vec!["-o".to_string(), exe_name.to_string_lossy().to_string()];
+        args.extend(objects.iter().map(|o| o.to_string_lossy().to_string()));
+        args
+    }
+
     /// The executable name is the source file's one without the extension.
     fn executable_name(&self, path: &Path) -> PathBuf {
         let name = PathBuf::from(path.file_name().expect("Invalid source file name"));
         PathBuf::from(name.file_stem().expect("Invalid source file name"))
     }
+
+    fn toolchain_version(&self) -> Option<String> {
+        probe_toolchain_version(self.compiler())
+    }
 }
 
 #[cfg(test)]
@@ -109,6 +155,28 @@ mod tests {
         assert_that!(new_args.iter()).contains("bar.cpp".to_string());
     }
 
+    #[test]
+    fn test_unit_compilation_args() {
+        let lang = LanguageCpp::new(LanguageCppVersion::GccCpp11);
+        let args = lang.unit_compilation_args(Path::new("foo.cpp"), Path::new("grader.cpp"));
+        assert_that!(args).contains("-c".to_string());
+        assert_that!(args).contains("-std=c++11".to_string());
+        assert_that!(args).contains("grader.cpp.o".to_string());
+        assert_that!(args).contains("grader.cpp".to_string());
+        assert_that!(args).does_not_contain("foo.cpp".to_string());
+    }
+
+    #[test]
+    fn test_link_args() {
+        let lang = LanguageCpp::new(LanguageCppVersion::GccCpp14);
+        let objects = vec![PathBuf::from("foo.cpp.o"), PathBuf::from("grader.cpp.o")];
+        let args = lang.link_args(Path::new("foo.cpp"), &objects);
+        assert_that!(args).contains("foo".to_string());
+        assert_that!(args).contains("foo.cpp.o".to_string());
+        assert_that!(args).contains("grader.cpp.o".to_string());
+        assert_that!(args).does_not_contain("-c".to_string());
+    }
+
     #[test]
     fn test_executable_name() {
         let lang = LanguageCpp::new(LanguageCppVersion::GccCpp14);
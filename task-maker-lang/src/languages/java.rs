@@ -0,0 +1,186 @@
+use crate::languages::{probe_toolchain_version, Language};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use task_maker_dag::*;
+
+/// The Java language. The classes are compiled with `javac` and packed in a jar, since the
+/// compilation produces a single executable, which is then run with `java`.
+#[derive(Debug)]
+pub struct LanguageJava;
+
+impl LanguageJava {
+    /// Make a new LanguageJava.
+    pub fn new() -> LanguageJava {
+        LanguageJava {}
+    }
+}
+
+impl Language for LanguageJava {
+    fn name(&self) -> &'static str {
+        "Java / JDK"
+    }
+
+    fn extensions(&self) -> Vec<&'static str> {
+        vec!["java"]
+    }
+
+    fn need_compilation(&self) -> bool {
+        true
+    }
+
+    /// `javac` produces a class file for each class, the shell packs them in the jar.
+    fn compilation_command(&self, _path: &Path) -> ExecutionCommand {
+        ExecutionCommand::system("sh")
+    }
+
+    fn compilation_args(&self, path: &Path) -> Vec<String> {
+        let jar = self.executable_name(path);
+        let script = format!(
+            "javac -encoding UTF-8 -d classes \"$@\" && jar cf {} -C classes .",
+            jar.to_string_lossy()
+        );
+        vec![
+            "-c".to_string(),
+            script,
+            "javac".to_string(),
+            path.file_name()
+                .expect("Invalid source file name")
+                .to_string_lossy()
+                .to_string(),
+        ]
+    }
+
+    fn compilation_add_file(&self, mut args: Vec<String>, file: &Path) -> Vec<String> {
+        args.push(file.to_string_lossy().to_string());
+        args
+    }
+
+    fn runtime_command(&self, _path: &Path) -> ExecutionCommand {
+        ExecutionCommand::system("java")
+    }
+
+    fn runtime_args(&self, path: &Path, args: Vec<String>) -> Vec<String> {
+        let mut res = vec![
+            "-cp".to_string(),
+            self.executable_name(path).to_string_lossy().to_string(),
+            class_name(path),
+        ];
+        res.extend(args);
+        res
+    }
+
+    /// The JVM reserves much more address space than the memory it uses and spawns some threads.
+    /// The memory limit is given to the JVM as the maximum heap size.
+    fn custom_limits(&self, limits: &mut ExecutionLimits) {
+        limits.nproc = None;
+        limits.memory_arg("-Xmx{}k");
+    }
+
+    /// `javac` runs in the JVM too, which cannot even start with the usual memory limit of the
+    /// compilations. Its heap is bounded by the JVM itself.
+    fn custom_compilation_limits(&self, limits: &mut ExecutionLimits) {
+        limits.memory = None;
+    }
+
+    /// The executable is the jar named after the main class.
+    fn executable_name(&self, path: &Path) -> PathBuf {
+        PathBuf::from(format!("{}.jar", class_name(path)))
+    }
+
+    fn toolchain_version(&self) -> Option<String> {
+        probe_toolchain_version("javac")
+    }
+}
+
+/// The name of the main class of a source file: the public class declared in it, which may have a
+/// different name than the file. If the file cannot be read or no public class is found, the file
+/// name without the extension is used.
+fn class_name(path: &Path) -> String {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"public\s+(?:(?:final|abstract|static)\s+)*class\s+([A-Za-z_$][\w$]*)")
+                .expect("Invalid regex");
+    }
+    if let Ok(content) = std::fs::read_to_string(path) {
+        if let Some(cap) = RE.captures(&content) {
+            return cap[1].to_string();
+        }
+    }
+    path.file_stem()
+        .expect("Invalid source file name")
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+    use std::fs::write;
+
+    #[test]
+    fn test_class_name() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("sol.java");
+        write(
+            &path,
+            "import java.util.*;\nclass Helper {}\npublic final class Main {}",
+        )
+        .unwrap();
+        assert_that!(class_name(&path)).is_equal_to("Main".to_string());
+        assert_that!(class_name(Path::new("missing.java"))).is_equal_to("missing".to_string());
+    }
+
+    #[test]
+    fn test_compilation_args() {
+        let lang = LanguageJava::new();
+        let args = lang.compilation_args(Path::new("Foo.java"));
+        assert_that!(args[0]).is_equal_to("-c".to_string());
+        assert_that!(args[1]).contains("javac");
+        assert_that!(args[1]).contains("Foo.jar");
+        assert_that!(args).contains("Foo.java".to_string());
+    }
+
+    #[test]
+    fn test_compilation_add_file() {
+        let lang = LanguageJava::new();
+        let args = lang.compilation_args(Path::new("Foo.java"));
+        let new_args = lang.compilation_add_file(args.clone(), Path::new("grader.java"));
+        assert_that!(new_args.iter()).contains_all_of(&args.iter());
+        assert_that!(new_args.iter()).contains("grader.java".to_string());
+    }
+
+    #[test]
+    fn test_runtime_args() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("sol.java");
+        write(&path, "public class Solution {}").unwrap();
+        let lang = LanguageJava::new();
+        assert_that!(lang.runtime_command(&path)).is_equal_to(ExecutionCommand::system("java"));
+        let args = lang.runtime_args(&path, vec!["arg".to_string()]);
+        assert_that!(args).is_equal_to(vec![
+            "-cp".to_string(),
+            "Solution.jar".to_string(),
+            "Solution".to_string(),
+            "arg".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_custom_limits() {
+        let lang = LanguageJava::new();
+        let mut limits = ExecutionLimits::default();
+        lang.custom_limits(&mut limits);
+        assert_that!(limits.nproc).is_none();
+        assert_that!(limits.memory_arg).is_equal_to(Some("-Xmx{}k".to_string()));
+    }
+
+    #[test]
+    fn test_custom_compilation_limits() {
+        let lang = LanguageJava::new();
+        let mut limits = ExecutionLimits::default();
+        limits.memory(1024);
+        lang.custom_compilation_limits(&mut limits);
+        assert_that!(limits.memory).is_none();
+    }
+}
@@ -1,10 +1,17 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
 use task_maker_dag::*;
 
+pub(crate) mod binary;
 pub(crate) mod c;
 pub(crate) mod cpp;
+pub(crate) mod java;
 pub(crate) mod python;
+pub(crate) mod rust;
+pub(crate) mod script;
 pub(crate) mod shell;
 
 /// A dependency of an execution, all the sandbox paths must be relative and inside of the sandbox.
@@ -31,6 +38,11 @@ pub struct Dependency {
 /// - `compilation_command`
 /// - `compilation_args`
 /// - `compilation_add_file`
+///
+/// A compiled language can also support the separate compilation of its units, in that case the
+/// extra required implementations are:
+/// - `unit_compilation_args`
+/// - `link_args`
 pub trait Language: std::fmt::Debug + Send + Sync {
     /// Full name of the language. This must be unique between all the other languages.
     fn name(&self) -> &'static str;
@@ -79,6 +91,38 @@ pub trait Language: std::fmt::Debug + Send + Sync {
         vec![]
     }
 
+    /// Whether the compilation units of a program in this language can be compiled in separate
+    /// executions and then linked together. When this is `true` also `unit_compilation_args` and
+    /// `link_args` must be implemented.
+    fn supports_separate_compilation(&self) -> bool {
+        false
+    }
+
+    /// Arguments to pass to the compiler to compile only the compilation unit located at `unit`
+    /// inside the sandbox, placing the result at `self.object_name(unit)`. `path` is the main
+    /// source file of the program.
+    ///
+    /// Will panic if this language does not support separate compilation.
+    fn unit_compilation_args(&self, _path: &Path, _unit: &Path) -> Vec<String> {
+        panic!("Language {} cannot be compiled separately!", self.name())
+    }
+
+    /// Arguments to pass to the compiler to link the object files of the compilation units into
+    /// the executable, placed at `self.executable_name(path)`.
+    ///
+    /// Will panic if this language does not support separate compilation.
+    fn link_args(&self, _path: &Path, _objects: &[PathBuf]) -> Vec<String> {
+        panic!("Language {} cannot be compiled separately!", self.name())
+    }
+
+    /// The name of the object file produced by the compilation of a single unit. It defaults to
+    /// the file name of the unit with the `.o` extension appended.
+    fn object_name(&self, unit: &Path) -> PathBuf {
+        let mut name = unit.file_name().expect("Invalid file name").to_os_string();
+        name.push(".o");
+        PathBuf::from(name)
+    }
+
     /// Command to use to run the program. It defaults to the executable name of the program.
     /// Languages that need to run a separate program (e.g. a system-wise interpreter) may change
     /// the return value of this method.
@@ -108,9 +152,76 @@ pub trait Language: std::fmt::Debug + Send + Sync {
     /// need to fork (hence use more processes).
     fn custom_limits(&self, _limits: &mut ExecutionLimits) {}
 
+    /// Update the limits of the compilation for some language-specific requirements of the
+    /// compiler.
+    fn custom_compilation_limits(&self, _limits: &mut ExecutionLimits) {}
+
     /// The name of the executable to call inside the sandbox. It defaults to the file name of
     /// program.
     fn executable_name(&self, path: &Path) -> PathBuf {
         PathBuf::from(path.file_name().expect("Invalid file name"))
     }
+
+    /// The version of the toolchain (e.g. the compiler or the interpreter) used by this language,
+    /// useful for knowing which toolchain produced the results. `None` is returned if the language
+    /// doesn't use a toolchain or its version cannot be found.
+    fn toolchain_version(&self) -> Option<String> {
+        None
+    }
+}
+
+lazy_static! {
+    /// The versions of the toolchains that have already been probed, indexed by command.
+    static ref TOOLCHAIN_VERSIONS: Mutex<HashMap<String, Option<String>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Probe the version of a toolchain running `command --version`. The version is probed only once
+/// for each command, the following calls return the cached value.
+pub(crate) fn probe_toolchain_version(command: &str) -> Option<String> {
+    let mut versions = TOOLCHAIN_VERSIONS.lock().unwrap();
+    versions
+        .entry(command.to_string())
+        .or_insert_with(|| {
+            let output = Command::new(command).arg("--version").output().ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            // some interpreters (e.g. python2) print their version to stderr
+            parse_version(&output.stdout).or_else(|| parse_version(&output.stderr))
+        })
+        .clone()
+}
+
+/// Extract the version of a toolchain from the output of `--version`: its first non-empty line.
+fn parse_version(output: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        let output = b"\ng++ (Ubuntu 9.3.0) 9.3.0\nCopyright (C) 2019\n";
+        assert_eq!(
+            parse_version(output),
+            Some("g++ (Ubuntu 9.3.0) 9.3.0".to_string())
+        );
+        assert_eq!(parse_version(b"  \n"), None);
+    }
+
+    #[test]
+    fn test_probe_toolchain_version_missing() {
+        assert_eq!(probe_toolchain_version("surely-not-a-compiler"), None);
+        assert!(TOOLCHAIN_VERSIONS
+            .lock()
+            .unwrap()
+            .contains_key("surely-not-a-compiler"));
+    }
 }
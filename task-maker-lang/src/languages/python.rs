@@ -70,6 +70,16 @@ impl Language for LanguagePython {
     fn runtime_dependencies(&self, path: &Path) -> Vec<Dependency> {
         find_python_deps(path)
     }
+
+    /// The version of the interpreter is known only if it's forced, with autodetect it depends on
+    /// the shebang of the source file.
+    fn toolchain_version(&self) -> Option<String> {
+        match self.version {
+            LanguagePythonVersion::Autodetect => None,
+            LanguagePythonVersion::Python2 => probe_toolchain_version("python2"),
+            LanguagePythonVersion::Python3 => probe_toolchain_version("python3"),
+        }
+    }
 }
 
 /// Perform a BFS visit on the file dependencies looking for all the .py files
@@ -0,0 +1,135 @@
+use crate::languages::{probe_toolchain_version, Language};
+use std::path::{Path, PathBuf};
+use task_maker_dag::*;
+
+/// The minimum stack size, in KiB, of the Rust programs.
+const MIN_STACK_SIZE: u64 = 8 * 1024;
+
+/// The Rust language, compiled with `rustc`.
+#[derive(Debug)]
+pub struct LanguageRust;
+
+impl LanguageRust {
+    /// Make a new LanguageRust.
+    pub fn new() -> LanguageRust {
+        LanguageRust {}
+    }
+}
+
+impl Language for LanguageRust {
+    fn name(&self) -> &'static str {
+        "Rust / rustc"
+    }
+
+    fn extensions(&self) -> Vec<&'static str> {
+        vec!["rs"]
+    }
+
+    fn need_compilation(&self) -> bool {
+        true
+    }
+
+    fn compilation_command(&self, _path: &Path) -> ExecutionCommand {
+        ExecutionCommand::system("rustc")
+    }
+
+    fn compilation_args(&self, path: &Path) -> Vec<String> {
+        let exe_name = self.executable_name(path);
+        let exe_name = exe_name.to_string_lossy();
+        let args = vec![
+            "-O",
+            "--edition=2018",
+            "--cfg",
+            "EVAL",
+            "-o",
+            exe_name.as_ref(),
+        ];
+        let mut args: Vec<_> = args.into_iter().map(|s| s.to_string()).collect();
+        args.push(
+            path.file_name()
+                .expect("Invalid source file name")
+                .to_string_lossy()
+                .to_string(),
+        );
+        args
+    }
+
+    /// `rustc` compiles a single crate root, the other files (e.g. the grader) are only put in the
+    /// sandbox and included by the source with `mod` or `include!`.
+    fn compilation_add_file(&self, args: Vec<String>, _file: &Path) -> Vec<String> {
+        args
+    }
+
+    /// The large arrays are often built on the stack before being moved (e.g. `Box::new([0; N])`),
+    /// make sure the stack is at least as large as the usual default of 8 MiB.
+    fn custom_limits(&self, limits: &mut ExecutionLimits) {
+        match limits.stack {
+            Some(0) | None => {}
+            Some(stack) if stack >= MIN_STACK_SIZE => {}
+            Some(_) => {
+                limits.stack(MIN_STACK_SIZE);
+            }
+        }
+    }
+
+    /// The executable name is the source file's one without the extension.
+    fn executable_name(&self, path: &Path) -> PathBuf {
+        let name = PathBuf::from(path.file_name().expect("Invalid source file name"));
+        PathBuf::from(name.file_stem().expect("Invalid source file name"))
+    }
+
+    fn toolchain_version(&self) -> Option<String> {
+        probe_toolchain_version("rustc")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_compilation_args() {
+        let lang = LanguageRust::new();
+        let args = lang.compilation_args(Path::new("foo.rs"));
+        assert_that!(args).contains("foo.rs".to_string());
+        assert_that!(args).contains("-O".to_string());
+        assert_that!(args).contains("-o".to_string());
+        assert_that!(args).contains("foo".to_string());
+    }
+
+    #[test]
+    fn test_compilation_add_file() {
+        let lang = LanguageRust::new();
+        let args = lang.compilation_args(Path::new("foo.rs"));
+        let new_args = lang.compilation_add_file(args.clone(), Path::new("grader.rs"));
+        assert_that!(new_args).is_equal_to(args);
+    }
+
+    #[test]
+    fn test_runtime_dependencies() {
+        let lang = LanguageRust::new();
+        assert_that!(lang.runtime_dependencies(Path::new("foo.rs"))).is_empty();
+    }
+
+    #[test]
+    fn test_custom_limits() {
+        let lang = LanguageRust::new();
+        let mut limits = ExecutionLimits::new();
+        limits.stack(1024);
+        lang.custom_limits(&mut limits);
+        assert_that!(limits.stack).is_equal_to(Some(MIN_STACK_SIZE));
+        limits.stack(0);
+        lang.custom_limits(&mut limits);
+        assert_that!(limits.stack).is_equal_to(Some(0));
+        limits.stack(2 * MIN_STACK_SIZE);
+        lang.custom_limits(&mut limits);
+        assert_that!(limits.stack).is_equal_to(Some(2 * MIN_STACK_SIZE));
+    }
+
+    #[test]
+    fn test_executable_name() {
+        let lang = LanguageRust::new();
+        assert_that!(lang.executable_name(Path::new("foo.rs"))).is_equal_to(PathBuf::from("foo"));
+    }
+}
@@ -0,0 +1,48 @@
+use crate::languages::*;
+use task_maker_dag::ExecutionLimits;
+
+/// A script run by the interpreter written in its shebang, for the interpreters without a
+/// dedicated language. It's not detected from the extension of the file, only from the shebang.
+#[derive(Debug)]
+pub struct LanguageScript;
+
+impl LanguageScript {
+    /// Make a new LanguageScript.
+    pub fn new() -> LanguageScript {
+        LanguageScript {}
+    }
+}
+
+impl Language for LanguageScript {
+    fn name(&self) -> &'static str {
+        "Script / shebang"
+    }
+
+    fn extensions(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn need_compilation(&self) -> bool {
+        false
+    }
+
+    /// Some interpreters (e.g. node) spawn threads.
+    fn custom_limits(&self, limits: &mut ExecutionLimits) {
+        limits.nproc = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_allow_threads() {
+        let lang = LanguageScript::new();
+        let mut limits = ExecutionLimits::new();
+        limits.nproc(1);
+        lang.custom_limits(&mut limits);
+        assert_that!(limits.nproc).is_none();
+    }
+}
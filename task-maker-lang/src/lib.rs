@@ -8,8 +8,8 @@
 //! this crate.
 //!
 //! The entry point of this crate is [`LanguageManager`](struct.LanguageManager.html), a struct that
-//! is able to detect the language of a source file based on its extension. A trait object is used
-//! to keep track of the language.
+//! is able to detect the language of a source file based on its extension, or on its shebang when
+//! the extension is not known. A trait object is used to keep track of the language.
 //!
 //! To actually use the language you can use [`SourceFile`](struct.SourceFile.html), it exposes the
 //! functionalities for compiling and running a source file.
@@ -36,15 +36,26 @@ pub use grader_map::GraderMap;
 pub use languages::{Dependency, Language};
 pub use source_file::SourceFile;
 
+use failure::{format_err, Error};
 use languages::*;
+use std::collections::HashMap;
+use std::io::Read;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+
+/// The maximum number of bytes read from the start of a file looking for its shebang.
+const SHEBANG_MAX_LEN: u64 = 256;
 
 /// Manager of all the known languages, you should use this to get
 /// [`Language`](languages/trait.Language.html) instances.
 pub struct LanguageManager {
-    /// The list of all the known languages.
+    /// The list of all the known languages, used for detecting the language from the extension.
     known_languages: Vec<Arc<dyn Language + Sync + Send>>,
+    /// The other versions of the known languages. They are never detected from the extension, but
+    /// they can be selected with an override.
+    other_versions: Vec<Arc<dyn Language + Sync + Send>>,
+    /// The languages to use for some extensions, instead of the detected ones.
+    overrides: HashMap<String, Arc<dyn Language + Sync + Send>>,
 }
 
 impl LanguageManager {
@@ -58,12 +69,29 @@ impl LanguageManager {
                 Arc::new(python::LanguagePython::new(
                     python::LanguagePythonVersion::Autodetect,
                 )),
+                Arc::new(rust::LanguageRust::new()),
+                Arc::new(java::LanguageJava::new()),
                 Arc::new(shell::LanguageShell::new()),
+                Arc::new(binary::LanguageBinary::new()),
             ],
+            other_versions: vec![
+                Arc::new(cpp::LanguageCpp::new(cpp::LanguageCppVersion::GccCpp11)),
+                Arc::new(cpp::LanguageCpp::new(cpp::LanguageCppVersion::ClangCpp11)),
+                Arc::new(c::LanguageC::new(c::LanguageCVersion::GccC99)),
+                Arc::new(python::LanguagePython::new(
+                    python::LanguagePythonVersion::Python2,
+                )),
+                Arc::new(python::LanguagePython::new(
+                    python::LanguagePythonVersion::Python3,
+                )),
+                Arc::new(script::LanguageScript::new()),
+            ],
+            overrides: HashMap::new(),
         }
     }
 
-    /// Given a path to a file guess the language that the source file probably is.
+    /// Given a path to a file guess the language that the source file probably is. If the
+    /// extension is not known, the shebang of the file (if it exists) is used.
     ///
     /// ```
     /// use task_maker_lang::LanguageManager;
@@ -76,38 +104,137 @@ impl LanguageManager {
     /// assert!(unknown.is_none());
     /// ```
     pub fn detect_language<P: AsRef<Path>>(path: P) -> Option<Arc<dyn Language>> {
-        let manager = &LANGUAGE_MANAGER_SINGL;
+        LANGUAGE_MANAGER_SINGL.read().unwrap().detect(path.as_ref())
+    }
+
+    /// Search between the known languages the one with the specified name and return it if found.
+    pub(crate) fn from_name<S: AsRef<str>>(name: S) -> Option<Arc<dyn Language>> {
+        LANGUAGE_MANAGER_SINGL.read().unwrap().find(name.as_ref())
+    }
+
+    /// Use the specified languages for some extensions, instead of the ones detected by default.
+    /// The overrides map an extension (without the dot) to the name of a language, and replace
+    /// the ones previously set. This should be called at the start, before detecting the language
+    /// of any file.
+    ///
+    /// ```
+    /// use task_maker_lang::LanguageManager;
+    ///
+    /// LanguageManager::set_overrides(vec![("py", "Python2")]).unwrap();
+    /// let py = LanguageManager::detect_language("test.py").unwrap();
+    /// assert_eq!(py.name(), "Python2");
+    /// assert!(LanguageManager::set_overrides(vec![("py", "Cobol")]).is_err());
+    /// ```
+    pub fn set_overrides<I, S1, S2>(overrides: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (S1, S2)>,
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        LANGUAGE_MANAGER_SINGL
+            .write()
+            .unwrap()
+            .set_overrides_impl(overrides)
+    }
+
+    /// Detect the language of the file at the specified path, looking at the overrides first and
+    /// falling back to the shebang.
+    fn detect(&self, path: &Path) -> Option<Arc<dyn Language>> {
         let ext = path
-            .as_ref()
             .extension()
             .map(|s| s.to_string_lossy())
             .unwrap_or_else(|| "".into())
             .to_lowercase();
-        for lang in manager.known_languages.iter() {
+        if let Some(lang) = self.overrides.get(&ext) {
+            return Some(lang.clone());
+        }
+        for lang in self.known_languages.iter() {
             for lang_ext in lang.extensions().iter() {
                 if ext == *lang_ext {
                     return Some(lang.clone());
                 }
             }
         }
-        None
+        self.detect_shebang(path)
     }
 
-    /// Search between the known languages the one with the specified name and return it if found.
-    pub(crate) fn from_name<S: AsRef<str>>(name: S) -> Option<Arc<dyn Language>> {
-        let manager = &LANGUAGE_MANAGER_SINGL;
-        for lang in manager.known_languages.iter() {
-            if lang.name() == name.as_ref() {
+    /// Detect the language of a script from the interpreter in its shebang. Only the start of the
+    /// file is read, `None` is returned if the file cannot be read or the interpreter is unknown.
+    fn detect_shebang(&self, path: &Path) -> Option<Arc<dyn Language>> {
+        let mut head = vec![];
+        std::fs::File::open(path)
+            .ok()?
+            .take(SHEBANG_MAX_LEN)
+            .read_to_end(&mut head)
+            .ok()?;
+        let name = match shebang_interpreter(&head)?.as_str() {
+            "python" | "python2" | "python3" => "Python / Autodetect",
+            "sh" | "bash" | "dash" | "zsh" => "Shell",
+            "perl" | "node" | "nodejs" => "Script / shebang",
+            _ => return None,
+        };
+        self.find(name)
+    }
+
+    /// Find the language with the specified name, including the other versions.
+    fn find(&self, name: &str) -> Option<Arc<dyn Language>> {
+        for lang in self
+            .known_languages
+            .iter()
+            .chain(self.other_versions.iter())
+        {
+            if lang.name() == name {
                 return Some(lang.clone());
             }
         }
         None
     }
+
+    /// Replace the overrides of this manager. Fails without changing them if a language is
+    /// unknown.
+    fn set_overrides_impl<I, S1, S2>(&mut self, overrides: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (S1, S2)>,
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        let mut new_overrides = HashMap::new();
+        for (ext, name) in overrides {
+            let lang = self
+                .known_languages
+                .iter()
+                .chain(self.other_versions.iter())
+                .find(|lang| lang.name() == name.as_ref())
+                .ok_or_else(|| format_err!("Unknown language {:?}", name.as_ref()))?;
+            let ext = ext.as_ref().trim_start_matches('.').to_lowercase();
+            new_overrides.insert(ext, lang.clone());
+        }
+        self.overrides = new_overrides;
+        Ok(())
+    }
+}
+
+/// The name of the interpreter in the shebang at the start of a script, without its directory and
+/// the minor version (e.g. `python3` for `#!/usr/bin/env python3.8`). `env` is skipped together
+/// with its options.
+fn shebang_interpreter(head: &[u8]) -> Option<String> {
+    if !head.starts_with(b"#!") {
+        return None;
+    }
+    let line = String::from_utf8_lossy(&head[2..]);
+    let line = line.lines().next()?;
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|w| !w.starts_with('-') && !w.contains('='))?;
+    }
+    Some(interpreter.split('.').next()?.to_string())
 }
 
 lazy_static! {
     /// The singleton instance of the `LanguageManager`.
-    static ref LANGUAGE_MANAGER_SINGL: LanguageManager = LanguageManager::new();
+    static ref LANGUAGE_MANAGER_SINGL: RwLock<LanguageManager> =
+        RwLock::new(LanguageManager::new());
 }
 
 #[cfg(test)]
@@ -130,6 +257,59 @@ mod tests {
         assert_that!(lang.name()).is_equal_to(name);
     }
 
+    #[test]
+    fn test_detect_language_rust() {
+        let lang = LanguageManager::detect_language("foo.rs").unwrap();
+        assert_that!(lang.name()).is_equal_to(rust::LanguageRust::new().name());
+        assert_that!(lang.need_compilation()).is_true();
+    }
+
+    #[test]
+    fn test_detect_language_java() {
+        let lang = LanguageManager::detect_language("Foo.java").unwrap();
+        assert_that!(lang.name()).is_equal_to(java::LanguageJava::new().name());
+    }
+
+    #[test]
+    fn test_detect_language_shebang() {
+        let tmpdir = tempdir::TempDir::new("tm-test").unwrap();
+        let path = tmpdir.path().join("checker");
+        std::fs::write(&path, "#!/usr/bin/env python3\nprint(42)\n").unwrap();
+        let lang = LanguageManager::detect_language(&path).unwrap();
+        assert_that!(lang.name()).is_equal_to("Python / Autodetect");
+        std::fs::write(&path, "#!/bin/bash\necho 42\n").unwrap();
+        let lang = LanguageManager::detect_language(&path).unwrap();
+        assert_that!(lang.name()).is_equal_to("Shell");
+        std::fs::write(&path, "#!/usr/bin/node\n").unwrap();
+        let lang = LanguageManager::detect_language(&path).unwrap();
+        assert_that!(lang.name()).is_equal_to("Script / shebang");
+        std::fs::write(&path, "#!/usr/bin/cobol\n").unwrap();
+        assert_that!(LanguageManager::detect_language(&path)).is_none();
+        std::fs::write(&path, "no shebang").unwrap();
+        assert_that!(LanguageManager::detect_language(&path)).is_none();
+        assert_that!(LanguageManager::detect_language(
+            tmpdir.path().join("missing")
+        ))
+        .is_none();
+    }
+
+    #[test]
+    fn test_shebang_interpreter() {
+        let interpreter = |s: &str| shebang_interpreter(s.as_bytes());
+        assert_eq!(
+            interpreter("#!/usr/bin/python2.7\n"),
+            Some("python2".into())
+        );
+        assert_eq!(interpreter("#! /bin/sh -e\n"), Some("sh".into()));
+        assert_eq!(
+            interpreter("#!/usr/bin/env -S FOO=1 perl -w"),
+            Some("perl".into())
+        );
+        assert_eq!(interpreter("#!/usr/bin/env"), None);
+        assert_eq!(interpreter("#!"), None);
+        assert_eq!(interpreter("import os"), None);
+    }
+
     #[test]
     fn test_detect_language_unknown() {
         let lang = LanguageManager::detect_language("foo.blah");
@@ -143,6 +323,37 @@ mod tests {
         assert_that!(lang.name()).is_equal_to(name);
     }
 
+    #[test]
+    fn test_detect_language_override() {
+        let mut manager = LanguageManager::new();
+        manager
+            .set_overrides_impl(vec![("cc", "C++11 / clang"), (".PY", "Python3")])
+            .unwrap();
+        let lang = manager.detect(Path::new("foo.cc")).unwrap();
+        let name = LanguageCpp::new(LanguageCppVersion::ClangCpp11).name();
+        assert_that!(lang.name()).is_equal_to(name);
+        let lang = manager.detect(Path::new("foo.py")).unwrap();
+        assert_that!(lang.name()).is_equal_to("Python3");
+        // the other extensions are not affected
+        let lang = manager.detect(Path::new("foo.cpp")).unwrap();
+        let name = LanguageCpp::new(LanguageCppVersion::GccCpp14).name();
+        assert_that!(lang.name()).is_equal_to(name);
+    }
+
+    #[test]
+    fn test_detect_language_override_unknown() {
+        let mut manager = LanguageManager::new();
+        manager.set_overrides_impl(vec![("cc", "Python2")]).unwrap();
+        assert!(manager
+            .set_overrides_impl(vec![("py", "Python2"), ("cc", "C++42")])
+            .is_err());
+        // the previous overrides are kept
+        let lang = manager.detect(Path::new("foo.cc")).unwrap();
+        assert_that!(lang.name()).is_equal_to("Python2");
+        assert_that!(manager.detect(Path::new("foo.py")).unwrap().name())
+            .is_equal_to("Python / Autodetect");
+    }
+
     #[test]
     fn test_from_name_unknown() {
         let lang = LanguageManager::from_name("Nope, this is not a language");
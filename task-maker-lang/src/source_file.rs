@@ -1,4 +1,4 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 use failure::Error;
@@ -30,12 +30,12 @@ pub struct SourceFile {
     grader_map: Option<Arc<GraderMap>>,
     /// Where to write the compiled executable.
     write_bin_to: Option<PathBuf>,
-    /// The stdout of the compilation, set if `prepare` has been called, and the language supports
-    /// compilation.
-    compilation_stdout: Arc<Mutex<Option<File>>>,
-    /// The stderr of the compilation, set if `prepare` has been called, and the language supports
-    /// compilation.
-    compilation_stderr: Arc<Mutex<Option<File>>>,
+    /// The stdout of each step of the compilation, set if `prepare` has been called, and the
+    /// language supports compilation.
+    compilation_stdout: Arc<Mutex<Vec<File>>>,
+    /// The stderr of each step of the compilation, set if `prepare` has been called, and the
+    /// language supports compilation.
+    compilation_stderr: Arc<Mutex<Vec<File>>>,
 }
 
 impl SourceFile {
@@ -64,11 +64,36 @@ impl SourceFile {
             executable: Arc::new(Mutex::new(None)),
             grader_map,
             write_bin_to: write_bin_to.map(|p| p.into()),
-            compilation_stdout: Arc::new(Mutex::new(None)),
-            compilation_stderr: Arc::new(Mutex::new(None)),
+            compilation_stdout: Arc::new(Mutex::new(Vec::new())),
+            compilation_stderr: Arc::new(Mutex::new(Vec::new())),
         })
     }
 
+    /// Make a new `SourceFile` from an already compiled executable. The file is executed as-is,
+    /// without any compilation step.
+    ///
+    /// ```
+    /// use task_maker_lang::SourceFile;
+    ///
+    /// let source = SourceFile::new_precompiled("path/to/managers/checker", "");
+    /// assert_eq!(source.name(), "checker");
+    /// ```
+    pub fn new_precompiled<P: Into<PathBuf>, P2: Into<PathBuf>>(
+        path: P,
+        base_path: P2,
+    ) -> SourceFile {
+        SourceFile {
+            path: path.into(),
+            base_path: base_path.into(),
+            language: Arc::new(binary::LanguageBinary::new()),
+            executable: Arc::new(Mutex::new(None)),
+            grader_map: None,
+            write_bin_to: None,
+            compilation_stdout: Arc::new(Mutex::new(Vec::new())),
+            compilation_stderr: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
     /// Execute the program relative to this source file with the specified args. If the file has
     /// not been compiled yet this may add the compilation to the DAG. The compilation is added to
     /// the DAG only once for each `SourceFile` instance.
@@ -137,22 +162,23 @@ impl SourceFile {
         &self,
         dag: &mut ExecutionDAG,
         description: S,
-        args: Vec<String>,
+        mut args: Vec<String>,
     ) -> Result<(Option<ExecutionUuid>, Execution), Error> {
         let comp = self.prepare(dag)?;
         let mut exec = Execution::new(
             description.as_ref(),
             self.language.runtime_command(&self.path),
         );
-        for arg in &args {
-            let path = self.base_path.join(arg);
-            if path.exists() {
+        for arg in args.iter_mut() {
+            if let Some((path, sandbox_path)) = command_line_dependency(&self.base_path, arg) {
                 let file = File::new(format!(
                     "Command line dependency {:?} of {:?}",
                     path, self.path
                 ));
-                exec.input(&file, arg, false);
+                exec.input(&file, &sandbox_path, false);
                 dag.provide_file(file, path)?;
+                // the file may be referenced with a different path outside the sandbox
+                *arg = sandbox_path.to_string_lossy().to_string();
             }
         }
         exec.args(self.language.runtime_args(&self.path, args));
@@ -172,10 +198,22 @@ impl SourceFile {
                 dag.provide_file(dep.file, &dep.local_path)?;
             }
         }
+        exec.limits_mut().seccomp(SeccompProfile::Strict);
         self.language.custom_limits(exec.limits_mut());
+        // the interpreted programs are run by the toolchain itself
+        if !self.language.need_compilation() {
+            if let Some(version) = self.language.toolchain_version() {
+                exec.toolchain(version);
+            }
+        }
         Ok((comp, exec))
     }
 
+    /// The language of the source file.
+    pub fn language(&self) -> &dyn Language {
+        self.language.as_ref()
+    }
+
     /// The file name of the source file.
     ///
     /// ```
@@ -194,56 +232,70 @@ impl SourceFile {
             .to_string()
     }
 
-    /// The standard output of the compilation, if the source file is compiled and `execute` has
-    /// been called at least once.
-    pub fn compilation_stdout(&self) -> Option<File> {
+    /// The standard output of each step of the compilation, if the source file is compiled and
+    /// `execute` has been called at least once. When the units are compiled separately there is
+    /// one for each unit, in order, followed by the one of the linking.
+    pub fn compilation_stdout(&self) -> Vec<File> {
         self.compilation_stdout.lock().unwrap().clone()
     }
 
-    /// The standard error of the compilation, if the source file is compiled and `execute` has
-    /// been called at least once.
-    pub fn compilation_stderr(&self) -> Option<File> {
+    /// The standard error of each step of the compilation, see
+    /// [`compilation_stdout`](#method.compilation_stdout).
+    pub fn compilation_stderr(&self) -> Vec<File> {
         self.compilation_stderr.lock().unwrap().clone()
     }
 
+    /// The extra compilation flags of the language of this source file, from the grader map.
+    fn compilation_flags(&self) -> Vec<String> {
+        self.grader_map
+            .as_ref()
+            .map(|map| map.get_compilation_flags(self.language.as_ref()))
+            .unwrap_or_default()
+    }
+
     /// Prepare the source file setting the `executable` and eventually compiling the source file.
     fn prepare(&self, dag: &mut ExecutionDAG) -> Result<Option<ExecutionUuid>, Error> {
         if self.executable.lock().unwrap().is_some() {
             return Ok(None);
         }
         if self.language.need_compilation() {
-            let mut comp = Execution::new(
-                &format!("Compilation of {:?}", self.name()),
-                self.language.compilation_command(&self.path),
-            );
-            comp.tag(ExecutionTag::from("compilation"));
-            comp.args = self.language.compilation_args(&self.path);
             let source = File::new(&format!("Source file of {:?}", self.path));
-            comp.input(
-                &source,
-                Path::new(self.path.file_name().expect("Invalid file name")),
-                false,
-            );
-            comp.limits.nproc = None;
-            comp.limits.read_only(false); // the compilers may need to store some temp files
-            for dep in self.language.compilation_dependencies(&self.path) {
-                comp.input(&dep.file, &dep.sandbox_path, dep.executable);
-                dag.provide_file(dep.file, &dep.local_path)?;
-            }
-            if let Some(grader_map) = self.grader_map.as_ref() {
-                for dep in grader_map.get_compilation_deps(self.language.as_ref()) {
-                    comp.input(&dep.file, &dep.sandbox_path, dep.executable);
+            let source_name = PathBuf::from(self.path.file_name().expect("Invalid file name"));
+            let grader_deps = self
+                .grader_map
+                .as_ref()
+                .map(|map| map.get_compilation_deps(self.language.as_ref()))
+                .unwrap_or_default();
+            let (exec, comp_uuid) = if dag.config_mut().parallel_compilation
+                && self.language.supports_separate_compilation()
+                && !grader_deps.is_empty()
+            {
+                self.compile_units(dag, &source, &source_name, &grader_deps)?
+            } else {
+                let mut comp = self.compilation_execution(
+                    dag,
+                    format!("Compilation of {:?}", self.name()),
+                    &source,
+                    &source_name,
+                    &grader_deps,
+                )?;
+                comp.args = self.language.compilation_args(&self.path);
+                comp.args.extend(self.compilation_flags());
+                for dep in &grader_deps {
                     comp.args = self
                         .language
                         .compilation_add_file(comp.args, &dep.sandbox_path);
-                    dag.provide_file(dep.file, &dep.local_path)?;
                 }
+                self.compilation_stdout.lock().unwrap().push(comp.stdout());
+                self.compilation_stderr.lock().unwrap().push(comp.stderr());
+                let exec = comp.output(&self.language.executable_name(&self.path));
+                let comp_uuid = comp.uuid;
+                dag.add_execution(comp);
+                (exec, comp_uuid)
+            };
+            for dep in grader_deps {
+                dag.provide_file(dep.file, &dep.local_path)?;
             }
-            *self.compilation_stdout.lock().unwrap() = Some(comp.stdout());
-            *self.compilation_stderr.lock().unwrap() = Some(comp.stderr());
-            let exec = comp.output(&self.language.executable_name(&self.path));
-            let comp_uuid = comp.uuid;
-            dag.add_execution(comp);
             dag.provide_file(source, &self.path)?;
             if dag.config_mut().copy_exe {
                 if let Some(write_bin_to) = &self.write_bin_to {
@@ -264,6 +316,103 @@ impl SourceFile {
             Ok(None)
         }
     }
+
+    /// Make a new compilation execution, without arguments, with the source file and all the
+    /// compilation dependencies as input. The language dependencies are provided to the DAG, the
+    /// ones of the grader and the source file are not.
+    fn compilation_execution(
+        &self,
+        dag: &mut ExecutionDAG,
+        description: String,
+        source: &File,
+        source_name: &Path,
+        grader_deps: &[Dependency],
+    ) -> Result<Execution, Error> {
+        let mut comp = Execution::new(&description, self.language.compilation_command(&self.path));
+        comp.tag(ExecutionTag::from("compilation"));
+        if let Some(version) = self.language.toolchain_version() {
+            comp.toolchain(version);
+        }
+        comp.input(source, source_name, false);
+        self.compilation_limits(dag, &mut comp.limits);
+        for dep in self.language.compilation_dependencies(&self.path) {
+            comp.input(&dep.file, &dep.sandbox_path, dep.executable);
+            dag.provide_file(dep.file, &dep.local_path)?;
+        }
+        for dep in grader_deps {
+            comp.input(&dep.file, &dep.sandbox_path, dep.executable);
+        }
+        Ok(comp)
+    }
+
+    /// Set the limits of a compilation (or a linking) execution, using the limits of the DAG
+    /// configuration, which are independent of the ones of the execution of the program.
+    fn compilation_limits(&self, dag: &mut ExecutionDAG, limits: &mut ExecutionLimits) {
+        limits.nproc = None;
+        limits.read_only(false); // the compilers may need to store some temp files
+        limits.seccomp(SeccompProfile::Permissive);
+        if let Some(time_limit) = dag.config_mut().compilation_time_limit {
+            limits.wall_time(time_limit);
+        }
+        if let Some(memory_limit) = dag.config_mut().compilation_memory_limit {
+            limits.memory(memory_limit);
+        }
+        self.language.custom_compilation_limits(limits);
+    }
+
+    /// Compile the source file and each of the graders in a separate execution, linking the object
+    /// files together in a final execution. Returns the executable and the UUID of the linking.
+    ///
+    /// The compilation messages of all the units and of the linking are reported.
+    fn compile_units(
+        &self,
+        dag: &mut ExecutionDAG,
+        source: &File,
+        source_name: &Path,
+        grader_deps: &[Dependency],
+    ) -> Result<(File, ExecutionUuid), Error> {
+        let units = std::iter::once(source_name)
+            .chain(grader_deps.iter().map(|d| d.sandbox_path.as_path()));
+        let mut objects = vec![];
+        for unit in units {
+            let mut comp = self.compilation_execution(
+                dag,
+                format!("Compilation of {:?} of {:?}", unit, self.name()),
+                source,
+                source_name,
+                grader_deps,
+            )?;
+            comp.args = self.language.unit_compilation_args(&self.path, unit);
+            comp.args.extend(self.compilation_flags());
+            self.compilation_stdout.lock().unwrap().push(comp.stdout());
+            self.compilation_stderr.lock().unwrap().push(comp.stderr());
+            let object_name = self.language.object_name(unit);
+            objects.push((comp.output(&object_name), object_name));
+            dag.add_execution(comp);
+        }
+        let mut link = Execution::new(
+            format!("Linking of {:?}", self.name()),
+            self.language.compilation_command(&self.path),
+        );
+        link.tag(ExecutionTag::from("compilation"));
+        if let Some(version) = self.language.toolchain_version() {
+            link.toolchain(version);
+        }
+        self.compilation_limits(dag, &mut link.limits);
+        for (object, object_name) in &objects {
+            link.input(object, object_name, false);
+        }
+        let object_names: Vec<_> = objects.into_iter().map(|(_, name)| name).collect();
+        link.args = self.language.link_args(&self.path, &object_names);
+        // some flags (e.g. the sanitizers) are needed also when linking
+        link.args.extend(self.compilation_flags());
+        self.compilation_stdout.lock().unwrap().push(link.stdout());
+        self.compilation_stderr.lock().unwrap().push(link.stderr());
+        let exec = link.output(&self.language.executable_name(&self.path));
+        let link_uuid = link.uuid;
+        dag.add_execution(link);
+        Ok((exec, link_uuid))
+    }
 }
 
 /// Serializer for `Arc<dyn Language>`. It serializes just the name of the language, expecting the
@@ -289,6 +438,42 @@ where
     )
 }
 
+/// Check whether a command line argument references a file inside the base directory, returning
+/// the path of the local file and the path, inside the sandbox, where it should be placed.
+///
+/// Only the arguments that are the path of an existing regular file are considered, and the
+/// numeric arguments (like the seed of a generator) are never files. The path can be relative to
+/// the base directory, possibly starting with `./`, or absolute as long as it's inside it.
+fn command_line_dependency(base_path: &Path, arg: &str) -> Option<(PathBuf, PathBuf)> {
+    if arg.parse::<f64>().is_ok() && arg.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let arg = Path::new(arg);
+    let relative = if arg.is_absolute() {
+        arg.strip_prefix(base_path).ok()?
+    } else {
+        arg
+    };
+    let mut sandbox_path = PathBuf::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(component) => sandbox_path.push(component),
+            Component::CurDir => {}
+            // the file would be outside the sandbox
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    if sandbox_path.as_os_str().is_empty() {
+        return None;
+    }
+    let path = base_path.join(&sandbox_path);
+    if path.is_file() {
+        Some((path, sandbox_path))
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
@@ -345,4 +530,187 @@ mod tests {
         assert!(!exec_skipped.load(Ordering::Relaxed));
         assert!(cwd.path().join("bin").exists());
     }
+
+    #[test]
+    fn test_source_file_cpp_parallel_compilation() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let source_path = cwd.path().join("source.cpp");
+        std::fs::write(&source_path, "int answer() { return 42; }").unwrap();
+        let grader_path = cwd.path().join("grader.cpp");
+        std::fs::write(
+            &grader_path,
+            "int answer();\nint main() { return answer() - 42; }",
+        )
+        .unwrap();
+
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut().parallel_compilation(true);
+        let grader_map = Arc::new(GraderMap::new(vec![&grader_path]));
+        let source = SourceFile::new(&source_path, "", Some(grader_map), None::<PathBuf>).unwrap();
+        let (comp, exec) = source.execute(&mut dag, "Testing exec", vec![]).unwrap();
+        let comp = comp.unwrap();
+        // the two units and the linking
+        assert_eq!(dag.data.executions.len(), 3);
+        let link = &dag.data.executions[&comp];
+        assert_eq!(link.inputs.len(), 2);
+        assert!(link.args.contains(&"source.cpp.o".to_string()));
+        assert!(link.args.contains(&"grader.cpp.o".to_string()));
+        // the messages of the units and of the linking are all reported
+        assert_eq!(source.compilation_stdout().len(), 3);
+        assert_eq!(source.compilation_stderr().len(), 3);
+
+        let exec_done = Arc::new(AtomicBool::new(false));
+        let exec_done2 = exec_done.clone();
+        dag.on_execution_done(&exec.uuid, move |res| {
+            if let ExecutionStatus::Success = res.status {
+                exec_done2.store(true, Ordering::Relaxed);
+            }
+            Ok(())
+        });
+        dag.add_execution(exec);
+
+        eval_dag_locally(dag, cwd.path(), 2, cwd.path());
+
+        assert!(exec_done.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_source_file_toolchain() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let source_path = cwd.path().join("source.cpp");
+        std::fs::write(&source_path, "int main() {return 0;}").unwrap();
+
+        let mut dag = ExecutionDAG::new();
+        let source = SourceFile::new(&source_path, "", None, None::<PathBuf>).unwrap();
+        let (comp, exec) = source.execute(&mut dag, "Testing exec", vec![]).unwrap();
+        let comp = &dag.data.executions[&comp.unwrap()];
+        assert_eq!(comp.toolchain, source.language().toolchain_version());
+        // the compiled program doesn't depend on the compiler
+        assert_eq!(exec.toolchain, None);
+    }
+
+    #[test]
+    fn test_source_file_compilation_flags() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let source_path = cwd.path().join("source.cpp");
+        std::fs::write(&source_path, "int main() {return 0;}").unwrap();
+
+        let mut dag = ExecutionDAG::new();
+        let mut grader_map = GraderMap::new(Vec::<PathBuf>::new());
+        let lang = LanguageManager::detect_language(&source_path).unwrap();
+        grader_map
+            .set_compilation_flags(lang.name(), vec!["-DFOO"])
+            .unwrap();
+        let source = SourceFile::new(
+            &source_path,
+            "",
+            Some(Arc::new(grader_map)),
+            None::<PathBuf>,
+        )
+        .unwrap();
+        let (comp, _) = source.execute(&mut dag, "Testing exec", vec![]).unwrap();
+        let comp = &dag.data.executions[&comp.unwrap()];
+        assert!(comp.args.contains(&"-DFOO".to_string()));
+    }
+
+    #[test]
+    fn test_source_file_compilation_limits() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let source_path = cwd.path().join("source.cpp");
+        std::fs::write(&source_path, "int main() {return 0;}").unwrap();
+
+        let mut dag = ExecutionDAG::new();
+        dag.config_mut()
+            .compilation_time_limit(Some(42.0))
+            .compilation_memory_limit(None);
+        let source = SourceFile::new(&source_path, "", None, None::<PathBuf>).unwrap();
+        let (comp, exec) = source.execute(&mut dag, "Testing exec", vec![]).unwrap();
+        let comp = &dag.data.executions[&comp.unwrap()];
+        assert_eq!(comp.limits.wall_time, Some(42.0));
+        assert_eq!(comp.limits.memory, None);
+        // the limits of the execution are not affected
+        assert_eq!(exec.limits.wall_time, None);
+    }
+
+    #[test]
+    fn test_source_file_seccomp_profiles() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let source_path = cwd.path().join("source.cpp");
+        std::fs::write(&source_path, "int main() {return 0;}").unwrap();
+
+        let mut dag = ExecutionDAG::new();
+        let source = SourceFile::new(&source_path, "", None, None::<PathBuf>).unwrap();
+        let (comp, exec) = source.execute(&mut dag, "Testing exec", vec![]).unwrap();
+        let comp = &dag.data.executions[&comp.unwrap()];
+        assert_eq!(comp.limits.seccomp, Some(SeccompProfile::Permissive));
+        assert_eq!(exec.limits.seccomp, Some(SeccompProfile::Strict));
+    }
+
+    #[test]
+    fn test_command_line_dependency_numeric() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        std::fs::write(cwd.path().join("source.py"), "").unwrap();
+        std::fs::write(cwd.path().join("42"), "").unwrap();
+        std::fs::write(cwd.path().join("input.txt"), "").unwrap();
+
+        let mut dag = ExecutionDAG::new();
+        let source = SourceFile::new(
+            cwd.path().join("source.py"),
+            cwd.path(),
+            None,
+            None::<PathBuf>,
+        )
+        .unwrap();
+        let args = vec!["42".to_string(), "input.txt".to_string()];
+        let (_, exec) = source.execute(&mut dag, "Testing exec", args).unwrap();
+        assert!(!exec.inputs.contains_key(Path::new("42")));
+        assert!(exec.inputs.contains_key(Path::new("input.txt")));
+        assert!(exec.args.contains(&"42".to_string()));
+        assert!(exec.args.contains(&"input.txt".to_string()));
+    }
+
+    #[test]
+    fn test_command_line_dependency_paths() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        let base = cwd.path().join("task");
+        std::fs::create_dir_all(base.join("data")).unwrap();
+        std::fs::write(base.join("source.py"), "").unwrap();
+        std::fs::write(base.join("data/in.txt"), "").unwrap();
+        std::fs::write(base.join("abs.txt"), "").unwrap();
+        std::fs::write(cwd.path().join("outside.txt"), "").unwrap();
+
+        let mut dag = ExecutionDAG::new();
+        let source = SourceFile::new(base.join("source.py"), &base, None, None::<PathBuf>).unwrap();
+        let args = vec![
+            "./data/in.txt".to_string(),
+            base.join("abs.txt").to_string_lossy().to_string(),
+            "data".to_string(),
+            "../outside.txt".to_string(),
+            cwd.path().join("outside.txt").to_string_lossy().to_string(),
+        ];
+        let (_, exec) = source.execute(&mut dag, "Testing exec", args).unwrap();
+        assert!(exec.inputs.contains_key(Path::new("data/in.txt")));
+        assert!(exec.inputs.contains_key(Path::new("abs.txt")));
+        assert!(!exec.inputs.contains_key(Path::new("data")));
+        assert!(!exec.inputs.contains_key(Path::new("../outside.txt")));
+        assert!(!exec.inputs.contains_key(Path::new("outside.txt")));
+        // the paths of the files are the ones inside the sandbox
+        assert!(exec.args.contains(&"data/in.txt".to_string()));
+        assert!(exec.args.contains(&"abs.txt".to_string()));
+        assert!(exec.args.contains(&"../outside.txt".to_string()));
+    }
+
+    #[test]
+    fn test_command_line_dependency() {
+        let cwd = TempDir::new("tm-test").unwrap();
+        std::fs::write(cwd.path().join("1e5"), "").unwrap();
+        std::fs::write(cwd.path().join("file"), "").unwrap();
+        assert_eq!(command_line_dependency(cwd.path(), "1e5"), None);
+        assert_eq!(command_line_dependency(cwd.path(), "."), None);
+        assert_eq!(command_line_dependency(cwd.path(), "missing"), None);
+        assert_eq!(
+            command_line_dependency(cwd.path(), "./file"),
+            Some((cwd.path().join("file"), PathBuf::from("file")))
+        );
+    }
 }
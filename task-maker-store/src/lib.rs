@@ -1,8 +1,9 @@
 //! This crate manages the file store on disk, a folder with many files indexed by their hash.
 //!
 //! The files are stored in a read-only manner (removing the write bit permission) and their access
-//! is granted via their hash. The size of the store folder is limited to a specific amount and the
-//! least-recently-used files are removed automatically.
+//! is granted via their hash. The size of the store folder can be limited to a specific amount
+//! (see [`FileStore::new_with_limit`](struct.FileStore.html#method.new_with_limit)), in that case
+//! the least-recently-used files are removed automatically.
 //!
 //! The access to the store directory via this crate is exclusive even between processes.
 //!
@@ -53,9 +54,8 @@ pub use read_file_iterator::ReadFileIterator;
 use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
-/// Whether to check the file integrity on the store before getting it.
-const INTEGRITY_CHECKS_ENABLED: bool = false;
 /// The name of the lock of the file store.
 const STORE_LOCK_FILE: &str = "exclusive.lock";
 
@@ -69,6 +69,37 @@ struct LockedFiles {
     ref_counts: HashMap<FileStoreKey, usize>,
 }
 
+/// The size and the last access of the files in the store, for evicting the least recently used
+/// ones when the store grows too big.
+#[derive(Debug)]
+struct StoreIndex {
+    /// The maximum total size of the files, in bytes.
+    max_size: u64,
+    /// The total size of the files in the store, in bytes.
+    total_size: u64,
+    /// The files in the store.
+    entries: HashMap<FileStoreKey, IndexEntry>,
+}
+
+/// A file inside the [`StoreIndex`](struct.StoreIndex.html).
+#[derive(Debug)]
+struct IndexEntry {
+    /// The size of the file, in bytes.
+    size: u64,
+    /// The last time the file has been stored or asked for.
+    last_access: SystemTime,
+}
+
+/// The files removed from the store to reclaim space, either evicted because the store is too big
+/// or garbage collected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvictionStats {
+    /// The total size of the removed files, in bytes.
+    pub bytes: u64,
+    /// The number of removed files.
+    pub entries: usize,
+}
+
 /// A file store will manage all the files in the store directory.
 ///
 /// This will manage a file storage directory with the ability of:
@@ -84,6 +115,12 @@ pub struct FileStore {
     file: File,
     /// The files locked because there are some handles still alive.
     locked_files: Arc<Mutex<LockedFiles>>,
+    /// The index of the files, only if the size of the store is limited.
+    index: Option<Mutex<StoreIndex>>,
+    /// Whether to check the integrity of the files before getting them.
+    verify_on_get: bool,
+    /// The files evicted since the creation of the store because it was too big.
+    evicted: Mutex<EvictionStats>,
 }
 
 /// Handle of a file in the `FileStore`, this must be computable given the content of the file, i.e.
@@ -144,9 +181,74 @@ impl FileStore {
             base_path,
             file,
             locked_files: Arc::new(Mutex::new(LockedFiles::new())),
+            index: None,
+            verify_on_get: false,
+            evicted: Mutex::new(EvictionStats::default()),
         })
     }
 
+    /// Check the integrity of each file before returning an handle to it with
+    /// [`get`](#method.get): the files whose content does not match their key are removed from the
+    /// store and treated as missing, so that they are provided or computed again. This requires
+    /// reading the whole file at each `get`.
+    pub fn verify_on_get(&mut self, verify_on_get: bool) -> &mut Self {
+        self.verify_on_get = verify_on_get;
+        self
+    }
+
+    /// Make a new `FileStore` like [`new`](#method.new), keeping the total size of the files below
+    /// `max_size` bytes. When a new file makes the store too big the least recently used files are
+    /// removed, except the ones with some handles still alive. The files stored during the
+    /// previous sessions are ordered by their last access time on the filesystem.
+    ///
+    /// ```
+    /// use task_maker_store::{FileStore, FileStoreKey};
+    ///
+    /// # use failure::Error;
+    /// # use tempdir::TempDir;
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmp = TempDir::new("tm-test")?;
+    /// let store = FileStore::new_with_limit(tmp.path(), 10)?;
+    /// let key1 = FileStoreKey::from_content(b"hello");
+    /// drop(store.store(&key1, vec![b"hello".to_vec()])?);
+    /// let key2 = FileStoreKey::from_content(b"world!");
+    /// let handle = store.store(&key2, vec![b"world!".to_vec()])?;
+    /// // the first file is gone to make room for the second one
+    /// assert!(store.get(&key1).is_none());
+    /// assert!(store.get(&key2).is_some());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_limit<P: Into<PathBuf>>(
+        base_path: P,
+        max_size: u64,
+    ) -> Result<FileStore, Error> {
+        let mut store = FileStore::new(base_path)?;
+        let mut index = StoreIndex {
+            max_size,
+            total_size: 0,
+            entries: HashMap::new(),
+        };
+        for (key, path) in store.list_files()? {
+            let metadata = std::fs::metadata(&path)?;
+            let last_access = metadata
+                .accessed()
+                .or_else(|_| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            index.total_size += metadata.len();
+            index.entries.insert(
+                key,
+                IndexEntry {
+                    size: metadata.len(),
+                    last_access,
+                },
+            );
+        }
+        store.index = Some(Mutex::new(index));
+        store.evict()?;
+        Ok(store)
+    }
+
     /// Given an iterator of `Vec<u8>` consume all of it writing the content to the disk if the file
     /// is not already present on disk. The file is stored inside the base directory and `chmod -w`.
     ///
@@ -180,6 +282,54 @@ impl FileStore {
     /// # }
     /// ```
     pub fn store<I>(&self, key: &FileStoreKey, content: I) -> Result<FileStoreHandle, Error>
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        match self.store_impl(key, content, false)? {
+            Some(handle) => Ok(handle),
+            None => unreachable!("The content is not verified"),
+        }
+    }
+
+    /// Like [`store`](#method.store) but the content is checked against the key, for example
+    /// because it comes from a transport that may corrupt it. If the content does not match the
+    /// key it's discarded and `None` is returned. If the file is already in the store the content
+    /// is not checked.
+    ///
+    /// ```
+    /// use task_maker_store::{FileStore, FileStoreKey};
+    ///
+    /// # use failure::Error;
+    /// # use tempdir::TempDir;
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmp = TempDir::new("tm-test").unwrap();
+    /// let store = FileStore::new(tmp.path())?;
+    /// let key = FileStoreKey::from_content(b"hello");
+    /// assert!(store.store_verified(&key, vec![b"hellp".to_vec()])?.is_none());
+    /// assert!(store.get(&key).is_none());
+    /// assert!(store.store_verified(&key, vec![b"hello".to_vec()])?.is_some());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn store_verified<I>(
+        &self,
+        key: &FileStoreKey,
+        content: I,
+    ) -> Result<Option<FileStoreHandle>, Error>
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        self.store_impl(key, content, true)
+    }
+
+    /// Store a file inside the store, returning `None` if the content has to be verified and it
+    /// does not match the key.
+    fn store_impl<I>(
+        &self,
+        key: &FileStoreKey,
+        content: I,
+        verify: bool,
+    ) -> Result<Option<FileStoreHandle>, Error>
     where
         I: IntoIterator<Item = Vec<u8>>,
     {
@@ -196,17 +346,31 @@ impl FileStore {
             let tmpdir = tempdir::TempDir::new_in(path.parent().unwrap(), "temp")?;
             let tmpfile_path = tmpdir.path().join("file");
             let mut tmpfile = std::fs::File::create(&tmpfile_path)?;
-            if !content
+            let mut hasher = Blake2b::new();
+            let written = content
                 .into_iter()
-                .map(|data| tmpfile.write_all(&data))
-                .all(|r| r.is_ok())
-            {
+                .map(|data| {
+                    if verify {
+                        hasher.input(&data);
+                    }
+                    tmpfile.write_all(&data)
+                })
+                .all(|r| r.is_ok());
+            if !written {
                 bail!("Failed to store file");
             }
+            if verify && hasher.result().as_slice() != key.hash.as_slice() {
+                warn!("The content of {:?} does not match its key", path);
+                return Ok(None);
+            }
             std::fs::rename(tmpfile_path, &path)?;
             FileStore::mark_readonly(&path)?;
         }
-        Ok(handle)
+        if self.index.is_some() {
+            self.touch(key, &path)?;
+            self.evict()?;
+        }
+        Ok(Some(handle))
     }
 
     /// Returns an handle to the file with that key or `None` if it's not in the
@@ -242,17 +406,141 @@ impl FileStore {
     /// ```
     pub fn get(&self, key: &FileStoreKey) -> Option<FileStoreHandle> {
         let path = self.key_to_path(key);
+        // make the handle before checking the file, so that it cannot be evicted in the meantime
+        let handle = FileStoreHandle::new(&self, key);
         if !path.exists() {
             return None;
         }
-        if INTEGRITY_CHECKS_ENABLED && !self.check_integrity(key) {
+        if self.verify_on_get && !self.check_integrity(key) {
             warn!("File {:?} failed the integrity check", path);
             if let Err(e) = FileStore::remove_file(&path) {
                 warn!("Cannot remove corrupted file: {:?}", e);
             }
+            self.remove_from_index(key);
             return None;
         }
-        Some(FileStoreHandle::new(&self, key))
+        if let Err(e) = self.touch(key, &path) {
+            warn!("Cannot update the index of the store: {:?}", e);
+        }
+        Some(handle)
+    }
+
+    /// Remove from the store the files that are not referenced anymore, i.e. the ones for which
+    /// `referenced` returns `false` and that have no live handles. The files in use by the running
+    /// evaluations are never removed, so this can be called at any time. Returns the number of
+    /// size and the number of the removed files.
+    ///
+    /// ```
+    /// use task_maker_store::{FileStore, FileStoreKey};
+    ///
+    /// # use failure::Error;
+    /// # use tempdir::TempDir;
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmp = TempDir::new("tm-test")?;
+    /// let store = FileStore::new(tmp.path())?;
+    /// let key = FileStoreKey::from_content(b"hello");
+    /// let handle = store.store(&key, vec![b"hello".to_vec()])?;
+    /// // the file is in use
+    /// assert_eq!(store.gc(|_| false)?.bytes, 0);
+    /// drop(handle);
+    /// assert_eq!(store.gc(|_| false)?.bytes, 5);
+    /// assert!(store.get(&key).is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn gc<F>(&self, referenced: F) -> Result<EvictionStats, Error>
+    where
+        F: Fn(&FileStoreKey) -> bool,
+    {
+        let mut reclaimed = EvictionStats::default();
+        for (key, path) in self.list_files()? {
+            if referenced(&key) {
+                continue;
+            }
+            // keep the lock while removing the file, so that no new handle is made meanwhile
+            let locked_files = self.locked_files.lock().unwrap();
+            if locked_files.ref_counts.contains_key(&key) {
+                continue;
+            }
+            let size = match std::fs::metadata(&path) {
+                Ok(metadata) => metadata.len(),
+                // the file has been removed meanwhile, for example evicted by another store
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            debug!("Removing {:?} from the store", path);
+            FileStore::remove_file(&path)?;
+            drop(locked_files);
+            reclaimed.bytes += size;
+            reclaimed.entries += 1;
+            self.remove_from_index(&key);
+        }
+        info!(
+            "Garbage collection of the store removed {} files, reclaiming {} bytes",
+            reclaimed.entries, reclaimed.bytes
+        );
+        Ok(reclaimed)
+    }
+
+    /// The files evicted since the creation of the store to keep it below its size limit.
+    ///
+    /// ```
+    /// use task_maker_store::{FileStore, FileStoreKey};
+    ///
+    /// # use failure::Error;
+    /// # use tempdir::TempDir;
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmp = TempDir::new("tm-test")?;
+    /// let store = FileStore::new_with_limit(tmp.path(), 10)?;
+    /// drop(store.store(&FileStoreKey::from_content(b"hello"), vec![b"hello".to_vec()])?);
+    /// assert_eq!(store.evicted().entries, 0);
+    /// drop(store.store(&FileStoreKey::from_content(b"world!"), vec![b"world!".to_vec()])?);
+    /// assert_eq!(store.evicted().entries, 1);
+    /// assert_eq!(store.evicted().bytes, 5);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn evicted(&self) -> EvictionStats {
+        *self.evicted.lock().unwrap()
+    }
+
+    /// Check the integrity of all the files in the store, reading them and comparing their content
+    /// with their key. The keys of the corrupted files are returned, the ones without live handles
+    /// are also removed from the store so that they are provided or computed again.
+    ///
+    /// ```
+    /// use task_maker_store::{FileStore, FileStoreKey};
+    ///
+    /// # use failure::Error;
+    /// # use tempdir::TempDir;
+    /// # fn main() -> Result<(), Error> {
+    /// # let tmp = TempDir::new("tm-test")?;
+    /// let store = FileStore::new(tmp.path())?;
+    /// let key = FileStoreKey::from_content(b"hello");
+    /// drop(store.store(&key, vec![b"hello".to_vec()])?);
+    /// assert!(store.verify()?.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn verify(&self) -> Result<Vec<FileStoreKey>, Error> {
+        let mut corrupted = vec![];
+        for (key, path) in self.list_files()? {
+            if self.check_integrity(&key) {
+                continue;
+            }
+            warn!("File {:?} failed the integrity check", path);
+            // keep the lock while removing the file, so that no new handle is made meanwhile
+            let locked_files = self.locked_files.lock().unwrap();
+            if locked_files.ref_counts.contains_key(&key) {
+                warn!("Cannot remove {:?}, it's in use", path);
+            } else {
+                FileStore::remove_file(&path)?;
+                drop(locked_files);
+                self.remove_from_index(&key);
+            }
+            corrupted.push(key);
+        }
+        Ok(corrupted)
     }
 
     /// Path of the file to disk.
@@ -260,6 +548,126 @@ impl FileStore {
         self.base_path.join(key.suffix())
     }
 
+    /// List all the files in the store, with their path.
+    fn list_files(&self) -> Result<Vec<(FileStoreKey, PathBuf)>, Error> {
+        let mut files = vec![];
+        // the files are stored in the directories at depth 2, for example aa/bb/aabbccdd...
+        for first in std::fs::read_dir(&self.base_path)? {
+            let first = first?.path();
+            if !first.is_dir() {
+                continue;
+            }
+            for second in std::fs::read_dir(first)? {
+                let second = second?.path();
+                if !second.is_dir() {
+                    continue;
+                }
+                for file in std::fs::read_dir(second)? {
+                    let path = file?.path();
+                    if !path.is_file() {
+                        continue;
+                    }
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    match hex::decode(name.as_ref()) {
+                        Ok(hash) => files.push((FileStoreKey { hash }, path)),
+                        Err(_) => warn!("Unexpected file in the store: {:?}", path),
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Mark the file as just used in the index, adding it if it's not there yet. Does nothing if
+    /// the size of the store is not limited.
+    fn touch(&self, key: &FileStoreKey, path: &Path) -> Result<(), Error> {
+        let mut index = match &self.index {
+            Some(index) => index.lock().unwrap(),
+            None => return Ok(()),
+        };
+        let now = SystemTime::now();
+        if let Some(entry) = index.entries.get_mut(key) {
+            entry.last_access = now;
+            return Ok(());
+        }
+        let size = std::fs::metadata(path)?.len();
+        index.total_size += size;
+        index.entries.insert(
+            key.clone(),
+            IndexEntry {
+                size,
+                last_access: now,
+            },
+        );
+        Ok(())
+    }
+
+    /// Forget about a file removed from the store. Does nothing if the size of the store is not
+    /// limited.
+    fn remove_from_index(&self, key: &FileStoreKey) {
+        if let Some(index) = &self.index {
+            let mut index = index.lock().unwrap();
+            if let Some(entry) = index.entries.remove(key) {
+                index.total_size -= entry.size;
+            }
+        }
+    }
+
+    /// Remove the least recently used files, without a live handle, until the store fits in its
+    /// size limit. Does nothing if the size of the store is not limited.
+    fn evict(&self) -> Result<(), Error> {
+        let mut index = match &self.index {
+            Some(index) => index.lock().unwrap(),
+            None => return Ok(()),
+        };
+        if index.total_size <= index.max_size {
+            return Ok(());
+        }
+        let mut candidates: Vec<_> = index
+            .entries
+            .iter()
+            .map(|(key, entry)| (entry.last_access, key.clone()))
+            .collect();
+        candidates.sort();
+        let mut evicted = EvictionStats::default();
+        for (_, key) in candidates {
+            if index.total_size <= index.max_size {
+                break;
+            }
+            // keep the lock while removing the file, so that no new handle is made meanwhile
+            let locked_files = self.locked_files.lock().unwrap();
+            if locked_files.ref_counts.contains_key(&key) {
+                continue;
+            }
+            let path = self.key_to_path(&key);
+            debug!("Evicting {:?} from the store", path);
+            if path.exists() {
+                FileStore::remove_file(&path)?;
+            }
+            drop(locked_files);
+            let entry = index.entries.remove(&key).expect("Index entry is gone");
+            index.total_size -= entry.size;
+            evicted.bytes += entry.size;
+            evicted.entries += 1;
+        }
+        if evicted.entries > 0 {
+            info!(
+                "Evicted {} files from the store, freeing {} bytes",
+                evicted.entries, evicted.bytes
+            );
+            let mut total = self.evicted.lock().unwrap();
+            total.bytes += evicted.bytes;
+            total.entries += evicted.entries;
+        }
+        if index.total_size > index.max_size {
+            warn!(
+                "The store is still too big ({} bytes) after removing the unused files",
+                index.total_size
+            );
+        }
+        Ok(())
+    }
+
     /// Mark a file as readonly.
     fn mark_readonly(path: &Path) -> Result<(), Error> {
         let mut perms = std::fs::metadata(path)?.permissions();
@@ -277,24 +685,10 @@ impl FileStore {
         Ok(())
     }
 
-    /// Check if the file is not corrupted.
+    /// Check if the file is not corrupted, hashing its content. The timestamps of the file cannot
+    /// be trusted since the corruption may happen below the filesystem (e.g. bad sectors).
     fn check_integrity(&self, key: &FileStoreKey) -> bool {
         let path = self.key_to_path(key);
-        let metadata = std::fs::metadata(&path);
-        // if the last modified time is the same of creation time assume it's
-        // not corrupted
-        if let Ok(metadata) = metadata {
-            let created = metadata.created();
-            let modified = metadata.modified();
-            match (created, modified) {
-                (Ok(created), Ok(modified)) => {
-                    if created == modified {
-                        return true;
-                    }
-                }
-                (_, _) => {}
-            }
-        }
         match FileStoreKey::from_file(&path) {
             Ok(key2) => key2.hash == key.hash,
             Err(_) => false,
@@ -560,11 +954,9 @@ mod tests {
 
     #[test]
     fn test_corrupted_file() {
-        if !INTEGRITY_CHECKS_ENABLED {
-            return;
-        }
         let cwd = get_cwd();
-        let store = FileStore::new(cwd.path()).unwrap();
+        let mut store = FileStore::new(cwd.path()).unwrap();
+        store.verify_on_get(true);
         let handle = add_file_to_store(&cwd.path().join("test.txt"), "ciao", &store);
         let path_in_store = store.key_to_path(&handle.key);
         corrupt_file(&path_in_store);
@@ -616,6 +1008,31 @@ mod tests {
         assert!(!store.check_integrity(&handle.key));
     }
 
+    #[test]
+    fn test_verify() {
+        let cwd = get_cwd();
+        let store = FileStore::new_with_limit(cwd.path().join("store"), 100).unwrap();
+        let good = add_file_to_store(&cwd.path().join("file1.txt"), "good", &store)
+            .key
+            .clone();
+        let bad = add_file_to_store(&cwd.path().join("file2.txt"), "bad", &store)
+            .key
+            .clone();
+        let in_use = add_file_to_store(&cwd.path().join("file3.txt"), "in use", &store);
+        corrupt_file(&store.key_to_path(&bad));
+        corrupt_file(&store.key_to_path(&in_use.key));
+        let mut corrupted = store.verify().unwrap();
+        corrupted.sort();
+        let mut expected = vec![bad.clone(), in_use.key.clone()];
+        expected.sort();
+        assert_eq!(corrupted, expected);
+        assert!(store.get(&good).is_some());
+        assert!(store.get(&bad).is_none());
+        // the files in use are not removed
+        assert!(in_use.path().exists());
+        assert_eq!(store.index.as_ref().unwrap().lock().unwrap().total_size, 10);
+    }
+
     #[test]
     fn test_locked_files() {
         let cwd = get_cwd();
@@ -675,4 +1092,101 @@ mod tests {
         assert_ne!(key1a, key2);
         assert_ne!(key1b, key2);
     }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let cwd = get_cwd();
+        let store = FileStore::new_with_limit(cwd.path().join("store"), 10).unwrap();
+        let key1 = add_file_to_store(&cwd.path().join("file1.txt"), "1111", &store)
+            .key
+            .clone();
+        let key2 = add_file_to_store(&cwd.path().join("file2.txt"), "2222", &store)
+            .key
+            .clone();
+        // the first file is now more recent than the second one
+        drop(store.get(&key1).unwrap());
+        let key3 = add_file_to_store(&cwd.path().join("file3.txt"), "3333", &store)
+            .key
+            .clone();
+        assert!(store.get(&key1).is_some());
+        assert!(store.get(&key2).is_none());
+        assert!(store.get(&key3).is_some());
+        assert_eq!(store.index.as_ref().unwrap().lock().unwrap().total_size, 8);
+        assert_eq!(
+            store.evicted(),
+            EvictionStats {
+                bytes: 4,
+                entries: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_evict_keeps_locked_files() {
+        let cwd = get_cwd();
+        let store = FileStore::new_with_limit(cwd.path().join("store"), 10).unwrap();
+        let handle1 = add_file_to_store(&cwd.path().join("file1.txt"), "111111", &store);
+        let handle2 = add_file_to_store(&cwd.path().join("file2.txt"), "222222", &store);
+        // both the files are in use, the store is allowed to exceed its limit
+        assert!(handle1.path().exists());
+        assert!(handle2.path().exists());
+        drop(handle1);
+        let handle3 = add_file_to_store(&cwd.path().join("file3.txt"), "333", &store);
+        assert!(store.get(&handle2.key).is_some());
+        assert!(store.get(&handle3.key).is_some());
+        assert_eq!(store.index.as_ref().unwrap().lock().unwrap().total_size, 9);
+    }
+
+    #[test]
+    fn test_limit_existing_store() {
+        let cwd = get_cwd();
+        let keys: Vec<_> = {
+            let store = FileStore::new(cwd.path().join("store")).unwrap();
+            (0..5)
+                .map(|i| {
+                    let path = cwd.path().join(format!("file{}.txt", i));
+                    add_file_to_store(&path, &format!("file{}", i), &store)
+                        .key
+                        .clone()
+                })
+                .collect()
+        };
+        let store = FileStore::new_with_limit(cwd.path().join("store"), 12).unwrap();
+        let present = keys.iter().filter(|k| store.get(k).is_some()).count();
+        assert_eq!(present, 2);
+    }
+
+    #[test]
+    fn test_gc() {
+        let cwd = get_cwd();
+        let store = FileStore::new_with_limit(cwd.path().join("store"), 100).unwrap();
+        let kept = add_file_to_store(&cwd.path().join("file1.txt"), "kept", &store)
+            .key
+            .clone();
+        let orphan = add_file_to_store(&cwd.path().join("file2.txt"), "orphan", &store)
+            .key
+            .clone();
+        let in_use = add_file_to_store(&cwd.path().join("file3.txt"), "in use", &store);
+        let reclaimed = store.gc(|key| key == &kept).unwrap();
+        assert_eq!(reclaimed.bytes, 6);
+        assert_eq!(reclaimed.entries, 1);
+        assert!(store.get(&kept).is_some());
+        assert!(store.get(&orphan).is_none());
+        assert!(store.get(&in_use.key).is_some());
+        assert_eq!(store.index.as_ref().unwrap().lock().unwrap().total_size, 10);
+    }
+
+    #[test]
+    fn test_gc_vanished_file() {
+        let cwd = get_cwd();
+        let store = FileStore::new(cwd.path().join("store")).unwrap();
+        add_file_to_store(&cwd.path().join("file1.txt"), "vanished", &store);
+        let reclaimed = store
+            .gc(|key| {
+                FileStore::remove_file(&store.key_to_path(key)).unwrap();
+                false
+            })
+            .unwrap();
+        assert_eq!(reclaimed.entries, 0);
+    }
 }
@@ -1,6 +1,6 @@
 use failure::Error;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 
 /// Buffer size when reading a file
@@ -44,6 +44,16 @@ impl ReadFileIterator {
             buf: [0; READ_FILE_BUFFER_SIZE],
         })
     }
+
+    /// Make a new iterator reading the file at that path, skipping the first `offset` bytes.
+    pub fn with_offset<P: AsRef<Path>>(path: P, offset: u64) -> Result<ReadFileIterator, Error> {
+        let mut file = std::fs::File::open(path.as_ref())?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(ReadFileIterator {
+            buf_reader: BufReader::new(file),
+            buf: [0; READ_FILE_BUFFER_SIZE],
+        })
+    }
 }
 
 impl Iterator for ReadFileIterator {
@@ -100,6 +110,18 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn test_read_file_iterator_with_offset() {
+        let cwd = get_cwd();
+        let path = cwd.path().join("file.txt");
+        fake_file(&path, vec![1, 2, 3, 4]);
+        let mut iter = ReadFileIterator::with_offset(&path, 1).unwrap();
+        assert_eq!(iter.next(), Some(vec![2, 3, 4]));
+        assert_eq!(iter.next(), None);
+        let mut iter = ReadFileIterator::with_offset(&path, 4).unwrap();
+        assert_eq!(iter.next(), None);
+    }
+
     #[test]
     fn test_read_file_iterator_chunk_file() {
         let cwd = get_cwd();
@@ -4,11 +4,11 @@ use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use task_maker_dag::ExecutionStatus;
 use task_maker_format::ioi::{
-    CompilationStatus, SolutionEvaluationState, SubtaskId, Task, TestcaseEvaluationStatus,
-    TestcaseGenerationStatus, UIState,
+    CompilationStatus, EvaluationSummary, SolutionEvaluationState, SubtaskId, Task,
+    TestcaseEvaluationStatus, TestcaseGenerationStatus, UIState,
 };
-use task_maker_format::ui::UIMessage;
-use task_maker_format::EvaluationConfig;
+use task_maker_format::ui::{JsonSummary, UIMessage};
+use task_maker_format::{EvaluationConfig, EvaluationPhase};
 
 /// Interface for testing a task.
 #[derive(Debug)]
@@ -33,6 +33,8 @@ pub struct TestInterface {
     pub solution_scores: HashMap<PathBuf, Vec<f64>>,
     /// The status of the evaluation of some solutions.
     pub solution_statuses: HashMap<PathBuf, Vec<TestcaseEvaluationStatus>>,
+    /// Whether the checker has been run on all the testcases of some solutions.
+    pub checker_ran: HashMap<PathBuf, bool>,
     /// Expect task-maker to fail with the specified message.
     pub fail: Option<String>,
     /// The status of the generations of the testcases.
@@ -41,6 +43,8 @@ pub struct TestInterface {
     pub generation_fails: Option<Vec<Option<String>>>,
     /// A list with the stderr message of the failing validations.
     pub validation_fails: Option<Vec<Option<String>>>,
+    /// Extra command line arguments to pass to task-maker.
+    pub args: Vec<String>,
 }
 
 impl TestInterface {
@@ -60,13 +64,21 @@ impl TestInterface {
             subtask_scores: None,
             solution_scores: HashMap::new(),
             solution_statuses: HashMap::new(),
+            checker_ran: HashMap::new(),
             fail: None,
             generation_statuses: None,
             generation_fails: None,
             validation_fails: None,
+            args: Vec::new(),
         }
     }
 
+    /// Pass an extra command line argument to task-maker.
+    pub fn arg<S: Into<String>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
     /// Check that task-maker fails with the specified message.
     pub fn fail<S: Into<String>>(&mut self, message: S) -> &mut Self {
         self.fail = Some(message.into());
@@ -139,6 +151,12 @@ impl TestInterface {
         self
     }
 
+    /// Check that the checker has been run (or not) on all the testcases of the solution.
+    pub fn checker_ran<P: Into<PathBuf>>(&mut self, solution: P, ran: bool) -> &mut Self {
+        self.checker_ran.insert(solution.into(), ran);
+        self
+    }
+
     /// Check that the statuses of the generation are those.
     pub fn generation_statuses<I: IntoIterator<Item = TestcaseGenerationStatus>>(
         &mut self,
@@ -175,6 +193,13 @@ impl TestInterface {
                 solution_filter: vec![],
                 booklet_solutions: false,
                 solution_paths: vec![],
+                preview_size: None,
+                phase: EvaluationPhase::Full,
+                verify_checker: false,
+                checker_fallback: false,
+                cross_check: vec![],
+                max_solution_executions: None,
+                ui_rate_limit: None,
             },
         )
         .unwrap();
@@ -189,6 +214,7 @@ impl TestInterface {
         command.arg("--ui").arg("json");
         command.arg("--no-cache");
         command.arg("--dry-run");
+        command.args(&self.args);
         command.env("RUST_BACKTRACE", "1");
         command.stdout(Stdio::piped());
         command.stderr(Stdio::piped());
@@ -213,8 +239,14 @@ impl TestInterface {
             );
         }
         let mut state = UIState::new(&task);
+        let mut summary = None;
         for message in String::from_utf8(output.stdout).unwrap().lines() {
-            let message = serde_json::from_str::<UIMessage>(&message).expect("Invalid message");
+            // the summary of the evaluation is printed at the end, outside the messages
+            if let Ok(json) = serde_json::from_str::<JsonSummary>(message) {
+                summary = Some(json.summary);
+                continue;
+            }
+            let message = serde_json::from_str::<UIMessage>(message).expect("Invalid message");
             state.apply(message);
         }
         println!("State is: {:#?}", state);
@@ -224,6 +256,38 @@ impl TestInterface {
         self.check_generations(&state);
         self.check_solution_scores(&state);
         self.check_solution_statuses(&state);
+        self.check_checker_ran(&state);
+        self.check_summary(&state, summary);
+    }
+
+    /// Check that the summary printed at the end agrees with the state of the evaluation.
+    fn check_summary(&self, state: &UIState, summary: Option<EvaluationSummary>) {
+        let summary = summary.expect("The summary of the evaluation has not been printed");
+        assert_eq!(
+            summary.report.solutions.len(),
+            state.evaluations.len(),
+            "Wrong number of solutions in the summary"
+        );
+        assert_eq!(
+            summary.compilations.len(),
+            state.compilations.len(),
+            "Wrong number of compilations in the summary"
+        );
+        for (name, scores) in self.solution_scores.iter() {
+            let report = summary
+                .report
+                .solutions
+                .iter()
+                .find(|sol| sol.solution.file_name() == Some(name.as_os_str()))
+                .unwrap_or_else(|| panic!("No summary for solution {:?}", name));
+            let score: f64 = scores.iter().sum();
+            assert!(
+                abs_diff_eq!(score, report.explanation.score),
+                "Solution score mismatch in the summary: {} != {}",
+                score,
+                report.explanation.score
+            );
+        }
     }
 
     /// Check the task limits are met.
@@ -374,6 +438,30 @@ impl TestInterface {
         }
     }
 
+    /// Check that the checker has been run on the testcases of the solutions.
+    fn check_checker_ran(&self, state: &UIState) {
+        let evaluations: HashMap<PathBuf, &SolutionEvaluationState> = state
+            .evaluations
+            .iter()
+            .map(|(file, eval)| (PathBuf::from(file.file_name().unwrap()), eval))
+            .collect();
+        for (name, ran) in self.checker_ran.iter() {
+            if !evaluations.contains_key(name) {
+                panic!("No evaluation for solution {:?}", name)
+            }
+            for subtask in evaluations[name].subtasks.values() {
+                for testcase in subtask.testcases.values() {
+                    assert_eq!(
+                        *ran,
+                        testcase.checker.is_some(),
+                        "Checker execution mismatch of {:?}",
+                        name
+                    );
+                }
+            }
+        }
+    }
+
     fn check_generations(&self, state: &UIState) {
         let generations: Vec<_> = state
             .generations
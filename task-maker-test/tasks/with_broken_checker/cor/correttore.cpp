@@ -0,0 +1,6 @@
+#include <iostream>
+
+int main(int argc, char** argv) {
+  std::cout << 1.0 << std::endl
+  std::cerr << "Ok!" << std::endl;
+}
@@ -0,0 +1 @@
+#error "The precompiled checker in managers/ should be used instead"
@@ -0,0 +1,4 @@
+#!/usr/bin/env bash
+
+read a b
+echo $((a + b))
@@ -0,0 +1,4 @@
+#!/usr/bin/env bash
+
+echo 3
+while :; do :; done
@@ -0,0 +1,6 @@
+#!/usr/bin/env bash
+
+echo 5 6
+echo 1 2
+echo 3 4
+echo 3 4
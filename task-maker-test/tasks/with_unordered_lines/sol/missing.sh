@@ -0,0 +1,4 @@
+#!/usr/bin/env bash
+
+echo 5 6
+echo 3 4
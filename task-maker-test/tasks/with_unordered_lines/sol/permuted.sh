@@ -0,0 +1,5 @@
+#!/usr/bin/env bash
+
+echo 5 6
+echo 1    2
+echo 3 4
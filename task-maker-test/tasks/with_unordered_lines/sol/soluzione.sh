@@ -0,0 +1,5 @@
+#!/usr/bin/env bash
+
+echo 1 2
+echo 3 4
+echo 5 6
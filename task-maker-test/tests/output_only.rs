@@ -0,0 +1,36 @@
+use task_maker_format::ioi::TestcaseEvaluationStatus::*;
+use task_maker_test::*;
+
+#[test]
+fn output_only() {
+    better_panic::install();
+
+    TestInterface::new("output_only")
+        .max_score(100.0)
+        .subtask_scores(vec![100.0])
+        .solution_score("correct", vec![100.0])
+        .solution_score("wrong", vec![50.0])
+        .solution_score("missing", vec![50.0])
+        .solution_statuses(
+            "correct",
+            vec![
+                Accepted("Output is correct".into()),
+                Accepted("Output is correct".into()),
+            ],
+        )
+        .solution_statuses(
+            "wrong",
+            vec![
+                Accepted("Output is correct".into()),
+                WrongAnswer("Output is incorrect".into()),
+            ],
+        )
+        .solution_statuses(
+            "missing",
+            vec![
+                WrongAnswer("Missing output file output_000.txt".into()),
+                Accepted("Output is correct".into()),
+            ],
+        )
+        .run();
+}
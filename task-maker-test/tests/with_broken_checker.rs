@@ -0,0 +1,17 @@
+use task_maker_format::ioi::TestcaseEvaluationStatus::*;
+use task_maker_test::*;
+
+#[test]
+fn with_broken_checker() {
+    better_panic::install();
+
+    TestInterface::new("with_broken_checker")
+        .arg("--checker-fallback")
+        .max_score(100.0)
+        .must_not_compile("correttore.cpp")
+        .solution_score("soluzione.sh", vec![100.0])
+        .solution_score("wrong.sh", vec![0.0])
+        .solution_statuses("soluzione.sh", vec![Accepted("Output is correct".into())])
+        .solution_statuses("wrong.sh", vec![WrongAnswer("Output is incorrect".into())])
+        .run();
+}
@@ -0,0 +1,18 @@
+use task_maker_format::ioi::TestcaseEvaluationStatus::*;
+use task_maker_test::*;
+
+#[test]
+fn with_ignore_case() {
+    better_panic::install();
+
+    TestInterface::new("with_ignore_case")
+        .time_limit(1.0)
+        .memory_limit(64)
+        .max_score(100.0)
+        .subtask_scores(vec![100.0])
+        .solution_score("soluzione.sh", vec![100.0])
+        .solution_score("lower.sh", vec![100.0])
+        .solution_statuses("soluzione.sh", vec![Accepted("Output is correct".into())])
+        .solution_statuses("lower.sh", vec![Accepted("Output is correct".into())])
+        .run();
+}
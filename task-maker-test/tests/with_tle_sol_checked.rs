@@ -0,0 +1,20 @@
+use task_maker_format::ioi::TestcaseEvaluationStatus::*;
+use task_maker_test::*;
+
+#[test]
+fn with_tle_sol_checked() {
+    better_panic::install();
+
+    TestInterface::new("with_tle_sol_checked")
+        .time_limit(1.0)
+        .memory_limit(64)
+        .max_score(100.0)
+        .subtask_scores(vec![100.0])
+        .solution_score("soluzione.sh", vec![100.0])
+        .solution_score("tle.sh", vec![0.0])
+        .solution_statuses("soluzione.sh", vec![Accepted("Output is correct".into())])
+        .solution_statuses("tle.sh", vec![TimeLimitExceeded])
+        .checker_ran("soluzione.sh", true)
+        .checker_ran("tle.sh", true)
+        .run();
+}
@@ -0,0 +1,28 @@
+use task_maker_format::ioi::TestcaseEvaluationStatus::*;
+use task_maker_test::*;
+
+#[test]
+fn with_unordered_lines() {
+    better_panic::install();
+
+    TestInterface::new("with_unordered_lines")
+        .time_limit(1.0)
+        .memory_limit(64)
+        .max_score(100.0)
+        .subtask_scores(vec![100.0])
+        .solution_score("soluzione.sh", vec![100.0])
+        .solution_score("permuted.sh", vec![100.0])
+        .solution_score("missing.sh", vec![0.0])
+        .solution_score("duplicated.sh", vec![0.0])
+        .solution_statuses("soluzione.sh", vec![Accepted("Output is correct".into())])
+        .solution_statuses("permuted.sh", vec![Accepted("Output is correct".into())])
+        .solution_statuses(
+            "missing.sh",
+            vec![WrongAnswer("Output is incorrect".into())],
+        )
+        .solution_statuses(
+            "duplicated.sh",
+            vec![WrongAnswer("Output is incorrect".into())],
+        )
+        .run();
+}